@@ -0,0 +1,64 @@
+use std::ops::Deref;
+
+use bytes::{Buf, BytesMut};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use socket_toolbox::{
+    BufferedDecoder, ItemFormat, ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat,
+};
+
+const MSG_COUNT: usize = 1000;
+
+fn decode(c: &mut Criterion) {
+    let fmt = MessageFormat::new(&[
+        ItemFormat::Len { len: 2 },
+        ItemFormat::Uint { len: 4 },
+        ItemFormat::Int { len: 2 },
+        ItemFormat::FixedString { len: 8 },
+        ItemFormat::VarString { len_idx: 0 },
+        ItemFormat::VarBytes { len_idx: 0 },
+    ])
+    .unwrap();
+    let msg = Message::new(vec![
+        ItemValue::Len(16),
+        ItemValue::Uint(0xcafe),
+        ItemValue::Int(-2),
+        ItemValue::String("socket..".to_string()),
+        ItemValue::String("toolbox.toolbox.".to_string()),
+        ItemValue::Bytes(vec![0xff; 16]),
+    ]);
+    let mut bytes = Vec::new();
+    for _ in 0..MSG_COUNT {
+        MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+    }
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(MSG_COUNT as u64));
+    group.bench_function("MessageDecoder", |b| {
+        b.iter(|| {
+            let mut bytes = bytes.deref();
+            for _ in 0..MSG_COUNT {
+                let msg = MessageDecoder::new(&fmt, &mut bytes)
+                    .decode(Default::default())
+                    .unwrap();
+                black_box(msg);
+            }
+        })
+    });
+    group.bench_function("BufferedDecoder", |b| {
+        let mut decoder = BufferedDecoder::new(fmt.clone());
+        let mut buf = BytesMut::with_capacity(bytes.len());
+        b.iter(|| {
+            buf.extend_from_slice(&bytes);
+            while let Some(msg) = decoder.decode(&buf).unwrap() {
+                let consumed = msg.raw().len();
+                black_box(msg.get(4));
+                buf.advance(consumed);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
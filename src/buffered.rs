@@ -0,0 +1,374 @@
+use std::result;
+
+use crate::{
+    error::{DecodeError, Error},
+    msg::{verify_checksum, ItemFormat, ItemValue, Message, MessageFormat, PayloadLen},
+};
+
+/// A view of an item value, strings and bytes are borrowed from the buffer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemValueRef<'a> {
+    /// Value of a `Len` item.
+    Len(u64),
+    /// Value of a `Uint` or `Checksum` item.
+    Uint(u64),
+    /// Value of an `Int` item.
+    Int(i64),
+    /// Value of a string item.
+    String(&'a str),
+    /// Value of a bytes item.
+    Bytes(&'a [u8]),
+    /// Payload decoded to JSON.
+    Json(&'a serde_json::Value),
+}
+
+impl ItemValueRef<'_> {
+    /// Copies to an owned value.
+    pub fn to_value(self) -> ItemValue {
+        match self {
+            ItemValueRef::Len(v) => ItemValue::Len(v),
+            ItemValueRef::Uint(v) => ItemValue::Uint(v),
+            ItemValueRef::Int(v) => ItemValue::Int(v),
+            ItemValueRef::String(s) => ItemValue::String(s.to_string()),
+            ItemValueRef::Bytes(bytes) => ItemValue::Bytes(bytes.to_vec()),
+            ItemValueRef::Json(v) => ItemValue::Json(v.clone()),
+        }
+    }
+}
+
+// Decoded value, strings and bytes are ranges of the buffer.
+#[derive(Debug, Clone, Copy)]
+enum Value {
+    Len(u64),
+    Uint(u64),
+    Int(i64),
+    String { offset: usize, len: usize },
+    Bytes { offset: usize, len: usize },
+    // Index of the decoded payload.
+    Json(usize),
+}
+
+/// A view of a message decoded by `BufferedDecoder`.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageRef<'a> {
+    raw: &'a [u8],
+    values: &'a [Value],
+    payloads: &'a [serde_json::Value],
+}
+
+impl<'a> MessageRef<'a> {
+    /// Bytes of the message.
+    pub fn raw(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    /// Count of items.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether there is no item.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Value of the item at `idx`.
+    pub fn get(&self, idx: usize) -> Option<ItemValueRef<'a>> {
+        Some(match *self.values.get(idx)? {
+            Value::Len(v) => ItemValueRef::Len(v),
+            Value::Uint(v) => ItemValueRef::Uint(v),
+            Value::Int(v) => ItemValueRef::Int(v),
+            Value::String { offset, len } => {
+                match std::str::from_utf8(&self.raw[offset..offset + len]) {
+                    Ok(s) => ItemValueRef::String(s),
+                    Err(_) => unreachable!("strings are validated when decoding"),
+                }
+            }
+            Value::Bytes { offset, len } => ItemValueRef::Bytes(&self.raw[offset..offset + len]),
+            Value::Json(idx) => ItemValueRef::Json(&self.payloads[idx]),
+        })
+    }
+
+    /// Values in the order of items.
+    pub fn iter(self) -> impl Iterator<Item = ItemValueRef<'a>> {
+        (0..self.len()).filter_map(move |idx| self.get(idx))
+    }
+
+    /// Copies to an owned message.
+    pub fn to_message(self) -> Message {
+        Message::new(self.iter().map(|v| v.to_value()).collect())
+    }
+}
+
+/// Decodes messages from a buffer without copying, such as a `BytesMut` reused for a stream.
+///
+/// Unlike `MessageDecoder`, it never blocks, `None` is returned if more bytes are needed.
+/// Payloads are decoded to owned values, so they are not zero-copy.
+pub struct BufferedDecoder {
+    fmt: MessageFormat,
+    max_len: usize,
+
+    // Reused for each message.
+    values: Vec<Value>,
+    payloads: Vec<serde_json::Value>,
+    offsets: Vec<usize>,
+}
+
+impl BufferedDecoder {
+    /// Decodes by the format.
+    pub fn new(fmt: MessageFormat) -> Self {
+        let values = Vec::with_capacity(fmt.len());
+        Self {
+            fmt,
+            max_len: usize::MAX,
+            values,
+            payloads: Vec::new(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Messages longer than `max_len` are rejected by `Error::MessageTooLarge`, rather than
+    /// waiting for more bytes, there is no limit by default.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Decodes a message at the front of the bytes, the count of bytes consumed is
+    /// the length of `MessageRef::raw`.
+    ///
+    /// If the format is greedy, the bytes should contain exactly the message, such as a frame.
+    pub fn decode<'a>(
+        &'a mut self,
+        bytes: &'a [u8],
+    ) -> result::Result<Option<MessageRef<'a>>, DecodeError> {
+        self.values.clear();
+        self.payloads.clear();
+        self.offsets.clear();
+
+        let mut offset = 0usize;
+        for (idx, item_fmt) in self.fmt.iter().enumerate() {
+            self.offsets.push(offset);
+            let len = match (item_fmt, item_fmt.payload_len()) {
+                (
+                    ItemFormat::Len { len }
+                    | ItemFormat::Uint { len }
+                    | ItemFormat::Int { len }
+                    | ItemFormat::FixedString { len }
+                    | ItemFormat::FixedBytes { len },
+                    _,
+                )
+                | (_, Some(PayloadLen::Fixed { len })) => *len,
+                (ItemFormat::Checksum { checksum, .. }, _) => checksum.width(),
+                (_, Some(PayloadLen::Greedy)) => bytes.len() - offset,
+                (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+                | (_, Some(PayloadLen::Var { len_idx })) => match self.values.get(*len_idx) {
+                    Some(Value::Len(v)) => *v as usize,
+                    _ => {
+                        let error = Error::NotALen {
+                            item_idx: idx,
+                            len_idx: *len_idx,
+                        };
+                        return Err(self.error(bytes, error, idx, offset, 0));
+                    }
+                },
+                _ => unreachable!(),
+            };
+
+            let end = match offset.checked_add(len) {
+                Some(end) if end <= self.max_len => end,
+                _ => {
+                    let error = Error::MessageTooLarge {
+                        max_len: self.max_len,
+                    };
+                    return Err(self.error(bytes, error, idx, offset, 0));
+                }
+            };
+            let item_bytes = match bytes.get(offset..end) {
+                Some(item_bytes) => item_bytes,
+                None => return Ok(None),
+            };
+
+            let value = match item_fmt {
+                ItemFormat::Len { .. } => Value::Len(get_uint(item_bytes)),
+                ItemFormat::Uint { .. } => Value::Uint(get_uint(item_bytes)),
+                ItemFormat::Checksum { .. } => {
+                    let v = get_uint(item_bytes);
+                    if let Err(error) = verify_checksum(item_fmt, idx, v, bytes, &self.offsets) {
+                        return Err(self.error(bytes, error, idx, offset, len));
+                    }
+                    Value::Uint(v)
+                }
+                ItemFormat::Int { .. } => Value::Int(get_int(item_bytes)),
+                ItemFormat::FixedString { .. } | ItemFormat::VarString { .. } => {
+                    if std::str::from_utf8(item_bytes).is_err() {
+                        let e = String::from_utf8(item_bytes.to_vec()).unwrap_err();
+                        let error = Error::FromUtf8 { item_idx: idx, e };
+                        return Err(self.error(bytes, error, idx, offset, len));
+                    }
+                    Value::String { offset, len }
+                }
+                ItemFormat::FixedBytes { .. } | ItemFormat::VarBytes { .. } => {
+                    Value::Bytes { offset, len }
+                }
+                ItemFormat::Protobuf { .. }
+                | ItemFormat::Cbor { .. }
+                | ItemFormat::MessagePack { .. }
+                | ItemFormat::Json { .. }
+                | ItemFormat::Ber { .. }
+                | ItemFormat::Resp { .. }
+                | ItemFormat::Http { .. }
+                | ItemFormat::Stomp { .. }
+                | ItemFormat::Memcache { .. }
+                | ItemFormat::Tlv { .. } => match item_fmt.decode_payload(item_bytes) {
+                    Ok(payload) => {
+                        self.payloads.push(payload);
+                        Value::Json(self.payloads.len() - 1)
+                    }
+                    Err(error) => return Err(self.error(bytes, error, idx, offset, len)),
+                },
+            };
+            self.values.push(value);
+            offset += len;
+        }
+
+        Ok(Some(MessageRef {
+            raw: &bytes[..offset],
+            values: &self.values,
+            payloads: &self.payloads,
+        }))
+    }
+
+    fn error(
+        &self,
+        bytes: &[u8],
+        error: Error,
+        item_idx: usize,
+        offset: usize,
+        len: usize,
+    ) -> DecodeError {
+        let partial = MessageRef {
+            raw: bytes,
+            values: &self.values,
+            payloads: &self.payloads,
+        };
+        DecodeError {
+            error,
+            partial: partial.to_message(),
+            item_idx,
+            offset,
+            consumed: offset + len,
+        }
+    }
+}
+
+#[inline]
+fn get_uint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |v, b| (v << 8) | u64::from(*b))
+}
+
+#[inline]
+fn get_int(bytes: &[u8]) -> i64 {
+    let sign = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+    bytes.iter().fold(sign, |v, b| (v << 8) | i64::from(*b))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+
+    use bytes::{Buf, BytesMut};
+
+    use crate::{
+        checksum::Checksum,
+        error::Error,
+        msg::{ItemFormat, ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat},
+    };
+
+    use super::{BufferedDecoder, ItemValueRef};
+
+    #[test]
+    fn buffered_decode_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::Int { len: 3 },
+            ItemFormat::VarString { len_idx: 0 },
+            ItemFormat::FixedBytes { len: 2 },
+        ])
+        .unwrap();
+        let msg = Message::new(vec![
+            ItemValue::Len(2),
+            ItemValue::Int(-2),
+            ItemValue::String("ok".to_string()),
+            ItemValue::Bytes(vec![0xca, 0xfe]),
+        ]);
+        let mut bytes = Vec::new();
+        MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+
+        // Bytes arrive in pieces.
+        let mut decoder = BufferedDecoder::new(fmt.clone());
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&bytes[..4]);
+        assert!(decoder.decode(&buf).unwrap().is_none());
+        buf.extend_from_slice(&bytes[4..]);
+        buf.extend_from_slice(&bytes);
+
+        for _ in 0..2 {
+            let msg_ref = decoder.decode(&buf).unwrap().unwrap();
+            assert_eq!(msg_ref.get(1), Some(ItemValueRef::Int(-2)));
+            assert_eq!(msg_ref.get(2), Some(ItemValueRef::String("ok")));
+            assert_eq!(
+                msg_ref.to_message(),
+                MessageDecoder::new(&fmt, &mut bytes.deref())
+                    .decode(Default::default())
+                    .unwrap()
+            );
+            let consumed = msg_ref.raw().len();
+            buf.advance(consumed);
+        }
+        assert!(buf.is_empty());
+
+        // Invalid UTF-8.
+        buf.extend_from_slice(&[2, 0, 0, 0, 0xff, 0xff]);
+        let e = decoder.decode(&buf).unwrap_err();
+        assert!(matches!(e.error, Error::FromUtf8 { item_idx: 2, .. }));
+        assert_eq!((e.partial.len(), e.consumed), (2, 6));
+
+        // Lengths which are too large.
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 8 },
+            ItemFormat::VarBytes { len_idx: 0 },
+        ])
+        .unwrap();
+        let mut decoder = BufferedDecoder::new(fmt.clone());
+        let e = decoder.decode(&[0xff; 8]).unwrap_err();
+        assert!(matches!(e.error, Error::MessageTooLarge { .. }));
+        assert_eq!(e.consumed, 8);
+        let mut decoder = BufferedDecoder::new(fmt).max_len(16);
+        assert!(decoder.decode(&[0, 0, 0, 0, 0, 0, 0, 8]).unwrap().is_none());
+        let e = decoder.decode(&[0, 0, 0, 0, 0, 0, 0, 9]).unwrap_err();
+        assert!(matches!(e.error, Error::MessageTooLarge { max_len: 16 }));
+    }
+
+    #[test]
+    fn buffered_checksum_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::FixedBytes { len: 2 },
+            ItemFormat::Checksum {
+                checksum: Checksum::Xor8,
+                start: 0,
+                end: 1,
+            },
+        ])
+        .unwrap();
+        let mut decoder = BufferedDecoder::new(fmt);
+
+        let msg_ref = decoder.decode(&[0x0f, 0xff, 0xf0]).unwrap().unwrap();
+        assert_eq!(msg_ref.get(1), Some(ItemValueRef::Uint(0xf0)));
+        let e = decoder.decode(&[0x0f, 0xff, 0xf1]).unwrap_err();
+        assert!(matches!(
+            e.error,
+            Error::ChecksumMismatch { item_idx: 1, .. }
+        ));
+    }
+}
@@ -0,0 +1,108 @@
+//! Checksums of checksum items, which cover the bytes of a range of items before them.
+
+use std::fmt;
+
+/// Algorithm of a checksum item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// Sum of the bytes modulo 256.
+    Sum8,
+    /// XOR of the bytes, such as the LRC of NMEA sentences.
+    Xor8,
+    /// CRC-16/MODBUS, but big-endian like other integers.
+    Crc16,
+    /// CRC-32/ISO-HDLC of Ethernet and zlib.
+    Crc32,
+}
+
+impl Checksum {
+    /// All the algorithms.
+    pub const ALL: [Checksum; 4] = [
+        Checksum::Sum8,
+        Checksum::Xor8,
+        Checksum::Crc16,
+        Checksum::Crc32,
+    ];
+
+    /// Width of the checksum in bytes.
+    pub fn width(self) -> usize {
+        match self {
+            Checksum::Sum8 | Checksum::Xor8 => 1,
+            Checksum::Crc16 => 2,
+            Checksum::Crc32 => 4,
+        }
+    }
+
+    /// Name in the format DSL, such as `crc16`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Checksum::Sum8 => "sum8",
+            Checksum::Xor8 => "xor8",
+            Checksum::Crc16 => "crc16",
+            Checksum::Crc32 => "crc32",
+        }
+    }
+
+    /// Parses a name in the format DSL.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.name() == name)
+    }
+
+    /// Computes the checksum of the bytes.
+    pub fn compute(self, bytes: &[u8]) -> u64 {
+        match self {
+            Checksum::Sum8 => bytes.iter().fold(0u8, |s, b| s.wrapping_add(*b)).into(),
+            Checksum::Xor8 => bytes.iter().fold(0u8, |s, b| s ^ b).into(),
+            Checksum::Crc16 => {
+                let mut crc = 0xffffu16;
+                for b in bytes {
+                    crc ^= u16::from(*b);
+                    for _ in 0..8 {
+                        crc = if crc & 1 != 0 {
+                            (crc >> 1) ^ 0xa001
+                        } else {
+                            crc >> 1
+                        };
+                    }
+                }
+                crc.into()
+            }
+            Checksum::Crc32 => {
+                let mut crc = 0xffff_ffffu32;
+                for b in bytes {
+                    crc ^= u32::from(*b);
+                    for _ in 0..8 {
+                        crc = if crc & 1 != 0 {
+                            (crc >> 1) ^ 0xedb8_8320
+                        } else {
+                            crc >> 1
+                        };
+                    }
+                }
+                (!crc).into()
+            }
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Checksum;
+
+    #[test]
+    fn check_values_ok() {
+        // Check values of the catalogue of CRCs.
+        assert_eq!(Checksum::Crc16.compute(b"123456789"), 0x4b37);
+        assert_eq!(Checksum::Crc32.compute(b"123456789"), 0xcbf4_3926);
+        assert_eq!(Checksum::Sum8.compute(&[0xff, 0x02]), 0x01);
+        assert_eq!(Checksum::Xor8.compute(&[0x0f, 0xff]), 0xf0);
+        assert_eq!(Checksum::from_name("crc16"), Some(Checksum::Crc16));
+        assert_eq!(Checksum::from_name("crc8"), None);
+    }
+}
@@ -0,0 +1,172 @@
+//! A `tokio_util` codec of messages, with the `codec` feature.
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::{
+    buffered::BufferedDecoder,
+    error::{Error, Result},
+    framing::Framer,
+    msg::{Message, MessageEncoder, MessageFormat},
+    stream::decode_frame,
+};
+
+/// A codec of `tokio_util`, messages are decoded incrementally as bytes arrive.
+///
+/// With a framer, the stream is split into frames and a message is decoded from each frame.
+pub struct MessageCodec {
+    fmt: MessageFormat,
+    decoder: BufferedDecoder,
+    framer: Option<Box<dyn Framer>>,
+
+    // Bytes waiting for the framer.
+    buf: Vec<u8>,
+}
+
+impl MessageCodec {
+    /// Decodes and encodes by the format.
+    pub fn new(fmt: MessageFormat) -> Self {
+        Self {
+            decoder: BufferedDecoder::new(fmt.clone()),
+            fmt,
+            framer: None,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Splits the stream into frames, each frame contains a message.
+    pub fn framer(mut self, framer: Option<Box<dyn Framer>>) -> Self {
+        self.framer = framer;
+        self
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = Message;
+    type Error = Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Message>> {
+        if let Some(framer) = self.framer.as_mut() {
+            self.buf.extend_from_slice(src);
+            src.clear();
+            return match framer.decode_frame(&mut self.buf)? {
+                Some(frame) => decode_frame(&self.fmt, &frame).map(Some),
+                None => Ok(None),
+            };
+        }
+
+        // The end of a greedy message is unknown without a framer.
+        if self.fmt.is_greedy() {
+            return Err(Error::GreedyWithoutFraming);
+        }
+
+        let (msg, consumed) = match self.decoder.decode(src) {
+            Ok(Some(msg)) => (msg.to_message(), msg.raw().len()),
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                // Skip the bytes decoded.
+                src.advance(e.consumed);
+                return Err(e.into());
+            }
+        };
+        src.advance(consumed);
+        Ok(Some(msg))
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Message>> {
+        if let Some(msg) = self.decode(src)? {
+            return Ok(Some(msg));
+        }
+
+        // Bytes left can't be a complete message, since no more bytes will arrive.
+        let len = src.len() + self.buf.len();
+        if len == 0 {
+            return Ok(None);
+        }
+        src.clear();
+        self.buf.clear();
+        Err(Error::TrailingBytes { len })
+    }
+}
+
+impl Encoder<&Message> for MessageCodec {
+    type Error = Error;
+
+    fn encode(&mut self, msg: &Message, dst: &mut BytesMut) -> Result<()> {
+        let mut bytes = Vec::new();
+        MessageEncoder::new(&self.fmt, &mut bytes).encode(msg)?;
+        if let Some(framer) = &self.framer {
+            bytes = framer.encode_frame(&bytes)?;
+        }
+        dst.extend_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::{Decoder, Encoder};
+
+    use crate::{
+        error::Error,
+        framing::Framing,
+        msg::{ItemFormat, ItemValue, Message, MessageFormat},
+    };
+
+    use super::MessageCodec;
+
+    #[test]
+    fn codec_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::VarString { len_idx: 0 },
+        ])
+        .unwrap();
+        let msg = Message::new(vec![ItemValue::Len(2), ItemValue::String("ok".to_string())]);
+
+        for framing in [None, Some(Framing::Slip { max_len: 16 })] {
+            let framer = framing.map(|f| f.framer().unwrap());
+            let mut codec = MessageCodec::new(fmt.clone()).framer(framer);
+            let mut bytes = BytesMut::new();
+            codec.encode(&msg, &mut bytes).unwrap();
+            codec.encode(&msg, &mut bytes).unwrap();
+
+            // Bytes arrive one by one.
+            let mut src = BytesMut::new();
+            let mut msgs = Vec::new();
+            for b in bytes {
+                src.extend_from_slice(&[b]);
+                msgs.extend(codec.decode(&mut src).unwrap());
+            }
+            assert_eq!(msgs, vec![msg.clone(), msg.clone()]);
+        }
+    }
+
+    #[test]
+    fn decode_eof_partial() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::VarString { len_idx: 0 },
+        ])
+        .unwrap();
+        let msg = Message::new(vec![ItemValue::Len(2), ItemValue::String("ok".to_string())]);
+
+        for framing in [None, Some(Framing::Slip { max_len: 16 })] {
+            let framer = framing.map(|f| f.framer().unwrap());
+            let mut codec = MessageCodec::new(fmt.clone()).framer(framer);
+            let mut bytes = BytesMut::new();
+            codec.encode(&msg, &mut bytes).unwrap();
+            codec.encode(&msg, &mut bytes).unwrap();
+
+            // The second message is cut off before its last byte.
+            let mut src = BytesMut::from(&bytes[..bytes.len() - 1]);
+            assert_eq!(codec.decode_eof(&mut src).unwrap(), Some(msg.clone()));
+            assert!(matches!(
+                codec.decode_eof(&mut src),
+                Err(Error::TrailingBytes { len }) if len > 0
+            ));
+            assert_eq!(codec.decode_eof(&mut src).unwrap(), None);
+        }
+    }
+}
@@ -0,0 +1,488 @@
+use std::fmt::Write;
+
+use crate::{
+    checksum::Checksum,
+    msg::{ItemFormat, MessageFormat, PayloadLen},
+};
+
+use super::{checksum_bounds, item_idents};
+
+// Keywords can't be field names.
+const KEYWORDS: [&str; 37] = [
+    "auto",
+    "break",
+    "case",
+    "char",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "struct",
+    "switch",
+    "typedef",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+    "_Bool",
+    "_Complex",
+    "_Imaginary",
+];
+
+fn uint_type(len: usize) -> &'static str {
+    match len {
+        1 => "uint8_t",
+        2 => "uint16_t",
+        3 | 4 => "uint32_t",
+        _ => "uint64_t",
+    }
+}
+
+fn int_type(len: usize) -> &'static str {
+    match len {
+        1 => "int8_t",
+        2 => "int16_t",
+        3 | 4 => "int32_t",
+        _ => "int64_t",
+    }
+}
+
+/// Generates a C header with a struct `<name>_t`, and functions `<name>_pack` and `<name>_unpack`
+/// producing the same bytes as `MessageEncoder` and `MessageDecoder`.
+///
+/// Var strings and bytes are pointers with lengths, which point into the buffer after unpacking.
+/// So are payloads such as protobuf, CBOR and JSON, which are kept as encoded bytes.
+/// Notice that strings are not validated as UTF-8.
+///
+/// Fields are named by item names, or `item_N` if unnamed, and lengths of pointers by `_len`
+/// appended.
+pub fn c_header(fmt: &MessageFormat, name: &str) -> String {
+    let guard = format!("{}_H", name.to_uppercase());
+    let idents = item_idents(fmt, &KEYWORDS);
+    // `_` is appended to lengths taken by items.
+    let lens = idents
+        .iter()
+        .map(|ident| {
+            let mut len = format!("{}_len", ident);
+            while idents.contains(&len) {
+                len.push('_');
+            }
+            len
+        })
+        .collect::<Vec<_>>();
+    let has_integers = fmt.iter().any(|f| {
+        matches!(
+            f,
+            ItemFormat::Len { .. }
+                | ItemFormat::Uint { .. }
+                | ItemFormat::Int { .. }
+                | ItemFormat::Checksum { .. }
+        )
+    });
+    // Offsets of these items are kept for checksums.
+    let bounds = checksum_bounds(fmt);
+    let has_var = fmt.iter().any(|f| {
+        matches!(
+            f,
+            ItemFormat::VarString { .. } | ItemFormat::VarBytes { .. }
+        ) || f.payload_len().is_some()
+    });
+    let has_fixed_string = fmt
+        .iter()
+        .any(|f| matches!(f, ItemFormat::FixedString { .. }));
+    let mut code = String::new();
+
+    writeln!(
+        code,
+        "/* Generated by socket_toolbox, format: `{}`. */",
+        fmt
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "/* Integers are big-endian, strings and bytes are padded with zeros to their length. */"
+    )
+    .unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "#ifndef {}", guard).unwrap();
+    writeln!(code, "#define {}", guard).unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "#include <stddef.h>").unwrap();
+    writeln!(code, "#include <stdint.h>").unwrap();
+    writeln!(code, "#include <string.h>").unwrap();
+    writeln!(code).unwrap();
+
+    // Struct.
+    writeln!(code, "typedef struct {{").unwrap();
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        match item_fmt {
+            ItemFormat::Len { len } | ItemFormat::Uint { len } => {
+                writeln!(code, "    {} {};", uint_type(*len), idents[idx])
+            }
+            ItemFormat::Checksum { checksum, .. } => {
+                writeln!(code, "    {} {};", uint_type(checksum.width()), idents[idx])
+            }
+            ItemFormat::Int { len } => writeln!(code, "    {} {};", int_type(*len), idents[idx]),
+            ItemFormat::FixedString { len } => {
+                writeln!(code, "    char {}[{}];", idents[idx], len)
+            }
+            ItemFormat::FixedBytes { len } => {
+                writeln!(code, "    uint8_t {}[{}];", idents[idx], len)
+            }
+            ItemFormat::VarString { .. } | ItemFormat::Json { .. } => writeln!(
+                code,
+                "    const char *{};\n    size_t {};",
+                idents[idx], lens[idx]
+            ),
+            ItemFormat::VarBytes { .. }
+            | ItemFormat::Protobuf { .. }
+            | ItemFormat::Cbor { .. }
+            | ItemFormat::MessagePack { .. }
+            | ItemFormat::Ber { .. }
+            | ItemFormat::Resp { .. }
+            | ItemFormat::Http { .. }
+            | ItemFormat::Stomp { .. }
+            | ItemFormat::Memcache { .. }
+            | ItemFormat::Tlv { .. } => writeln!(
+                code,
+                "    const uint8_t *{};\n    size_t {};",
+                idents[idx], lens[idx]
+            ),
+        }
+        .unwrap();
+    }
+    writeln!(code, "}} {}_t;", name).unwrap();
+    writeln!(code).unwrap();
+
+    // Checksums, only the used ones are generated.
+    for checksum in &Checksum::ALL {
+        if fmt
+            .iter()
+            .any(|f| matches!(f, ItemFormat::Checksum { checksum: c, .. } if c == checksum))
+        {
+            writeln!(
+                code,
+                "static inline uint64_t {}_{}(const uint8_t *p, size_t n) {{",
+                name, checksum
+            )
+            .unwrap();
+            writeln!(code, "{}", checksum_body(*checksum)).unwrap();
+            writeln!(code, "}}").unwrap();
+            writeln!(code).unwrap();
+        }
+    }
+
+    // Pack.
+    writeln!(
+        code,
+        "/* Returns the count of bytes written, or -1 if the buffer is too small or a value is too long. */"
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "static inline long {0}_pack(const {0}_t *msg, uint8_t *buf, size_t size) {{",
+        name
+    )
+    .unwrap();
+    writeln!(code, "    size_t offset = 0;").unwrap();
+    if has_var {
+        writeln!(code, "    size_t len;").unwrap();
+    }
+    if has_fixed_string {
+        writeln!(code, "    size_t n;").unwrap();
+    }
+    for idx in &bounds {
+        writeln!(code, "    size_t offset_{};", idx).unwrap();
+    }
+    if !bounds.is_empty() {
+        writeln!(code, "    uint64_t v;").unwrap();
+    }
+    if has_integers {
+        writeln!(code, "    int i;").unwrap();
+    }
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        if bounds.contains(&idx) {
+            writeln!(code, "    offset_{} = offset;", idx).unwrap();
+        }
+        match item_fmt {
+            ItemFormat::Len { len } | ItemFormat::Uint { len } | ItemFormat::Int { len } => {
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(code, "    for (i = 0; i < {}; i++) {{", len).unwrap();
+                writeln!(
+                    code,
+                    "        buf[offset++] = (uint8_t)((uint64_t)msg->{} >> (8 * ({} - 1 - i)));",
+                    idents[idx], len
+                )
+                .unwrap();
+                writeln!(code, "    }}").unwrap();
+            }
+            // The field is ignored like `MessageEncoder`.
+            ItemFormat::Checksum {
+                checksum,
+                start,
+                end,
+            } => {
+                let len = checksum.width();
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(
+                    code,
+                    "    v = {}_{}(buf + offset_{2}, offset_{3} - offset_{2});",
+                    name, checksum, start, end
+                )
+                .unwrap();
+                writeln!(code, "    for (i = 0; i < {}; i++) {{", len).unwrap();
+                writeln!(
+                    code,
+                    "        buf[offset++] = (uint8_t)(v >> (8 * ({} - 1 - i)));",
+                    len
+                )
+                .unwrap();
+                writeln!(code, "    }}").unwrap();
+            }
+            ItemFormat::FixedString { len } => {
+                // Copy until the terminating zero, and pad with zeros.
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(
+                    code,
+                    "    for (n = 0; n < {0} && msg->{1}[n]; n++) buf[offset + n] = (uint8_t)msg->{1}[n];",
+                    len, idents[idx]
+                )
+                .unwrap();
+                writeln!(code, "    memset(buf + offset + n, 0, {} - n);", len).unwrap();
+                writeln!(code, "    offset += {};", len).unwrap();
+            }
+            ItemFormat::FixedBytes { len } => {
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(
+                    code,
+                    "    memcpy(buf + offset, msg->{}, {});",
+                    idents[idx], len
+                )
+                .unwrap();
+                writeln!(code, "    offset += {};", len).unwrap();
+            }
+            _ if matches!(item_fmt.payload_len(), Some(PayloadLen::Greedy)) => {
+                writeln!(code, "    len = msg->{};", lens[idx]).unwrap();
+                writeln!(code, "    if (size - offset < len) return -1;").unwrap();
+                writeln!(code, "    memcpy(buf + offset, msg->{}, len);", idents[idx]).unwrap();
+                writeln!(code, "    offset += len;").unwrap();
+            }
+            ItemFormat::VarString { .. }
+            | ItemFormat::VarBytes { .. }
+            | ItemFormat::Protobuf { .. }
+            | ItemFormat::Cbor { .. }
+            | ItemFormat::MessagePack { .. }
+            | ItemFormat::Json { .. }
+            | ItemFormat::Ber { .. }
+            | ItemFormat::Resp { .. }
+            | ItemFormat::Http { .. }
+            | ItemFormat::Stomp { .. }
+            | ItemFormat::Memcache { .. }
+            | ItemFormat::Tlv { .. } => {
+                match (item_fmt, item_fmt.payload_len()) {
+                    (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+                    | (_, Some(PayloadLen::Var { len_idx })) => {
+                        writeln!(code, "    len = (size_t)msg->{};", idents[*len_idx])
+                    }
+                    (_, Some(PayloadLen::Fixed { len })) => writeln!(code, "    len = {};", len),
+                    _ => unreachable!(),
+                }
+                .unwrap();
+                writeln!(
+                    code,
+                    "    if (size - offset < len || msg->{} > len) return -1;",
+                    lens[idx]
+                )
+                .unwrap();
+                writeln!(code, "    memset(buf + offset, 0, len);").unwrap();
+                writeln!(
+                    code,
+                    "    memcpy(buf + offset, msg->{}, msg->{});",
+                    idents[idx], lens[idx]
+                )
+                .unwrap();
+                writeln!(code, "    offset += len;").unwrap();
+            }
+        }
+    }
+    writeln!(code, "    return (long)offset;").unwrap();
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    // Unpack.
+    if bounds.is_empty() {
+        writeln!(
+            code,
+            "/* Returns the count of bytes read, or -1 if there are not enough bytes. */"
+        )
+    } else {
+        writeln!(
+            code,
+            "/* Returns the count of bytes read, -1 if there are not enough bytes, or -2 if a checksum mismatches. */"
+        )
+    }
+    .unwrap();
+    writeln!(
+        code,
+        "static inline long {0}_unpack({0}_t *msg, const uint8_t *buf, size_t size) {{",
+        name
+    )
+    .unwrap();
+    writeln!(code, "    size_t offset = 0;").unwrap();
+    if has_var {
+        writeln!(code, "    size_t len;").unwrap();
+    }
+    for idx in &bounds {
+        writeln!(code, "    size_t offset_{};", idx).unwrap();
+    }
+    if has_integers {
+        writeln!(code, "    uint64_t v;").unwrap();
+        writeln!(code, "    int i;").unwrap();
+    }
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        if bounds.contains(&idx) {
+            writeln!(code, "    offset_{} = offset;", idx).unwrap();
+        }
+        match item_fmt {
+            ItemFormat::Len { len } | ItemFormat::Uint { len } | ItemFormat::Int { len } => {
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(code, "    v = 0;").unwrap();
+                writeln!(code, "    for (i = 0; i < {}; i++) {{", len).unwrap();
+                writeln!(code, "        v = (v << 8) | buf[offset++];").unwrap();
+                writeln!(code, "    }}").unwrap();
+                if let ItemFormat::Int { len } = item_fmt {
+                    if *len < 8 {
+                        writeln!(
+                            code,
+                            "    if ((v >> {}) & 1) v |= ~(uint64_t)0 << {};",
+                            len * 8 - 1,
+                            len * 8
+                        )
+                        .unwrap();
+                    }
+                    writeln!(code, "    msg->{} = ({})v;", idents[idx], int_type(*len)).unwrap();
+                } else {
+                    writeln!(code, "    msg->{} = ({})v;", idents[idx], uint_type(*len)).unwrap();
+                }
+            }
+            ItemFormat::Checksum {
+                checksum,
+                start,
+                end,
+            } => {
+                let len = checksum.width();
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(code, "    v = 0;").unwrap();
+                writeln!(code, "    for (i = 0; i < {}; i++) {{", len).unwrap();
+                writeln!(code, "        v = (v << 8) | buf[offset++];").unwrap();
+                writeln!(code, "    }}").unwrap();
+                writeln!(code, "    msg->{} = ({})v;", idents[idx], uint_type(len)).unwrap();
+                writeln!(
+                    code,
+                    "    if (v != {}_{}(buf + offset_{2}, offset_{3} - offset_{2})) return -2;",
+                    name, checksum, start, end
+                )
+                .unwrap();
+            }
+            ItemFormat::FixedString { len } | ItemFormat::FixedBytes { len } => {
+                writeln!(code, "    if (size - offset < {}) return -1;", len).unwrap();
+                writeln!(
+                    code,
+                    "    memcpy(msg->{}, buf + offset, {});",
+                    idents[idx], len
+                )
+                .unwrap();
+                writeln!(code, "    offset += {};", len).unwrap();
+            }
+            ItemFormat::VarString { .. }
+            | ItemFormat::VarBytes { .. }
+            | ItemFormat::Protobuf { .. }
+            | ItemFormat::Cbor { .. }
+            | ItemFormat::MessagePack { .. }
+            | ItemFormat::Json { .. }
+            | ItemFormat::Ber { .. }
+            | ItemFormat::Resp { .. }
+            | ItemFormat::Http { .. }
+            | ItemFormat::Stomp { .. }
+            | ItemFormat::Memcache { .. }
+            | ItemFormat::Tlv { .. } => {
+                let ty = match item_fmt {
+                    ItemFormat::VarString { .. } | ItemFormat::Json { .. } => "const char *",
+                    _ => "const uint8_t *",
+                };
+                match (item_fmt, item_fmt.payload_len()) {
+                    (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+                    | (_, Some(PayloadLen::Var { len_idx })) => {
+                        writeln!(code, "    len = (size_t)msg->{};", idents[*len_idx])
+                    }
+                    (_, Some(PayloadLen::Fixed { len })) => writeln!(code, "    len = {};", len),
+                    // The rest of the buffer.
+                    _ => writeln!(code, "    len = size - offset;"),
+                }
+                .unwrap();
+                writeln!(code, "    if (size - offset < len) return -1;").unwrap();
+                writeln!(code, "    msg->{} = ({})(buf + offset);", idents[idx], ty).unwrap();
+                writeln!(code, "    msg->{} = len;", lens[idx]).unwrap();
+                writeln!(code, "    offset += len;").unwrap();
+            }
+        }
+    }
+    writeln!(code, "    return (long)offset;").unwrap();
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+    writeln!(code, "#endif").unwrap();
+
+    code
+}
+
+fn checksum_body(checksum: Checksum) -> &'static str {
+    match checksum {
+        Checksum::Sum8 => {
+            "    uint8_t s = 0;
+    while (n--) s += *p++;
+    return s;"
+        }
+        Checksum::Xor8 => {
+            "    uint8_t s = 0;
+    while (n--) s ^= *p++;
+    return s;"
+        }
+        Checksum::Crc16 => {
+            "    uint16_t crc = 0xffff;
+    int i;
+    while (n--) {
+        crc ^= *p++;
+        for (i = 0; i < 8; i++) crc = (crc & 1) ? (uint16_t)((crc >> 1) ^ 0xa001) : (uint16_t)(crc >> 1);
+    }
+    return crc;"
+        }
+        Checksum::Crc32 => {
+            "    uint32_t crc = 0xffffffffu;
+    int i;
+    while (n--) {
+        crc ^= *p++;
+        for (i = 0; i < 8; i++) crc = (crc & 1) ? (crc >> 1) ^ 0xedb88320u : crc >> 1;
+    }
+    return ~crc;"
+        }
+    }
+}
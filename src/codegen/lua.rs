@@ -0,0 +1,289 @@
+use std::fmt::Write;
+
+use crate::msg::{ItemFormat, MessageFormat, PayloadLen};
+
+use super::item_idents;
+
+/// Transport of the port which the dissector is registered to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    /// Registered to `tcp.port`.
+    Tcp,
+    /// Registered to `udp.port`.
+    Udp,
+}
+
+fn kind_name(fmt: &ItemFormat) -> &'static str {
+    match fmt {
+        ItemFormat::Len { .. } => "Len",
+        ItemFormat::Uint { .. } => "Uint",
+        ItemFormat::Int { .. } => "Int",
+        ItemFormat::FixedString { .. } => "FixedString",
+        ItemFormat::VarString { .. } => "VarString",
+        ItemFormat::FixedBytes { .. } => "FixedBytes",
+        ItemFormat::VarBytes { .. } => "VarBytes",
+        ItemFormat::Checksum { .. } => "Checksum",
+        ItemFormat::Protobuf { .. } => "Protobuf",
+        ItemFormat::Cbor { .. } => "Cbor",
+        ItemFormat::MessagePack { .. } => "MessagePack",
+        ItemFormat::Json { .. } => "Json",
+        ItemFormat::Ber { .. } => "Ber",
+        ItemFormat::Resp { .. } => "Resp",
+        ItemFormat::Http { .. } => "Http",
+        ItemFormat::Stomp { .. } => "Stomp",
+        ItemFormat::Memcache { .. } => "Memcache",
+        ItemFormat::Tlv { .. } => "Tlv",
+    }
+}
+
+fn proto_field(fmt: &ItemFormat) -> String {
+    match fmt {
+        ItemFormat::Len { len } | ItemFormat::Uint { len } if *len <= 4 => {
+            format!("uint{}", len * 8)
+        }
+        ItemFormat::Len { .. } | ItemFormat::Uint { .. } => "uint64".to_string(),
+        ItemFormat::Checksum { checksum, .. } => format!("uint{}", checksum.width() * 8),
+        ItemFormat::Int { len } if *len <= 4 => format!("int{}", len * 8),
+        ItemFormat::Int { .. } => "int64".to_string(),
+        ItemFormat::FixedString { .. } | ItemFormat::VarString { .. } | ItemFormat::Json { .. } => {
+            "string".to_string()
+        }
+        ItemFormat::FixedBytes { .. } | ItemFormat::VarBytes { .. } => "bytes".to_string(),
+        ItemFormat::Protobuf { .. }
+        | ItemFormat::Cbor { .. }
+        | ItemFormat::MessagePack { .. }
+        | ItemFormat::Ber { .. }
+        | ItemFormat::Resp { .. }
+        | ItemFormat::Http { .. }
+        | ItemFormat::Stomp { .. }
+        | ItemFormat::Memcache { .. }
+        | ItemFormat::Tlv { .. } => "bytes".to_string(),
+    }
+}
+
+// A Lua string literal of the string.
+fn lua_string(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            c if c.is_control() => c
+                .to_string()
+                .bytes()
+                .for_each(|b| write!(literal, "\\{:03}", b).unwrap()),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Generates a Wireshark dissector in Lua for messages of the format,
+/// which is registered on the port of TCP or UDP.
+///
+/// A TCP segment may contain several messages, and a message may span segments.
+pub fn lua_dissector(fmt: &MessageFormat, name: &str, transport: Transport, port: u16) -> String {
+    let idents = item_idents(fmt, &[]);
+    let mut code = String::new();
+
+    writeln!(code, "-- Generated by socket_toolbox, format: `{}`.", fmt).unwrap();
+    writeln!(code, "-- Integers are big-endian.").unwrap();
+    writeln!(code).unwrap();
+    writeln!(
+        code,
+        "local proto = Proto({}, {})",
+        lua_string(name),
+        lua_string(&format!("{} (socket_toolbox)", name))
+    )
+    .unwrap();
+    writeln!(code).unwrap();
+
+    // Fields.
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        let description = match (item_fmt, item_fmt.payload_len()) {
+            (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+            | (_, Some(PayloadLen::Var { len_idx })) => {
+                format!(
+                    "{} ({}, length: {})",
+                    idents[idx],
+                    kind_name(item_fmt),
+                    idents[*len_idx]
+                )
+            }
+            (
+                ItemFormat::Checksum {
+                    checksum,
+                    start,
+                    end,
+                },
+                _,
+            ) => format!(
+                "{} ({}, {} of {}..{})",
+                idents[idx],
+                kind_name(item_fmt),
+                checksum,
+                idents[*start],
+                idents[*end]
+            ),
+            _ => format!("{} ({})", idents[idx], kind_name(item_fmt)),
+        };
+        let base = match item_fmt {
+            ItemFormat::Len { .. } | ItemFormat::Uint { .. } | ItemFormat::Int { .. } => {
+                ", base.DEC"
+            }
+            ItemFormat::Checksum { .. } => ", base.HEX",
+            _ => "",
+        };
+        writeln!(
+            code,
+            "local f_{} = ProtoField.{}({}, {}{})",
+            idents[idx],
+            proto_field(item_fmt),
+            lua_string(&format!("{}.{}", name, idents[idx])),
+            lua_string(&description),
+            base
+        )
+        .unwrap();
+    }
+    writeln!(code).unwrap();
+    writeln!(code, "proto.fields = {{").unwrap();
+    for ident in &idents {
+        writeln!(code, "    f_{},", ident).unwrap();
+    }
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    // Dissect a message at the offset, returns the length of it,
+    // or nil with the count of missing bytes if the buffer is too short.
+    writeln!(code, "local function dissect_message(buffer, offset, tree)").unwrap();
+    writeln!(code, "    local start = offset").unwrap();
+    writeln!(code, "    local len").unwrap();
+    writeln!(code, "    local values = {{}}").unwrap();
+    writeln!(code, "    local items = {{}}").unwrap();
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        match (item_fmt, item_fmt.payload_len()) {
+            (
+                ItemFormat::Len { len }
+                | ItemFormat::Uint { len }
+                | ItemFormat::Int { len }
+                | ItemFormat::FixedString { len }
+                | ItemFormat::FixedBytes { len },
+                _,
+            )
+            | (_, Some(PayloadLen::Fixed { len })) => writeln!(code, "    len = {}", len),
+            (ItemFormat::Checksum { checksum, .. }, _) => {
+                writeln!(code, "    len = {}", checksum.width())
+            }
+            (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+            | (_, Some(PayloadLen::Var { len_idx })) => {
+                writeln!(code, "    len = values[{}]", len_idx)
+            }
+            // The rest of the buffer, which should contain exactly the message.
+            _ => writeln!(code, "    len = buffer:len() - offset"),
+        }
+        .unwrap();
+        writeln!(code, "    if buffer:len() - offset < len then").unwrap();
+        writeln!(code, "        return nil, len - (buffer:len() - offset)").unwrap();
+        writeln!(code, "    end").unwrap();
+
+        let value = match item_fmt {
+            ItemFormat::Len { len } | ItemFormat::Uint { len } if *len <= 4 => {
+                Some("buffer(offset, len):uint()")
+            }
+            ItemFormat::Checksum { .. } => Some("buffer(offset, len):uint()"),
+            ItemFormat::Len { .. } => Some("buffer(offset, len):uint64():tonumber()"),
+            ItemFormat::Uint { .. } => Some("buffer(offset, len):uint64()"),
+            ItemFormat::Int { len } if *len <= 4 => Some("buffer(offset, len):int()"),
+            ItemFormat::Int { .. } => Some("buffer(offset, len):int64()"),
+            _ => None,
+        };
+        if let Some(value) = value {
+            writeln!(code, "    values[{}] = {}", idx, value).unwrap();
+            writeln!(
+                code,
+                "    items[#items + 1] = {{ f_{}, offset, len, values[{}] }}",
+                idents[idx], idx
+            )
+            .unwrap();
+        } else {
+            writeln!(
+                code,
+                "    items[#items + 1] = {{ f_{}, offset, len }}",
+                idents[idx]
+            )
+            .unwrap();
+        }
+        writeln!(code, "    offset = offset + len").unwrap();
+    }
+    writeln!(code).unwrap();
+    writeln!(
+        code,
+        "    local subtree = tree:add(proto, buffer(start, offset - start))"
+    )
+    .unwrap();
+    writeln!(code, "    for _, item in ipairs(items) do").unwrap();
+    writeln!(code, "        if item[3] > 0 then").unwrap();
+    writeln!(code, "            if item[4] ~= nil then").unwrap();
+    writeln!(
+        code,
+        "                subtree:add(item[1], buffer(item[2], item[3]), item[4])"
+    )
+    .unwrap();
+    writeln!(code, "            else").unwrap();
+    writeln!(
+        code,
+        "                subtree:add(item[1], buffer(item[2], item[3]))"
+    )
+    .unwrap();
+    writeln!(code, "            end").unwrap();
+    writeln!(code, "        end").unwrap();
+    writeln!(code, "    end").unwrap();
+    writeln!(code, "    return offset - start").unwrap();
+    writeln!(code, "end").unwrap();
+    writeln!(code).unwrap();
+
+    // Dissector.
+    writeln!(code, "function proto.dissector(buffer, pinfo, tree)").unwrap();
+    writeln!(code, "    pinfo.cols.protocol = proto.name").unwrap();
+    writeln!(code, "    local offset = 0").unwrap();
+    writeln!(code, "    while offset < buffer:len() do").unwrap();
+    writeln!(
+        code,
+        "        local len, missing = dissect_message(buffer, offset, tree)"
+    )
+    .unwrap();
+    writeln!(code, "        if len == nil then").unwrap();
+    match transport {
+        Transport::Tcp => {
+            // Ask for more segments.
+            writeln!(code, "            pinfo.desegment_offset = offset").unwrap();
+            writeln!(code, "            pinfo.desegment_len = missing").unwrap();
+            writeln!(code, "            return buffer:len()").unwrap();
+        }
+        Transport::Udp => {
+            writeln!(code, "            return 0").unwrap();
+        }
+    }
+    writeln!(code, "        end").unwrap();
+    writeln!(code, "        offset = offset + len").unwrap();
+    writeln!(code, "    end").unwrap();
+    writeln!(code, "    return offset").unwrap();
+    writeln!(code, "end").unwrap();
+    writeln!(code).unwrap();
+
+    let table = match transport {
+        Transport::Tcp => "tcp.port",
+        Transport::Udp => "udp.port",
+    };
+    writeln!(
+        code,
+        "DissectorTable.get(\"{}\"):add({}, proto)",
+        table, port
+    )
+    .unwrap();
+
+    code
+}
@@ -0,0 +1,262 @@
+mod c;
+mod lua;
+mod rust;
+
+pub use c::*;
+pub use lua::*;
+pub use rust::*;
+
+use std::collections::HashSet;
+
+use crate::msg::{ItemFormat, MessageFormat};
+
+// Indices of the items bounding checksums, whose offsets are needed.
+fn checksum_bounds(fmt: &MessageFormat) -> Vec<usize> {
+    let mut bounds = fmt
+        .iter()
+        .filter_map(|f| match f {
+            ItemFormat::Checksum { start, end, .. } => Some([*start, *end]),
+            _ => None,
+        })
+        .flatten()
+        .collect::<Vec<_>>();
+    bounds.sort_unstable();
+    bounds.dedup();
+    bounds
+}
+
+// Identifiers of items, which are names of items or `item_N` if unnamed. `_` is appended to
+// keywords and to identifiers taken by other items.
+fn item_idents(fmt: &MessageFormat, keywords: &[&str]) -> Vec<String> {
+    let names = (0..fmt.len()).map(|idx| fmt.name(idx)).collect::<Vec<_>>();
+    let mut used = HashSet::new();
+    names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let mut ident = match name {
+                Some(name) => name.to_string(),
+                None => format!("item_{}", idx),
+            };
+            while keywords.contains(&ident.as_str())
+                || used.contains(&ident)
+                || (name.is_none() && names.contains(&Some(ident.as_str())))
+            {
+                ident.push('_');
+            }
+            used.insert(ident.clone());
+            ident
+        })
+        .collect()
+}
+
+fn camel_case(name: &str) -> String {
+    name.split('_')
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect::<String>()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::Write,
+        ops::Deref,
+        process::{Command, Stdio},
+    };
+
+    use crate::msg::{ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat};
+
+    use super::{c_header, item_idents, lua_dissector, rust_code, Transport};
+
+    mod packet {
+        include!("testdata/packet.rs");
+    }
+
+    const FORMAT: &str =
+        "len:len16; type:u8; i24; stamp:i64; tag:str[4]; name:str[#0]; bytes[3]; data:bytes[#0]; crc:crc16(1..8)";
+
+    #[test]
+    fn generated_code_up_to_date() {
+        let fmt = FORMAT.parse::<MessageFormat>().unwrap();
+        assert_eq!(
+            rust_code(&fmt, "packet"),
+            include_str!("testdata/packet.rs")
+        );
+        assert_eq!(c_header(&fmt, "packet"), include_str!("testdata/packet.h"));
+        assert_eq!(
+            lua_dissector(&fmt, "packet", Transport::Tcp, 9000),
+            include_str!("testdata/packet.lua")
+        );
+    }
+
+    #[test]
+    fn item_idents_ok() {
+        let fmt = "item_1:u8; u8; type:u8; type_:u8"
+            .parse::<MessageFormat>()
+            .unwrap();
+        assert_eq!(
+            item_idents(&fmt, &["type"]),
+            ["item_1", "item_1_", "type_", "type__"]
+        );
+    }
+
+    #[test]
+    fn field_names_ok() {
+        let fmt = "data_len:len8; data:bytes[#0]; int:u8; fn:u8"
+            .parse::<MessageFormat>()
+            .unwrap();
+        let header = c_header(&fmt, "packet");
+        assert!(header.contains("    uint8_t data_len;\n    const uint8_t *data;\n    size_t data_len_;\n    uint8_t int_;\n    uint8_t fn;\n"));
+        let code = rust_code(&fmt, "packet");
+        assert!(code.contains(
+            "    pub data_len: u8,\n    pub data: Vec<u8>,\n    pub int: u8,\n    pub fn_: u8,\n"
+        ));
+    }
+
+    #[test]
+    fn lua_name_escaped() {
+        let fmt = "u8".parse::<MessageFormat>().unwrap();
+        let code = lua_dissector(&fmt, "a\"b\\", Transport::Udp, 9000);
+        assert!(code.contains(r#"Proto("a\"b\\", "a\"b\\ (socket_toolbox)")"#));
+        assert!(code.contains(r#"ProtoField.uint8("a\"b\\.item_0", "item_0 (Uint)""#));
+    }
+
+    // Unpacks a message from stdin and packs it again to stdout,
+    // the exit code is the negated result of unpacking if it fails.
+    const C_MAIN: &str = r#"#include <stdio.h>
+#include "packet.h"
+
+int main(void) {
+    static uint8_t in[1024], out[1024];
+    size_t n = fread(in, 1, sizeof(in), stdin);
+    packet_t msg;
+    long len = packet_unpack(&msg, in, n);
+    if (len < 0) return (int)-len;
+    len = packet_pack(&msg, out, sizeof(out));
+    if (len < 0) return 10;
+    fwrite(out, 1, (size_t)len, stdout);
+    return 0;
+}
+"#;
+
+    fn message() -> Message {
+        Message::new(vec![
+            ItemValue::Len(5),
+            ItemValue::Uint(233),
+            ItemValue::Int(-65536),
+            ItemValue::Int(i64::MIN),
+            ItemValue::String("ab".to_string()),
+            ItemValue::String("abcd".to_string()),
+            ItemValue::Bytes(vec![1, 2, 3]),
+            ItemValue::Bytes(vec![4, 5, 6, 7, 8]),
+            ItemValue::Uint(0),
+        ])
+    }
+
+    #[test]
+    fn generated_rust_round_trip() {
+        let fmt = FORMAT.parse::<MessageFormat>().unwrap();
+        let msg = message();
+        let mut bytes = Vec::<u8>::default();
+        MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+
+        let packet = packet::Packet {
+            len: 5,
+            type_: 233,
+            item_2: -65536,
+            stamp: i64::MIN,
+            tag: "ab".to_string(),
+            name: "abcd".to_string(),
+            item_6: vec![1, 2, 3],
+            data: vec![4, 5, 6, 7, 8],
+            crc: 0,
+        };
+        assert_eq!(packet.encode().unwrap(), bytes);
+
+        // Strings are padded with zeros by both sides.
+        let (decoded, len) = packet::Packet::decode(&bytes).unwrap();
+        assert_eq!(len, bytes.len());
+        let decoded_msg = MessageDecoder::new(&fmt, &mut bytes.deref())
+            .decode(Default::default())
+            .unwrap();
+        assert_eq!(decoded.tag, "ab\0\0");
+        assert_eq!(decoded_msg[4], ItemValue::String(decoded.tag.clone()));
+        assert_eq!(decoded.name, "abcd\0");
+        assert_eq!(decoded_msg[5], ItemValue::String(decoded.name.clone()));
+        assert_eq!(decoded_msg[8], ItemValue::Uint(decoded.crc.into()));
+        assert_eq!(decoded.encode().unwrap(), bytes);
+
+        assert_eq!(
+            packet::Packet::decode(&bytes[..bytes.len() - 1]),
+            Err(packet::PacketError::EndOfBytes { item_idx: 8 })
+        );
+        let mut corrupted = bytes.clone();
+        corrupted[2] ^= 1;
+        assert_eq!(
+            packet::Packet::decode(&corrupted),
+            Err(packet::PacketError::ChecksumMismatch { item_idx: 8 })
+        );
+    }
+
+    #[test]
+    fn generated_c_round_trip() {
+        // Skipped without a C compiler.
+        if Command::new("cc").arg("--version").output().is_err() {
+            eprintln!("no C compiler, skipped");
+            return;
+        }
+
+        let fmt = FORMAT.parse::<MessageFormat>().unwrap();
+        let dir = std::env::temp_dir().join(format!("socket_toolbox_c_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("packet.h"), c_header(&fmt, "packet")).unwrap();
+        fs::write(dir.join("main.c"), C_MAIN).unwrap();
+        let exe = dir.join("packet");
+        let output = Command::new("cc")
+            .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-o"])
+            .arg(&exe)
+            .arg(dir.join("main.c"))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let run = |bytes: &[u8]| {
+            let mut child = Command::new(&exe)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            child.stdin.take().unwrap().write_all(bytes).unwrap();
+            child.wait_with_output().unwrap()
+        };
+
+        let mut bytes = Vec::<u8>::default();
+        MessageEncoder::new(&fmt, &mut bytes)
+            .encode(&message())
+            .unwrap();
+        let output = run(&bytes);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, bytes);
+
+        // Not enough bytes, and a corrupted message.
+        assert_eq!(run(&bytes[..bytes.len() - 1]).status.code(), Some(1));
+        bytes[2] ^= 1;
+        assert_eq!(run(&bytes).status.code(), Some(2));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
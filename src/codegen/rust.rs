@@ -0,0 +1,473 @@
+use std::fmt::Write;
+
+use crate::{
+    checksum::Checksum,
+    msg::{ItemFormat, MessageFormat, PayloadLen},
+};
+
+use super::{camel_case, checksum_bounds, item_idents};
+
+// Keywords can't be field names, nor can `_`.
+const KEYWORDS: [&str; 53] = [
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn uint_type(len: usize) -> &'static str {
+    match len {
+        1 => "u8",
+        2 => "u16",
+        3 | 4 => "u32",
+        _ => "u64",
+    }
+}
+
+fn int_type(len: usize) -> &'static str {
+    match len {
+        1 => "i8",
+        2 => "i16",
+        3 | 4 => "i32",
+        _ => "i64",
+    }
+}
+
+fn field_type(fmt: &ItemFormat) -> &'static str {
+    match fmt {
+        ItemFormat::Len { len } | ItemFormat::Uint { len } => uint_type(*len),
+        ItemFormat::Checksum { checksum, .. } => uint_type(checksum.width()),
+        ItemFormat::Int { len } => int_type(*len),
+        ItemFormat::FixedString { .. } | ItemFormat::VarString { .. } => "String",
+        ItemFormat::FixedBytes { .. } | ItemFormat::VarBytes { .. } => "Vec<u8>",
+        ItemFormat::Protobuf { .. }
+        | ItemFormat::Cbor { .. }
+        | ItemFormat::MessagePack { .. }
+        | ItemFormat::Json { .. }
+        | ItemFormat::Ber { .. }
+        | ItemFormat::Resp { .. }
+        | ItemFormat::Http { .. }
+        | ItemFormat::Stomp { .. }
+        | ItemFormat::Memcache { .. }
+        | ItemFormat::Tlv { .. } => "Vec<u8>",
+    }
+}
+
+// Convert an integer to or from the 64-bit one, without useless conversions.
+fn widen(ty: &str, expr: &str, wide: &str) -> String {
+    if ty == wide {
+        expr.to_string()
+    } else {
+        format!("{}::from({})", wide, expr)
+    }
+}
+
+fn narrow(ty: &str, expr: &str, wide: &str) -> String {
+    if ty == wide {
+        expr.to_string()
+    } else {
+        format!("{} as {}", expr, ty)
+    }
+}
+
+/// Generates a Rust struct named by `name` in camel case, with `encode` and `decode`
+/// producing the same bytes as `MessageEncoder` and `MessageDecoder`.
+///
+/// Fields are named by item names, or `item_N` if unnamed.
+pub fn rust_code(fmt: &MessageFormat, name: &str) -> String {
+    let ty = camel_case(name);
+    let idents = item_idents(fmt, &KEYWORDS);
+    // Offsets of these items are kept for checksums.
+    let bounds = checksum_bounds(fmt);
+    let mut code = String::new();
+
+    writeln!(code, "// Generated by socket_toolbox, format: `{}`.", fmt).unwrap();
+    writeln!(
+        code,
+        "// Integers are big-endian, strings and bytes are padded with zeros to their length."
+    )
+    .unwrap();
+    if fmt.iter().any(|f| f.payload_len().is_some()) {
+        writeln!(
+            code,
+            "// Payloads such as protobuf, CBOR and JSON are kept as encoded bytes."
+        )
+        .unwrap();
+    }
+    writeln!(code).unwrap();
+
+    // Struct.
+    writeln!(code, "#[derive(Debug, Clone, Default, PartialEq)]").unwrap();
+    writeln!(code, "pub struct {} {{", ty).unwrap();
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        writeln!(code, "    pub {}: {},", idents[idx], field_type(item_fmt)).unwrap();
+    }
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    // Error.
+    writeln!(code, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+    writeln!(code, "pub enum {}Error {{", ty).unwrap();
+    writeln!(code, "    EndOfBytes {{ item_idx: usize }},").unwrap();
+    writeln!(code, "    ValueTooLong {{ item_idx: usize }},").unwrap();
+    writeln!(code, "    FromUtf8 {{ item_idx: usize }},").unwrap();
+    if !bounds.is_empty() {
+        writeln!(code, "    ChecksumMismatch {{ item_idx: usize }},").unwrap();
+    }
+    writeln!(code, "}}").unwrap();
+    writeln!(code).unwrap();
+
+    writeln!(code, "impl {} {{", ty).unwrap();
+
+    // Encode.
+    writeln!(
+        code,
+        "    pub fn encode(&self) -> Result<Vec<u8>, {}Error> {{",
+        ty
+    )
+    .unwrap();
+    writeln!(code, "        let mut buf = Vec::new();").unwrap();
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        if bounds.contains(&idx) {
+            writeln!(code, "        let offset_{} = buf.len();", idx).unwrap();
+        }
+        let field = format!("self.{}", idents[idx]);
+        match item_fmt {
+            _ if matches!(item_fmt.payload_len(), Some(PayloadLen::Greedy)) => {
+                writeln!(code, "        buf.extend_from_slice(&{});", field)
+            }
+            ItemFormat::Len { len } | ItemFormat::Uint { len } | ItemFormat::Int { len } => {
+                let value = match item_fmt {
+                    ItemFormat::Int { .. } => widen(int_type(*len), &field, "i64"),
+                    _ => widen(uint_type(*len), &field, "u64"),
+                };
+                // Truncate the high bytes like `MessageEncoder`.
+                let range = if *len < 8 {
+                    format!("[{}..]", 8 - len)
+                } else {
+                    String::new()
+                };
+                writeln!(
+                    code,
+                    "        buf.extend_from_slice(&{}.to_be_bytes(){});",
+                    value, range
+                )
+            }
+            // The field is ignored like `MessageEncoder`.
+            ItemFormat::Checksum {
+                checksum,
+                start,
+                end,
+            } => writeln!(
+                code,
+                "        let item_{0} = Self::{1}(&buf[offset_{2}..offset_{3}]);\n        buf.extend_from_slice(&item_{0}.to_be_bytes()[{4}..]);",
+                idx,
+                checksum,
+                start,
+                end,
+                8 - checksum.width()
+            ),
+            _ => {
+                let value = match item_fmt {
+                    ItemFormat::FixedString { .. } | ItemFormat::VarString { .. } => {
+                        format!("{}.as_bytes()", field)
+                    }
+                    _ => format!("&{}", field),
+                };
+                let len = match (item_fmt, item_fmt.payload_len()) {
+                    (ItemFormat::FixedString { len } | ItemFormat::FixedBytes { len }, _)
+                    | (_, Some(PayloadLen::Fixed { len })) => len.to_string(),
+                    (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+                    | (_, Some(PayloadLen::Var { len_idx })) => {
+                        format!("self.{} as usize", idents[*len_idx])
+                    }
+                    _ => unreachable!(),
+                };
+                writeln!(
+                    code,
+                    "        Self::put_bytes(&mut buf, {}, {}, {})?;",
+                    idx, value, len
+                )
+            }
+        }
+        .unwrap();
+    }
+    writeln!(code, "        Ok(buf)").unwrap();
+    writeln!(code, "    }}").unwrap();
+    writeln!(code).unwrap();
+
+    // Decode.
+    writeln!(
+        code,
+        "    /// Returns the message and the count of bytes consumed."
+    )
+    .unwrap();
+    writeln!(
+        code,
+        "    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), {}Error> {{",
+        ty
+    )
+    .unwrap();
+    writeln!(code, "        let mut offset = 0;").unwrap();
+    for (idx, item_fmt) in fmt.iter().enumerate() {
+        if bounds.contains(&idx) {
+            writeln!(code, "        let offset_{} = offset;", idx).unwrap();
+        }
+        let get = |len: String| {
+            format!(
+                "Self::get_bytes(bytes, &mut offset, {}, {})?",
+                idx,
+                len.as_str()
+            )
+        };
+        let value = match item_fmt {
+            ItemFormat::Len { len } | ItemFormat::Uint { len } => {
+                let expr = format!("Self::get_uint({})", get(len.to_string()));
+                narrow(uint_type(*len), &expr, "u64")
+            }
+            ItemFormat::Int { len } => {
+                let expr = format!("Self::get_int({})", get(len.to_string()));
+                narrow(int_type(*len), &expr, "i64")
+            }
+            ItemFormat::Checksum {
+                checksum,
+                start,
+                end,
+            } => {
+                let len = checksum.width();
+                let expr = format!("Self::get_uint({})", get(len.to_string()));
+                writeln!(
+                    code,
+                    "        let item_{} = {};",
+                    idx,
+                    narrow(uint_type(len), &expr, "u64")
+                )
+                .unwrap();
+                writeln!(
+                    code,
+                    "        if {} != Self::{}(&bytes[offset_{}..offset_{}]) {{",
+                    widen(uint_type(len), &format!("item_{}", idx), "u64"),
+                    checksum,
+                    start,
+                    end
+                )
+                .unwrap();
+                writeln!(
+                    code,
+                    "            return Err({}Error::ChecksumMismatch {{ item_idx: {} }});",
+                    ty, idx
+                )
+                .unwrap();
+                writeln!(code, "        }}").unwrap();
+                continue;
+            }
+            ItemFormat::FixedString { len } => {
+                format!("Self::get_string({}, {})?", idx, get(len.to_string()))
+            }
+            ItemFormat::VarString { len_idx } => format!(
+                "Self::get_string({}, {})?",
+                idx,
+                get(format!("item_{} as usize", len_idx))
+            ),
+            ItemFormat::FixedBytes { len } => format!("{}.to_vec()", get(len.to_string())),
+            ItemFormat::VarBytes { len_idx } => {
+                format!("{}.to_vec()", get(format!("item_{} as usize", len_idx)))
+            }
+            ItemFormat::Protobuf { .. }
+            | ItemFormat::Cbor { .. }
+            | ItemFormat::MessagePack { .. }
+            | ItemFormat::Json { .. }
+            | ItemFormat::Ber { .. }
+            | ItemFormat::Resp { .. }
+            | ItemFormat::Http { .. }
+            | ItemFormat::Stomp { .. }
+            | ItemFormat::Memcache { .. }
+            | ItemFormat::Tlv { .. } => {
+                match item_fmt.payload_len().unwrap() {
+                    PayloadLen::Fixed { len } => format!("{}.to_vec()", get(len.to_string())),
+                    PayloadLen::Var { len_idx } => {
+                        format!("{}.to_vec()", get(format!("item_{} as usize", len_idx)))
+                    }
+                    // The rest of the bytes.
+                    PayloadLen::Greedy => {
+                        writeln!(code, "        let item_{} = bytes[offset..].to_vec();", idx)
+                            .unwrap();
+                        writeln!(code, "        offset = bytes.len();").unwrap();
+                        continue;
+                    }
+                }
+            }
+        };
+        writeln!(code, "        let item_{} = {};", idx, value).unwrap();
+    }
+    writeln!(code, "        Ok((").unwrap();
+    writeln!(code, "            Self {{").unwrap();
+    // Locals are named by indexes, so that names of items don't shadow them.
+    for (idx, ident) in idents.iter().enumerate() {
+        if *ident == format!("item_{}", idx) {
+            writeln!(code, "                {},", ident).unwrap();
+        } else {
+            writeln!(code, "                {}: item_{},", ident, idx).unwrap();
+        }
+    }
+    writeln!(code, "            }},").unwrap();
+    writeln!(code, "            offset,").unwrap();
+    writeln!(code, "        ))").unwrap();
+    writeln!(code, "    }}").unwrap();
+
+    // Helpers, only the used ones are generated.
+    let has = |f: fn(&ItemFormat) -> bool| fmt.iter().any(f);
+    if has(|f| {
+        matches!(
+            f,
+            ItemFormat::FixedString { .. }
+                | ItemFormat::VarString { .. }
+                | ItemFormat::FixedBytes { .. }
+                | ItemFormat::VarBytes { .. }
+        ) || matches!(
+            f.payload_len(),
+            Some(PayloadLen::Fixed { .. } | PayloadLen::Var { .. })
+        )
+    }) {
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "    fn put_bytes(buf: &mut Vec<u8>, item_idx: usize, value: &[u8], len: usize) -> Result<(), {}Error> {{",
+            ty
+        )
+        .unwrap();
+        writeln!(code, "        if value.len() > len {{").unwrap();
+        writeln!(
+            code,
+            "            return Err({}Error::ValueTooLong {{ item_idx }});",
+            ty
+        )
+        .unwrap();
+        writeln!(code, "        }}").unwrap();
+        writeln!(code, "        buf.extend_from_slice(value);").unwrap();
+        writeln!(
+            code,
+            "        buf.resize(buf.len() + len - value.len(), 0);"
+        )
+        .unwrap();
+        writeln!(code, "        Ok(())").unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    if has(|f| !matches!(f.payload_len(), Some(PayloadLen::Greedy))) {
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "    fn get_bytes<'a>(bytes: &'a [u8], offset: &mut usize, item_idx: usize, len: usize) -> Result<&'a [u8], {}Error> {{",
+        ty
+    )
+    .unwrap();
+        writeln!(
+        code,
+        "        let value = bytes.get(*offset..*offset + len).ok_or({}Error::EndOfBytes {{ item_idx }})?;",
+        ty
+    )
+    .unwrap();
+        writeln!(code, "        *offset += len;").unwrap();
+        writeln!(code, "        Ok(value)").unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    if has(|f| {
+        matches!(
+            f,
+            ItemFormat::Len { .. } | ItemFormat::Uint { .. } | ItemFormat::Checksum { .. }
+        )
+    }) {
+        writeln!(code).unwrap();
+        writeln!(code, "    fn get_uint(value: &[u8]) -> u64 {{").unwrap();
+        writeln!(
+            code,
+            "        value.iter().fold(0, |v, b| (v << 8) | u64::from(*b))"
+        )
+        .unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    if has(|f| matches!(f, ItemFormat::Int { .. })) {
+        writeln!(code).unwrap();
+        writeln!(code, "    fn get_int(value: &[u8]) -> i64 {{").unwrap();
+        writeln!(
+            code,
+            "        let sign = if value[0] & 0x80 != 0 {{ -1 }} else {{ 0 }};"
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "        value.iter().fold(sign, |v, b| (v << 8) | i64::from(*b))"
+        )
+        .unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    if has(|f| {
+        matches!(
+            f,
+            ItemFormat::FixedString { .. } | ItemFormat::VarString { .. }
+        )
+    }) {
+        writeln!(code).unwrap();
+        writeln!(
+            code,
+            "    fn get_string(item_idx: usize, value: &[u8]) -> Result<String, {}Error> {{",
+            ty
+        )
+        .unwrap();
+        writeln!(
+            code,
+            "        String::from_utf8(value.to_vec()).map_err(|_| {}Error::FromUtf8 {{ item_idx }})",
+            ty
+        )
+        .unwrap();
+        writeln!(code, "    }}").unwrap();
+    }
+
+    for checksum in &Checksum::ALL {
+        if fmt
+            .iter()
+            .any(|f| matches!(f, ItemFormat::Checksum { checksum: c, .. } if c == checksum))
+        {
+            writeln!(code).unwrap();
+            writeln!(code, "    fn {}(bytes: &[u8]) -> u64 {{", checksum).unwrap();
+            writeln!(code, "{}", checksum_body(*checksum)).unwrap();
+            writeln!(code, "    }}").unwrap();
+        }
+    }
+
+    writeln!(code, "}}").unwrap();
+
+    code
+}
+
+fn checksum_body(checksum: Checksum) -> &'static str {
+    match checksum {
+        Checksum::Sum8 => "        bytes.iter().fold(0u8, |s, b| s.wrapping_add(*b)).into()",
+        Checksum::Xor8 => "        bytes.iter().fold(0u8, |s, b| s ^ b).into()",
+        Checksum::Crc16 => {
+            "        let mut crc = 0xffffu16;
+        for b in bytes {
+            crc ^= u16::from(*b);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xa001 } else { crc >> 1 };
+            }
+        }
+        crc.into()"
+        }
+        Checksum::Crc32 => {
+            "        let mut crc = 0xffff_ffffu32;
+        for b in bytes {
+            crc ^= u32::from(*b);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+        (!crc).into()"
+        }
+    }
+}
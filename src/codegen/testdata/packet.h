@@ -0,0 +1,148 @@
+/* Generated by socket_toolbox, format: `len:len16; type:u8; i24; stamp:i64; tag:str[4]; name:str[len]; bytes[3]; data:bytes[len]; crc:crc16(1..8)`. */
+/* Integers are big-endian, strings and bytes are padded with zeros to their length. */
+
+#ifndef PACKET_H
+#define PACKET_H
+
+#include <stddef.h>
+#include <stdint.h>
+#include <string.h>
+
+typedef struct {
+    uint16_t len;
+    uint8_t type;
+    int32_t item_2;
+    int64_t stamp;
+    char tag[4];
+    const char *name;
+    size_t name_len;
+    uint8_t item_6[3];
+    const uint8_t *data;
+    size_t data_len;
+    uint16_t crc;
+} packet_t;
+
+static inline uint64_t packet_crc16(const uint8_t *p, size_t n) {
+    uint16_t crc = 0xffff;
+    int i;
+    while (n--) {
+        crc ^= *p++;
+        for (i = 0; i < 8; i++) crc = (crc & 1) ? (uint16_t)((crc >> 1) ^ 0xa001) : (uint16_t)(crc >> 1);
+    }
+    return crc;
+}
+
+/* Returns the count of bytes written, or -1 if the buffer is too small or a value is too long. */
+static inline long packet_pack(const packet_t *msg, uint8_t *buf, size_t size) {
+    size_t offset = 0;
+    size_t len;
+    size_t n;
+    size_t offset_1;
+    size_t offset_8;
+    uint64_t v;
+    int i;
+    if (size - offset < 2) return -1;
+    for (i = 0; i < 2; i++) {
+        buf[offset++] = (uint8_t)((uint64_t)msg->len >> (8 * (2 - 1 - i)));
+    }
+    offset_1 = offset;
+    if (size - offset < 1) return -1;
+    for (i = 0; i < 1; i++) {
+        buf[offset++] = (uint8_t)((uint64_t)msg->type >> (8 * (1 - 1 - i)));
+    }
+    if (size - offset < 3) return -1;
+    for (i = 0; i < 3; i++) {
+        buf[offset++] = (uint8_t)((uint64_t)msg->item_2 >> (8 * (3 - 1 - i)));
+    }
+    if (size - offset < 8) return -1;
+    for (i = 0; i < 8; i++) {
+        buf[offset++] = (uint8_t)((uint64_t)msg->stamp >> (8 * (8 - 1 - i)));
+    }
+    if (size - offset < 4) return -1;
+    for (n = 0; n < 4 && msg->tag[n]; n++) buf[offset + n] = (uint8_t)msg->tag[n];
+    memset(buf + offset + n, 0, 4 - n);
+    offset += 4;
+    len = (size_t)msg->len;
+    if (size - offset < len || msg->name_len > len) return -1;
+    memset(buf + offset, 0, len);
+    memcpy(buf + offset, msg->name, msg->name_len);
+    offset += len;
+    if (size - offset < 3) return -1;
+    memcpy(buf + offset, msg->item_6, 3);
+    offset += 3;
+    len = (size_t)msg->len;
+    if (size - offset < len || msg->data_len > len) return -1;
+    memset(buf + offset, 0, len);
+    memcpy(buf + offset, msg->data, msg->data_len);
+    offset += len;
+    offset_8 = offset;
+    if (size - offset < 2) return -1;
+    v = packet_crc16(buf + offset_1, offset_8 - offset_1);
+    for (i = 0; i < 2; i++) {
+        buf[offset++] = (uint8_t)(v >> (8 * (2 - 1 - i)));
+    }
+    return (long)offset;
+}
+
+/* Returns the count of bytes read, -1 if there are not enough bytes, or -2 if a checksum mismatches. */
+static inline long packet_unpack(packet_t *msg, const uint8_t *buf, size_t size) {
+    size_t offset = 0;
+    size_t len;
+    size_t offset_1;
+    size_t offset_8;
+    uint64_t v;
+    int i;
+    if (size - offset < 2) return -1;
+    v = 0;
+    for (i = 0; i < 2; i++) {
+        v = (v << 8) | buf[offset++];
+    }
+    msg->len = (uint16_t)v;
+    offset_1 = offset;
+    if (size - offset < 1) return -1;
+    v = 0;
+    for (i = 0; i < 1; i++) {
+        v = (v << 8) | buf[offset++];
+    }
+    msg->type = (uint8_t)v;
+    if (size - offset < 3) return -1;
+    v = 0;
+    for (i = 0; i < 3; i++) {
+        v = (v << 8) | buf[offset++];
+    }
+    if ((v >> 23) & 1) v |= ~(uint64_t)0 << 24;
+    msg->item_2 = (int32_t)v;
+    if (size - offset < 8) return -1;
+    v = 0;
+    for (i = 0; i < 8; i++) {
+        v = (v << 8) | buf[offset++];
+    }
+    msg->stamp = (int64_t)v;
+    if (size - offset < 4) return -1;
+    memcpy(msg->tag, buf + offset, 4);
+    offset += 4;
+    len = (size_t)msg->len;
+    if (size - offset < len) return -1;
+    msg->name = (const char *)(buf + offset);
+    msg->name_len = len;
+    offset += len;
+    if (size - offset < 3) return -1;
+    memcpy(msg->item_6, buf + offset, 3);
+    offset += 3;
+    len = (size_t)msg->len;
+    if (size - offset < len) return -1;
+    msg->data = (const uint8_t *)(buf + offset);
+    msg->data_len = len;
+    offset += len;
+    offset_8 = offset;
+    if (size - offset < 2) return -1;
+    v = 0;
+    for (i = 0; i < 2; i++) {
+        v = (v << 8) | buf[offset++];
+    }
+    msg->crc = (uint16_t)v;
+    if (v != packet_crc16(buf + offset_1, offset_8 - offset_1)) return -2;
+    return (long)offset;
+}
+
+#endif
@@ -0,0 +1,121 @@
+-- Generated by socket_toolbox, format: `len:len16; type:u8; i24; stamp:i64; tag:str[4]; name:str[len]; bytes[3]; data:bytes[len]; crc:crc16(1..8)`.
+-- Integers are big-endian.
+
+local proto = Proto("packet", "packet (socket_toolbox)")
+
+local f_len = ProtoField.uint16("packet.len", "len (Len)", base.DEC)
+local f_type = ProtoField.uint8("packet.type", "type (Uint)", base.DEC)
+local f_item_2 = ProtoField.int24("packet.item_2", "item_2 (Int)", base.DEC)
+local f_stamp = ProtoField.int64("packet.stamp", "stamp (Int)", base.DEC)
+local f_tag = ProtoField.string("packet.tag", "tag (FixedString)")
+local f_name = ProtoField.string("packet.name", "name (VarString, length: len)")
+local f_item_6 = ProtoField.bytes("packet.item_6", "item_6 (FixedBytes)")
+local f_data = ProtoField.bytes("packet.data", "data (VarBytes, length: len)")
+local f_crc = ProtoField.uint16("packet.crc", "crc (Checksum, crc16 of type..crc)", base.HEX)
+
+proto.fields = {
+    f_len,
+    f_type,
+    f_item_2,
+    f_stamp,
+    f_tag,
+    f_name,
+    f_item_6,
+    f_data,
+    f_crc,
+}
+
+local function dissect_message(buffer, offset, tree)
+    local start = offset
+    local len
+    local values = {}
+    local items = {}
+    len = 2
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    values[0] = buffer(offset, len):uint()
+    items[#items + 1] = { f_len, offset, len, values[0] }
+    offset = offset + len
+    len = 1
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    values[1] = buffer(offset, len):uint()
+    items[#items + 1] = { f_type, offset, len, values[1] }
+    offset = offset + len
+    len = 3
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    values[2] = buffer(offset, len):int()
+    items[#items + 1] = { f_item_2, offset, len, values[2] }
+    offset = offset + len
+    len = 8
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    values[3] = buffer(offset, len):int64()
+    items[#items + 1] = { f_stamp, offset, len, values[3] }
+    offset = offset + len
+    len = 4
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    items[#items + 1] = { f_tag, offset, len }
+    offset = offset + len
+    len = values[0]
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    items[#items + 1] = { f_name, offset, len }
+    offset = offset + len
+    len = 3
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    items[#items + 1] = { f_item_6, offset, len }
+    offset = offset + len
+    len = values[0]
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    items[#items + 1] = { f_data, offset, len }
+    offset = offset + len
+    len = 2
+    if buffer:len() - offset < len then
+        return nil, len - (buffer:len() - offset)
+    end
+    values[8] = buffer(offset, len):uint()
+    items[#items + 1] = { f_crc, offset, len, values[8] }
+    offset = offset + len
+
+    local subtree = tree:add(proto, buffer(start, offset - start))
+    for _, item in ipairs(items) do
+        if item[3] > 0 then
+            if item[4] ~= nil then
+                subtree:add(item[1], buffer(item[2], item[3]), item[4])
+            else
+                subtree:add(item[1], buffer(item[2], item[3]))
+            end
+        end
+    end
+    return offset - start
+end
+
+function proto.dissector(buffer, pinfo, tree)
+    pinfo.cols.protocol = proto.name
+    local offset = 0
+    while offset < buffer:len() do
+        local len, missing = dissect_message(buffer, offset, tree)
+        if len == nil then
+            pinfo.desegment_offset = offset
+            pinfo.desegment_len = missing
+            return buffer:len()
+        end
+        offset = offset + len
+    end
+    return offset
+end
+
+DissectorTable.get("tcp.port"):add(9000, proto)
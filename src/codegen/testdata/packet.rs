@@ -0,0 +1,114 @@
+// Generated by socket_toolbox, format: `len:len16; type:u8; i24; stamp:i64; tag:str[4]; name:str[len]; bytes[3]; data:bytes[len]; crc:crc16(1..8)`.
+// Integers are big-endian, strings and bytes are padded with zeros to their length.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Packet {
+    pub len: u16,
+    pub type_: u8,
+    pub item_2: i32,
+    pub stamp: i64,
+    pub tag: String,
+    pub name: String,
+    pub item_6: Vec<u8>,
+    pub data: Vec<u8>,
+    pub crc: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PacketError {
+    EndOfBytes { item_idx: usize },
+    ValueTooLong { item_idx: usize },
+    FromUtf8 { item_idx: usize },
+    ChecksumMismatch { item_idx: usize },
+}
+
+impl Packet {
+    pub fn encode(&self) -> Result<Vec<u8>, PacketError> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&u64::from(self.len).to_be_bytes()[6..]);
+        let offset_1 = buf.len();
+        buf.extend_from_slice(&u64::from(self.type_).to_be_bytes()[7..]);
+        buf.extend_from_slice(&i64::from(self.item_2).to_be_bytes()[5..]);
+        buf.extend_from_slice(&self.stamp.to_be_bytes());
+        Self::put_bytes(&mut buf, 4, self.tag.as_bytes(), 4)?;
+        Self::put_bytes(&mut buf, 5, self.name.as_bytes(), self.len as usize)?;
+        Self::put_bytes(&mut buf, 6, &self.item_6, 3)?;
+        Self::put_bytes(&mut buf, 7, &self.data, self.len as usize)?;
+        let offset_8 = buf.len();
+        let item_8 = Self::crc16(&buf[offset_1..offset_8]);
+        buf.extend_from_slice(&item_8.to_be_bytes()[6..]);
+        Ok(buf)
+    }
+
+    /// Returns the message and the count of bytes consumed.
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), PacketError> {
+        let mut offset = 0;
+        let item_0 = Self::get_uint(Self::get_bytes(bytes, &mut offset, 0, 2)?) as u16;
+        let offset_1 = offset;
+        let item_1 = Self::get_uint(Self::get_bytes(bytes, &mut offset, 1, 1)?) as u8;
+        let item_2 = Self::get_int(Self::get_bytes(bytes, &mut offset, 2, 3)?) as i32;
+        let item_3 = Self::get_int(Self::get_bytes(bytes, &mut offset, 3, 8)?);
+        let item_4 = Self::get_string(4, Self::get_bytes(bytes, &mut offset, 4, 4)?)?;
+        let item_5 = Self::get_string(5, Self::get_bytes(bytes, &mut offset, 5, item_0 as usize)?)?;
+        let item_6 = Self::get_bytes(bytes, &mut offset, 6, 3)?.to_vec();
+        let item_7 = Self::get_bytes(bytes, &mut offset, 7, item_0 as usize)?.to_vec();
+        let offset_8 = offset;
+        let item_8 = Self::get_uint(Self::get_bytes(bytes, &mut offset, 8, 2)?) as u16;
+        if u64::from(item_8) != Self::crc16(&bytes[offset_1..offset_8]) {
+            return Err(PacketError::ChecksumMismatch { item_idx: 8 });
+        }
+        Ok((
+            Self {
+                len: item_0,
+                type_: item_1,
+                item_2,
+                stamp: item_3,
+                tag: item_4,
+                name: item_5,
+                item_6,
+                data: item_7,
+                crc: item_8,
+            },
+            offset,
+        ))
+    }
+
+    fn put_bytes(buf: &mut Vec<u8>, item_idx: usize, value: &[u8], len: usize) -> Result<(), PacketError> {
+        if value.len() > len {
+            return Err(PacketError::ValueTooLong { item_idx });
+        }
+        buf.extend_from_slice(value);
+        buf.resize(buf.len() + len - value.len(), 0);
+        Ok(())
+    }
+
+    fn get_bytes<'a>(bytes: &'a [u8], offset: &mut usize, item_idx: usize, len: usize) -> Result<&'a [u8], PacketError> {
+        let value = bytes.get(*offset..*offset + len).ok_or(PacketError::EndOfBytes { item_idx })?;
+        *offset += len;
+        Ok(value)
+    }
+
+    fn get_uint(value: &[u8]) -> u64 {
+        value.iter().fold(0, |v, b| (v << 8) | u64::from(*b))
+    }
+
+    fn get_int(value: &[u8]) -> i64 {
+        let sign = if value[0] & 0x80 != 0 { -1 } else { 0 };
+        value.iter().fold(sign, |v, b| (v << 8) | i64::from(*b))
+    }
+
+    fn get_string(item_idx: usize, value: &[u8]) -> Result<String, PacketError> {
+        String::from_utf8(value.to_vec()).map_err(|_| PacketError::FromUtf8 { item_idx })
+    }
+
+    fn crc16(bytes: &[u8]) -> u64 {
+        let mut crc = 0xffffu16;
+        for b in bytes {
+            crc ^= u16::from(*b);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xa001 } else { crc >> 1 };
+            }
+        }
+        crc.into()
+    }
+}
@@ -0,0 +1,739 @@
+//! The format DSL, such as `len:u16; cmd:u8; name:str[len]`, which is parsed to a
+//! `MessageFormat` by `FromStr` and printed by `Display`.
+
+use std::{collections::HashMap, fmt, ops::Range, str::FromStr};
+
+use crate::{
+    checksum::Checksum,
+    error::{Error, Result},
+    msg::{ItemFormat, MessageFormat, PayloadLen},
+};
+
+// Text syntax of a MessageFormat, items are separated by `;`:
+//
+// ``` text
+// item  := [name ':'] kind [order]
+// kind  := 'u'BITS | 'i'BITS | 'len'BITS | 'str' '[' size ']' | 'bytes' '[' size ']'
+//        | 'cbor' '[' size ']' | 'msgpack' '[' size ']' | 'json' '[' size ']'
+//        | 'ber' '[' size ']' | 'tlv'BITS'_'BITS '[' size ']'
+//        | checksum '(' INDEX '..' INDEX ')'
+// size  := LEN | name | '#'INDEX | '*'
+// order := 'be'
+// checksum := 'sum8' | 'xor8' | 'crc16' | 'crc32'
+// ```
+//
+// For example `len:u16 be; cmd:u8; name:str[len]; data:bytes[#0]; body:cbor[*]`.
+// A checksum covers the items in the range before it, such as `crc:crc16(0..3)`.
+// An unsigned integer referenced by the size of a string or bytes becomes a Len.
+// `*` is the rest of the message, only for payloads, and only JSON can have a fixed LEN.
+// The bits of `tlv` are the widths of tags and lengths, such as `tlv8_16[*]`.
+// Protobuf items are printed as `proto[#INDEX]` or `proto[*]`, but they can't be parsed,
+// since the schema is not part of the text.
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Ident(String),
+    Integer(usize),
+    Colon,
+    Semicolon,
+    Hash,
+    Star,
+    LeftBracket,
+    RightBracket,
+    LeftParen,
+    RightParen,
+    DotDot,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn syntax_error(span: Range<usize>, details: impl Into<String>) -> Error {
+    Error::FormatSyntax {
+        start: span.start,
+        end: span.end,
+        details: details.into(),
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            ':' => TokenKind::Colon,
+            ';' => TokenKind::Semicolon,
+            '#' => TokenKind::Hash,
+            '*' => TokenKind::Star,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '.' if chars.peek().map(|&(_, c)| c) == Some('.') => {
+                chars.next();
+                TokenKind::DotDot
+            }
+            c if c.is_ascii_alphanumeric() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some(&(idx, c)) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    end = idx + c.len_utf8();
+                    chars.next();
+                }
+
+                let word = &s[start..end];
+                if c.is_ascii_digit() {
+                    let v = word
+                        .parse()
+                        .map_err(|_| syntax_error(start..end, "invalid integer"))?;
+                    TokenKind::Integer(v)
+                } else {
+                    TokenKind::Ident(word.to_string())
+                }
+            }
+            c => {
+                return Err(syntax_error(
+                    start..start + c.len_utf8(),
+                    format!("unexpected character `{}`", c),
+                ))
+            }
+        };
+        tokens.push(Token {
+            kind,
+            span: start..chars.peek().map(|&(idx, _)| idx).unwrap_or(s.len()),
+        });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        span: s.len()..s.len(),
+    });
+
+    Ok(tokens)
+}
+
+enum Size {
+    Fixed(usize),
+    Name(String),
+    Index(usize),
+    Rest,
+}
+
+enum Kind {
+    Uint(usize),
+    Int(usize),
+    Len(usize),
+    String(Size),
+    Bytes(Size),
+    Cbor(Size),
+    MessagePack(Size),
+    Json(Size),
+    Ber(Size),
+    Resp(Size),
+    Http(Size),
+    Stomp(Size),
+    Memcache(Size),
+    Tlv(Size, usize, usize),
+    Checksum(Checksum, usize, usize),
+}
+
+impl Kind {
+    fn size(&self) -> Option<&Size> {
+        match self {
+            Kind::Uint(_) | Kind::Int(_) | Kind::Len(_) | Kind::Checksum(..) => None,
+            Kind::String(size)
+            | Kind::Bytes(size)
+            | Kind::Cbor(size)
+            | Kind::MessagePack(size)
+            | Kind::Json(size)
+            | Kind::Ber(size)
+            | Kind::Resp(size)
+            | Kind::Http(size)
+            | Kind::Stomp(size)
+            | Kind::Memcache(size)
+            | Kind::Tlv(size, ..) => Some(size),
+        }
+    }
+}
+
+struct Item {
+    name: Option<String>,
+    kind: Kind,
+    kind_span: Range<usize>,
+    size_span: Range<usize>,
+    span: Range<usize>,
+}
+
+impl Item {
+    fn fixed_len(&self) -> usize {
+        match &self.kind {
+            Kind::String(Size::Fixed(len)) | Kind::Bytes(Size::Fixed(len)) => *len,
+            _ => unreachable!(),
+        }
+    }
+
+    fn payload_len(&self, len_idx: Option<usize>) -> PayloadLen {
+        match (self.kind.size(), len_idx) {
+            (_, Some(len_idx)) => PayloadLen::Var { len_idx },
+            (Some(Size::Fixed(len)), _) => PayloadLen::Fixed { len: *len },
+            _ => PayloadLen::Greedy,
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos]
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].clone();
+        if token.kind != TokenKind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token> {
+        let token = self.next();
+        if token.kind == kind {
+            Ok(token)
+        } else {
+            Err(syntax_error(token.span, format!("expected {}", expected)))
+        }
+    }
+
+    // The size in brackets, and the span of it.
+    fn parse_size(&mut self) -> Result<(Size, Range<usize>)> {
+        self.expect(TokenKind::LeftBracket, "`[`")?;
+        let token = self.next();
+        let mut size_span = token.span.clone();
+        let size = match token.kind {
+            TokenKind::Integer(len) => Size::Fixed(len),
+            TokenKind::Ident(name) => Size::Name(name),
+            TokenKind::Star => Size::Rest,
+            TokenKind::Hash => {
+                let token = self.next();
+                size_span.end = token.span.end;
+                match token.kind {
+                    TokenKind::Integer(idx) => Size::Index(idx),
+                    _ => return Err(syntax_error(token.span, "expected an index")),
+                }
+            }
+            _ => {
+                return Err(syntax_error(
+                    token.span,
+                    "expected a length, a name or an index",
+                ))
+            }
+        };
+        self.expect(TokenKind::RightBracket, "`]`")?;
+        Ok((size, size_span))
+    }
+
+    // The range of items in parentheses, and the span of it.
+    fn parse_range(&mut self) -> Result<(usize, usize, Range<usize>)> {
+        self.expect(TokenKind::LeftParen, "`(`")?;
+        let mut bounds = [0usize; 2];
+        let mut span = self.peek().span.clone();
+        for (i, bound) in bounds.iter_mut().enumerate() {
+            if i == 1 {
+                self.expect(TokenKind::DotDot, "`..`")?;
+            }
+            let token = self.next();
+            span.end = token.span.end;
+            match token.kind {
+                TokenKind::Integer(idx) => *bound = idx,
+                _ => return Err(syntax_error(token.span, "expected an index")),
+            }
+        }
+        self.expect(TokenKind::RightParen, "`)`")?;
+        Ok((bounds[0], bounds[1], span))
+    }
+
+    fn parse_items(&mut self) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        loop {
+            if self.peek().kind == TokenKind::End {
+                break;
+            }
+            items.push(self.parse_item()?);
+
+            let token = self.next();
+            match token.kind {
+                TokenKind::Semicolon => {}
+                TokenKind::End => break,
+                _ => return Err(syntax_error(token.span, "expected `;`")),
+            }
+        }
+        Ok(items)
+    }
+
+    fn parse_item(&mut self) -> Result<Item> {
+        let start = self.peek().span.start;
+
+        // The optional name is an identifier followed by a colon.
+        let mut name = None;
+        if let (TokenKind::Ident(ident), TokenKind::Colon) =
+            (&self.peek().kind, &self.tokens[self.pos + 1].kind)
+        {
+            name = Some(ident.clone());
+            self.pos += 2;
+        }
+
+        let token = self.next();
+        let kind_span = token.span.clone();
+        let word = match token.kind {
+            TokenKind::Ident(word) => word,
+            _ => return Err(syntax_error(token.span, "expected a kind of item")),
+        };
+
+        let mut size_span = kind_span.clone();
+        let kind = match word.as_str() {
+            "proto" => {
+                return Err(syntax_error(
+                    kind_span,
+                    "protobuf items can only be imported from a .proto file",
+                ))
+            }
+            "str" | "bytes" | "cbor" | "msgpack" | "json" | "ber" | "resp" | "http" | "stomp"
+            | "memcache" => {
+                let (size, span) = self.parse_size()?;
+                size_span = span;
+
+                if let (Size::Rest, "str" | "bytes") = (&size, word.as_str()) {
+                    return Err(syntax_error(
+                        size_span,
+                        "only payloads can take the rest of the message",
+                    ));
+                }
+
+                match word.as_str() {
+                    "str" => Kind::String(size),
+                    "bytes" => Kind::Bytes(size),
+                    "cbor" => Kind::Cbor(size),
+                    "msgpack" => Kind::MessagePack(size),
+                    "json" => Kind::Json(size),
+                    "ber" => Kind::Ber(size),
+                    "resp" => Kind::Resp(size),
+                    "http" => Kind::Http(size),
+                    "stomp" => Kind::Stomp(size),
+                    _ => Kind::Memcache(size),
+                }
+            }
+            _ if Checksum::from_name(&word).is_some() => {
+                let (start, end, span) = self.parse_range()?;
+                size_span = span;
+                Kind::Checksum(Checksum::from_name(&word).unwrap(), start, end)
+            }
+            _ if word.starts_with("tlv") => {
+                let widths = word[3..]
+                    .split_once('_')
+                    .and_then(|(tag_bits, len_bits)| {
+                        Some((
+                            tag_bits.parse::<usize>().ok()?,
+                            len_bits.parse::<usize>().ok()?,
+                        ))
+                    })
+                    .ok_or_else(|| {
+                        syntax_error(
+                            kind_span.clone(),
+                            "expected the bits of tags and lengths, such as `tlv8_16`",
+                        )
+                    })?;
+                if widths.0 % u8::BITS as usize != 0 || widths.1 % u8::BITS as usize != 0 {
+                    return Err(syntax_error(
+                        kind_span,
+                        format!("the bits of `{}` should be multiples of 8", word),
+                    ));
+                }
+
+                let (size, span) = self.parse_size()?;
+                size_span = span;
+                Kind::Tlv(
+                    size,
+                    widths.0 / u8::BITS as usize,
+                    widths.1 / u8::BITS as usize,
+                )
+            }
+            _ => {
+                let (prefix, bits) = ["len", "u", "i"]
+                    .iter()
+                    .find_map(|prefix| {
+                        word.strip_prefix(prefix)
+                            .and_then(|bits| bits.parse::<usize>().ok())
+                            .map(|bits| (*prefix, bits))
+                    })
+                    .ok_or_else(|| {
+                        syntax_error(kind_span.clone(), format!("unknown kind `{}`", word))
+                    })?;
+                if bits == 0 || bits % u8::BITS as usize != 0 {
+                    return Err(syntax_error(
+                        kind_span,
+                        format!("the bits of `{}` should be a multiple of 8", word),
+                    ));
+                }
+
+                let len = bits / u8::BITS as usize;
+                match prefix {
+                    "len" => Kind::Len(len),
+                    "u" => Kind::Uint(len),
+                    _ => Kind::Int(len),
+                }
+            }
+        };
+
+        // The byte order is optional, and only big-endian integers are supported.
+        if let TokenKind::Ident(order) = &self.peek().kind {
+            let is_integer = matches!(kind, Kind::Uint(_) | Kind::Int(_) | Kind::Len(_));
+            let span = self.peek().span.clone();
+            match order.as_str() {
+                "be" if is_integer => {
+                    self.next();
+                }
+                "be" | "le" if !is_integer => {
+                    return Err(syntax_error(span, "byte order only applies to integers"))
+                }
+                "le" => return Err(syntax_error(span, "integers are always big-endian")),
+                _ => return Err(syntax_error(span, "expected `be` or `;`")),
+            }
+        }
+
+        let end = self.tokens[self.pos - 1].span.end;
+        Ok(Item {
+            name,
+            kind,
+            kind_span,
+            size_span,
+            span: start..end,
+        })
+    }
+}
+
+impl FromStr for MessageFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let items = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        }
+        .parse_items()?;
+
+        // Resolve the index of length referenced by each item.
+        let mut idx_by_name = HashMap::<&str, usize>::default();
+        let mut len_indices = Vec::<Option<usize>>::with_capacity(items.len());
+        for (idx, item) in items.iter().enumerate() {
+            let len_idx = match item.kind.size() {
+                Some(Size::Name(name)) => {
+                    Some(*idx_by_name.get(name.as_str()).ok_or_else(|| {
+                        syntax_error(
+                            item.size_span.clone(),
+                            format!("there is no item named `{}` before", name),
+                        )
+                    })?)
+                }
+                Some(Size::Index(len_idx)) => Some(*len_idx),
+                _ => None,
+            };
+            len_indices.push(len_idx);
+
+            if let Some(name) = &item.name {
+                if idx_by_name.insert(name, idx).is_some() {
+                    return Err(syntax_error(
+                        item.span.clone(),
+                        format!("the name `{}` is defined more than once", name),
+                    ));
+                }
+            }
+        }
+
+        let fmts = items
+            .iter()
+            .zip(len_indices.iter())
+            .enumerate()
+            .map(|(idx, (item, len_idx))| match (&item.kind, *len_idx) {
+                (Kind::Len(len), _) => ItemFormat::Len { len: *len },
+                // Unsigned integers referenced as lengths are Len.
+                (Kind::Uint(len), _) if len_indices.contains(&Some(idx)) => {
+                    ItemFormat::Len { len: *len }
+                }
+                (Kind::Uint(len), _) => ItemFormat::Uint { len: *len },
+                (Kind::Int(len), _) => ItemFormat::Int { len: *len },
+                (Kind::String(_), Some(len_idx)) => ItemFormat::VarString { len_idx },
+                (Kind::String(_), None) => ItemFormat::FixedString {
+                    len: item.fixed_len(),
+                },
+                (Kind::Bytes(_), Some(len_idx)) => ItemFormat::VarBytes { len_idx },
+                (Kind::Bytes(_), None) => ItemFormat::FixedBytes {
+                    len: item.fixed_len(),
+                },
+                (Kind::Cbor(_), len_idx) => ItemFormat::Cbor {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::MessagePack(_), len_idx) => ItemFormat::MessagePack {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Json(_), len_idx) => ItemFormat::Json {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Ber(_), len_idx) => ItemFormat::Ber {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Resp(_), len_idx) => ItemFormat::Resp {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Http(_), len_idx) => ItemFormat::Http {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Stomp(_), len_idx) => ItemFormat::Stomp {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Memcache(_), len_idx) => ItemFormat::Memcache {
+                    len: item.payload_len(len_idx),
+                },
+                (Kind::Tlv(_, tag_width, len_width), len_idx) => ItemFormat::Tlv {
+                    len: item.payload_len(len_idx),
+                    tag_width: *tag_width,
+                    len_width: *len_width,
+                },
+                (Kind::Checksum(checksum, start, end), _) => ItemFormat::Checksum {
+                    checksum: *checksum,
+                    start: *start,
+                    end: *end,
+                },
+            })
+            .collect::<Vec<_>>();
+
+        // Point validation errors to the item.
+        MessageFormat::new(&fmts)
+            .and_then(|fmt| fmt.with_names(items.iter().map(|item| item.name.clone())))
+            .map_err(|e| {
+                let span = match &e {
+                    Error::MessageFormatEmpty => 0..s.len(),
+                    Error::LenTooSmall { item_idx, .. } | Error::LenTooLarge { item_idx, .. } => {
+                        items[*item_idx].kind_span.clone()
+                    }
+                    Error::LenIdxTooLarge { item_idx, .. }
+                    | Error::NotALen { item_idx, .. }
+                    | Error::GreedyNotLast { item_idx }
+                    | Error::FixedPayload { item_idx }
+                    | Error::InvalidChecksumRange { item_idx, .. } => {
+                        items[*item_idx].size_span.clone()
+                    }
+                    Error::InvalidItemName { item_idx, .. } => items[*item_idx].span.clone(),
+                    _ => 0..s.len(),
+                };
+                syntax_error(span, e.to_string())
+            })
+    }
+}
+
+// The name of the length item, or its index.
+struct LenRef<'a>(&'a MessageFormat, usize);
+
+impl fmt::Display for LenRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.name(self.1) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "#{}", self.1),
+        }
+    }
+}
+
+struct PayloadSize<'a>(&'a MessageFormat, &'a PayloadLen);
+
+impl fmt::Display for PayloadSize<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            PayloadLen::Fixed { len } => write!(f, "{}", len),
+            PayloadLen::Var { len_idx } => write!(f, "{}", LenRef(self.0, *len_idx)),
+            PayloadLen::Greedy => write!(f, "*"),
+        }
+    }
+}
+
+/// Items are printed with their names, and sizes refer to named lengths by name.
+impl fmt::Display for MessageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, item_fmt) in self.iter().enumerate() {
+            if idx != 0 {
+                write!(f, "; ")?;
+            }
+            if let Some(name) = self.name(idx) {
+                write!(f, "{}:", name)?;
+            }
+            let size = |len| PayloadSize(self, len);
+            match item_fmt {
+                ItemFormat::Len { len } => write!(f, "len{}", len * u8::BITS as usize)?,
+                ItemFormat::Uint { len } => write!(f, "u{}", len * u8::BITS as usize)?,
+                ItemFormat::Int { len } => write!(f, "i{}", len * u8::BITS as usize)?,
+                ItemFormat::FixedString { len } => write!(f, "str[{}]", len)?,
+                ItemFormat::VarString { len_idx } => write!(f, "str[{}]", LenRef(self, *len_idx))?,
+                ItemFormat::FixedBytes { len } => write!(f, "bytes[{}]", len)?,
+                ItemFormat::VarBytes { len_idx } => write!(f, "bytes[{}]", LenRef(self, *len_idx))?,
+                ItemFormat::Checksum {
+                    checksum,
+                    start,
+                    end,
+                } => write!(f, "{}({}..{})", checksum, start, end)?,
+                ItemFormat::Protobuf { len, .. } => write!(f, "proto[{}]", size(len))?,
+                ItemFormat::Cbor { len } => write!(f, "cbor[{}]", size(len))?,
+                ItemFormat::MessagePack { len } => write!(f, "msgpack[{}]", size(len))?,
+                ItemFormat::Json { len } => write!(f, "json[{}]", size(len))?,
+                ItemFormat::Ber { len } => write!(f, "ber[{}]", size(len))?,
+                ItemFormat::Resp { len } => write!(f, "resp[{}]", size(len))?,
+                ItemFormat::Http { len } => write!(f, "http[{}]", size(len))?,
+                ItemFormat::Stomp { len } => write!(f, "stomp[{}]", size(len))?,
+                ItemFormat::Memcache { len } => write!(f, "memcache[{}]", size(len))?,
+                ItemFormat::Tlv {
+                    len,
+                    tag_width,
+                    len_width,
+                } => write!(
+                    f,
+                    "tlv{}_{}[{}]",
+                    tag_width * u8::BITS as usize,
+                    len_width * u8::BITS as usize,
+                    size(len)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        checksum::Checksum,
+        error::Error,
+        msg::{ItemFormat, MessageFormat},
+    };
+
+    #[test]
+    fn parse_and_print_ok() {
+        let fmt: MessageFormat = "len:u16 be; cmd:u8; name:str[len]; i32; bytes[#0]; str[4]"
+            .parse()
+            .unwrap();
+
+        let names = ["len", "cmd", "name"].iter().map(|n| Some(n.to_string()));
+        assert_eq!(
+            fmt,
+            MessageFormat::new(&[
+                ItemFormat::Len { len: 2 },
+                ItemFormat::Uint { len: 1 },
+                ItemFormat::VarString { len_idx: 0 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::VarBytes { len_idx: 0 },
+                ItemFormat::FixedString { len: 4 },
+            ])
+            .and_then(|fmt| fmt.with_names(names))
+            .unwrap()
+        );
+
+        // Names are kept when printed.
+        assert_eq!(
+            fmt.to_string(),
+            "len:len16; cmd:u8; name:str[len]; i32; bytes[len]; str[4]"
+        );
+        assert_eq!(fmt, fmt.to_string().parse().unwrap());
+
+        let fmt: MessageFormat = "len:u8; meta:msgpack[len]; head:json[16]; body:cbor[*]"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            fmt.to_string(),
+            "len:len8; meta:msgpack[len]; head:json[16]; body:cbor[*]"
+        );
+        let fmt: MessageFormat = "u16; ber[#0]; tlv8_16[*]".parse().unwrap();
+        assert_eq!(fmt.to_string(), "len16; ber[#0]; tlv8_16[*]");
+        assert!(fmt.is_greedy());
+
+        let fmt: MessageFormat = "cmd:resp[*]".parse().unwrap();
+        assert_eq!(fmt.to_string(), "cmd:resp[*]");
+        let fmt: MessageFormat = "http[*]".parse().unwrap();
+        assert_eq!(fmt.to_string(), "http[*]");
+        let fmt: MessageFormat = "stomp[*]".parse().unwrap();
+        assert_eq!(fmt.to_string(), "stomp[*]");
+        let fmt: MessageFormat = "memcache[*]".parse().unwrap();
+        assert_eq!(fmt.to_string(), "memcache[*]");
+    }
+
+    #[test]
+    fn parse_checksum_ok() {
+        let s = "len:u16 be; cmd:u8; name:str[len]; crc:crc16(0..3)";
+        let fmt: MessageFormat = s.parse().unwrap();
+        assert_eq!(
+            fmt[3],
+            ItemFormat::Checksum {
+                checksum: Checksum::Crc16,
+                start: 0,
+                end: 3,
+            }
+        );
+        assert_eq!(fmt.name(3), Some("crc"));
+        assert_eq!(
+            fmt.to_string(),
+            "len:len16; cmd:u8; name:str[len]; crc:crc16(0..3)"
+        );
+        assert_eq!(fmt, fmt.to_string().parse().unwrap());
+    }
+
+    #[test]
+    fn parse_error_span() {
+        let s = "len:u16; cmd:u12; name:str[len]";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "u12"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let s = "cmd:i8; name:str[cmd]";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "cmd"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let s = "body:cbor[*]; crc:u16";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "*"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let s = "body:cbor[8]";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "8"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let s = "ies:tlv12_8[*]";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "tlv12_8"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // The checksum covers itself.
+        let s = "cmd:u8; crc:sum8(0..2)";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "0..2"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let s = "cmd:u8; crc:sum8(0.1)";
+        match s.parse::<MessageFormat>() {
+            Err(Error::FormatSyntax { start, end, .. }) => assert_eq!(&s[start..end], "."),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+}
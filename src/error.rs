@@ -2,6 +2,10 @@ use std::{num::ParseIntError, result};
 
 use hex::FromHexError;
 
+use crate::msg::Message;
+
+/// Error of the crate, variants are described by their messages.
+#[allow(missing_docs)]
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("io error, kind: `{:?}`", std::io::Error::kind(.0))]
@@ -10,6 +14,58 @@ pub enum Error {
     #[error("the MessageFormat should not be empty")]
     MessageFormatEmpty,
 
+    #[error("invalid syntax of MessageFormat at `{start}..{end}`, details: {details}")]
+    FormatSyntax {
+        start: usize,
+        end: usize,
+        details: String,
+    },
+
+    #[error("failed to import C struct at line `{line}`, details: {details}")]
+    CImport { line: usize, details: String },
+
+    #[error("there is no such struct in the C header `{name}`")]
+    NoSuchCStruct { name: String },
+
+    #[error("failed to import Kaitai Struct at `{path}`, details: {details}")]
+    KsyImport { path: String, details: String },
+
+    #[error("failed to import protobuf schema at line `{line}`, details: {details}")]
+    ProtoImport { line: usize, details: String },
+
+    #[error("there is no such message in the protobuf schema `{name}`")]
+    NoSuchProtoMessage { name: String },
+
+    #[error("invalid protobuf message, details: {details}")]
+    InvalidProtobuf { details: String },
+
+    #[error("invalid {encoding} payload, details: {details}")]
+    InvalidPayload {
+        encoding: &'static str,
+        details: String,
+    },
+
+    #[error("invalid Modbus message, details: {details}")]
+    InvalidModbus { details: String },
+
+    #[error("Modbus exception `{code}` of function `{function}`, {}", crate::modbus::exception_name(*.code))]
+    ModbusException { function: u8, code: u8 },
+
+    #[error("invalid Redis command, details: {details}")]
+    InvalidRedis { details: String },
+
+    #[error("invalid memcached command, details: {details}")]
+    InvalidMemcache { details: String },
+
+    #[error("invalid HTTP request, details: {details}")]
+    InvalidHttp { details: String },
+
+    #[error("invalid WebSocket handshake, details: {details}")]
+    InvalidWebSocket { details: String },
+
+    #[error("invalid MQTT packet, details: {details}")]
+    InvalidMqtt { details: String },
+
     #[error("invalid IP address syntax, `{invalid_addr}`")]
     AddrParse { invalid_addr: String },
 
@@ -27,6 +83,13 @@ pub enum Error {
         e: FromHexError,
     },
 
+    #[error("`{s}` couldn't be parsed to JSON, index of item: `{item_idx}`, details: {details}")]
+    JsonParse {
+        s: String,
+        item_idx: usize,
+        details: String,
+    },
+
     #[error("there is no such client connected `{addr}`")]
     NoSuchClient { addr: String },
 
@@ -41,6 +104,35 @@ pub enum Error {
     )]
     NotALen { item_idx: usize, len_idx: usize },
 
+    #[error("the checksum should cover items before it, index of item: `{item_idx}`, range: `{start}..{end}`")]
+    InvalidChecksumRange {
+        item_idx: usize,
+        start: usize,
+        end: usize,
+    },
+
+    #[error("the checksum mismatches, index of item: `{item_idx}`, expected: `{expected:#x}`, actual: `{actual:#x}`")]
+    ChecksumMismatch {
+        item_idx: usize,
+        expected: u64,
+        actual: u64,
+    },
+
+    #[error("the format has no checksum item to resync by")]
+    NoChecksumItem,
+
+    #[error("the name `{name}` of item `{item_idx}` should be a unique identifier")]
+    InvalidItemName { item_idx: usize, name: String },
+
+    #[error("the greedy item should be the last, index of item: `{item_idx}`")]
+    GreedyNotLast { item_idx: usize },
+
+    #[error("only JSON payloads can be padded to a fixed length, index of item: `{item_idx}`")]
+    FixedPayload { item_idx: usize },
+
+    #[error("the greedy item needs a framing to find the end of messages")]
+    GreedyWithoutFraming,
+
     #[error("the length for this kind of item is too small, min len: `{min_len}`, index of item: `{item_idx}`, actual len: `{len}`")]
     LenTooSmall {
         min_len: usize,
@@ -62,12 +154,46 @@ pub enum Error {
         len: usize,
     },
 
+    #[error(
+        "the count of values mismatches the format, expected: `{expected}`, actual: `{actual}`"
+    )]
+    ValueCountMismatch { expected: usize, actual: usize },
+
+    #[error("the kind of value mismatches the format, index of item: `{item_idx}`")]
+    ValueKindMismatch { item_idx: usize },
+
+    #[error("the item is not a payload item, whose value is JSON")]
+    NotAPayload,
+
     #[error("the HEX of message is too long, expected bytes: `{expected_len}`, actual: `{len}`")]
     MessageHexTooLong { expected_len: usize, len: usize },
 
+    #[error("the message is too large, max len: `{max_len}`")]
+    MessageTooLarge { max_len: usize },
+
+    #[error("invalid framing, details: {details}")]
+    InvalidFraming { details: String },
+
+    #[error("invalid frame, details: {details}")]
+    InvalidFrame { details: String },
+
+    #[error("the frame is too large, max len: `{max_len}`")]
+    FrameTooLarge { max_len: usize },
+
+    #[error("the frame is too short for the message, len: `{len}`")]
+    FrameTooShort { len: usize },
+
+    #[error(
+        "the frame is too long for the message, expected bytes: `{expected_len}`, actual: `{len}`"
+    )]
+    FrameTooLong { expected_len: usize, len: usize },
+
     #[error("no more bytes can be read")]
     EndOfStream,
 
+    #[error("the stream ends with `{len}` bytes of an incomplete message")]
+    TrailingBytes { len: usize },
+
     #[error("socket need to be stopped")]
     Stopped,
 
@@ -77,6 +203,46 @@ pub enum Error {
         #[source]
         e: std::string::FromUtf8Error,
     },
+
+    #[error(transparent)]
+    Decode(Box<DecodeError>),
+
+    #[error(
+        "the stream is resynchronised after discarding `{discarded}` bytes, details: {source}"
+    )]
+    Resync {
+        discarded: usize,
+        #[source]
+        source: Box<Error>,
+    },
 }
 
+/// Result of the crate.
 pub type Result<T> = result::Result<T, Error>;
+
+/// Error of decoding a message, with the items decoded before the error.
+#[derive(Debug, thiserror::Error)]
+#[error("failed to decode item `{item_idx}` at offset `{offset}`, consumed bytes: `{consumed}`, details: {error}")]
+pub struct DecodeError {
+    /// Error of the item.
+    #[source]
+    pub error: Error,
+    /// Values of the items before the error.
+    pub partial: Message,
+    /// Index of the item failed.
+    pub item_idx: usize,
+    /// Offset of the item failed.
+    pub offset: usize,
+    /// Including the bytes of the item failed.
+    pub consumed: usize,
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        match e.error {
+            // Not errors of bytes, the partial message is useless.
+            Error::EndOfStream | Error::Stopped | Error::Io(_) => e.error,
+            _ => Error::Decode(Box::new(e)),
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::error::{Error, Result};
+
+use super::{take_until, Framer};
+
+/// Consistent Overhead Byte Stuffing, frames end with zero.
+///
+/// Frames longer than `max_len` before decoding are discarded.
+pub struct CobsFramer {
+    max_len: usize,
+
+    // Whether discarding bytes of a frame which is too large.
+    discarding: bool,
+}
+
+impl CobsFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            discarding: false,
+        }
+    }
+}
+
+impl Framer for CobsFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        while let Some(frame) = take_until(buf, 0, self.max_len, &mut self.discarding)? {
+            if frame.is_empty() {
+                continue;
+            }
+
+            // Each block starts with a code, which is the offset of the next zero.
+            let mut bytes = Vec::with_capacity(frame.len());
+            let mut idx = 0;
+            while idx < frame.len() {
+                let code = frame[idx] as usize;
+                if idx + code > frame.len() {
+                    return Err(Error::InvalidFrame {
+                        details: "the code of COBS is out of the frame".to_string(),
+                    });
+                }
+                bytes.extend_from_slice(&frame[idx + 1..idx + code]);
+                idx += code;
+
+                // A block of the max code has no zero after it.
+                if code < 0xff && idx < frame.len() {
+                    bytes.push(0);
+                }
+            }
+            return Ok(Some(bytes));
+        }
+        Ok(None)
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut frame = vec![0];
+        let mut code_idx = 0;
+        for b in bytes {
+            if *b != 0 {
+                frame.push(*b);
+            }
+            if *b == 0 || frame.len() - code_idx == 0xff {
+                frame[code_idx] = (frame.len() - code_idx) as u8;
+                code_idx = frame.len();
+                frame.push(0);
+            }
+        }
+        frame[code_idx] = (frame.len() - code_idx) as u8;
+        if frame.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        frame.push(0);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::CobsFramer;
+
+    #[test]
+    fn cobs_ok() {
+        let mut framer = CobsFramer::new(1024);
+        let mut buf = framer.encode_frame(&[0x11, 0x00, 0x00, 0x22]).unwrap();
+        assert_eq!(buf, [0x02, 0x11, 0x01, 0x02, 0x22, 0x00]);
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            [0x11, 0x00, 0x00, 0x22]
+        );
+
+        // A block of the max code.
+        let bytes = (1..=0xff).collect::<Vec<u8>>();
+        let mut buf = framer.encode_frame(&bytes).unwrap();
+        assert_eq!(buf.len(), bytes.len() + 3);
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), bytes);
+
+        let mut buf = vec![0x05, 0x11, 0x00];
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::InvalidFrame { .. })
+        ));
+    }
+
+    #[test]
+    fn cobs_too_large() {
+        let mut framer = CobsFramer::new(4);
+        assert!(matches!(
+            framer.encode_frame(&[0x01, 0x02, 0x03, 0x04]),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+
+        // A frame which is too large is discarded until zero.
+        let mut buf = vec![0x06, 0x01, 0x02, 0x03];
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.push(0x05);
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+        buf.extend_from_slice(&[0x00, 0x02, 0x11, 0x00]);
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), [0x11]);
+    }
+}
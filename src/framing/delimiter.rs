@@ -0,0 +1,119 @@
+use std::ops::Deref;
+
+use crate::error::{Error, Result};
+
+use super::Framer;
+
+/// Frames end with the delimiter, frames longer than `max_len` are discarded.
+pub struct DelimiterFramer {
+    delimiter: Vec<u8>,
+    max_len: usize,
+
+    // Whether discarding bytes of a frame which is too large.
+    discarding: bool,
+}
+
+impl DelimiterFramer {
+    /// The delimiter should not be empty.
+    pub fn new(delimiter: Vec<u8>, max_len: usize) -> Result<Self> {
+        if delimiter.is_empty() {
+            return Err(Error::InvalidFraming {
+                details: "the delimiter should not be empty".to_string(),
+            });
+        }
+
+        Ok(Self {
+            delimiter,
+            max_len,
+            discarding: false,
+        })
+    }
+}
+
+impl Framer for DelimiterFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        loop {
+            match buf
+                .windows(self.delimiter.len())
+                .position(|w| w == self.delimiter.deref())
+            {
+                Some(pos) => {
+                    let frame = buf[..pos].to_vec();
+                    buf.drain(..pos + self.delimiter.len());
+
+                    // The end of the frame which is too large.
+                    if self.discarding {
+                        self.discarding = false;
+                        continue;
+                    }
+
+                    if frame.len() > self.max_len {
+                        return Err(Error::FrameTooLarge {
+                            max_len: self.max_len,
+                        });
+                    }
+                    return Ok(Some(frame));
+                }
+                None => {
+                    // Keep bytes which may be the beginning of the delimiter.
+                    let kept = self.delimiter.len() - 1;
+                    if buf.len() > self.max_len + kept {
+                        buf.drain(..buf.len() - kept);
+                        if !self.discarding {
+                            self.discarding = true;
+                            return Err(Error::FrameTooLarge {
+                                max_len: self.max_len,
+                            });
+                        }
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes
+            .windows(self.delimiter.len())
+            .any(|w| w == self.delimiter.deref())
+        {
+            return Err(Error::InvalidFraming {
+                details: "the message contains the delimiter".to_string(),
+            });
+        }
+
+        let mut frame = bytes.to_vec();
+        frame.extend_from_slice(&self.delimiter);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::DelimiterFramer;
+
+    #[test]
+    fn delimiter_ok() {
+        let mut framer = DelimiterFramer::new(b"\r\n".to_vec(), 4).unwrap();
+        assert_eq!(framer.encode_frame(b"AT").unwrap(), b"AT\r\n");
+        assert!(framer.encode_frame(b"A\r\nT").is_err());
+
+        let mut buf = b"AT\r\nOK\r".to_vec();
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"AT");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\n");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"OK");
+        assert!(buf.is_empty());
+
+        // A frame which is too large is discarded until the delimiter.
+        let mut buf = b"ERROR!".to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+        buf.extend_from_slice(b"ERROR\r\nOK\r\n");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"OK");
+    }
+}
@@ -0,0 +1,146 @@
+use crate::error::{Error, Result};
+
+use super::{take_until, Framer};
+
+const FLAG: u8 = 0x7e;
+const ESC: u8 = 0x7d;
+
+// FCS-16 of RFC 1662.
+fn fcs16(bytes: &[u8]) -> u16 {
+    let mut fcs = 0xffffu16;
+    for b in bytes {
+        fcs ^= u16::from(*b);
+        for _ in 0..8 {
+            fcs = if fcs & 1 != 0 {
+                (fcs >> 1) ^ 0x8408
+            } else {
+                fcs >> 1
+            };
+        }
+    }
+    !fcs
+}
+
+/// HDLC-like framing of RFC 1662, frames are delimited by `FLAG` and end with the FCS-16.
+///
+/// Frames longer than `max_len` before unescaping are discarded.
+pub struct HdlcFramer {
+    max_len: usize,
+
+    // Whether discarding bytes of a frame which is too large.
+    discarding: bool,
+}
+
+impl HdlcFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            discarding: false,
+        }
+    }
+}
+
+impl Framer for HdlcFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        while let Some(frame) = take_until(buf, FLAG, self.max_len, &mut self.discarding)? {
+            // Skip the `FLAG` at the start of a frame.
+            if frame.is_empty() {
+                continue;
+            }
+
+            let mut bytes = Vec::with_capacity(frame.len());
+            let mut iter = frame.into_iter();
+            while let Some(b) = iter.next() {
+                bytes.push(match b {
+                    ESC => match iter.next() {
+                        Some(b) => b ^ 0x20,
+                        None => {
+                            return Err(Error::InvalidFrame {
+                                details: "invalid escape of HDLC".to_string(),
+                            })
+                        }
+                    },
+                    b => b,
+                });
+            }
+
+            if bytes.len() < 2 {
+                return Err(Error::InvalidFrame {
+                    details: "the frame of HDLC is too short for the FCS".to_string(),
+                });
+            }
+            let fcs = bytes.split_off(bytes.len() - 2);
+            if fcs16(&bytes).to_le_bytes() != fcs.as_slice() {
+                return Err(Error::InvalidFrame {
+                    details: "the FCS of HDLC mismatches".to_string(),
+                });
+            }
+            return Ok(Some(bytes));
+        }
+        Ok(None)
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut frame = vec![FLAG];
+        for b in bytes.iter().chain(fcs16(bytes).to_le_bytes().iter()) {
+            match *b {
+                FLAG | ESC => frame.extend_from_slice(&[ESC, b ^ 0x20]),
+                b => frame.push(b),
+            }
+        }
+        if frame.len() - 1 > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        frame.push(FLAG);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::{fcs16, HdlcFramer};
+
+    #[test]
+    fn hdlc_ok() {
+        assert_eq!(fcs16(b"123456789"), 0x906e);
+
+        let mut framer = HdlcFramer::new(1024);
+        let mut buf = framer.encode_frame(&[0x01, 0x7e, 0x7d]).unwrap();
+        assert_eq!(buf[..6], [0x7e, 0x01, 0x7d, 0x5e, 0x7d, 0x5d]);
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            [0x01, 0x7e, 0x7d]
+        );
+
+        let mut buf = vec![0x7e, 0x01, 0x02, 0x03, 0x7e];
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::InvalidFrame { .. })
+        ));
+    }
+
+    #[test]
+    fn hdlc_too_large() {
+        let mut framer = HdlcFramer::new(4);
+        assert!(matches!(
+            framer.encode_frame(&[0x01, 0x02, 0x03]),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+
+        // A frame which is too large is discarded until `FLAG`.
+        let mut buf = vec![0x7e, 0x01, 0x02, 0x03, 0x04, 0x05];
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+        assert!(buf.is_empty());
+        buf.push(0x06);
+        buf.extend(framer.encode_frame(&[0x01]).unwrap());
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), [0x01]);
+    }
+}
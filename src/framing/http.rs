@@ -0,0 +1,107 @@
+use crate::{
+    error::{Error, Result},
+    payload::http_len,
+};
+
+use super::Framer;
+
+/// Each frame is a whole HTTP/1.1 request or response, whose body is delimited by
+/// `Content-Length` or chunked.
+///
+/// Responses to `HEAD` aren't known, so their bodies are expected as if they were sent.
+/// The buffer is parsed again as more bytes arrive, so frames are limited to `max_len`.
+pub struct HttpFramer {
+    max_len: usize,
+}
+
+impl HttpFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self { max_len }
+    }
+}
+
+impl Framer for HttpFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        match http_len(buf) {
+            Ok(Some(len)) if len <= self.max_len => Ok(Some(buf.drain(..len).collect())),
+            Ok(None) if buf.len() <= self.max_len => Ok(None),
+            Ok(_) => {
+                // The rest of the message is unknown, as an invalid message.
+                buf.clear();
+                Err(Error::FrameTooLarge {
+                    max_len: self.max_len,
+                })
+            }
+            Err(e) => {
+                // The stream can't be recovered, since the end of the message is unknown.
+                buf.clear();
+                Err(Error::InvalidFrame {
+                    details: e.to_string(),
+                })
+            }
+        }
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::HttpFramer;
+
+    #[test]
+    fn http_ok() {
+        let mut framer = HttpFramer::new(1024);
+        let mut buf =
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nokHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok"
+                .to_vec();
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"
+        );
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\r\n0\r\n\r\n");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n"
+        );
+        assert!(buf.is_empty());
+
+        let mut buf = b"HTTP/1.1 200 OK\r\n\r\nuntil the end".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn http_too_large() {
+        let mut framer = HttpFramer::new(40);
+        let frame = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nabcd";
+        assert!(matches!(
+            framer.encode_frame(frame),
+            Err(Error::FrameTooLarge { max_len: 40 })
+        ));
+        let mut buf = frame.to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 40 })
+        ));
+        assert!(buf.is_empty());
+
+        // Bytes of the body are not buffered without limit.
+        let mut buf = b"HTTP/1.1 200 OK\r\nContent-Length: 99\r\n\r\n".to_vec();
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"abc");
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use crate::error::{Error, Result};
+
+use super::Framer;
+
+/// Frames start with the length, which is an unsigned integer of `width` bytes.
+///
+/// Frames longer than `max_len` without the prefix are discarded, which is known from the prefix.
+pub struct LengthPrefixFramer {
+    width: usize,
+    big_endian: bool,
+
+    // Whether the length includes the prefix itself.
+    includes_self: bool,
+    max_len: usize,
+
+    // Count of bytes to discard of a frame which is too large.
+    discarding: u64,
+}
+
+impl LengthPrefixFramer {
+    /// The width should be 1 to 8.
+    pub fn new(
+        width: usize,
+        big_endian: bool,
+        includes_self: bool,
+        max_len: usize,
+    ) -> Result<Self> {
+        if !(1..=8).contains(&width) {
+            return Err(Error::InvalidFraming {
+                details: format!("the width of length should be 1 to 8, actual: `{}`", width),
+            });
+        }
+
+        Ok(Self {
+            width,
+            big_endian,
+            includes_self,
+            max_len,
+            discarding: 0,
+        })
+    }
+
+    // Discard bytes of the frame which is too large, returns whether all of them are discarded.
+    fn discard(&mut self, buf: &mut Vec<u8>) -> bool {
+        let n = self.discarding.min(buf.len() as u64);
+        buf.drain(..n as usize);
+        self.discarding -= n;
+        self.discarding == 0
+    }
+}
+
+impl Framer for LengthPrefixFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        if !self.discard(buf) || buf.len() < self.width {
+            return Ok(None);
+        }
+
+        let prefix = &buf[..self.width];
+        let len = if self.big_endian {
+            prefix.iter().fold(0u64, |v, b| (v << 8) | u64::from(*b))
+        } else {
+            prefix
+                .iter()
+                .rev()
+                .fold(0u64, |v, b| (v << 8) | u64::from(*b))
+        };
+        let end = if self.includes_self {
+            if len < self.width as u64 {
+                // The stream can't be recovered, since the end of the frame is unknown.
+                buf.clear();
+                return Err(Error::InvalidFrame {
+                    details: format!("the length `{}` is smaller than the prefix", len),
+                });
+            }
+            len
+        } else {
+            len.saturating_add(self.width as u64)
+        };
+
+        // Don't wait for the bytes of a frame which is too large.
+        if end - self.width as u64 > self.max_len as u64 {
+            self.discarding = end;
+            self.discard(buf);
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+
+        if (buf.len() as u64) < end {
+            return Ok(None);
+        }
+        let end = end as usize;
+        let frame = buf[self.width..end].to_vec();
+        buf.drain(..end);
+        Ok(Some(frame))
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+
+        let mut len = bytes.len() as u64;
+        if self.includes_self {
+            len += self.width as u64;
+        }
+        if self.width < 8 && len >> (self.width * 8) != 0 {
+            return Err(Error::InvalidFraming {
+                details: format!(
+                    "the length `{}` is too large for the prefix of `{}` bytes",
+                    len, self.width
+                ),
+            });
+        }
+
+        let mut frame = if self.big_endian {
+            len.to_be_bytes()[8 - self.width..].to_vec()
+        } else {
+            len.to_le_bytes()[..self.width].to_vec()
+        };
+        frame.extend_from_slice(bytes);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::LengthPrefixFramer;
+
+    #[test]
+    fn length_prefix_ok() {
+        let mut framer = LengthPrefixFramer::new(4, true, false, 16).unwrap();
+        let mut buf = framer.encode_frame(b"abc").unwrap();
+        assert_eq!(buf, b"\x00\x00\x00\x03abc");
+        buf.extend_from_slice(b"\x00\x00\x00\x02d");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"abc");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+
+        let mut framer = LengthPrefixFramer::new(2, false, true, 16).unwrap();
+        let mut buf = framer.encode_frame(b"abc").unwrap();
+        assert_eq!(buf, b"\x05\x00abc");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"abc");
+
+        let framer = LengthPrefixFramer::new(1, true, false, 1024).unwrap();
+        assert!(framer.encode_frame(&[0; 256]).is_err());
+    }
+
+    #[test]
+    fn length_prefix_too_large() {
+        let mut framer = LengthPrefixFramer::new(4, true, false, 4).unwrap();
+        assert!(matches!(
+            framer.encode_frame(b"abcde"),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+
+        // The error is returned before the frame arrives, and the frame is discarded.
+        let mut buf = b"\xff\xff\xff\xffab".to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+        assert!(buf.is_empty());
+
+        let mut framer = LengthPrefixFramer::new(1, true, false, 4).unwrap();
+        let mut buf = b"\x05abc".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        buf.extend_from_slice(b"de\x02fg");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"fg");
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{
+    error::{Error, Result},
+    payload::memcache_len,
+};
+
+use super::Framer;
+
+/// Each frame is a command or a reply of the memcached text protocol with the data blocks,
+/// replies to retrieval commands are framed from the first `VALUE` line to `END`.
+///
+/// The buffer is parsed again as more bytes arrive, so frames are limited to `max_len`.
+pub struct MemcacheFramer {
+    max_len: usize,
+}
+
+impl MemcacheFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self { max_len }
+    }
+}
+
+impl Framer for MemcacheFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        match memcache_len(buf) {
+            Ok(Some(len)) if len <= self.max_len => Ok(Some(buf.drain(..len).collect())),
+            Ok(None) if buf.len() <= self.max_len => Ok(None),
+            Ok(_) => {
+                // Data blocks may contain CRLFs, so the rest of the frame is unknown.
+                buf.clear();
+                Err(Error::FrameTooLarge {
+                    max_len: self.max_len,
+                })
+            }
+            Err(e) => {
+                // The next command or reply may start after the next CRLF.
+                match buf.windows(2).position(|w| w == b"\r\n") {
+                    Some(pos) => buf.drain(..pos + 2),
+                    None => buf.drain(..),
+                };
+                Err(Error::InvalidFrame {
+                    details: e.to_string(),
+                })
+            }
+        }
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::MemcacheFramer;
+
+    #[test]
+    fn memcache_ok() {
+        let mut framer = MemcacheFramer::new(1024);
+        let mut buf = b"VALUE k 0 2\r\nab\r\nEND\r\nSTO".to_vec();
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"VALUE k 0 2\r\nab\r\nEND\r\n"
+        );
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"RED\r\n");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"STORED\r\n"
+        );
+        assert!(buf.is_empty());
+
+        let mut buf = b"set k 0 0 x\r\ndelete k\r\n".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"delete k\r\n"
+        );
+
+        let mut buf = b"set k 0 0 18446744073709551615\r\nab".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+    }
+
+    #[test]
+    fn memcache_too_large() {
+        let mut framer = MemcacheFramer::new(16);
+        assert!(matches!(
+            framer.encode_frame(b"set k 0 0 4\r\nabcd\r\n"),
+            Err(Error::FrameTooLarge { max_len: 16 })
+        ));
+
+        // Bytes of the data block are not buffered without limit.
+        let mut buf = b"set k 0 0 1000\r\nab".to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 16 })
+        ));
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b"get k\r\n");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"get k\r\n"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Framings which split a stream into frames, each frame contains a message.
+
+mod cobs;
+mod delimiter;
+mod hdlc;
+mod http;
+mod length_prefix;
+mod memcache;
+mod mqtt;
+mod resp;
+mod slip;
+mod stomp;
+mod varint_prefix;
+
+pub use cobs::*;
+pub use delimiter::*;
+pub use hdlc::*;
+pub use http::*;
+pub use length_prefix::*;
+pub use memcache::*;
+pub use mqtt::*;
+pub use resp::*;
+pub use slip::*;
+pub use stomp::*;
+pub use varint_prefix::*;
+
+use crate::error::{Error, Result};
+
+/// Splits a stream into frames, each frame contains a message.
+pub trait Framer: Send {
+    /// Takes a frame from the front of the buffer, `None` if more bytes are needed.
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>>;
+
+    /// Wraps the bytes of a message into a frame.
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>>;
+}
+
+// Takes the bytes before the first `byte` from the buffer, the byte is dropped.
+//
+// Frames longer than `max_len` are discarded until the byte, `discarding` is whether
+// discarding bytes of such a frame.
+fn take_until(
+    buf: &mut Vec<u8>,
+    byte: u8,
+    max_len: usize,
+    discarding: &mut bool,
+) -> Result<Option<Vec<u8>>> {
+    loop {
+        match buf.iter().position(|b| *b == byte) {
+            Some(pos) => {
+                let frame = buf[..pos].to_vec();
+                buf.drain(..=pos);
+
+                // The end of the frame which is too large.
+                if *discarding {
+                    *discarding = false;
+                    continue;
+                }
+
+                if frame.len() > max_len {
+                    return Err(Error::FrameTooLarge { max_len });
+                }
+                return Ok(Some(frame));
+            }
+            None => {
+                if buf.len() > max_len {
+                    buf.clear();
+                    if !*discarding {
+                        *discarding = true;
+                        return Err(Error::FrameTooLarge { max_len });
+                    }
+                }
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Framing of a stream, which creates a framer for each stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Framing {
+    /// Frames end with the delimiter, such as `\r\n`.
+    Delimiter {
+        /// Bytes which end a frame.
+        delimiter: Vec<u8>,
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+    /// Frames are escaped and end with `0xc0`, as RFC 1055.
+    Slip {
+        /// Longer frames before unescaping are discarded.
+        max_len: usize,
+    },
+    /// Frames are encoded by COBS and end with `0x00`.
+    Cobs {
+        /// Longer frames before decoding are discarded.
+        max_len: usize,
+    },
+    /// Frames are escaped and delimited by `0x7e`, as asynchronous HDLC.
+    Hdlc {
+        /// Longer frames before unescaping are discarded.
+        max_len: usize,
+    },
+    /// Frames start with the length, which is independent of the message format.
+    LengthPrefix {
+        /// Width of the length in bytes, 1 to 8.
+        width: usize,
+        /// Whether the length is big-endian.
+        big_endian: bool,
+        /// Whether the length includes the prefix itself.
+        includes_self: bool,
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+    /// Frames start with the length in a base-128 varint, as delimited protobuf messages.
+    VarintPrefix {
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+    /// Each frame is a whole RESP value of Redis.
+    Resp {
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+    /// Each frame is a whole HTTP/1.1 request or response.
+    Http {
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+    /// Each frame is a whole MQTT control packet.
+    Mqtt,
+    /// Each frame is a whole STOMP frame with its NUL.
+    Stomp {
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+    /// Each frame is a whole memcached command or reply.
+    Memcache {
+        /// Longer frames are discarded.
+        max_len: usize,
+    },
+}
+
+impl Framing {
+    /// Creates a framer, each stream should have its own framer.
+    pub fn framer(&self) -> Result<Box<dyn Framer>> {
+        match self {
+            Framing::Delimiter { delimiter, max_len } => {
+                Ok(Box::new(DelimiterFramer::new(delimiter.clone(), *max_len)?))
+            }
+            Framing::Slip { max_len } => Ok(Box::new(SlipFramer::new(*max_len))),
+            Framing::Cobs { max_len } => Ok(Box::new(CobsFramer::new(*max_len))),
+            Framing::Hdlc { max_len } => Ok(Box::new(HdlcFramer::new(*max_len))),
+            Framing::LengthPrefix {
+                width,
+                big_endian,
+                includes_self,
+                max_len,
+            } => Ok(Box::new(LengthPrefixFramer::new(
+                *width,
+                *big_endian,
+                *includes_self,
+                *max_len,
+            )?)),
+            Framing::VarintPrefix { max_len } => Ok(Box::new(VarintPrefixFramer::new(*max_len))),
+            Framing::Resp { max_len } => Ok(Box::new(RespFramer::new(*max_len))),
+            Framing::Http { max_len } => Ok(Box::new(HttpFramer::new(*max_len))),
+            Framing::Mqtt => Ok(Box::new(MqttFramer)),
+            Framing::Stomp { max_len } => Ok(Box::new(StompFramer::new(*max_len))),
+            Framing::Memcache { max_len } => Ok(Box::new(MemcacheFramer::new(*max_len))),
+        }
+    }
+}
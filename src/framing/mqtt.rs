@@ -0,0 +1,59 @@
+use crate::{
+    error::{Error, Result},
+    mqtt::remaining_len,
+};
+
+use super::Framer;
+
+/// Each frame is a whole MQTT control packet, whose length follows the fixed header.
+pub struct MqttFramer;
+
+impl Framer for MqttFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        match remaining_len(&buf[1..]) {
+            Ok(Some((len, used))) if buf.len() >= 1 + used + len => {
+                Ok(Some(buf.drain(..1 + used + len).collect()))
+            }
+            Ok(_) => Ok(None),
+            Err(e) => {
+                // The stream can't be recovered, since the end of the packet is unknown.
+                buf.clear();
+                Err(Error::InvalidFrame {
+                    details: e.to_string(),
+                })
+            }
+        }
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::framing::Framer;
+
+    use super::MqttFramer;
+
+    #[test]
+    fn mqtt_ok() {
+        let mut framer = MqttFramer;
+        let mut buf = vec![0xd0, 0x00, 0x30, 0x04, 0x00, 0x01, b't'];
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), Some(vec![0xd0, 0]));
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.push(b'x');
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap(),
+            Some(vec![0x30, 0x04, 0x00, 0x01, b't', b'x'])
+        );
+        assert!(buf.is_empty());
+
+        let mut buf = vec![0x30, 0xff, 0xff, 0xff, 0xff];
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{
+    error::{Error, Result},
+    payload::resp_len,
+};
+
+use super::Framer;
+
+/// Each frame is a whole value of the Redis protocol RESP2 or RESP3, such as a command.
+///
+/// The buffer is parsed again as more bytes arrive, so frames are limited to `max_len`.
+pub struct RespFramer {
+    max_len: usize,
+}
+
+impl RespFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self { max_len }
+    }
+}
+
+impl Framer for RespFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        match resp_len(buf) {
+            Ok(Some(len)) if len <= self.max_len => Ok(Some(buf.drain(..len).collect())),
+            Ok(None) if buf.len() <= self.max_len => Ok(None),
+            Ok(_) => {
+                // The rest of the value is unknown, as an invalid value.
+                buf.clear();
+                Err(Error::FrameTooLarge {
+                    max_len: self.max_len,
+                })
+            }
+            Err(e) => {
+                // The stream can't be recovered, since the end of the value is unknown.
+                buf.clear();
+                Err(Error::InvalidFrame {
+                    details: e.to_string(),
+                })
+            }
+        }
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::RespFramer;
+
+    #[test]
+    fn resp_ok() {
+        let mut framer = RespFramer::new(1024);
+        let mut buf = b"+OK\r\n*2\r\n$3\r\nGET\r\n$1".to_vec();
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"+OK\r\n");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\r\nk\r\n");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n"
+        );
+        assert!(buf.is_empty());
+
+        let mut buf = b"?\r\n".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn resp_too_large() {
+        let mut framer = RespFramer::new(8);
+        assert!(matches!(
+            framer.encode_frame(b"$4\r\nabcd\r\n"),
+            Err(Error::FrameTooLarge { max_len: 8 })
+        ));
+
+        let mut buf = b"$4\r\nabcd\r\n".to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 8 })
+        ));
+        assert!(buf.is_empty());
+
+        // Bytes of the value are not buffered without limit.
+        let mut buf = b"$100\r\na".to_vec();
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"bc");
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b":1\r\n");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b":1\r\n");
+    }
+}
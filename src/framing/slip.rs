@@ -0,0 +1,120 @@
+use crate::error::{Error, Result};
+
+use super::{take_until, Framer};
+
+const END: u8 = 0xc0;
+const ESC: u8 = 0xdb;
+const ESC_END: u8 = 0xdc;
+const ESC_ESC: u8 = 0xdd;
+
+/// SLIP framing of RFC 1055, frames start and end with `END`.
+///
+/// Frames longer than `max_len` before unescaping are discarded.
+pub struct SlipFramer {
+    max_len: usize,
+
+    // Whether discarding bytes of a frame which is too large.
+    discarding: bool,
+}
+
+impl SlipFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            discarding: false,
+        }
+    }
+}
+
+impl Framer for SlipFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        while let Some(frame) = take_until(buf, END, self.max_len, &mut self.discarding)? {
+            // Skip the `END` at the start of a frame.
+            if frame.is_empty() {
+                continue;
+            }
+
+            let mut bytes = Vec::with_capacity(frame.len());
+            let mut iter = frame.into_iter();
+            while let Some(b) = iter.next() {
+                bytes.push(match b {
+                    ESC => match iter.next() {
+                        Some(ESC_END) => END,
+                        Some(ESC_ESC) => ESC,
+                        _ => {
+                            return Err(Error::InvalidFrame {
+                                details: "invalid escape of SLIP".to_string(),
+                            })
+                        }
+                    },
+                    b => b,
+                });
+            }
+            return Ok(Some(bytes));
+        }
+        Ok(None)
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut frame = vec![END];
+        for b in bytes {
+            match *b {
+                END => frame.extend_from_slice(&[ESC, ESC_END]),
+                ESC => frame.extend_from_slice(&[ESC, ESC_ESC]),
+                b => frame.push(b),
+            }
+        }
+        if frame.len() - 1 > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        frame.push(END);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::SlipFramer;
+
+    #[test]
+    fn slip_ok() {
+        let mut framer = SlipFramer::new(1024);
+        let mut buf = framer.encode_frame(&[0x01, 0xc0, 0xdb, 0x02]).unwrap();
+        assert_eq!(buf, [0xc0, 0x01, 0xdb, 0xdc, 0xdb, 0xdd, 0x02, 0xc0]);
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            [0x01, 0xc0, 0xdb, 0x02]
+        );
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+
+        let mut buf = vec![0xc0, 0xdb, 0x01, 0xc0];
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::InvalidFrame { .. })
+        ));
+    }
+
+    #[test]
+    fn slip_too_large() {
+        let mut framer = SlipFramer::new(4);
+        assert!(matches!(
+            framer.encode_frame(&[0x01, 0x02, 0x03, 0xc0]),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+
+        // A frame which is too large is discarded until `END`.
+        let mut buf = vec![0xc0, 0x01, 0x02, 0x03, 0x04, 0x05];
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+        assert!(buf.is_empty());
+        buf.extend_from_slice(&[0x06, 0xc0, 0x07, 0xc0]);
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), [0x07]);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{
+    error::{Error, Result},
+    payload::stomp_len,
+};
+
+use super::Framer;
+
+/// Each frame is a whole STOMP 1.2 frame with its NUL, whose body ends with NUL or is delimited
+/// by `content-length`. EOLs between frames, which are heart-beats, are skipped.
+///
+/// The buffer is parsed again as more bytes arrive, so frames are limited to `max_len`.
+/// Bytes of a frame which is too large are discarded until the next NUL.
+pub struct StompFramer {
+    max_len: usize,
+
+    // Whether discarding bytes of a frame which is too large.
+    discarding: bool,
+}
+
+impl StompFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            discarding: false,
+        }
+    }
+
+    // Discard bytes until the next NUL, returns whether it's found.
+    fn discard(&mut self, buf: &mut Vec<u8>) -> bool {
+        match buf.iter().position(|b| *b == 0) {
+            Some(pos) => {
+                buf.drain(..=pos);
+                self.discarding = false;
+            }
+            None => buf.clear(),
+        }
+        !self.discarding
+    }
+}
+
+impl Framer for StompFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        if self.discarding && !self.discard(buf) {
+            return Ok(None);
+        }
+
+        let heart_beats = buf
+            .iter()
+            .take_while(|b| **b == b'\n' || **b == b'\r')
+            .count();
+        buf.drain(..heart_beats);
+
+        match stomp_len(buf) {
+            Ok(Some(len)) if len <= self.max_len => Ok(Some(buf.drain(..len).collect())),
+            Ok(None) if buf.len() <= self.max_len => Ok(None),
+            Ok(_) => {
+                self.discarding = true;
+                self.discard(buf);
+                Err(Error::FrameTooLarge {
+                    max_len: self.max_len,
+                })
+            }
+            Err(e) => {
+                // The next frame may start after the next NUL.
+                match buf.iter().position(|b| *b == 0) {
+                    Some(pos) => buf.drain(..=pos),
+                    None => buf.drain(..),
+                };
+                Err(Error::InvalidFrame {
+                    details: e.to_string(),
+                })
+            }
+        }
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+        Ok(bytes.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::StompFramer;
+
+    #[test]
+    fn stomp_ok() {
+        let mut framer = StompFramer::new(1024);
+        let mut buf = b"\n\r\nCONNECTED\nversion:1.2\n\n\0\nMESSAGE\ncontent-length:1\n\n".to_vec();
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"CONNECTED\nversion:1.2\n\n\0"
+        );
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"\0\0");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"MESSAGE\ncontent-length:1\n\n\0\0"
+        );
+        assert!(buf.is_empty());
+
+        let mut buf = b"SEND\nbad header\n\n\0ERROR\n\n\0".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"ERROR\n\n\0"
+        );
+    }
+
+    #[test]
+    fn stomp_too_large() {
+        let mut framer = StompFramer::new(16);
+        assert!(matches!(
+            framer.encode_frame(b"SEND\n\nabcdefghijk\0"),
+            Err(Error::FrameTooLarge { max_len: 16 })
+        ));
+
+        // A huge length, whose frame is discarded until the next NUL.
+        let mut buf = b"SEND\ncontent-length:99999\n\nab".to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 16 })
+        ));
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b"cd\0ERROR\n\n\0");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"ERROR\n\n\0"
+        );
+
+        // A missing NUL.
+        let mut buf = b"SEND\n\nabcdefghijk".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        buf.extend_from_slice(b"lmn");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+        buf.extend_from_slice(b"\0\nACK\nid:1\n\n\0");
+        assert_eq!(
+            framer.decode_frame(&mut buf).unwrap().unwrap(),
+            b"ACK\nid:1\n\n\0"
+        );
+    }
+}
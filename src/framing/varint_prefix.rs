@@ -0,0 +1,133 @@
+use crate::{
+    error::{Error, Result},
+    protobuf::{get_varint, put_varint},
+};
+
+use super::Framer;
+
+/// Frames start with the length in a base-128 varint, such as `writeDelimitedTo` of protobuf.
+///
+/// Frames longer than `max_len` without the prefix are discarded, which is known from the prefix.
+pub struct VarintPrefixFramer {
+    max_len: usize,
+
+    // Count of bytes to discard of a frame which is too large.
+    discarding: u64,
+}
+
+impl VarintPrefixFramer {
+    /// Frames are limited to `max_len`.
+    pub fn new(max_len: usize) -> Self {
+        Self {
+            max_len,
+            discarding: 0,
+        }
+    }
+
+    // Discard bytes of the frame which is too large, returns whether all of them are discarded.
+    fn discard(&mut self, buf: &mut Vec<u8>) -> bool {
+        let n = self.discarding.min(buf.len() as u64);
+        buf.drain(..n as usize);
+        self.discarding -= n;
+        self.discarding == 0
+    }
+}
+
+impl Framer for VarintPrefixFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        if !self.discard(buf) {
+            return Ok(None);
+        }
+
+        let (len, prefix_len) = match get_varint(buf) {
+            Ok(Some(prefix)) => prefix,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                // The stream can't be recovered, since the end of the frame is unknown.
+                buf.clear();
+                return Err(Error::InvalidFrame {
+                    details: e.to_string(),
+                });
+            }
+        };
+
+        let end = len.saturating_add(prefix_len as u64);
+
+        // Don't wait for the bytes of a frame which is too large.
+        if len > self.max_len as u64 {
+            self.discarding = end;
+            self.discard(buf);
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+
+        if (buf.len() as u64) < end {
+            return Ok(None);
+        }
+        let end = end as usize;
+        let frame = buf[prefix_len..end].to_vec();
+        buf.drain(..end);
+        Ok(Some(frame))
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        if bytes.len() > self.max_len {
+            return Err(Error::FrameTooLarge {
+                max_len: self.max_len,
+            });
+        }
+
+        let mut frame = Vec::with_capacity(bytes.len() + 10);
+        put_varint(&mut frame, bytes.len() as u64);
+        frame.extend_from_slice(bytes);
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::Error, framing::Framer};
+
+    use super::VarintPrefixFramer;
+
+    #[test]
+    fn varint_prefix_ok() {
+        let mut framer = VarintPrefixFramer::new(1024);
+        let mut buf = framer.encode_frame(&[0xab; 300]).unwrap();
+        assert_eq!(buf[..2], [0xac, 0x02]);
+        buf.extend_from_slice(b"\x02a");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), [0xab; 300]);
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.push(b'b');
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"ab");
+
+        let mut buf = vec![0xff; 11];
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn varint_prefix_too_large() {
+        let mut framer = VarintPrefixFramer::new(4);
+        assert!(matches!(
+            framer.encode_frame(b"abcde"),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+
+        // The error is returned before the frame arrives, and the frame is discarded.
+        let mut buf = b"\xff\xff\xff\xff\x0fab".to_vec();
+        assert!(matches!(
+            framer.decode_frame(&mut buf),
+            Err(Error::FrameTooLarge { max_len: 4 })
+        ));
+        assert!(buf.is_empty());
+
+        let mut framer = VarintPrefixFramer::new(4);
+        let mut buf = b"\x05abc".to_vec();
+        assert!(framer.decode_frame(&mut buf).is_err());
+        assert_eq!(framer.decode_frame(&mut buf).unwrap(), None);
+        buf.extend_from_slice(b"de\x02fg");
+        assert_eq!(framer.decode_frame(&mut buf).unwrap().unwrap(), b"fg");
+    }
+}
@@ -0,0 +1,136 @@
+//! HTTP/1.1 requests composed from a method, headers and a body, with framed responses.
+
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::{
+    error::{Error, Result},
+    framing::Framing,
+    msg::{ItemValue, Message, MessageFormat},
+};
+
+/// The format of the HTTP mode, each message is a request or a response split by
+/// `Framing::Http`.
+pub fn format() -> MessageFormat {
+    "http[*]".parse().unwrap()
+}
+
+pub fn framing() -> Framing {
+    Framing::Http { max_len: 1 << 20 }
+}
+
+/// Builds a request, headers are lines of `Name: value` and lines without a colon are sent
+/// as they are. `Content-Length` is added for the body unless the length is given.
+pub fn request(method: &str, target: &str, headers: &str, body: &str) -> Result<Message> {
+    if method.is_empty() {
+        return Err(Error::InvalidHttp {
+            details: "the method is empty".to_string(),
+        });
+    }
+
+    let mut lines = headers
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line.split_once(':') {
+            Some((name, value)) => json!([name.trim(), value.trim()]),
+            None => json!(line),
+        })
+        .collect::<Vec<_>>();
+    let has_len = lines.iter().any(|h| match h[0].as_str() {
+        Some(name) => {
+            name.eq_ignore_ascii_case("Content-Length")
+                || name.eq_ignore_ascii_case("Transfer-Encoding")
+        }
+        None => false,
+    });
+    if !body.is_empty() && !has_len {
+        lines.push(json!(["Content-Length", body.len().to_string()]));
+    }
+
+    Ok(Message::new(vec![ItemValue::Json(json!({
+        "method": method,
+        "target": target,
+        "version": "HTTP/1.1",
+        "headers": lines,
+        "body": body,
+    }))]))
+}
+
+/// Displays the status line and the length of the body of a decoded response.
+pub struct Summary<'a>(pub &'a Value);
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resp = self.0;
+        let len = |v: &Value| match v {
+            Value::String(s) => s.len(),
+            // Bytes in HEX.
+            v => v["$bytes"].as_str().map_or(0, |s| s.len() / 2),
+        };
+        write!(
+            f,
+            "{} {} {}",
+            resp["version"].as_str().unwrap_or_default(),
+            resp["status"],
+            resp["reason"].as_str().unwrap_or_default()
+        )?;
+        match &resp["chunks"] {
+            Value::Array(chunks) => write!(
+                f,
+                ", body: `{}` bytes in `{}` chunks",
+                chunks.iter().map(len).sum::<usize>(),
+                chunks.len()
+            ),
+            _ => write!(f, ", body: `{}` bytes", len(&resp["body"])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{msg::ItemValue, payload::http_encode};
+
+    use super::{request, Summary};
+
+    #[test]
+    fn request_ok() {
+        let msg = request("POST", "/", "Host: localhost\nbroken header\n", "hi").unwrap();
+        let value = match &msg.values()[0] {
+            ItemValue::Json(value) => value,
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            http_encode(value).unwrap(),
+            b"POST / HTTP/1.1\r\nHost: localhost\r\nbroken header\r\nContent-Length: 2\r\n\r\nhi"
+        );
+
+        // The given length is kept, even if it's wrong.
+        let msg = request("POST", "/", "content-length: 5", "hi").unwrap();
+        assert_eq!(
+            msg.values()[0],
+            ItemValue::Json(json!({
+                "method": "POST",
+                "target": "/",
+                "version": "HTTP/1.1",
+                "headers": [["content-length", "5"]],
+                "body": "hi",
+            }))
+        );
+        assert!(request("", "/", "", "").is_err());
+
+        let resp = json!({
+            "version": "HTTP/1.1",
+            "status": 200,
+            "reason": "OK",
+            "headers": [],
+            "chunks": ["abc", { "$bytes": "ff" }],
+        });
+        assert_eq!(
+            Summary(&resp).to_string(),
+            "HTTP/1.1 200 OK, body: `4` bytes in `2` chunks"
+        );
+    }
+}
@@ -0,0 +1,738 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    error::{Error, Result},
+    msg::{ItemFormat, MessageFormat},
+};
+
+// Imports a MessageFormat from the layout of a C struct.
+//
+// The supported subset of C:
+//  * `struct` definitions, `typedef struct`, and `typedef` of integers or structs.
+//  * Fixed-width integers of `stdint.h`, `char`, `short`, `int` and `long long`.
+//  * Arrays, `char[N]` becomes FixedString, `uint8_t[N]` becomes FixedBytes,
+//    other arrays are expanded to items.
+//  * Nested structs, which are flattened.
+//  * `#pragma pack(...)` of 1, 2, 4, 8 or 16, `__attribute__((packed))` and `#define` of
+//    integer constants.
+//
+// Items are named by fields, nested fields are joined by `_` and elements of expanded arrays
+// are suffixed by their indexes, such as `points_0_x`. Padding between fields is imported as
+// FixedBytes without names, and the count of items is limited by `MAX_ITEMS`.
+// Notice that integers of MessageFormat are always big-endian.
+
+// Max count of items after expanding arrays and nested structs.
+const MAX_ITEMS: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Integer(usize),
+    Punct(char),
+    Pack(Pack),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Pack {
+    Push(Option<usize>),
+    Pop,
+    Set(Option<usize>),
+}
+
+#[derive(Debug, Clone)]
+enum CType {
+    Int { len: usize, signed: bool },
+    Char,
+    Struct(String),
+    Array(Box<CType>, usize),
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    ty: CType,
+    line: usize,
+}
+
+#[derive(Debug, Clone)]
+struct CStruct {
+    fields: Vec<Field>,
+    pack: Option<usize>,
+}
+
+fn import_error(line: usize, details: impl Into<String>) -> Error {
+    Error::CImport {
+        line,
+        details: details.into(),
+    }
+}
+
+fn parse_integer(s: &str) -> Option<usize> {
+    let s = s.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        usize::from_str_radix(hex, 16).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+fn parse_pack(line_no: usize, args: &str) -> Result<Pack> {
+    let args = args
+        .split(',')
+        .map(str::trim)
+        .filter(|arg| !arg.is_empty())
+        .collect::<Vec<_>>();
+    let align = |arg: Option<&&str>| match arg {
+        Some(arg) => parse_integer(arg)
+            .filter(|align| align.is_power_of_two() && *align <= 16)
+            .map(Some)
+            .ok_or_else(|| import_error(line_no, format!("invalid pack alignment `{}`", arg))),
+        None => Ok(None),
+    };
+    match args.first() {
+        Some(&"push") => Ok(Pack::Push(align(args.get(1))?)),
+        Some(&"pop") => Ok(Pack::Pop),
+        arg => Ok(Pack::Set(align(arg)?)),
+    }
+}
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut defines = HashMap::<String, usize>::default();
+    let mut in_comment = false;
+    for (line_no, line) in src.lines().enumerate() {
+        let line_no = line_no + 1;
+
+        // Strip comments.
+        let mut code = String::new();
+        let mut rest = line;
+        loop {
+            if in_comment {
+                match rest.find("*/") {
+                    Some(end) => {
+                        rest = &rest[end + 2..];
+                        in_comment = false;
+                    }
+                    None => break,
+                }
+            } else {
+                match (rest.find("/*"), rest.find("//")) {
+                    (Some(block), line_comment) if line_comment.is_none_or(|l| block < l) => {
+                        code.push_str(&rest[..block]);
+                        code.push(' ');
+                        rest = &rest[block + 2..];
+                        in_comment = true;
+                    }
+                    (_, Some(line_comment)) => {
+                        code.push_str(&rest[..line_comment]);
+                        break;
+                    }
+                    _ => {
+                        code.push_str(rest);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Preprocessor directives.
+        let code = code.trim();
+        if let Some(directive) = code.strip_prefix('#') {
+            let mut words = directive.split_whitespace();
+            match words.next() {
+                Some("pragma") => {
+                    let pragma = words.collect::<String>();
+                    if let Some(args) = pragma
+                        .strip_prefix("pack(")
+                        .and_then(|args| args.strip_suffix(')'))
+                    {
+                        tokens.push((line_no, Token::Pack(parse_pack(line_no, args)?)));
+                    }
+                }
+                Some("define") => {
+                    if let (Some(name), Some(v)) = (words.next(), words.next()) {
+                        if let Some(v) = parse_integer(v.trim_matches(|c| c == '(' || c == ')')) {
+                            defines.insert(name.to_string(), v);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        }
+
+        let mut chars = code.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+            if c.is_ascii_alphanumeric() || c == '_' {
+                let mut end = start + 1;
+                while let Some(&(idx, c)) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' {
+                        break;
+                    }
+                    end = idx + 1;
+                    chars.next();
+                }
+
+                let word = &code[start..end];
+                let token = if c.is_ascii_digit() {
+                    Token::Integer(parse_integer(word).ok_or_else(|| {
+                        import_error(line_no, format!("invalid integer `{}`", word))
+                    })?)
+                } else if let Some(v) = defines.get(word) {
+                    Token::Integer(*v)
+                } else {
+                    Token::Ident(word.to_string())
+                };
+                tokens.push((line_no, token));
+            } else {
+                tokens.push((line_no, Token::Punct(c)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Default)]
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+
+    pack_stack: Vec<Option<usize>>,
+    pack: Option<usize>,
+
+    typedefs: HashMap<String, CType>,
+    structs: HashMap<String, CStruct>,
+    anonymous_cnt: usize,
+}
+
+impl Parser {
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map(|(line, _)| *line)
+            .unwrap_or_default()
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| import_error(self.line(), "unexpected end of header"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect_punct(&mut self, c: char) -> Result<()> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            token => Err(import_error(
+                self.line(),
+                format!("expected `{}`, found {:?}", c, token),
+            )),
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(ident) => Ok(ident),
+            token => Err(import_error(
+                self.line(),
+                format!("expected an identifier, found {:?}", token),
+            )),
+        }
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        while let Some(token) = self.peek().cloned() {
+            match token {
+                Token::Pack(pack) => {
+                    self.pos += 1;
+                    match pack {
+                        Pack::Push(align) => {
+                            self.pack_stack.push(self.pack);
+                            if align.is_some() {
+                                self.pack = align;
+                            }
+                        }
+                        Pack::Pop => self.pack = self.pack_stack.pop().flatten(),
+                        Pack::Set(align) => self.pack = align,
+                    }
+                }
+                Token::Punct(';') => self.pos += 1,
+                Token::Ident(ident) if ident == "typedef" => {
+                    self.pos += 1;
+                    let ty = self.parse_type()?;
+                    let name = self.expect_ident()?;
+                    self.expect_punct(';')?;
+                    self.typedefs.insert(name, ty);
+                }
+                Token::Ident(ident) if ident == "struct" => {
+                    self.parse_type()?;
+                    self.expect_punct(';')?;
+                }
+                token => {
+                    return Err(import_error(
+                        self.line(),
+                        format!("unsupported declaration starting with {:?}", token),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Skip `__attribute__((...))`, returns whether it is packed.
+    fn parse_attributes(&mut self) -> Result<bool> {
+        let mut packed = false;
+        while self.peek() == Some(&Token::Ident("__attribute__".to_string())) {
+            self.pos += 1;
+            let mut depth = 0;
+            loop {
+                match self.next()? {
+                    Token::Punct('(') => depth += 1,
+                    Token::Punct(')') => {
+                        depth -= 1;
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                    Token::Ident(ident) if ident == "packed" || ident == "__packed__" => {
+                        packed = true
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(packed)
+    }
+
+    fn parse_type(&mut self) -> Result<CType> {
+        let line = self.line();
+        let mut words = Vec::<String>::new();
+        loop {
+            match self.peek() {
+                Some(Token::Ident(ident))
+                    if matches!(
+                        ident.as_str(),
+                        "const"
+                            | "volatile"
+                            | "unsigned"
+                            | "signed"
+                            | "char"
+                            | "short"
+                            | "int"
+                            | "long"
+                    ) =>
+                {
+                    let ident = ident.clone();
+                    self.pos += 1;
+                    if ident != "const" && ident != "volatile" {
+                        words.push(ident);
+                    }
+                }
+                Some(Token::Ident(ident)) if ident == "struct" && words.is_empty() => {
+                    self.pos += 1;
+                    return self.parse_struct();
+                }
+                Some(Token::Ident(ident)) if words.is_empty() => {
+                    let ident = ident.clone();
+                    self.pos += 1;
+                    return self.named_type(line, &ident);
+                }
+                _ => break,
+            }
+        }
+
+        let words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        let (len, signed) = match words.as_slice() {
+            ["char"] => return Ok(CType::Char),
+            ["signed", "char"] => (1, true),
+            ["unsigned", "char"] => (1, false),
+            ["short"] | ["short", "int"] | ["signed", "short"] | ["signed", "short", "int"] => {
+                (2, true)
+            }
+            ["unsigned", "short"] | ["unsigned", "short", "int"] => (2, false),
+            ["int"] | ["signed"] | ["signed", "int"] => (4, true),
+            ["unsigned"] | ["unsigned", "int"] => (4, false),
+            ["long", "long"] | ["long", "long", "int"] | ["signed", "long", "long"] => (8, true),
+            ["unsigned", "long", "long"] | ["unsigned", "long", "long", "int"] => (8, false),
+            words if words.contains(&"long") => {
+                return Err(import_error(
+                    line,
+                    "the size of `long` depends on the platform, use fixed-width integers instead",
+                ))
+            }
+            _ => return Err(import_error(line, "expected a type")),
+        };
+        Ok(CType::Int { len, signed })
+    }
+
+    fn named_type(&mut self, line: usize, name: &str) -> Result<CType> {
+        let ty = match name {
+            "int8_t" => CType::Int {
+                len: 1,
+                signed: true,
+            },
+            "uint8_t" | "bool" | "_Bool" => CType::Int {
+                len: 1,
+                signed: false,
+            },
+            "int16_t" => CType::Int {
+                len: 2,
+                signed: true,
+            },
+            "uint16_t" => CType::Int {
+                len: 2,
+                signed: false,
+            },
+            "int32_t" => CType::Int {
+                len: 4,
+                signed: true,
+            },
+            "uint32_t" => CType::Int {
+                len: 4,
+                signed: false,
+            },
+            "int64_t" => CType::Int {
+                len: 8,
+                signed: true,
+            },
+            "uint64_t" => CType::Int {
+                len: 8,
+                signed: false,
+            },
+            "float" | "double" | "union" | "enum" => {
+                return Err(import_error(line, format!("`{}` is not supported", name)))
+            }
+            _ => self
+                .typedefs
+                .get(name)
+                .cloned()
+                .ok_or_else(|| import_error(line, format!("unknown type `{}`", name)))?,
+        };
+        Ok(ty)
+    }
+
+    // Parse the rest of a struct type after the keyword `struct`.
+    fn parse_struct(&mut self) -> Result<CType> {
+        let mut packed = self.parse_attributes()?;
+
+        let tag = match self.peek() {
+            Some(Token::Ident(tag)) => {
+                let tag = tag.clone();
+                self.pos += 1;
+                Some(tag)
+            }
+            _ => None,
+        };
+
+        if self.peek() != Some(&Token::Punct('{')) {
+            // Reference to a defined struct.
+            let tag = tag.ok_or_else(|| import_error(self.line(), "expected a struct"))?;
+            if !self.structs.contains_key(&tag) {
+                return Err(import_error(
+                    self.line(),
+                    format!("unknown struct `{}`", tag),
+                ));
+            }
+            return Ok(CType::Struct(tag));
+        }
+        self.pos += 1;
+
+        let mut fields = Vec::new();
+        while self.peek() != Some(&Token::Punct('}')) {
+            let ty = self.parse_type()?;
+            loop {
+                if self.peek() == Some(&Token::Punct('*')) {
+                    return Err(import_error(self.line(), "pointers are not supported"));
+                }
+                let line = self.line();
+                let name = self.expect_ident()?;
+
+                // Arrays, multi-dimensional arrays are in row-major order.
+                let mut dims = Vec::new();
+                while self.peek() == Some(&Token::Punct('[')) {
+                    self.pos += 1;
+                    match self.next()? {
+                        Token::Integer(n) if n > 0 => dims.push(n),
+                        token => {
+                            return Err(import_error(
+                                self.line(),
+                                format!("expected a positive array length, found {:?}", token),
+                            ))
+                        }
+                    }
+                    self.expect_punct(']')?;
+                }
+                fields.push(Field {
+                    name,
+                    ty: dims
+                        .into_iter()
+                        .rev()
+                        .fold(ty.clone(), |ty, n| CType::Array(Box::new(ty), n)),
+                    line,
+                });
+
+                match self.next()? {
+                    Token::Punct(',') => {}
+                    Token::Punct(';') => break,
+                    Token::Punct(':') => {
+                        return Err(import_error(self.line(), "bit-fields are not supported"))
+                    }
+                    token => {
+                        return Err(import_error(
+                            self.line(),
+                            format!("expected `;`, found {:?}", token),
+                        ))
+                    }
+                }
+            }
+        }
+        self.pos += 1;
+        packed |= self.parse_attributes()?;
+
+        let tag = tag.unwrap_or_else(|| {
+            self.anonymous_cnt += 1;
+            format!("<anonymous {}>", self.anonymous_cnt)
+        });
+        let pack = if packed { Some(1) } else { self.pack };
+        self.structs.insert(tag.clone(), CStruct { fields, pack });
+
+        Ok(CType::Struct(tag))
+    }
+
+    // The size and alignment of a type, which is declared at the line.
+    fn layout(&self, ty: &CType, line: usize) -> Result<(usize, usize)> {
+        let too_large = || import_error(line, "the size of the type overflows");
+        match ty {
+            CType::Int { len, .. } => Ok((*len, *len)),
+            CType::Char => Ok((1, 1)),
+            CType::Array(ty, n) => {
+                let (size, align) = self.layout(ty, line)?;
+                Ok((size.checked_mul(*n).ok_or_else(too_large)?, align))
+            }
+            CType::Struct(tag) => {
+                let s = &self.structs[tag];
+                let mut offset = 0usize;
+                let mut struct_align = 1;
+                for field in &s.fields {
+                    let (size, align) = self.layout(&field.ty, field.line)?;
+                    let align = s.pack.map_or(align, |pack| align.min(pack));
+                    offset = offset
+                        .div_ceil(align)
+                        .checked_mul(align)
+                        .and_then(|offset| offset.checked_add(size))
+                        .ok_or_else(|| {
+                            import_error(field.line, "the size of the type overflows")
+                        })?;
+                    struct_align = struct_align.max(align);
+                }
+                let size = offset
+                    .div_ceil(struct_align)
+                    .checked_mul(struct_align)
+                    .ok_or_else(too_large)?;
+                Ok((size, struct_align))
+            }
+        }
+    }
+
+    // Pushes items of a type named `name`, which is declared at the line. The layout of the type
+    // should have been checked.
+    fn flatten(
+        &self,
+        ty: &CType,
+        name: Option<String>,
+        line: usize,
+        items: &mut Vec<(Option<String>, ItemFormat)>,
+    ) -> Result<()> {
+        let join = |suffix: &dyn fmt::Display| match &name {
+            Some(name) => format!("{}_{}", name, suffix),
+            None => suffix.to_string(),
+        };
+        match ty {
+            CType::Int { len, signed: true } => {
+                push_item(items, name, ItemFormat::Int { len: *len }, line)
+            }
+            CType::Int { len, signed: false } => {
+                push_item(items, name, ItemFormat::Uint { len: *len }, line)
+            }
+            CType::Char => push_item(items, name, ItemFormat::Int { len: 1 }, line),
+            CType::Array(elem, n) => match elem.as_ref() {
+                CType::Char => push_item(items, name, ItemFormat::FixedString { len: *n }, line),
+                CType::Int { len: 1, .. } => {
+                    push_item(items, name, ItemFormat::FixedBytes { len: *n }, line)
+                }
+                elem => {
+                    (0..*n).try_for_each(|idx| self.flatten(elem, Some(join(&idx)), line, items))
+                }
+            },
+            CType::Struct(tag) => {
+                let s = &self.structs[tag];
+                let mut offset = 0;
+                let mut struct_align = 1;
+                for field in &s.fields {
+                    let (size, align) = self.layout(&field.ty, field.line)?;
+                    let align = s.pack.map_or(align, |pack| align.min(pack));
+                    let padding = (align - offset % align) % align;
+                    if padding != 0 {
+                        let fmt = ItemFormat::FixedBytes { len: padding };
+                        push_item(items, None, fmt, field.line)?;
+                    }
+                    self.flatten(&field.ty, Some(join(&field.name)), field.line, items)?;
+                    offset += padding + size;
+                    struct_align = struct_align.max(align);
+                }
+                let padding = (struct_align - offset % struct_align) % struct_align;
+                if padding != 0 {
+                    push_item(items, None, ItemFormat::FixedBytes { len: padding }, line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn push_item(
+    items: &mut Vec<(Option<String>, ItemFormat)>,
+    name: Option<String>,
+    fmt: ItemFormat,
+    line: usize,
+) -> Result<()> {
+    if items.len() >= MAX_ITEMS {
+        return Err(import_error(
+            line,
+            format!("the count of items exceeds {}", MAX_ITEMS),
+        ));
+    }
+    items.push((name, fmt));
+    Ok(())
+}
+
+/// Imports the struct named `name` from a C header, which is either a struct tag or a typedef.
+///
+/// Items are named by the fields, such as `points_0_x` for `points[0].x`. Integers are imported
+/// as big-endian, since integers of [`MessageFormat`] are always big-endian, so structs of
+/// little-endian firmware are decoded with swapped bytes.
+pub fn from_c_struct(header: &str, name: &str) -> Result<MessageFormat> {
+    let mut parser = Parser {
+        tokens: tokenize(header)?,
+        ..Default::default()
+    };
+    parser.parse()?;
+
+    let ty = match parser.typedefs.get(name) {
+        Some(ty @ CType::Struct(_)) => ty.clone(),
+        _ if parser.structs.contains_key(name) => CType::Struct(name.to_string()),
+        _ => {
+            return Err(Error::NoSuchCStruct {
+                name: name.to_string(),
+            })
+        }
+    };
+
+    let line = parser.line();
+    parser.layout(&ty, line)?;
+    let mut items = Vec::new();
+    parser.flatten(&ty, None, line, &mut items)?;
+    let (names, fmts): (Vec<_>, Vec<_>) = items.into_iter().unzip();
+    MessageFormat::new(&fmts)?.with_names(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msg::{ItemFormat, MessageFormat};
+
+    use super::from_c_struct;
+
+    #[test]
+    fn import_ok() {
+        let header = r#"
+            #include <stdint.h>
+
+            #define NAME_LEN 8
+
+            typedef uint16_t cmd_t;
+
+            struct point {
+                int32_t x, y;
+            };
+
+            #pragma pack(push, 1)
+            typedef struct {
+                cmd_t cmd;           // Command.
+                uint8_t flags[2];
+                char name[NAME_LEN]; /* Name. */
+                struct point points[2];
+            } packet_t;
+            #pragma pack(pop)
+
+            struct aligned {
+                uint8_t kind;
+                uint32_t value;
+                int8_t tail;
+            };
+        "#;
+
+        assert_eq!(
+            from_c_struct(header, "packet_t").unwrap(),
+            MessageFormat::new(&[
+                ItemFormat::Uint { len: 2 },
+                ItemFormat::FixedBytes { len: 2 },
+                ItemFormat::FixedString { len: 8 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::Int { len: 4 },
+            ])
+            .unwrap()
+            .with_names(
+                [
+                    "cmd",
+                    "flags",
+                    "name",
+                    "points_0_x",
+                    "points_0_y",
+                    "points_1_x",
+                    "points_1_y"
+                ]
+                .iter()
+                .map(|name| Some(name.to_string()))
+            )
+            .unwrap()
+        );
+
+        assert_eq!(
+            from_c_struct(header, "aligned").unwrap(),
+            MessageFormat::new(&[
+                ItemFormat::Uint { len: 1 },
+                ItemFormat::FixedBytes { len: 3 },
+                ItemFormat::Uint { len: 4 },
+                ItemFormat::Int { len: 1 },
+                ItemFormat::FixedBytes { len: 3 },
+            ])
+            .unwrap()
+            .with_names(
+                [Some("kind"), None, Some("value"), Some("tail"), None]
+                    .iter()
+                    .map(|name| name.map(str::to_string))
+            )
+            .unwrap()
+        );
+
+        assert!(from_c_struct("struct s { long v; };", "s").is_err());
+        assert!(from_c_struct("struct s { uint8_t *p; };", "s").is_err());
+        assert!(from_c_struct("#pragma pack(0)\nstruct s { uint16_t v; };", "s").is_err());
+        assert!(from_c_struct("#pragma pack(push, 3)\nstruct s { uint16_t v; };", "s").is_err());
+        assert!(from_c_struct("struct s { uint16_t v[5000]; };", "s").is_err());
+        assert!(from_c_struct("struct s { uint64_t v[0x4000000000000000][2]; };", "s").is_err());
+    }
+}
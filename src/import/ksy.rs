@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+
+use serde_yaml::Value;
+
+use crate::{
+    error::{Error, Result},
+    msg::{ItemFormat, MessageFormat},
+};
+
+// Imports a MessageFormat from a Kaitai Struct definition, the `seq` of the top level is imported.
+//
+// The supported subset of attributes:
+//  * `type`: `u1`..`u8`, `s1`..`s8` with big-endian, `str`, and user types, which are flattened.
+//  * `size`: an integer, or the id of an integer attribute before in the same type.
+//  * `contents`: imported as FixedBytes, the contents are not checked.
+//  * `repeat: expr` with an integer `repeat-expr`, which is expanded to items,
+//    so the count of items is limited by `MAX_ITEMS`.
+//  * `id`, `doc`, `enum`, `encoding` of UTF-8 or ASCII, which don't affect the layout.
+
+// Max count of items after expanding repeats and user types.
+const MAX_ITEMS: usize = 4096;
+
+fn ksy_error(path: &str, details: impl Into<String>) -> Error {
+    Error::KsyImport {
+        path: path.to_string(),
+        details: details.into(),
+    }
+}
+
+struct Importer<'a> {
+    types: Option<&'a Value>,
+    big_endian: bool,
+    fmts: Vec<ItemFormat>,
+}
+
+impl<'a> Importer<'a> {
+    fn import_seq(&mut self, seq: &'a Value, path: &str, depth: usize) -> Result<()> {
+        if depth > 16 {
+            return Err(ksy_error(path, "the types are nested too deep"));
+        }
+
+        let seq = seq
+            .as_sequence()
+            .ok_or_else(|| ksy_error(path, "`seq` should be a sequence"))?;
+
+        // Index of items by id, which are in the scope of this type.
+        let mut idx_by_id = HashMap::<&str, usize>::default();
+        for (attr_idx, attr) in seq.iter().enumerate() {
+            let path = format!("{}[{}]", path, attr_idx);
+            let attr = attr
+                .as_mapping()
+                .ok_or_else(|| ksy_error(&path, "an attribute should be a mapping"))?;
+            let get = |key: &str| attr.get(&Value::String(key.to_string()));
+
+            for (key, _) in attr.iter() {
+                match key.as_str() {
+                    Some(
+                        "id" | "doc" | "doc-ref" | "enum" | "encoding" | "type" | "size"
+                        | "contents" | "repeat" | "repeat-expr",
+                    ) => {}
+                    Some(key) => {
+                        return Err(ksy_error(&path, format!("`{}` is not supported", key)))
+                    }
+                    None => return Err(ksy_error(&path, "keys should be strings")),
+                }
+            }
+
+            if let Some(encoding) = get("encoding").and_then(Value::as_str) {
+                if !matches!(encoding.to_uppercase().as_str(), "UTF-8" | "UTF8" | "ASCII") {
+                    return Err(ksy_error(
+                        &path,
+                        format!("the encoding `{}` is not supported", encoding),
+                    ));
+                }
+            }
+
+            let cnt = match (
+                get("repeat").map(|v| v.as_str()),
+                get("repeat-expr").map(Value::as_u64),
+            ) {
+                (None, None) => 1,
+                (Some(Some("expr")), Some(Some(cnt))) if cnt <= MAX_ITEMS as u64 => cnt as usize,
+                (Some(Some("expr")), Some(Some(cnt))) => {
+                    return Err(ksy_error(
+                        &path,
+                        format!("`repeat-expr: {}` exceeds {} items", cnt, MAX_ITEMS),
+                    ))
+                }
+                (Some(Some("expr")), _) => {
+                    return Err(ksy_error(&path, "`repeat-expr` should be an integer"))
+                }
+                (Some(repeat), _) => {
+                    return Err(ksy_error(
+                        &path,
+                        format!("`repeat: {}` is not supported", repeat.unwrap_or("?")),
+                    ))
+                }
+                (None, Some(_)) => return Err(ksy_error(&path, "`repeat` is missing")),
+            };
+
+            let first_idx = self.fmts.len();
+            for _ in 0..cnt {
+                self.import_attr(&path, &get, &idx_by_id, depth)?;
+                // Nested repeats multiply.
+                if self.fmts.len() > MAX_ITEMS {
+                    return Err(ksy_error(
+                        &path,
+                        format!("the count of items exceeds {}", MAX_ITEMS),
+                    ));
+                }
+            }
+
+            // Only a single integer can be referenced by a size.
+            if let Some(id) = get("id").and_then(Value::as_str) {
+                if cnt == 1 && self.fmts.len() == first_idx + 1 {
+                    idx_by_id.insert(id, first_idx);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn import_attr(
+        &mut self,
+        path: &str,
+        get: &dyn Fn(&str) -> Option<&'a Value>,
+        idx_by_id: &HashMap<&str, usize>,
+        depth: usize,
+    ) -> Result<()> {
+        // The size is either fixed or the index of length.
+        enum Size {
+            Fixed(usize),
+            Var(usize),
+        }
+        let size = match get("size") {
+            None => None,
+            Some(Value::Number(n)) => match n.as_u64() {
+                Some(n) if n > 0 => Some(Size::Fixed(n as usize)),
+                _ => return Err(ksy_error(path, "`size` should be a positive integer")),
+            },
+            Some(Value::String(id)) => match idx_by_id.get(id.as_str()) {
+                Some(len_idx) => Some(Size::Var(*len_idx)),
+                None => {
+                    return Err(ksy_error(
+                        path,
+                        format!(
+                            "`size: {}` should be an integer or the id of an integer before",
+                            id
+                        ),
+                    ))
+                }
+            },
+            Some(_) => return Err(ksy_error(path, "`size` should be an integer or an id")),
+        };
+
+        if let Some(contents) = get("contents") {
+            let len = match contents {
+                Value::String(s) => s.len(),
+                Value::Sequence(seq) => seq.len(),
+                _ => return Err(ksy_error(path, "`contents` should be a string or an array")),
+            };
+            if len == 0 {
+                return Err(ksy_error(path, "`contents` should not be empty"));
+            }
+            self.fmts.push(ItemFormat::FixedBytes { len });
+            return Ok(());
+        }
+
+        let ty = match get("type") {
+            Some(ty) => Some(
+                ty.as_str()
+                    .ok_or_else(|| ksy_error(path, "switch types are not supported"))?,
+            ),
+            None => None,
+        };
+        let fmt = match (ty, size) {
+            (None, Some(Size::Fixed(len))) => ItemFormat::FixedBytes { len },
+            (None, Some(Size::Var(len_idx))) => ItemFormat::VarBytes { len_idx },
+            (None, None) => return Err(ksy_error(path, "either `type` or `size` is required")),
+            (Some("str"), Some(Size::Fixed(len))) => ItemFormat::FixedString { len },
+            (Some("str"), Some(Size::Var(len_idx))) => ItemFormat::VarString { len_idx },
+            (Some("str"), None) => return Err(ksy_error(path, "`str` requires `size`")),
+            (Some(ty), None) => {
+                if let Some(fmt) = self.integer(path, ty) {
+                    self.fmts.push(fmt?);
+                    return Ok(());
+                }
+
+                let seq = self
+                    .types
+                    .and_then(|types| types.get(ty))
+                    .and_then(|t| t.get("seq"))
+                    .ok_or_else(|| ksy_error(path, format!("unknown type `{}`", ty)))?;
+                return self.import_seq(seq, &format!("types.{}.seq", ty), depth + 1);
+            }
+            (Some(_), Some(_)) => {
+                return Err(ksy_error(path, "`size` of user types is not supported"))
+            }
+        };
+        self.fmts.push(fmt);
+        Ok(())
+    }
+
+    // Parse integer types such as `u4`, `s2be`.
+    fn integer(&self, path: &str, ty: &str) -> Option<Result<ItemFormat>> {
+        let (signed, rest) = match ty.split_at(1.min(ty.len())) {
+            ("u", rest) => (false, rest),
+            ("s", rest) => (true, rest),
+            _ => return None,
+        };
+        let (len, big_endian) = match rest.split_at(1.min(rest.len())) {
+            (len, "") => (len, self.big_endian),
+            (len, "be") => (len, true),
+            (len, "le") => (len, false),
+            _ => return None,
+        };
+        let len = match len {
+            "1" => 1,
+            "2" => 2,
+            "4" => 4,
+            "8" => 8,
+            _ => return None,
+        };
+
+        if !big_endian && len > 1 {
+            return Some(Err(ksy_error(
+                path,
+                format!("`{}` is little-endian, integers are always big-endian", ty),
+            )));
+        }
+        if signed {
+            Some(Ok(ItemFormat::Int { len }))
+        } else {
+            Some(Ok(ItemFormat::Uint { len }))
+        }
+    }
+}
+
+/// Imports the top level `seq` of a Kaitai Struct definition.
+pub fn from_ksy(ksy: &str) -> Result<MessageFormat> {
+    let root: Value = serde_yaml::from_str(ksy).map_err(|e| ksy_error("", e.to_string()))?;
+
+    let big_endian = match root.get("meta").and_then(|meta| meta.get("endian")) {
+        None => true,
+        Some(endian) => match endian.as_str() {
+            Some("be") => true,
+            Some("le") => false,
+            _ => return Err(ksy_error("meta.endian", "switch endian is not supported")),
+        },
+    };
+
+    let mut importer = Importer {
+        types: root.get("types"),
+        big_endian,
+        fmts: Vec::new(),
+    };
+    let seq = root
+        .get("seq")
+        .ok_or_else(|| ksy_error("seq", "`seq` is required"))?;
+    importer.import_seq(seq, "seq", 0)?;
+
+    // Integers referenced by sizes are lengths.
+    let mut fmts = importer.fmts;
+    let len_indices = fmts
+        .iter()
+        .filter_map(|fmt| match fmt {
+            ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx } => Some(*len_idx),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for len_idx in len_indices {
+        match fmts[len_idx] {
+            ItemFormat::Uint { len } => fmts[len_idx] = ItemFormat::Len { len },
+            ItemFormat::Len { .. } => {}
+            _ => {
+                return Err(ksy_error(
+                    "seq",
+                    format!("the size referenced by item `{}` is not unsigned", len_idx),
+                ))
+            }
+        }
+    }
+
+    MessageFormat::new(&fmts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        error::Error,
+        msg::{ItemFormat, MessageFormat},
+    };
+
+    use super::from_ksy;
+
+    #[test]
+    fn import_ok() {
+        let ksy = r#"
+meta:
+  id: packet
+  endian: be
+seq:
+  - id: magic
+    contents: [0xca, 0xfe]
+  - id: name_len
+    type: u2
+  - id: name
+    type: str
+    size: name_len
+    encoding: UTF-8
+  - id: points
+    type: point
+    repeat: expr
+    repeat-expr: 2
+  - id: reserved
+    size: 3
+types:
+  point:
+    seq:
+      - id: x
+        type: s4
+      - id: y
+        type: s4be
+"#;
+
+        assert_eq!(
+            from_ksy(ksy).unwrap(),
+            MessageFormat::new(&[
+                ItemFormat::FixedBytes { len: 2 },
+                ItemFormat::Len { len: 2 },
+                ItemFormat::VarString { len_idx: 1 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::Int { len: 4 },
+                ItemFormat::FixedBytes { len: 3 },
+            ])
+            .unwrap()
+        );
+
+        match from_ksy("seq:\n  - id: a\n    type: u1\n  - id: b\n    type: strz\n") {
+            Err(Error::KsyImport { path, .. }) => assert_eq!(path, "seq[1]"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        match from_ksy("seq:\n  - id: a\n    size-eos: true\n") {
+            Err(Error::KsyImport { path, .. }) => assert_eq!(path, "seq[0]"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn import_repeat_limited() {
+        let ksy = "seq:\n  - id: a\n    type: u1\n    repeat: expr\n    repeat-expr: 4294967296\n";
+        match from_ksy(ksy) {
+            Err(Error::KsyImport { path, .. }) => assert_eq!(path, "seq[0]"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // Each repeat is small, but nested ones are not.
+        let ksy = r#"
+seq:
+  - id: a
+    type: b
+    repeat: expr
+    repeat-expr: 100
+types:
+  b:
+    seq:
+      - id: c
+        type: u1
+        repeat: expr
+        repeat-expr: 100
+"#;
+        match from_ksy(ksy) {
+            Err(Error::KsyImport { path, .. }) => assert_eq!(path, "types.b.seq[0]"),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(from_ksy(&ksy.replace("100", "10")).is_ok());
+    }
+}
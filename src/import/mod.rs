@@ -0,0 +1,7 @@
+mod c_struct;
+mod ksy;
+mod proto;
+
+pub use c_struct::*;
+pub use ksy::*;
+pub use proto::*;
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use crate::{
+    error::Result,
+    msg::{ItemFormat, MessageFormat, PayloadLen},
+    protobuf::ProtoSchema,
+};
+
+/// Imports the message named `name` from a `.proto` file, which is either the full name
+/// or a unique suffix of it.
+///
+/// The format has a single protobuf item taking the whole message, so streams need a framing,
+/// such as `Framing::VarintPrefix` for the delimited format of protobuf.
+pub fn from_proto(proto: &str, name: &str) -> Result<MessageFormat> {
+    let schema = ProtoSchema::from_proto(proto, name)?;
+    MessageFormat::new(&[ItemFormat::Protobuf {
+        len: PayloadLen::Greedy,
+        schema: Arc::new(schema),
+    }])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Deref;
+
+    use serde_json::json;
+
+    use crate::{
+        buffered::BufferedDecoder,
+        error::Error,
+        framing::Framing,
+        msg::{ItemValue, Message, MessageDecoder, MessageEncoder},
+        stream::{MessageReader, MessageWriter},
+    };
+
+    use super::from_proto;
+
+    #[test]
+    fn import_ok() {
+        let proto = r#"
+            syntax = "proto3";
+            message Reading {
+                string sensor = 1;
+                repeated float values = 2 [packed = true];
+            }
+        "#;
+        let fmt = from_proto(proto, "Reading").unwrap();
+        assert!(fmt.is_greedy());
+        assert_eq!(fmt.to_string(), "proto[*]");
+
+        let msg = Message::new(vec![ItemValue::Json(
+            json!({ "sensor": "t1", "values": [1.5, -2.0] }),
+        )]);
+        let mut bytes = Vec::new();
+        MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+        assert_eq!(
+            MessageDecoder::new(&fmt, &mut bytes.deref())
+                .decode(Default::default())
+                .unwrap(),
+            msg
+        );
+        let mut decoder = BufferedDecoder::new(fmt.clone());
+        assert_eq!(decoder.decode(&bytes).unwrap().unwrap().to_message(), msg);
+
+        // Delimited messages in a stream.
+        let mut stream = Vec::new();
+        let framing = Framing::VarintPrefix { max_len: 1024 };
+        let mut writer =
+            MessageWriter::new(fmt.clone(), &mut stream).framer(Some(framing.framer().unwrap()));
+        writer.write(&msg).unwrap();
+        writer.write(&msg).unwrap();
+        let mut reader =
+            MessageReader::new(fmt, &stream[..]).framer(Some(framing.framer().unwrap()));
+        let stop_flag = Default::default();
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+        assert!(matches!(reader.read(&stop_flag), Err(Error::EndOfStream)));
+
+        assert!(matches!(
+            from_proto(proto, "Report"),
+            Err(Error::NoSuchProtoMessage { .. })
+        ));
+    }
+}
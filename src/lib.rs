@@ -0,0 +1,86 @@
+//! Encodes and decodes binary messages over sockets.
+//!
+//! A message is a list of items described by a [`MessageFormat`], which is built from
+//! [`ItemFormat`]s or parsed from the format DSL, such as `len:u16 be; cmd:u8; name:str[len]`.
+//! Messages are encoded with [`MessageEncoder`] and decoded with [`MessageDecoder`], or
+//! [`BufferedDecoder`] for high-throughput streams. [`Server`] and [`Client`] exchange
+//! messages over TCP, optionally split into frames by a [`Framing`] or carried in WebSocket
+//! messages, see [`WebSocket`].
+//!
+//! ```
+//! use socket_toolbox::{ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat};
+//!
+//! let fmt: MessageFormat = "len:u8; name:str[len]".parse().unwrap();
+//! let msg = Message::new(vec![ItemValue::Len(2), ItemValue::String("ok".to_string())]);
+//!
+//! let mut bytes = Vec::new();
+//! MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+//! assert_eq!(bytes, b"\x02ok");
+//!
+//! let decoded = MessageDecoder::new(&fmt, &mut bytes.as_slice())
+//!     .decode(Default::default())
+//!     .unwrap();
+//! assert_eq!(decoded, msg);
+//! ```
+//!
+//! Payloads of protobuf are decoded to JSON by a schema imported from a `.proto` file,
+//! see [`from_proto`]. Formats are also imported from C structs and Kaitai Struct, and
+//! codecs of formats are generated in Rust and C, and as Wireshark dissectors in Lua.
+//! Self-describing payloads of CBOR, MessagePack, JSON text, BER, TLVs, RESP, HTTP/1.1,
+//! STOMP and the memcached text protocol are decoded without a schema, see [`ItemFormat`].
+//! Formats of well-known protocols, such as Modbus/TCP and NTP, are in [`preset`].
+//! In the Modbus/TCP mode, a [`Server`] answers requests from a simulated [`RegisterMap`].
+//! In the Redis mode, RESP commands are answered by a mock [`KeyStore`]. In the memcached mode,
+//! commands of the text protocol are answered by a mock [`CacheStore`]. In the HTTP mode,
+//! a [`Client`] composes requests and responses are split into frames. In the MQTT mode,
+//! a [`Client`] publishes messages to a broker and decodes the payloads of received
+//! publishes, see [`Mqtt`].
+//!
+//! Modules other than [`msg`], [`dsl`], [`framing`], [`stream`], `codec` and [`preset`]
+//! are private, their public items are re-exported here.
+//!
+//! The `codec` feature provides a `tokio_util` codec of messages.
+
+#![warn(missing_docs)]
+
+mod buffered;
+mod checksum;
+#[cfg(feature = "codec")]
+pub mod codec;
+mod codegen;
+pub mod dsl;
+mod error;
+pub mod framing;
+mod http;
+mod import;
+mod memcache;
+mod modbus;
+mod mqtt;
+pub mod msg;
+mod payload;
+pub mod preset;
+mod protobuf;
+mod redis;
+mod socket;
+pub mod stream;
+mod websocket;
+
+pub use buffered::{BufferedDecoder, ItemValueRef, MessageRef};
+pub use checksum::Checksum;
+#[cfg(feature = "codec")]
+pub use codec::MessageCodec;
+pub use codegen::{c_header, lua_dissector, rust_code, Transport as DissectorTransport};
+pub use error::{DecodeError, Error, Result};
+pub use framing::{Framer, Framing};
+pub use import::{from_c_struct, from_ksy, from_proto};
+pub use memcache::{CacheItem, CacheStore};
+pub use modbus::{RegisterMap, Request as ModbusRequest};
+pub use mqtt::{Mqtt, Version as MqttVersion};
+pub use msg::{
+    ItemFormat, ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat, PayloadLen,
+};
+pub use protobuf::ProtoSchema;
+pub use redis::KeyStore;
+pub use socket::{Client, Server};
+pub use stream::{MessageReader, MessageWriter, Resync};
+pub use websocket::WebSocket;
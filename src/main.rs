@@ -1,9 +1,6 @@
 use simplelog::SimpleLogger;
 use ui::app::App;
 
-mod error;
-mod msg;
-mod socket;
 mod ui;
 
 fn main() {
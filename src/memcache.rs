@@ -0,0 +1,320 @@
+//! memcached commands over the text protocol, with a mock item store for servers.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde_json::{json, Value};
+
+use crate::{
+    error::{Error, Result},
+    framing::Framing,
+    msg::{ItemValue, Message, MessageFormat},
+    payload::{body_bytes, body_value},
+    redis::split_args,
+};
+
+/// The format of the memcached mode, each message is a command or a reply split by
+/// `Framing::Memcache`.
+pub fn format() -> MessageFormat {
+    "memcache[*]".parse().unwrap()
+}
+
+pub fn framing() -> Framing {
+    Framing::Memcache { max_len: 1 << 20 }
+}
+
+fn is_storage(cmd: &str) -> bool {
+    matches!(
+        cmd,
+        "set" | "add" | "replace" | "append" | "prepend" | "cas"
+    )
+}
+
+/// Builds a command from a line, arguments are split by whitespace unless quoted by `"`.
+///
+/// The data of storage commands is given in place of the length, such as
+/// `set key 0 0 "a value"`, and the length is filled in.
+pub fn command(line: &str) -> Result<Message> {
+    let invalid = |details: String| Error::InvalidMemcache { details };
+    let mut args = split_args(line).map_err(invalid)?;
+    let data = match args.first() {
+        None => return Err(invalid("the command is empty".to_string())),
+        Some(cmd) if is_storage(cmd) => {
+            if args.len() < 5 {
+                return Err(invalid(format!("the data is missing in `{}`", line)));
+            }
+            let data = std::mem::take(&mut args[4]);
+            args[4] = data.len().to_string();
+            Some(data)
+        }
+        Some(_) => None,
+    };
+    if let Some(arg) = args
+        .iter()
+        .find(|arg| arg.is_empty() || arg.contains(char::is_whitespace))
+    {
+        return Err(invalid(format!(
+            "only the data can be empty or contain whitespace, `{}`",
+            arg
+        )));
+    }
+
+    let line = args.join(" ");
+    let entry = match data {
+        Some(data) => json!({ "line": line, "data": data }),
+        None => json!({ "line": line }),
+    };
+    Ok(Message::new(vec![ItemValue::Json(json!([entry]))]))
+}
+
+/// Displays a reply line by line, each data block follows its line as a quoted string or bytes.
+pub struct Reply<'a>(pub &'a Value);
+
+impl fmt::Display for Reply<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = match self.0.as_array() {
+            Some(entries) => entries,
+            None => return write!(f, "{}", self.0),
+        };
+        for (idx, entry) in entries.iter().enumerate() {
+            if idx != 0 {
+                writeln!(f)?;
+            }
+            match entry.get("line") {
+                Some(Value::String(line)) => write!(f, "{}", line)?,
+                _ => write!(f, "{}", entry)?,
+            }
+            match entry.get("data") {
+                Some(Value::String(s)) => write!(f, "\n{:?}", s)?,
+                Some(data) => write!(f, "\n{}", data)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn line(line: &str) -> Value {
+    json!([{ "line": line }])
+}
+
+/// An item of the mock server.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CacheItem {
+    /// Flags given by the client, which are returned with the data.
+    pub flags: u32,
+    /// The data block.
+    pub data: Vec<u8>,
+    /// The unique value checked by `cas`, which changes on each update.
+    pub cas: u64,
+}
+
+/// Items answered by the mock server, expiration times are ignored.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CacheStore {
+    /// Items by keys.
+    pub items: BTreeMap<String, CacheItem>,
+
+    last_cas: u64,
+}
+
+impl CacheStore {
+    /// Stores an item with a new unique value for `cas`.
+    pub fn insert(&mut self, key: &str, data: Vec<u8>, flags: u32) {
+        self.last_cas += 1;
+        let item = CacheItem {
+            flags,
+            data,
+            cas: self.last_cas,
+        };
+        self.items.insert(key.to_string(), item);
+    }
+
+    /// Replies to a command of `get`, `gets`, `set`, `add`, `replace`, `append`, `prepend`,
+    /// `cas`, `delete`, `incr`, `decr` or `version`, other commands are answered by `ERROR`.
+    /// `None` if the command is `noreply`.
+    pub fn handle(&mut self, cmd: &Value) -> Option<Value> {
+        let (line, data) = match cmd.as_array().map(Vec::as_slice) {
+            Some([entry]) => match entry.get("line") {
+                Some(Value::String(line)) => (line, entry.get("data")),
+                _ => return Some(self::line("ERROR")),
+            },
+            _ => return Some(self::line("ERROR")),
+        };
+
+        let mut words = line.split_whitespace().collect::<Vec<_>>();
+        let noreply = words.len() > 1
+            && words.last() == Some(&"noreply")
+            && !matches!(words[0], "get" | "gets" | "version");
+        if noreply {
+            words.pop();
+        }
+        let reply = self.reply(&words, data);
+        if noreply {
+            None
+        } else {
+            Some(reply)
+        }
+    }
+
+    fn reply(&mut self, words: &[&str], data: Option<&Value>) -> Value {
+        let bad_format = || line("CLIENT_ERROR bad command line format");
+        match words {
+            [cmd @ ("get" | "gets"), keys @ ..] if !keys.is_empty() => {
+                let mut entries = keys
+                    .iter()
+                    .filter_map(|key| {
+                        let item = self.items.get(*key)?;
+                        let mut line = format!("VALUE {} {} {}", key, item.flags, item.data.len());
+                        if *cmd == "gets" {
+                            line.push_str(&format!(" {}", item.cas));
+                        }
+                        Some(json!({ "line": line, "data": body_value(&item.data) }))
+                    })
+                    .collect::<Vec<_>>();
+                entries.push(json!({ "line": "END" }));
+                Value::Array(entries)
+            }
+            [cmd, key, flags, exptime, _, rest @ ..] if is_storage(cmd) => {
+                let cas = match (*cmd, rest) {
+                    ("cas", [cas]) => match cas.parse::<u64>() {
+                        Ok(cas) => Some(cas),
+                        Err(_) => return bad_format(),
+                    },
+                    (_, []) if *cmd != "cas" => None,
+                    _ => return bad_format(),
+                };
+                let (flags, data) = match (
+                    flags.parse::<u32>(),
+                    exptime.parse::<i64>(),
+                    data.and_then(|data| body_bytes("memcached", data).ok()),
+                ) {
+                    (Ok(flags), Ok(_), Some(data)) => (flags, data),
+                    _ => return bad_format(),
+                };
+
+                let (flags, data) = match (*cmd, self.items.get(*key)) {
+                    ("add", Some(_)) | ("replace" | "append" | "prepend", None) => {
+                        return line("NOT_STORED")
+                    }
+                    ("cas", None) => return line("NOT_FOUND"),
+                    ("cas", Some(item)) if Some(item.cas) != cas => return line("EXISTS"),
+                    ("append", Some(item)) => (item.flags, [&item.data[..], &data].concat()),
+                    ("prepend", Some(item)) => (item.flags, [&data[..], &item.data].concat()),
+                    _ => (flags, data),
+                };
+                self.insert(key, data, flags);
+                line("STORED")
+            }
+            ["delete", key] => match self.items.remove(*key) {
+                Some(_) => line("DELETED"),
+                None => line("NOT_FOUND"),
+            },
+            [cmd @ ("incr" | "decr"), key, delta] => {
+                let delta = match delta.parse::<u64>() {
+                    Ok(delta) => delta,
+                    Err(_) => return line("CLIENT_ERROR invalid numeric delta argument"),
+                };
+                let item = match self.items.get(*key) {
+                    Some(item) => item,
+                    None => return line("NOT_FOUND"),
+                };
+                let value = match std::str::from_utf8(&item.data)
+                    .ok()
+                    .and_then(|s| s.trim_end().parse::<u64>().ok())
+                {
+                    Some(value) if *cmd == "incr" => value.wrapping_add(delta),
+                    Some(value) => value.saturating_sub(delta),
+                    None => {
+                        return line("CLIENT_ERROR cannot increment or decrement non-numeric value")
+                    }
+                };
+                let flags = item.flags;
+                self.insert(key, value.to_string().into_bytes(), flags);
+                line(&value.to_string())
+            }
+            ["version"] => line(&format!("VERSION {}", env!("CARGO_PKG_VERSION"))),
+            [cmd, ..] if is_storage(cmd) => bad_format(),
+            ["get" | "gets" | "delete" | "incr" | "decr" | "version", ..] => bad_format(),
+            _ => line("ERROR"),
+        }
+    }
+}
+
+impl From<BTreeMap<String, String>> for CacheStore {
+    fn from(items: BTreeMap<String, String>) -> Self {
+        let mut store = Self::default();
+        for (key, data) in items {
+            store.insert(&key, data.into_bytes(), 0);
+        }
+        store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::msg::ItemValue;
+
+    use super::{command, CacheStore, Reply};
+
+    #[test]
+    fn command_ok() {
+        let msg = command(r#"set key 1 0 "a value" noreply"#).unwrap();
+        assert_eq!(
+            msg.values()[0],
+            ItemValue::Json(json!([{ "line": "set key 1 0 7 noreply", "data": "a value" }]))
+        );
+        assert_eq!(
+            command("get a  b").unwrap().values()[0],
+            ItemValue::Json(json!([{ "line": "get a b" }]))
+        );
+        assert!(command(" ").is_err());
+        assert!(command("set key 0 0").is_err());
+        assert!(command(r#"get "a b""#).is_err());
+        assert!(command(r#"get "key"#).is_err());
+    }
+
+    #[test]
+    fn cache_store_ok() {
+        let mut store = CacheStore::default();
+        let mut exchange = |line: &str| {
+            let cmd = match command(line).unwrap().values()[0].clone() {
+                ItemValue::Json(cmd) => cmd,
+                _ => unreachable!(),
+            };
+            store.handle(&cmd).map(|reply| Reply(&reply).to_string())
+        };
+
+        assert_eq!(exchange("get k").unwrap(), "END");
+        assert_eq!(exchange("set k 5 0 1").unwrap(), "STORED");
+        assert_eq!(exchange("add k 0 0 x").unwrap(), "NOT_STORED");
+        assert_eq!(exchange("append k 0 0 0").unwrap(), "STORED");
+        assert_eq!(exchange("incr k 9").unwrap(), "19");
+        assert_eq!(exchange("decr k 20").unwrap(), "0");
+        assert_eq!(exchange("gets k x").unwrap(), "VALUE k 5 1 4\n\"0\"\nEND");
+        assert_eq!(exchange("cas k 0 0 a 3").unwrap(), "EXISTS");
+        assert_eq!(exchange("cas k 0 0 a 4").unwrap(), "STORED");
+        assert_eq!(exchange("prepend k 0 0 \"b \" noreply"), None);
+        assert_eq!(exchange("get k").unwrap(), "VALUE k 0 3\n\"b a\"\nEND");
+        assert_eq!(
+            exchange("incr k 1").unwrap(),
+            "CLIENT_ERROR cannot increment or decrement non-numeric value"
+        );
+        assert_eq!(exchange("delete k").unwrap(), "DELETED");
+        assert_eq!(exchange("delete k").unwrap(), "NOT_FOUND");
+        assert_eq!(
+            exchange("delete").unwrap(),
+            "CLIENT_ERROR bad command line format"
+        );
+        assert_eq!(exchange("flush_all").unwrap(), "ERROR");
+
+        assert_eq!(
+            Reply(
+                &json!([{ "line": "VALUE k 0 1", "data": { "$bytes": "ff" } }, { "line": "END" }])
+            )
+            .to_string(),
+            "VALUE k 0 1\n{\"$bytes\":\"ff\"}\nEND"
+        );
+    }
+}
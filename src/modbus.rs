@@ -0,0 +1,669 @@
+//! Modbus/TCP requests and responses, with a simulated register table for servers.
+
+use std::fmt;
+
+use crate::{
+    error::{Error, Result},
+    msg::{ItemValue, Message, MessageFormat},
+    preset,
+};
+
+pub const READ_COILS: u8 = 0x01;
+pub const READ_HOLDING_REGISTERS: u8 = 0x03;
+pub const WRITE_SINGLE_COIL: u8 = 0x05;
+pub const WRITE_SINGLE_REGISTER: u8 = 0x06;
+pub const WRITE_MULTIPLE_COILS: u8 = 0x0f;
+pub const WRITE_MULTIPLE_REGISTERS: u8 = 0x10;
+
+pub const ILLEGAL_FUNCTION: u8 = 0x01;
+pub const ILLEGAL_DATA_ADDRESS: u8 = 0x02;
+pub const ILLEGAL_DATA_VALUE: u8 = 0x03;
+
+fn function_name(function: u8) -> &'static str {
+    match function {
+        READ_COILS => "read coils",
+        READ_HOLDING_REGISTERS => "read holding registers",
+        WRITE_SINGLE_COIL => "write single coil",
+        WRITE_SINGLE_REGISTER => "write single register",
+        WRITE_MULTIPLE_COILS => "write multiple coils",
+        WRITE_MULTIPLE_REGISTERS => "write multiple registers",
+        _ => "unknown function",
+    }
+}
+
+/// Name of an exception code.
+pub fn exception_name(code: u8) -> &'static str {
+    match code {
+        ILLEGAL_FUNCTION => "illegal function",
+        ILLEGAL_DATA_ADDRESS => "illegal data address",
+        ILLEGAL_DATA_VALUE => "illegal data value",
+        0x04 => "server device failure",
+        0x06 => "server device busy",
+        _ => "unknown exception",
+    }
+}
+
+fn exception(function: u8, code: u8) -> Error {
+    Error::ModbusException { function, code }
+}
+
+fn invalid(details: impl Into<String>) -> Error {
+    Error::InvalidModbus {
+        details: details.into(),
+    }
+}
+
+/// The format of Modbus/TCP ADUs, whose last item is the unit id followed by the PDU.
+pub fn format() -> MessageFormat {
+    preset::preset("Modbus/TCP").unwrap().format()
+}
+
+/// Builds an ADU of the Modbus/TCP format.
+pub fn adu(tid: u16, unit: u8, pdu: &[u8]) -> Message {
+    let mut unit_pdu = vec![unit];
+    unit_pdu.extend_from_slice(pdu);
+    Message::new(vec![
+        ItemValue::Uint(u64::from(tid)),
+        ItemValue::Uint(0),
+        ItemValue::Len(unit_pdu.len() as u64),
+        ItemValue::Bytes(unit_pdu),
+    ])
+}
+
+/// Splits an ADU of the Modbus/TCP format to the transaction id, the unit id and the PDU.
+pub fn split_adu(msg: &Message) -> Result<(u16, u8, &[u8])> {
+    match msg.values().as_slice() {
+        [ItemValue::Uint(tid), ItemValue::Uint(0), _, ItemValue::Bytes(unit_pdu)]
+            if !unit_pdu.is_empty() =>
+        {
+            Ok((*tid as u16, unit_pdu[0], &unit_pdu[1..]))
+        }
+        [_, ItemValue::Uint(pid), ..] if *pid != 0 => {
+            Err(invalid(format!("the protocol id `{}` is not 0", pid)))
+        }
+        _ => Err(invalid("the message is not a Modbus/TCP ADU")),
+    }
+}
+
+fn get_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+// Coils are packed from the lowest bit.
+fn pack_coils(coils: &[bool]) -> Vec<u8> {
+    coils
+        .chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |b, (idx, coil)| b | (u8::from(*coil) << idx))
+        })
+        .collect()
+}
+
+fn unpack_coils(bytes: &[u8], count: usize) -> Vec<bool> {
+    (0..count)
+        .map(|idx| bytes[idx / 8] & (1 << (idx % 8)) != 0)
+        .collect()
+}
+
+/// Request of a Modbus function, `addr` is the address of the first coil or register.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    ReadCoils { addr: u16, count: u16 },
+    ReadHoldingRegisters { addr: u16, count: u16 },
+    WriteSingleCoil { addr: u16, value: bool },
+    WriteSingleRegister { addr: u16, value: u16 },
+    WriteMultipleCoils { addr: u16, values: Vec<bool> },
+    WriteMultipleRegisters { addr: u16, values: Vec<u16> },
+}
+
+impl Request {
+    /// Function code of the request.
+    pub fn function(&self) -> u8 {
+        match self {
+            Request::ReadCoils { .. } => READ_COILS,
+            Request::ReadHoldingRegisters { .. } => READ_HOLDING_REGISTERS,
+            Request::WriteSingleCoil { .. } => WRITE_SINGLE_COIL,
+            Request::WriteSingleRegister { .. } => WRITE_SINGLE_REGISTER,
+            Request::WriteMultipleCoils { .. } => WRITE_MULTIPLE_COILS,
+            Request::WriteMultipleRegisters { .. } => WRITE_MULTIPLE_REGISTERS,
+        }
+    }
+
+    /// Encodes to a PDU.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut pdu = vec![self.function()];
+        match self {
+            Request::ReadCoils { addr, count } | Request::ReadHoldingRegisters { addr, count } => {
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(&count.to_be_bytes());
+            }
+            Request::WriteSingleCoil { addr, value } => {
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(if *value { &[0xff, 0x00] } else { &[0x00, 0x00] });
+            }
+            Request::WriteSingleRegister { addr, value } => {
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(&value.to_be_bytes());
+            }
+            Request::WriteMultipleCoils { addr, values } => {
+                let coils = pack_coils(values);
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(&(values.len() as u16).to_be_bytes());
+                pdu.push(coils.len() as u8);
+                pdu.extend_from_slice(&coils);
+            }
+            Request::WriteMultipleRegisters { addr, values } => {
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(&(values.len() as u16).to_be_bytes());
+                pdu.push((values.len() * 2) as u8);
+                values
+                    .iter()
+                    .for_each(|v| pdu.extend_from_slice(&v.to_be_bytes()));
+            }
+        }
+        pdu
+    }
+
+    /// Decodes a request PDU, errors are the exceptions to answer.
+    pub fn decode(pdu: &[u8]) -> Result<Self> {
+        let function = *pdu.first().ok_or_else(|| exception(0, ILLEGAL_FUNCTION))?;
+        if !matches!(
+            function,
+            READ_COILS
+                | READ_HOLDING_REGISTERS
+                | WRITE_SINGLE_COIL
+                | WRITE_SINGLE_REGISTER
+                | WRITE_MULTIPLE_COILS
+                | WRITE_MULTIPLE_REGISTERS
+        ) {
+            return Err(exception(function, ILLEGAL_FUNCTION));
+        }
+        let bad_value = || exception(function, ILLEGAL_DATA_VALUE);
+        let addr = get_u16(pdu, 1).ok_or_else(bad_value)?;
+        let arg = get_u16(pdu, 3).ok_or_else(bad_value)?;
+
+        // Bytes following the count of multiple writes.
+        let data = |len: usize| match pdu.get(5) {
+            Some(n) if *n as usize == len && pdu.len() == 6 + len => Ok(&pdu[6..]),
+            _ => Err(bad_value()),
+        };
+        let check_count = |max: u16| {
+            if (1..=max).contains(&arg) {
+                Ok(arg)
+            } else {
+                Err(bad_value())
+            }
+        };
+
+        let req = match function {
+            READ_COILS => Request::ReadCoils {
+                addr,
+                count: check_count(2000)?,
+            },
+            READ_HOLDING_REGISTERS => Request::ReadHoldingRegisters {
+                addr,
+                count: check_count(125)?,
+            },
+            WRITE_SINGLE_COIL => Request::WriteSingleCoil {
+                addr,
+                value: match arg {
+                    0xff00 => true,
+                    0x0000 => false,
+                    _ => return Err(bad_value()),
+                },
+            },
+            WRITE_SINGLE_REGISTER => Request::WriteSingleRegister { addr, value: arg },
+            WRITE_MULTIPLE_COILS => {
+                let count = check_count(1968)? as usize;
+                let coils = data(count.div_ceil(8))?;
+                Request::WriteMultipleCoils {
+                    addr,
+                    values: unpack_coils(coils, count),
+                }
+            }
+            WRITE_MULTIPLE_REGISTERS => {
+                let count = check_count(123)? as usize;
+                let registers = data(count * 2)?;
+                Request::WriteMultipleRegisters {
+                    addr,
+                    values: (0..count)
+                        .map(|idx| get_u16(registers, idx * 2).unwrap())
+                        .collect(),
+                }
+            }
+            _ => unreachable!(),
+        };
+        match req {
+            Request::ReadCoils { .. }
+            | Request::ReadHoldingRegisters { .. }
+            | Request::WriteSingleCoil { .. }
+            | Request::WriteSingleRegister { .. }
+                if pdu.len() != 5 =>
+            {
+                Err(bad_value())
+            }
+            req => Ok(req),
+        }
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", function_name(self.function()))?;
+        match self {
+            Request::ReadCoils { addr, count } | Request::ReadHoldingRegisters { addr, count } => {
+                write!(f, ", addr: `{}`, count: `{}`", addr, count)
+            }
+            Request::WriteSingleCoil { addr, value } => {
+                write!(f, ", addr: `{}`, value: `{}`", addr, value)
+            }
+            Request::WriteSingleRegister { addr, value } => {
+                write!(f, ", addr: `{}`, value: `{}`", addr, value)
+            }
+            Request::WriteMultipleCoils { addr, values } => {
+                write!(f, ", addr: `{}`, values: `{:?}`", addr, values)
+            }
+            Request::WriteMultipleRegisters { addr, values } => {
+                write!(f, ", addr: `{}`, values: `{:?}`", addr, values)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    /// Coils are padded with `false` to whole bytes, as the count is only in the request.
+    ReadCoils {
+        values: Vec<bool>,
+    },
+    ReadHoldingRegisters {
+        values: Vec<u16>,
+    },
+    WriteSingleCoil {
+        addr: u16,
+        value: bool,
+    },
+    WriteSingleRegister {
+        addr: u16,
+        value: u16,
+    },
+    WriteMultipleCoils {
+        addr: u16,
+        count: u16,
+    },
+    WriteMultipleRegisters {
+        addr: u16,
+        count: u16,
+    },
+    Exception {
+        function: u8,
+        code: u8,
+    },
+}
+
+impl Response {
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Response::ReadCoils { values } => {
+                let coils = pack_coils(values);
+                let mut pdu = vec![READ_COILS, coils.len() as u8];
+                pdu.extend_from_slice(&coils);
+                pdu
+            }
+            Response::ReadHoldingRegisters { values } => {
+                let mut pdu = vec![READ_HOLDING_REGISTERS, (values.len() * 2) as u8];
+                values
+                    .iter()
+                    .for_each(|v| pdu.extend_from_slice(&v.to_be_bytes()));
+                pdu
+            }
+            // Echoes of the requests.
+            Response::WriteSingleCoil { addr, value } => Request::WriteSingleCoil {
+                addr: *addr,
+                value: *value,
+            }
+            .encode(),
+            Response::WriteSingleRegister { addr, value } => Request::WriteSingleRegister {
+                addr: *addr,
+                value: *value,
+            }
+            .encode(),
+            Response::WriteMultipleCoils { addr, count } => {
+                let mut pdu = vec![WRITE_MULTIPLE_COILS];
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(&count.to_be_bytes());
+                pdu
+            }
+            Response::WriteMultipleRegisters { addr, count } => {
+                let mut pdu = vec![WRITE_MULTIPLE_REGISTERS];
+                pdu.extend_from_slice(&addr.to_be_bytes());
+                pdu.extend_from_slice(&count.to_be_bytes());
+                pdu
+            }
+            Response::Exception { function, code } => vec![function | 0x80, *code],
+        }
+    }
+
+    pub fn decode(pdu: &[u8]) -> Result<Self> {
+        let function = *pdu
+            .first()
+            .ok_or_else(|| invalid("the response is empty"))?;
+        let bad_len = || invalid(format!("invalid length `{}` of the response", pdu.len()));
+
+        // Bytes following the byte count of reads.
+        let data = || match pdu.get(1) {
+            Some(n) if pdu.len() == 2 + *n as usize => Ok(&pdu[2..]),
+            _ => Err(bad_len()),
+        };
+        let addr_arg = || match (get_u16(pdu, 1), get_u16(pdu, 3)) {
+            (Some(addr), Some(arg)) if pdu.len() == 5 => Ok((addr, arg)),
+            _ => Err(bad_len()),
+        };
+
+        Ok(match function {
+            _ if function & 0x80 != 0 => match pdu {
+                [_, code] => Response::Exception {
+                    function: function & 0x7f,
+                    code: *code,
+                },
+                _ => return Err(bad_len()),
+            },
+            READ_COILS => {
+                let coils = data()?;
+                Response::ReadCoils {
+                    values: unpack_coils(coils, coils.len() * 8),
+                }
+            }
+            READ_HOLDING_REGISTERS => {
+                let registers = data()?;
+                if registers.len() % 2 != 0 {
+                    return Err(bad_len());
+                }
+                Response::ReadHoldingRegisters {
+                    values: registers
+                        .chunks(2)
+                        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                        .collect(),
+                }
+            }
+            WRITE_SINGLE_COIL => {
+                let (addr, value) = addr_arg()?;
+                Response::WriteSingleCoil {
+                    addr,
+                    value: value == 0xff00,
+                }
+            }
+            WRITE_SINGLE_REGISTER => {
+                let (addr, value) = addr_arg()?;
+                Response::WriteSingleRegister { addr, value }
+            }
+            WRITE_MULTIPLE_COILS => {
+                let (addr, count) = addr_arg()?;
+                Response::WriteMultipleCoils { addr, count }
+            }
+            WRITE_MULTIPLE_REGISTERS => {
+                let (addr, count) = addr_arg()?;
+                Response::WriteMultipleRegisters { addr, count }
+            }
+            _ => return Err(invalid(format!("unknown function `{:#04x}`", function))),
+        })
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Response::ReadCoils { values } => {
+                write!(f, "{}, values: `{:?}`", function_name(READ_COILS), values)
+            }
+            Response::ReadHoldingRegisters { values } => write!(
+                f,
+                "{}, values: `{:?}`",
+                function_name(READ_HOLDING_REGISTERS),
+                values
+            ),
+            Response::WriteSingleCoil { addr, value } => write!(
+                f,
+                "{}, addr: `{}`, value: `{}`",
+                function_name(WRITE_SINGLE_COIL),
+                addr,
+                value
+            ),
+            Response::WriteSingleRegister { addr, value } => write!(
+                f,
+                "{}, addr: `{}`, value: `{}`",
+                function_name(WRITE_SINGLE_REGISTER),
+                addr,
+                value
+            ),
+            Response::WriteMultipleCoils { addr, count } => write!(
+                f,
+                "{}, addr: `{}`, count: `{}`",
+                function_name(WRITE_MULTIPLE_COILS),
+                addr,
+                count
+            ),
+            Response::WriteMultipleRegisters { addr, count } => write!(
+                f,
+                "{}, addr: `{}`, count: `{}`",
+                function_name(WRITE_MULTIPLE_REGISTERS),
+                addr,
+                count
+            ),
+            Response::Exception { function, code } => write!(
+                f,
+                "exception of {}, code: `{}` ({})",
+                function_name(*function),
+                code,
+                exception_name(*code)
+            ),
+        }
+    }
+}
+
+/// Simulated coils and holding registers, from address 0.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegisterMap {
+    /// Coils by addresses.
+    pub coils: Vec<bool>,
+    /// Holding registers by addresses.
+    pub holding_registers: Vec<u16>,
+}
+
+impl RegisterMap {
+    /// Creates a table of `len` coils and `len` holding registers, all zeros.
+    pub fn new(len: usize) -> Self {
+        Self {
+            coils: vec![false; len],
+            holding_registers: vec![0; len],
+        }
+    }
+
+    /// Handles a request, errors are the exceptions to answer.
+    pub fn handle(&mut self, req: &Request) -> Result<Response> {
+        fn range<T>(table: &mut [T], function: u8, addr: u16, count: usize) -> Result<&mut [T]> {
+            let start = addr as usize;
+            table
+                .get_mut(start..start + count)
+                .ok_or_else(|| exception(function, ILLEGAL_DATA_ADDRESS))
+        }
+        let function = req.function();
+
+        Ok(match req {
+            Request::ReadCoils { addr, count } => Response::ReadCoils {
+                values: range(&mut self.coils, function, *addr, *count as usize)?.to_vec(),
+            },
+            Request::ReadHoldingRegisters { addr, count } => Response::ReadHoldingRegisters {
+                values: range(
+                    &mut self.holding_registers,
+                    function,
+                    *addr,
+                    *count as usize,
+                )?
+                .to_vec(),
+            },
+            Request::WriteSingleCoil { addr, value } => {
+                range(&mut self.coils, function, *addr, 1)?[0] = *value;
+                Response::WriteSingleCoil {
+                    addr: *addr,
+                    value: *value,
+                }
+            }
+            Request::WriteSingleRegister { addr, value } => {
+                range(&mut self.holding_registers, function, *addr, 1)?[0] = *value;
+                Response::WriteSingleRegister {
+                    addr: *addr,
+                    value: *value,
+                }
+            }
+            Request::WriteMultipleCoils { addr, values } => {
+                range(&mut self.coils, function, *addr, values.len())?.copy_from_slice(values);
+                Response::WriteMultipleCoils {
+                    addr: *addr,
+                    count: values.len() as u16,
+                }
+            }
+            Request::WriteMultipleRegisters { addr, values } => {
+                range(&mut self.holding_registers, function, *addr, values.len())?
+                    .copy_from_slice(values);
+                Response::WriteMultipleRegisters {
+                    addr: *addr,
+                    count: values.len() as u16,
+                }
+            }
+        })
+    }
+
+    /// Answers a request PDU, invalid requests are answered by exceptions.
+    pub fn serve(&mut self, pdu: &[u8]) -> Response {
+        match Request::decode(pdu).and_then(|req| self.handle(&req)) {
+            Ok(resp) => resp,
+            Err(Error::ModbusException { function, code }) => {
+                Response::Exception { function, code }
+            }
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        adu, split_adu, RegisterMap, Request, Response, ILLEGAL_DATA_ADDRESS, ILLEGAL_DATA_VALUE,
+        ILLEGAL_FUNCTION, READ_HOLDING_REGISTERS,
+    };
+
+    #[test]
+    fn request_ok() {
+        let reqs = [
+            Request::ReadCoils { addr: 1, count: 10 },
+            Request::ReadHoldingRegisters { addr: 2, count: 3 },
+            Request::WriteSingleCoil {
+                addr: 3,
+                value: true,
+            },
+            Request::WriteSingleRegister { addr: 4, value: 5 },
+            Request::WriteMultipleCoils {
+                addr: 5,
+                values: vec![true, false, true, true, false, false, false, false, true],
+            },
+            Request::WriteMultipleRegisters {
+                addr: 6,
+                values: vec![0x1234, 0xabcd],
+            },
+        ];
+        for req in reqs {
+            assert_eq!(Request::decode(&req.encode()).unwrap(), req);
+        }
+
+        let pdu = Request::WriteMultipleCoils {
+            addr: 0x13,
+            values: vec![
+                true, false, true, true, false, false, true, true, true, false,
+            ],
+        }
+        .encode();
+        assert_eq!(hex::encode(pdu), "0f0013000a02cd01");
+        assert!(Request::decode(&hex::decode("0300000000").unwrap()).is_err());
+        assert!(Request::decode(&hex::decode("03000000").unwrap()).is_err());
+    }
+
+    #[test]
+    fn register_map_ok() {
+        let mut map = RegisterMap::new(16);
+        let serve = |map: &mut RegisterMap, req: Request| {
+            let resp = map.serve(&req.encode());
+            let bytes = resp.encode();
+            assert_eq!(Response::decode(&bytes).unwrap().encode(), bytes);
+            resp
+        };
+
+        serve(
+            &mut map,
+            Request::WriteMultipleRegisters {
+                addr: 14,
+                values: vec![1, 2],
+            },
+        );
+        assert_eq!(
+            serve(
+                &mut map,
+                Request::ReadHoldingRegisters { addr: 13, count: 3 }
+            ),
+            Response::ReadHoldingRegisters {
+                values: vec![0, 1, 2]
+            }
+        );
+        serve(
+            &mut map,
+            Request::WriteSingleCoil {
+                addr: 1,
+                value: true,
+            },
+        );
+        assert_eq!(
+            serve(&mut map, Request::ReadCoils { addr: 0, count: 2 }),
+            Response::ReadCoils {
+                values: vec![false, true]
+            }
+        );
+
+        // Exceptions.
+        assert_eq!(
+            serve(
+                &mut map,
+                Request::ReadHoldingRegisters { addr: 15, count: 2 }
+            ),
+            Response::Exception {
+                function: READ_HOLDING_REGISTERS,
+                code: ILLEGAL_DATA_ADDRESS
+            }
+        );
+        assert_eq!(
+            map.serve(&[0x2b, 0x0e, 0x01, 0x00]),
+            Response::Exception {
+                function: 0x2b,
+                code: ILLEGAL_FUNCTION
+            }
+        );
+        assert_eq!(
+            map.serve(&hex::decode("050000ffff").unwrap()).encode(),
+            vec![0x85, ILLEGAL_DATA_VALUE]
+        );
+    }
+
+    #[test]
+    fn adu_ok() {
+        let msg = adu(7, 1, &[0x03, 0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(
+            split_adu(&msg).unwrap(),
+            (7, 1, &[0x03, 0x00, 0x00, 0x00, 0x02][..])
+        );
+    }
+}
@@ -0,0 +1,573 @@
+//! MQTT 3.1.1 and 5 packets of clients, payloads of publishes are messages of the format.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+use crate::error::{Error, Result};
+
+const CONNECT: u8 = 1;
+const CONNACK: u8 = 2;
+const PUBLISH: u8 = 3;
+const PUBACK: u8 = 4;
+const PUBREC: u8 = 5;
+const PUBREL: u8 = 6;
+const PUBCOMP: u8 = 7;
+const SUBSCRIBE: u8 = 8;
+const SUBACK: u8 = 9;
+const PINGREQ: u8 = 12;
+const PINGRESP: u8 = 13;
+const DISCONNECT: u8 = 14;
+
+/// Version of the MQTT protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    /// MQTT 3.1.1.
+    V311,
+    /// MQTT 5.0.
+    V5,
+}
+
+impl Version {
+    fn level(self) -> u8 {
+        match self {
+            Version::V311 => 4,
+            Version::V5 => 5,
+        }
+    }
+}
+
+/// Options of the MQTT mode of clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mqtt {
+    /// Version of the protocol.
+    pub version: Version,
+    /// Client identifier of the `CONNECT` packet.
+    pub client_id: String,
+    /// Seconds between pings, no pings if `0`.
+    pub keep_alive: u16,
+    /// The topic which messages are published to.
+    pub topic: String,
+    /// QoS of publishes, 0, 1 or 2.
+    pub qos: u8,
+    /// Whether publishes are retained by the broker.
+    pub retain: bool,
+}
+
+impl Default for Mqtt {
+    fn default() -> Self {
+        Self {
+            version: Version::V311,
+            client_id: "socket_toolbox".to_string(),
+            keep_alive: 60,
+            topic: "test".to_string(),
+            qos: 0,
+            retain: false,
+        }
+    }
+}
+
+fn invalid(details: impl Into<String>) -> Error {
+    Error::InvalidMqtt {
+        details: details.into(),
+    }
+}
+
+/// The remaining length of a packet and the count of its bytes, `None` if more bytes are
+/// needed.
+pub fn remaining_len(bytes: &[u8]) -> Result<Option<(usize, usize)>> {
+    let mut len = 0;
+    for (idx, b) in bytes.iter().take(4).enumerate() {
+        len |= usize::from(b & 0x7f) << (7 * idx);
+        if b & 0x80 == 0 {
+            return Ok(Some((len, idx + 1)));
+        }
+    }
+    if bytes.len() >= 4 {
+        Err(invalid("the remaining length is longer than 4 bytes"))
+    } else {
+        Ok(None)
+    }
+}
+
+fn put_remaining_len(buf: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let b = (len & 0x7f) as u8;
+        len >>= 7;
+        if len == 0 {
+            buf.push(b);
+            return;
+        }
+        buf.push(b | 0x80);
+    }
+}
+
+/// Reads a packet from a stream, no byte after the packet is consumed.
+pub fn read_packet(r: &mut impl Read) -> Result<Vec<u8>> {
+    let read_exact = |r: &mut dyn Read, buf: &mut [u8]| {
+        r.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::EndOfStream,
+            _ => Error::Io(e),
+        })
+    };
+
+    let mut packet = vec![0];
+    read_exact(r, &mut packet)?;
+    let len = loop {
+        if let Some((len, _)) = remaining_len(&packet[1..])? {
+            break len;
+        }
+        let mut b = [0];
+        read_exact(r, &mut b)?;
+        packet.push(b[0]);
+    };
+    let header_len = packet.len();
+    packet.resize(header_len + len, 0);
+    read_exact(r, &mut packet[header_len..])?;
+    Ok(packet)
+}
+
+/// Sends a CONNECT with a clean start and waits for the CONNACK, returns whether a session
+/// is present.
+pub fn connect(stream: &mut (impl Read + Write), mqtt: &Mqtt) -> Result<bool> {
+    let connect = Packet::Connect {
+        client_id: mqtt.client_id.clone(),
+        keep_alive: mqtt.keep_alive,
+        clean_start: true,
+    };
+    stream
+        .write_all(&connect.encode(mqtt.version))
+        .map_err(Error::Io)?;
+    match Packet::decode(&read_packet(stream)?, mqtt.version)? {
+        Packet::ConnAck {
+            session_present,
+            code: 0,
+        } => Ok(session_present),
+        Packet::ConnAck { code, .. } => Err(invalid(format!(
+            "the connection is refused, code: `{:#04x}`",
+            code
+        ))),
+        packet => Err(invalid(format!("expected CONNACK, got {}", packet))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Packet {
+    Connect {
+        client_id: String,
+        keep_alive: u16,
+        clean_start: bool,
+    },
+    ConnAck {
+        session_present: bool,
+        code: u8,
+    },
+    /// `packet_id` is `0` for QoS 0.
+    Publish {
+        dup: bool,
+        qos: u8,
+        retain: bool,
+        topic: String,
+        packet_id: u16,
+        payload: Vec<u8>,
+    },
+    PubAck {
+        packet_id: u16,
+    },
+    PubRec {
+        packet_id: u16,
+    },
+    PubRel {
+        packet_id: u16,
+    },
+    PubComp {
+        packet_id: u16,
+    },
+    /// Topic filters with their maximum QoS.
+    Subscribe {
+        packet_id: u16,
+        filters: Vec<(String, u8)>,
+    },
+    /// Granted QoS of each filter, or a failure code of at least `0x80`.
+    SubAck {
+        packet_id: u16,
+        codes: Vec<u8>,
+    },
+    PingReq,
+    PingResp,
+    Disconnect,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("unexpected end of the packet"));
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u16()?;
+        String::from_utf8(self.take(len.into())?.to_vec())
+            .map_err(|_| invalid("the string is not UTF-8"))
+    }
+
+    // Properties of MQTT 5 are skipped.
+    fn properties(&mut self, version: Version) -> Result<()> {
+        if version == Version::V5 {
+            let (len, used) = remaining_len(self.bytes)?
+                .ok_or_else(|| invalid("unexpected end of the packet"))?;
+            self.take(used + len)?;
+        }
+        Ok(())
+    }
+}
+
+fn put_u16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn put_string(buf: &mut Vec<u8>, s: &str) {
+    put_u16(buf, s.len() as u16);
+    buf.extend_from_slice(s.as_bytes());
+}
+
+// Properties of MQTT 5 are empty.
+fn put_properties(buf: &mut Vec<u8>, version: Version) {
+    if version == Version::V5 {
+        buf.push(0);
+    }
+}
+
+impl Packet {
+    /// Encodes the packet, strings are expected to be shorter than 64 KiB.
+    pub fn encode(&self, version: Version) -> Vec<u8> {
+        let mut body = Vec::new();
+        let header = match self {
+            Packet::Connect {
+                client_id,
+                keep_alive,
+                clean_start,
+            } => {
+                put_string(&mut body, "MQTT");
+                body.push(version.level());
+                body.push(if *clean_start { 0x02 } else { 0 });
+                put_u16(&mut body, *keep_alive);
+                put_properties(&mut body, version);
+                put_string(&mut body, client_id);
+                CONNECT << 4
+            }
+            Packet::ConnAck {
+                session_present,
+                code,
+            } => {
+                body.push(u8::from(*session_present));
+                body.push(*code);
+                put_properties(&mut body, version);
+                CONNACK << 4
+            }
+            Packet::Publish {
+                dup,
+                qos,
+                retain,
+                topic,
+                packet_id,
+                payload,
+            } => {
+                put_string(&mut body, topic);
+                if *qos > 0 {
+                    put_u16(&mut body, *packet_id);
+                }
+                put_properties(&mut body, version);
+                body.extend_from_slice(payload);
+                PUBLISH << 4 | u8::from(*dup) << 3 | qos << 1 | u8::from(*retain)
+            }
+            Packet::PubAck { packet_id }
+            | Packet::PubRec { packet_id }
+            | Packet::PubRel { packet_id }
+            | Packet::PubComp { packet_id } => {
+                // The reason code and properties of MQTT 5 can be omitted for success.
+                put_u16(&mut body, *packet_id);
+                match self {
+                    Packet::PubAck { .. } => PUBACK << 4,
+                    Packet::PubRec { .. } => PUBREC << 4,
+                    Packet::PubRel { .. } => PUBREL << 4 | 0x02,
+                    _ => PUBCOMP << 4,
+                }
+            }
+            Packet::Subscribe { packet_id, filters } => {
+                put_u16(&mut body, *packet_id);
+                put_properties(&mut body, version);
+                for (filter, qos) in filters {
+                    put_string(&mut body, filter);
+                    body.push(*qos);
+                }
+                SUBSCRIBE << 4 | 0x02
+            }
+            Packet::SubAck { packet_id, codes } => {
+                put_u16(&mut body, *packet_id);
+                put_properties(&mut body, version);
+                body.extend_from_slice(codes);
+                SUBACK << 4
+            }
+            Packet::PingReq => PINGREQ << 4,
+            Packet::PingResp => PINGRESP << 4,
+            Packet::Disconnect => DISCONNECT << 4,
+        };
+
+        let mut packet = vec![header];
+        put_remaining_len(&mut packet, body.len());
+        packet.extend(body);
+        packet
+    }
+
+    /// Decodes a whole packet, such as a frame of `Framing::Mqtt`.
+    pub fn decode(packet: &[u8], version: Version) -> Result<Packet> {
+        let header = *packet
+            .first()
+            .ok_or_else(|| invalid("the packet is empty"))?;
+        let (len, used) =
+            remaining_len(&packet[1..])?.ok_or_else(|| invalid("unexpected end of the packet"))?;
+        if packet.len() != 1 + used + len {
+            return Err(invalid(format!(
+                "the remaining length `{}` doesn't match the packet",
+                len
+            )));
+        }
+        let mut r = Reader {
+            bytes: &packet[1 + used..],
+        };
+
+        let packet = match header >> 4 {
+            CONNECT => {
+                if r.string()? != "MQTT" {
+                    return Err(invalid("the protocol name is not `MQTT`"));
+                }
+                r.u8()?;
+                let flags = r.u8()?;
+                let keep_alive = r.u16()?;
+                r.properties(version)?;
+                let client_id = r.string()?;
+                // The will, the user name and the password are dropped.
+                r.bytes = &[];
+                Packet::Connect {
+                    client_id,
+                    keep_alive,
+                    clean_start: flags & 0x02 != 0,
+                }
+            }
+            CONNACK => {
+                let session_present = r.u8()? & 0x01 != 0;
+                let code = r.u8()?;
+                r.properties(version)?;
+                Packet::ConnAck {
+                    session_present,
+                    code,
+                }
+            }
+            PUBLISH => {
+                let qos = (header >> 1) & 0x03;
+                if qos == 3 {
+                    return Err(invalid("QoS 3 is invalid"));
+                }
+                let topic = r.string()?;
+                let packet_id = if qos > 0 { r.u16()? } else { 0 };
+                r.properties(version)?;
+                Packet::Publish {
+                    dup: header & 0x08 != 0,
+                    qos,
+                    retain: header & 0x01 != 0,
+                    topic,
+                    packet_id,
+                    payload: r.take(r.bytes.len())?.to_vec(),
+                }
+            }
+            ty @ (PUBACK | PUBREC | PUBREL | PUBCOMP) => {
+                let packet_id = r.u16()?;
+                // The reason code and properties of MQTT 5.
+                r.bytes = &[];
+                match ty {
+                    PUBACK => Packet::PubAck { packet_id },
+                    PUBREC => Packet::PubRec { packet_id },
+                    PUBREL => Packet::PubRel { packet_id },
+                    _ => Packet::PubComp { packet_id },
+                }
+            }
+            SUBSCRIBE => {
+                let packet_id = r.u16()?;
+                r.properties(version)?;
+                let mut filters = Vec::new();
+                while !r.bytes.is_empty() {
+                    filters.push((r.string()?, r.u8()? & 0x03));
+                }
+                Packet::Subscribe { packet_id, filters }
+            }
+            SUBACK => {
+                let packet_id = r.u16()?;
+                r.properties(version)?;
+                Packet::SubAck {
+                    packet_id,
+                    codes: r.take(r.bytes.len())?.to_vec(),
+                }
+            }
+            PINGREQ => Packet::PingReq,
+            PINGRESP => Packet::PingResp,
+            DISCONNECT => {
+                // The reason code and properties of MQTT 5.
+                r.bytes = &[];
+                Packet::Disconnect
+            }
+            ty => return Err(invalid(format!("unknown packet type `{}`", ty))),
+        };
+        if !r.bytes.is_empty() {
+            return Err(invalid(format!(
+                "`{}` bytes after the packet",
+                r.bytes.len()
+            )));
+        }
+        Ok(packet)
+    }
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Packet::Connect {
+                client_id,
+                keep_alive,
+                clean_start,
+            } => write!(
+                f,
+                "CONNECT, client id: `{}`, keep alive: `{}`, clean start: `{}`",
+                client_id, keep_alive, clean_start
+            ),
+            Packet::ConnAck {
+                session_present,
+                code,
+            } => write!(
+                f,
+                "CONNACK, session present: `{}`, code: `{:#04x}`",
+                session_present, code
+            ),
+            Packet::Publish {
+                qos,
+                retain,
+                topic,
+                packet_id,
+                payload,
+                ..
+            } => write!(
+                f,
+                "PUBLISH, topic: `{}`, QoS: `{}`, retain: `{}`, packet id: `{}`, payload: {}",
+                topic,
+                qos,
+                retain,
+                packet_id,
+                hex::encode(payload)
+            ),
+            Packet::PubAck { packet_id } => write!(f, "PUBACK, packet id: `{}`", packet_id),
+            Packet::PubRec { packet_id } => write!(f, "PUBREC, packet id: `{}`", packet_id),
+            Packet::PubRel { packet_id } => write!(f, "PUBREL, packet id: `{}`", packet_id),
+            Packet::PubComp { packet_id } => write!(f, "PUBCOMP, packet id: `{}`", packet_id),
+            Packet::Subscribe { packet_id, filters } => write!(
+                f,
+                "SUBSCRIBE, packet id: `{}`, filters: `{:?}`",
+                packet_id, filters
+            ),
+            Packet::SubAck { packet_id, codes } => write!(
+                f,
+                "SUBACK, packet id: `{}`, codes: `{:02x?}`",
+                packet_id, codes
+            ),
+            Packet::PingReq => write!(f, "PINGREQ"),
+            Packet::PingResp => write!(f, "PINGRESP"),
+            Packet::Disconnect => write!(f, "DISCONNECT"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_packet, remaining_len, Packet, Version};
+
+    #[test]
+    fn packet_ok() {
+        let connect = Packet::Connect {
+            client_id: "c".to_string(),
+            keep_alive: 60,
+            clean_start: true,
+        };
+        let bytes = connect.encode(Version::V311);
+        assert_eq!(hex::encode(&bytes), "100d00044d5154540402003c000163");
+        assert_eq!(Packet::decode(&bytes, Version::V311).unwrap(), connect);
+        // Empty properties of MQTT 5.
+        let bytes = connect.encode(Version::V5);
+        assert_eq!(hex::encode(&bytes), "100e00044d5154540502003c00000163");
+        assert_eq!(Packet::decode(&bytes, Version::V5).unwrap(), connect);
+
+        let publish = Packet::Publish {
+            dup: false,
+            qos: 2,
+            retain: true,
+            topic: "a/b".to_string(),
+            packet_id: 7,
+            payload: vec![0xca, 0xfe],
+        };
+        for version in [Version::V311, Version::V5] {
+            let bytes = publish.encode(version);
+            assert_eq!(Packet::decode(&bytes, version).unwrap(), publish);
+        }
+        assert_eq!(
+            hex::encode(publish.encode(Version::V311)),
+            "35090003612f620007cafe"
+        );
+
+        // PUBACK of MQTT 5 with a reason code and properties.
+        assert_eq!(
+            Packet::decode(&hex::decode("400400071000").unwrap(), Version::V5).unwrap(),
+            Packet::PubAck { packet_id: 7 }
+        );
+        assert_eq!(
+            Packet::PubRel { packet_id: 1 }.encode(Version::V5),
+            [0x62, 2, 0, 1]
+        );
+
+        // A payload of 200 bytes, whose remaining length takes 2 bytes.
+        let publish = Packet::Publish {
+            dup: false,
+            qos: 0,
+            retain: false,
+            topic: "t".to_string(),
+            packet_id: 0,
+            payload: vec![0; 200],
+        };
+        let bytes = publish.encode(Version::V311);
+        assert_eq!(remaining_len(&bytes[1..]).unwrap(), Some((203, 2)));
+        assert_eq!(remaining_len(&[0xff]).unwrap(), None);
+        assert!(remaining_len(&[0xff; 4]).is_err());
+
+        let mut stream = [bytes.as_slice(), &[0xc0, 0]].concat();
+        let mut r = stream.as_slice();
+        assert_eq!(read_packet(&mut r).unwrap(), bytes);
+        assert_eq!(r, [0xc0, 0]);
+        stream.truncate(10);
+        assert!(read_packet(&mut stream.as_slice()).is_err());
+        assert!(Packet::decode(&[0x30, 1, 0], Version::V311).is_err());
+    }
+}
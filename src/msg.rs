@@ -1,36 +1,223 @@
+//! Formats and values of messages, and encoding and decoding of them.
+
 use std::{
+    fmt,
     io::{self},
     mem::{size_of, size_of_val},
     ops::Deref,
+    result,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
-    thread::sleep,
-    time::Duration,
 };
 
 use bytes::{Buf, BufMut};
 
-use crate::error::{Error, Result};
+use crate::{
+    checksum::Checksum,
+    error::{DecodeError, Error, Result},
+    payload::{
+        ber_decode, ber_encode, cbor_decode, cbor_encode, http_decode, http_encode, json_decode,
+        json_encode, memcache_decode, memcache_encode, msgpack_decode, msgpack_encode, resp_decode,
+        resp_encode, stomp_decode, stomp_encode, tlv_decode, tlv_encode,
+    },
+    protobuf::ProtoSchema,
+};
+
+/// Length of a payload item.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayloadLen {
+    /// Padded with zeros to the length, only for JSON whose padding can be stripped.
+    Fixed {
+        /// Length in bytes.
+        len: usize,
+    },
+    /// Specified by the `Len` item at `len_idx`.
+    Var {
+        /// Index of the `Len` item.
+        len_idx: usize,
+    },
+    /// The rest of the message, the item should be the last one and streams need a framing.
+    Greedy,
+}
 
+/// Format of an item, lengths are in bytes and integers are big-endian.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ItemFormat {
-    Len { len: usize },
-    Uint { len: usize },
-    Int { len: usize },
-    FixedString { len: usize },
-    VarString { len_idx: usize },
-    FixedBytes { len: usize },
-    VarBytes { len_idx: usize },
+    /// Length of a later `VarString`, `VarBytes` or payload item, whose value is `ItemValue::Len`.
+    Len {
+        /// Width in bytes.
+        len: usize,
+    },
+    /// Unsigned integer, whose value is `ItemValue::Uint`.
+    Uint {
+        /// Width in bytes.
+        len: usize,
+    },
+    /// Signed integer in two's complement, whose value is `ItemValue::Int`.
+    Int {
+        /// Width in bytes.
+        len: usize,
+    },
+    /// UTF-8 string of a fixed length, whose value is `ItemValue::String`.
+    FixedString {
+        /// Length in bytes.
+        len: usize,
+    },
+    /// UTF-8 string whose length is the value of a `Len` item.
+    VarString {
+        /// Index of the `Len` item.
+        len_idx: usize,
+    },
+    /// Bytes of a fixed length, whose value is `ItemValue::Bytes`.
+    FixedBytes {
+        /// Length in bytes.
+        len: usize,
+    },
+    /// Bytes whose length is the value of a `Len` item.
+    VarBytes {
+        /// Index of the `Len` item.
+        len_idx: usize,
+    },
+    /// Checksum of the bytes of the items `start..end` before it, whose value is `ItemValue::Uint`.
+    Checksum {
+        /// Algorithm, which also gives the width.
+        checksum: Checksum,
+        /// Index of the first item covered.
+        start: usize,
+        /// Index after the last item covered.
+        end: usize,
+    },
+    /// Protobuf message of the schema, which is decoded to `ItemValue::Json`.
+    Protobuf {
+        /// Length of the payload.
+        len: PayloadLen,
+        /// Schema whose first message is the payload.
+        schema: Arc<ProtoSchema>,
+    },
+    /// CBOR of RFC 8949, which is decoded to `ItemValue::Json`.
+    Cbor {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// MessagePack, which is decoded to `ItemValue::Json`.
+    MessagePack {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// JSON text, which is validated on both encoding and decoding.
+    Json {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// ASN.1 elements in BER or DER, which are decoded to `ItemValue::Json`.
+    Ber {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// A value of the Redis protocol RESP2 or RESP3, which is decoded to `ItemValue::Json`.
+    Resp {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// An HTTP/1.1 request or response, which is decoded to `ItemValue::Json`.
+    Http {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// A STOMP 1.2 frame with its NUL, which is decoded to `ItemValue::Json`.
+    Stomp {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// A memcached command or reply of the text protocol, which is decoded to `ItemValue::Json`.
+    Memcache {
+        /// Length of the payload.
+        len: PayloadLen,
+    },
+    /// Flat TLVs of a proprietary protocol, tags and lengths are big-endian of the widths.
+    Tlv {
+        /// Length of the payload.
+        len: PayloadLen,
+        /// Width of tags in bytes.
+        tag_width: usize,
+        /// Width of lengths in bytes.
+        len_width: usize,
+    },
 }
 
+impl ItemFormat {
+    /// Length of a payload item, whose value is `ItemValue::Json`.
+    pub fn payload_len(&self) -> Option<&PayloadLen> {
+        match self {
+            ItemFormat::Protobuf { len, .. }
+            | ItemFormat::Cbor { len }
+            | ItemFormat::MessagePack { len }
+            | ItemFormat::Json { len }
+            | ItemFormat::Ber { len }
+            | ItemFormat::Resp { len }
+            | ItemFormat::Http { len }
+            | ItemFormat::Stomp { len }
+            | ItemFormat::Memcache { len }
+            | ItemFormat::Tlv { len, .. } => Some(len),
+            _ => None,
+        }
+    }
+
+    /// Decodes the bytes of a payload item, `Error::NotAPayload` if it's not a payload item.
+    pub fn decode_payload(&self, bytes: &[u8]) -> Result<serde_json::Value> {
+        match self {
+            ItemFormat::Protobuf { schema, .. } => schema.decode(bytes),
+            ItemFormat::Cbor { .. } => cbor_decode(bytes),
+            ItemFormat::MessagePack { .. } => msgpack_decode(bytes),
+            ItemFormat::Json { .. } => json_decode(bytes),
+            ItemFormat::Ber { .. } => ber_decode(bytes),
+            ItemFormat::Resp { .. } => resp_decode(bytes),
+            ItemFormat::Http { .. } => http_decode(bytes),
+            ItemFormat::Stomp { .. } => stomp_decode(bytes),
+            ItemFormat::Memcache { .. } => memcache_decode(bytes),
+            ItemFormat::Tlv {
+                tag_width,
+                len_width,
+                ..
+            } => tlv_decode(bytes, *tag_width, *len_width),
+            _ => Err(Error::NotAPayload),
+        }
+    }
+
+    /// Encodes the value of a payload item, `Error::NotAPayload` if it's not a payload item.
+    pub fn encode_payload(&self, value: &serde_json::Value) -> Result<Vec<u8>> {
+        match self {
+            ItemFormat::Protobuf { schema, .. } => schema.encode(value),
+            ItemFormat::Cbor { .. } => cbor_encode(value),
+            ItemFormat::MessagePack { .. } => msgpack_encode(value),
+            ItemFormat::Json { .. } => json_encode(value),
+            ItemFormat::Ber { .. } => ber_encode(value),
+            ItemFormat::Resp { .. } => resp_encode(value),
+            ItemFormat::Http { .. } => http_encode(value),
+            ItemFormat::Stomp { .. } => stomp_encode(value),
+            ItemFormat::Memcache { .. } => memcache_encode(value),
+            ItemFormat::Tlv {
+                tag_width,
+                len_width,
+                ..
+            } => tlv_encode(value, *tag_width, *len_width),
+            _ => Err(Error::NotAPayload),
+        }
+    }
+}
+
+/// Formats of the items of a message, which are validated when created.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MessageFormat {
     fmts: Vec<ItemFormat>,
+    // Optional names of items, such as `len` of `len:u16` in the DSL.
+    names: Vec<Option<String>>,
 }
 
 impl MessageFormat {
+    /// Validates the formats, such as indexes of lengths and ranges of checksums.
     pub fn new(fmts: &[ItemFormat]) -> Result<Self> {
         if fmts.is_empty() {
             return Err(Error::MessageFormatEmpty);
@@ -42,9 +229,54 @@ impl MessageFormat {
 
         Ok(Self {
             fmts: fmts.to_vec(),
+            names: vec![None; fmts.len()],
         })
     }
 
+    /// Names the items in order, names should be unique identifiers. Items after the names
+    /// keep their names.
+    pub fn with_names<I>(mut self, names: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = Option<String>>,
+    {
+        // Names are checked after all of them are replaced, so that names can be swapped.
+        let mut new_names = self.names.clone();
+        for (item_idx, name) in names.into_iter().take(self.fmts.len()).enumerate() {
+            new_names[item_idx] = name;
+        }
+        for (item_idx, name) in new_names.iter().enumerate() {
+            if let Some(name) = name {
+                let is_ident = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !is_ident
+                    || new_names[..item_idx]
+                        .iter()
+                        .any(|n| n.as_ref() == Some(name))
+                {
+                    return Err(Error::InvalidItemName {
+                        item_idx,
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+        self.names = new_names;
+        Ok(self)
+    }
+
+    /// Name of the item at `idx`, if it's named.
+    pub fn name(&self, idx: usize) -> Option<&str> {
+        self.names.get(idx).and_then(Option::as_deref)
+    }
+
+    /// Whether the last item takes the rest of the message.
+    pub fn is_greedy(&self) -> bool {
+        matches!(
+            self.fmts.last().and_then(ItemFormat::payload_len),
+            Some(PayloadLen::Greedy)
+        )
+    }
+
     fn validate_fmt(fmt: &ItemFormat, idx: usize, fmts: &[ItemFormat]) -> Result<()> {
         let min_len = 1;
         let mut max_len = usize::MAX;
@@ -54,13 +286,58 @@ impl MessageFormat {
             _ => {}
         }
 
-        match fmt {
+        if let (Some(PayloadLen::Fixed { .. }), false) =
+            (fmt.payload_len(), matches!(fmt, ItemFormat::Json { .. }))
+        {
+            return Err(Error::FixedPayload { item_idx: idx });
+        }
+
+        // Validate the widths of tags and lengths.
+        if let ItemFormat::Tlv {
+            tag_width,
+            len_width,
+            ..
+        } = fmt
+        {
+            for width in &[*tag_width, *len_width] {
+                if *width < min_len {
+                    return Err(Error::LenTooSmall {
+                        min_len,
+                        item_idx: idx,
+                        len: *width,
+                    });
+                } else if *width > size_of::<u64>() {
+                    return Err(Error::LenTooLarge {
+                        max_len: size_of::<u64>(),
+                        item_idx: idx,
+                        len: *width,
+                    });
+                }
+            }
+        }
+
+        if let ItemFormat::Checksum { start, end, .. } = fmt {
+            if start >= end || *end > idx {
+                return Err(Error::InvalidChecksumRange {
+                    item_idx: idx,
+                    start: *start,
+                    end: *end,
+                });
+            }
+            return Ok(());
+        }
+
+        match (fmt, fmt.payload_len()) {
             // Validate the length.
-            ItemFormat::Len { len }
-            | ItemFormat::Uint { len }
-            | ItemFormat::Int { len }
-            | ItemFormat::FixedString { len }
-            | ItemFormat::FixedBytes { len } => {
+            (
+                ItemFormat::Len { len }
+                | ItemFormat::Uint { len }
+                | ItemFormat::Int { len }
+                | ItemFormat::FixedString { len }
+                | ItemFormat::FixedBytes { len },
+                _,
+            )
+            | (_, Some(PayloadLen::Fixed { len })) => {
                 if *len < min_len {
                     return Err(Error::LenTooSmall {
                         min_len,
@@ -77,7 +354,8 @@ impl MessageFormat {
             }
 
             // Validate the index of length.
-            ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx } => {
+            (ItemFormat::VarString { len_idx } | ItemFormat::VarBytes { len_idx }, _)
+            | (_, Some(PayloadLen::Var { len_idx })) => {
                 if *len_idx > idx {
                     return Err(Error::LenIdxTooLarge {
                         item_idx: idx,
@@ -90,6 +368,14 @@ impl MessageFormat {
                     });
                 }
             }
+
+            (_, Some(PayloadLen::Greedy)) => {
+                if idx + 1 != fmts.len() {
+                    return Err(Error::GreedyNotLast { item_idx: idx });
+                }
+            }
+
+            _ => unreachable!(),
         }
         Ok(())
     }
@@ -103,30 +389,71 @@ impl Deref for MessageFormat {
     }
 }
 
+/// Value of an item, the variant should match the kind of the item format.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ItemValue {
+    /// Value of a `Len` item.
     Len(u64),
+    /// Value of a `Uint` or `Checksum` item.
     Uint(u64),
+    /// Value of an `Int` item.
     Int(i64),
+    /// Value of a string item.
     String(String),
+    /// Value of a bytes item.
     Bytes(Vec<u8>),
+    /// Payload decoded to JSON, such as a protobuf message.
+    Json(serde_json::Value),
 }
 
+impl fmt::Display for ItemValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemValue::Len(v) | ItemValue::Uint(v) => write!(f, "{}", v),
+            ItemValue::Int(v) => write!(f, "{}", v),
+            ItemValue::String(s) => write!(f, "{:?}", s),
+            ItemValue::Bytes(bytes) => write!(f, "{}", hex::encode(bytes)),
+            ItemValue::Json(v) if f.alternate() => write!(f, "{:#}", v),
+            ItemValue::Json(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+/// Values of the items of a message.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Message {
     values: Vec<ItemValue>,
 }
 
 impl Message {
+    /// Values in the order of items, they are validated against a format when encoded.
     pub fn new(values: Vec<ItemValue>) -> Self {
         Self { values }
     }
 
+    /// Values in the order of items.
     pub fn values(&self) -> &Vec<ItemValue> {
         &self.values
     }
 }
 
+/// Values are separated by `; `, or with `{:#}`, each value is on a line and JSON is pretty-printed.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, value) in self.values.iter().enumerate() {
+            if f.alternate() {
+                write!(f, "\n#{}: {:#}", idx, value)?;
+            } else {
+                if idx != 0 {
+                    write!(f, "; ")?;
+                }
+                write!(f, "{}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Deref for Message {
     type Target = Vec<ItemValue>;
 
@@ -135,31 +462,81 @@ impl Deref for Message {
     }
 }
 
+/// How an item is decoded, the bytes are `raw` at `offset` of the message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemTrace {
+    /// Offset of the item in the message.
+    pub offset: usize,
+    /// Length specified by the format, `raw` is shorter if there are not enough bytes.
+    /// For an item which takes the rest of the message, it's the length of `raw`.
+    pub len: usize,
+    /// Bytes read for the item.
+    pub raw: Vec<u8>,
+    /// Value decoded, or the error message.
+    pub value: result::Result<ItemValue, String>,
+}
+
 #[inline]
-fn value_len_by_idx(len_idx: usize, values: &[ItemValue]) -> usize {
-    if let Some(value) = values.get(len_idx) {
-        match value {
-            ItemValue::Len(v) => *v as usize,
-            _ => panic!(),
-        }
-    } else {
-        panic!()
+fn value_len_by_idx(item_idx: usize, len_idx: usize, values: &[ItemValue]) -> Result<usize> {
+    match values.get(len_idx) {
+        Some(ItemValue::Len(v)) => Ok(*v as usize),
+        _ => Err(Error::NotALen { item_idx, len_idx }),
     }
 }
 
+// `None` if the item takes the rest of the message.
 #[inline]
-fn value_len(fmt: &ItemFormat, values: &[ItemValue]) -> usize {
+fn value_len(fmt: &ItemFormat, idx: usize, values: &[ItemValue]) -> Result<Option<usize>> {
     match fmt {
-        ItemFormat::Len { len } => *len,
-        ItemFormat::Uint { len } => *len,
-        ItemFormat::Int { len } => *len,
-        ItemFormat::FixedString { len } => *len,
-        ItemFormat::VarString { len_idx } => value_len_by_idx(*len_idx, values),
-        ItemFormat::FixedBytes { len } => *len,
-        ItemFormat::VarBytes { len_idx } => value_len_by_idx(*len_idx, values),
+        ItemFormat::Len { len } => Ok(Some(*len)),
+        ItemFormat::Uint { len } => Ok(Some(*len)),
+        ItemFormat::Int { len } => Ok(Some(*len)),
+        ItemFormat::FixedString { len } => Ok(Some(*len)),
+        ItemFormat::VarString { len_idx } => value_len_by_idx(idx, *len_idx, values).map(Some),
+        ItemFormat::FixedBytes { len } => Ok(Some(*len)),
+        ItemFormat::VarBytes { len_idx } => value_len_by_idx(idx, *len_idx, values).map(Some),
+        ItemFormat::Checksum { checksum, .. } => Ok(Some(checksum.width())),
+        ItemFormat::Protobuf { len, .. }
+        | ItemFormat::Cbor { len }
+        | ItemFormat::MessagePack { len }
+        | ItemFormat::Json { len }
+        | ItemFormat::Ber { len }
+        | ItemFormat::Resp { len }
+        | ItemFormat::Http { len }
+        | ItemFormat::Stomp { len }
+        | ItemFormat::Memcache { len }
+        | ItemFormat::Tlv { len, .. } => match len {
+            PayloadLen::Fixed { len } => Ok(Some(*len)),
+            PayloadLen::Var { len_idx } => value_len_by_idx(idx, *len_idx, values).map(Some),
+            PayloadLen::Greedy => Ok(None),
+        },
     }
 }
 
+#[inline]
+fn kind_matches(fmt: &ItemFormat, value: &ItemValue) -> bool {
+    if let ItemValue::Json(_) = value {
+        return fmt.payload_len().is_some();
+    }
+    matches!(
+        (fmt, value),
+        (ItemFormat::Len { .. }, ItemValue::Len(_))
+            | (
+                ItemFormat::Uint { .. } | ItemFormat::Checksum { .. },
+                ItemValue::Uint(_)
+            )
+            | (ItemFormat::Int { .. }, ItemValue::Int(_))
+            | (
+                ItemFormat::FixedString { .. } | ItemFormat::VarString { .. },
+                ItemValue::String(_)
+            )
+            | (
+                ItemFormat::FixedBytes { .. } | ItemFormat::VarBytes { .. },
+                ItemValue::Bytes(_)
+            )
+    )
+}
+
 trait Read {
     fn read(&mut self, fmt: &ItemFormat, idx: usize, values: &[ItemValue]) -> Result<ItemValue>;
 }
@@ -167,7 +544,7 @@ trait Read {
 impl Read for &[u8] {
     #[inline]
     fn read(&mut self, fmt: &ItemFormat, idx: usize, values: &[ItemValue]) -> Result<ItemValue> {
-        let len = value_len(fmt, values);
+        let len = value_len(fmt, idx, values)?.unwrap_or(self.len());
 
         if self.len() < len {
             return Err(Error::EndOfStream);
@@ -175,7 +552,9 @@ impl Read for &[u8] {
 
         match fmt {
             ItemFormat::Len { .. } => Ok(ItemValue::Len(self.get_uint(len))),
-            ItemFormat::Uint { .. } => Ok(ItemValue::Uint(self.get_uint(len))),
+            ItemFormat::Uint { .. } | ItemFormat::Checksum { .. } => {
+                Ok(ItemValue::Uint(self.get_uint(len)))
+            }
             ItemFormat::Int { .. } => {
                 let offset = (size_of::<i64>() - len) * u8::BITS as usize;
                 Ok(ItemValue::Int(self.get_int(len) << offset >> offset))
@@ -195,6 +574,21 @@ impl Read for &[u8] {
                 io::Read::read_exact(self, &mut bytes_buf).unwrap();
                 Ok(ItemValue::Bytes(bytes_buf))
             }
+
+            ItemFormat::Protobuf { .. }
+            | ItemFormat::Cbor { .. }
+            | ItemFormat::MessagePack { .. }
+            | ItemFormat::Json { .. }
+            | ItemFormat::Ber { .. }
+            | ItemFormat::Resp { .. }
+            | ItemFormat::Http { .. }
+            | ItemFormat::Stomp { .. }
+            | ItemFormat::Memcache { .. }
+            | ItemFormat::Tlv { .. } => {
+                let value = fmt.decode_payload(&self[..len]);
+                self.advance(len);
+                value.map(ItemValue::Json)
+            }
         }
     }
 }
@@ -218,7 +612,7 @@ impl Write for &mut [u8] {
         value: &ItemValue,
         values: &[ItemValue],
     ) -> Result<()> {
-        let len = value_len(fmt, values);
+        let len = value_len(fmt, idx, values)?.unwrap_or(self.len());
 
         // Validate the length
         let mut max_len = usize::MAX;
@@ -229,6 +623,7 @@ impl Write for &mut [u8] {
 
             ItemValue::String(s) => min_len = s.len(),
             ItemValue::Bytes(bytes) => min_len = bytes.len(),
+            ItemValue::Json(_) => {}
         }
 
         if len > max_len {
@@ -249,7 +644,9 @@ impl Write for &mut [u8] {
         // Write value to buf.
         match (fmt, value) {
             (ItemFormat::Len { .. }, ItemValue::Len(v))
-            | (ItemFormat::Uint { .. }, ItemValue::Uint(v)) => self.put_uint(*v, len),
+            | (ItemFormat::Uint { .. } | ItemFormat::Checksum { .. }, ItemValue::Uint(v)) => {
+                self.put_uint(*v, len)
+            }
             (ItemFormat::Int { .. }, ItemValue::Int(v)) => self.put_int(*v, len),
             (
                 ItemFormat::FixedString { .. } | ItemFormat::VarString { .. },
@@ -259,79 +656,248 @@ impl Write for &mut [u8] {
                 ItemFormat::FixedBytes { .. } | ItemFormat::VarBytes { .. },
                 ItemValue::Bytes(bytes_buf),
             ) => self.put(bytes_buf.as_slice()),
-            _ => panic!(),
+            _ => return Err(Error::ValueKindMismatch { item_idx: idx }),
         }
 
         Ok(())
     }
 }
 
+/// Verifies a checksum item against `raw`, the bytes of the message with `offsets` of the items
+/// until it, does nothing for other items.
+pub(crate) fn verify_checksum(
+    fmt: &ItemFormat,
+    item_idx: usize,
+    actual: u64,
+    raw: &[u8],
+    offsets: &[usize],
+) -> Result<()> {
+    if let ItemFormat::Checksum {
+        checksum,
+        start,
+        end,
+    } = fmt
+    {
+        let expected = checksum.compute(&raw[offsets[*start]..offsets[*end]]);
+        if expected != actual {
+            return Err(Error::ChecksumMismatch {
+                item_idx,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Decodes a message from a reader, reads are retried on `WouldBlock` or `TimedOut` until the
+/// stop flag is set, so the reader should block or have a read timeout.
 pub struct MessageDecoder<'a, R: io::Read> {
     fmt: &'a MessageFormat,
     r: &'a mut R,
 }
 
 impl<'a, R: io::Read> MessageDecoder<'a, R> {
+    /// Decodes by the format from the reader.
     pub fn new(fmt: &'a MessageFormat, r: &'a mut R) -> Self {
         Self { fmt, r }
     }
 
-    pub fn decode(self, stop_flag: Arc<AtomicBool>) -> Result<Message> {
+    /// Decodes a message, `Error::Stopped` is returned once the stop flag is set.
+    pub fn decode(self, stop_flag: Arc<AtomicBool>) -> result::Result<Message, DecodeError> {
+        self.decode_items(stop_flag, None)
+    }
+
+    /// Decodes a message, with the trace of items until an error occurs.
+    pub fn decode_trace(
+        self,
+        stop_flag: Arc<AtomicBool>,
+    ) -> (result::Result<Message, DecodeError>, Vec<ItemTrace>) {
+        let mut trace = Vec::with_capacity(self.fmt.len());
+        let res = self.decode_items(stop_flag, Some(&mut trace));
+        (res, trace)
+    }
+
+    fn decode_items(
+        self,
+        stop_flag: Arc<AtomicBool>,
+        mut trace: Option<&mut Vec<ItemTrace>>,
+    ) -> result::Result<Message, DecodeError> {
         let mut values = Vec::<ItemValue>::with_capacity(self.fmt.len());
+        let mut offset = 0usize;
+        // Bytes and offsets of items are kept for checksums.
+        let has_checksum = self
+            .fmt
+            .iter()
+            .any(|fmt| matches!(fmt, ItemFormat::Checksum { .. }));
+        let mut raw = Vec::new();
+        let mut offsets = Vec::with_capacity(self.fmt.len());
         for (idx, item_fmt) in self.fmt.iter().enumerate() {
-            let len = value_len(item_fmt, &values);
+            offsets.push(offset);
+            let len = match value_len(item_fmt, idx, &values) {
+                Ok(len) => len,
+                Err(error) => {
+                    return Err(DecodeError {
+                        error,
+                        partial: Message { values },
+                        item_idx: idx,
+                        offset,
+                        consumed: offset,
+                    })
+                }
+            };
 
-            let mut buf = vec![0u8; len];
+            // Without a length, bytes are read until the end of the stream.
+            let mut buf = vec![0u8; len.unwrap_or(0)];
             let mut cnt = 0usize;
-            loop {
-                match self.r.read(&mut buf[cnt..len]) {
+            let res = loop {
+                if len.is_none() && cnt == buf.len() {
+                    buf.resize(cnt + 1024, 0);
+                }
+                match self.r.read(&mut buf[cnt..]) {
                     Ok(n) => {
                         cnt += n;
-                        if cnt == len {
-                            break;
+                        if len == Some(cnt) || (len.is_none() && n == 0) {
+                            break (&buf[..cnt])
+                                .read(item_fmt, idx, &values)
+                                .and_then(|value| match value {
+                                    ItemValue::Uint(v) => {
+                                        verify_checksum(item_fmt, idx, v, &raw, &offsets)
+                                            .map(|_| value)
+                                    }
+                                    _ => Ok(value),
+                                });
                         }
                         if n == 0 {
-                            return Err(Error::EndOfStream);
+                            break Err(Error::EndOfStream);
                         }
                     }
 
                     Err(e) => {
                         match e.kind() {
-                            io::ErrorKind::ConnectionReset => return Err(Error::EndOfStream),
+                            io::ErrorKind::ConnectionReset => break Err(Error::EndOfStream),
                             io::ErrorKind::WouldBlock
                             | io::ErrorKind::TimedOut
                             | io::ErrorKind::Interrupted => {
                                 if stop_flag.load(Ordering::Relaxed) {
-                                    return Err(Error::Stopped);
+                                    break Err(Error::Stopped);
                                 }
-                                sleep(Duration::from_millis(300))
                             }
-                            _ => return Err(Error::Io(e)),
+                            _ => break Err(Error::Io(e)),
                         };
                     }
                 }
+            };
+
+            if has_checksum {
+                raw.extend_from_slice(&buf[..cnt]);
+            }
+            if let Some(trace) = trace.as_mut() {
+                buf.truncate(cnt);
+                trace.push(ItemTrace {
+                    offset,
+                    len: len.unwrap_or(cnt),
+                    raw: buf,
+                    value: match &res {
+                        Ok(value) => Ok(value.clone()),
+                        Err(e) => Err(e.to_string()),
+                    },
+                });
             }
-            values.push(buf.deref().read(item_fmt, idx, &values)?);
+            match res {
+                Ok(value) => values.push(value),
+                Err(error) => {
+                    return Err(DecodeError {
+                        error,
+                        partial: Message { values },
+                        item_idx: idx,
+                        offset,
+                        consumed: offset + cnt,
+                    })
+                }
+            }
+            offset += cnt;
         }
 
         Ok(Message { values })
     }
 }
 
+/// Encodes a message to a writer, the message is validated against the format first.
 pub struct MessageEncoder<'a, W: io::Write> {
     fmt: &'a MessageFormat,
     w: W,
 }
 
 impl<'a, W: io::Write> MessageEncoder<'a, W> {
+    /// Encodes by the format to the writer.
     pub fn new(fmt: &'a MessageFormat, w: W) -> Self {
         Self { fmt, w }
     }
 
+    /// Encodes a message, values of checksum items are computed instead of taken.
     pub fn encode(mut self, msg: &Message) -> Result<()> {
+        // Validate the message against the format.
+        if msg.len() != self.fmt.len() {
+            return Err(Error::ValueCountMismatch {
+                expected: self.fmt.len(),
+                actual: msg.len(),
+            });
+        }
+        if let Some(item_idx) = self
+            .fmt
+            .iter()
+            .zip(msg.iter())
+            .position(|(item_fmt, item_value)| !kind_matches(item_fmt, item_value))
+        {
+            return Err(Error::ValueKindMismatch { item_idx });
+        }
+
         let mut buf = Vec::<u8>::default();
+        let mut offsets = Vec::with_capacity(self.fmt.len());
         for (idx, (item_fmt, item_value)) in self.fmt.iter().zip(msg.iter()).enumerate() {
-            let len = value_len(item_fmt, msg);
+            let len = value_len(item_fmt, idx, msg)?;
+            offsets.push(buf.len());
+
+            // Payloads are encoded first, since their lengths are unknown until then.
+            if let ItemValue::Json(value) = item_value {
+                let payload = item_fmt.encode_payload(value)?;
+                // Only a fixed length can be padded.
+                let is_fixed = matches!(item_fmt.payload_len(), Some(PayloadLen::Fixed { .. }));
+                match len {
+                    Some(len) if len < payload.len() || (len > payload.len() && !is_fixed) => {
+                        return Err(Error::ValueLenOutOfBound {
+                            specified_len: len,
+                            item_idx: idx,
+                            len: payload.len(),
+                        })
+                    }
+                    _ => {
+                        let start = buf.len();
+                        buf.extend_from_slice(&payload);
+                        if let Some(len) = len {
+                            buf.resize(start + len, 0);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // The given value of a checksum is ignored.
+            let checksum;
+            let item_value = match item_fmt {
+                ItemFormat::Checksum {
+                    checksum: c,
+                    start,
+                    end,
+                } => {
+                    checksum = ItemValue::Uint(c.compute(&buf[offsets[*start]..offsets[*end]]));
+                    &checksum
+                }
+                _ => item_value,
+            };
+
+            let len = len.unwrap_or_default();
             let prev_len = buf.len();
             buf.resize(buf.len() + len, 0);
             let mut slice = &mut buf[prev_len..];
@@ -347,8 +913,15 @@ impl<'a, W: io::Write> MessageEncoder<'a, W> {
 mod tests {
     use std::ops::Deref;
 
-    use crate::msg::{
-        ItemFormat, ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat,
+    use serde_json::json;
+
+    use crate::{
+        checksum::Checksum,
+        error::Error,
+        msg::{
+            value_len_by_idx, ItemFormat, ItemTrace, ItemValue, Message, MessageDecoder,
+            MessageEncoder, MessageFormat, PayloadLen,
+        },
     };
 
     #[test]
@@ -379,4 +952,262 @@ mod tests {
 
         assert_eq!(msg, decoded_msg.unwrap());
     }
+
+    #[test]
+    fn encode_mismatched_message() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::VarBytes { len_idx: 0 },
+        ])
+        .unwrap();
+        let encode = |values| MessageEncoder::new(&fmt, Vec::new()).encode(&Message::new(values));
+
+        assert!(matches!(
+            encode(vec![ItemValue::Len(1)]),
+            Err(Error::ValueCountMismatch {
+                expected: 2,
+                actual: 1
+            })
+        ));
+        assert!(matches!(
+            encode(vec![ItemValue::Uint(1), ItemValue::Bytes(vec![1])]),
+            Err(Error::ValueKindMismatch { item_idx: 0 })
+        ));
+        assert!(matches!(
+            encode(vec![ItemValue::Len(1), ItemValue::String("a".to_string())]),
+            Err(Error::ValueKindMismatch { item_idx: 1 })
+        ));
+        assert!(encode(vec![ItemValue::Len(1), ItemValue::Bytes(vec![1])]).is_ok());
+
+        // The length is resolved from a value which is not a length.
+        assert!(matches!(
+            value_len_by_idx(1, 0, &[ItemValue::Uint(1)]),
+            Err(Error::NotALen {
+                item_idx: 1,
+                len_idx: 0
+            })
+        ));
+        assert!(matches!(
+            value_len_by_idx(1, 0, &[]),
+            Err(Error::NotALen {
+                item_idx: 1,
+                len_idx: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn decode_trace_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::VarString { len_idx: 0 },
+            ItemFormat::FixedBytes { len: 2 },
+        ])
+        .unwrap();
+
+        let bytes = vec![2u8, b'o', b'k', 0xff];
+        let (res, trace) =
+            MessageDecoder::new(&fmt, &mut bytes.deref()).decode_trace(Default::default());
+
+        // Items before the error are kept.
+        let e = res.unwrap_err();
+        assert!(matches!(e.error, Error::EndOfStream));
+        assert_eq!(
+            e.partial,
+            Message::new(vec![ItemValue::Len(2), ItemValue::String("ok".to_string())])
+        );
+        assert_eq!((e.item_idx, e.offset, e.consumed), (2, 3, 4));
+
+        assert_eq!(
+            trace,
+            vec![
+                ItemTrace {
+                    offset: 0,
+                    len: 1,
+                    raw: vec![2],
+                    value: Ok(ItemValue::Len(2)),
+                },
+                ItemTrace {
+                    offset: 1,
+                    len: 2,
+                    raw: b"ok".to_vec(),
+                    value: Ok(ItemValue::String("ok".to_string())),
+                },
+                ItemTrace {
+                    offset: 3,
+                    len: 2,
+                    raw: vec![0xff],
+                    value: Err(Error::EndOfStream.to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn json_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Json {
+                len: PayloadLen::Fixed { len: 10 },
+            },
+            ItemFormat::Len { len: 1 },
+            ItemFormat::Json {
+                len: PayloadLen::Var { len_idx: 1 },
+            },
+        ])
+        .unwrap();
+
+        let msg = Message::new(vec![
+            ItemValue::Json(json!({ "a": 1 })),
+            ItemValue::Len(2),
+            ItemValue::Json(json!([])),
+        ]);
+        let mut bytes = Vec::new();
+        MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+        assert_eq!(bytes, b"{\"a\":1}\0\0\0\x02[]");
+        assert_eq!(
+            MessageDecoder::new(&fmt, &mut bytes.deref())
+                .decode(Default::default())
+                .unwrap(),
+            msg
+        );
+
+        // Invalid JSON is rejected on decoding, and a long value on encoding.
+        let bytes = b"{\"a\":1}   \x02[,";
+        let e = MessageDecoder::new(&fmt, &mut bytes.as_ref())
+            .decode(Default::default())
+            .unwrap_err();
+        assert!(matches!(e.error, Error::InvalidPayload { .. }));
+        assert_eq!(e.item_idx, 2);
+        let msg = Message::new(vec![
+            ItemValue::Json(json!({ "abcde": 1 })),
+            ItemValue::Len(2),
+            ItemValue::Json(json!([])),
+        ]);
+        assert!(matches!(
+            MessageEncoder::new(&fmt, Vec::new()).encode(&msg),
+            Err(Error::ValueLenOutOfBound { item_idx: 0, .. })
+        ));
+
+        // Only payload items have JSON values.
+        let fmt = ItemFormat::Uint { len: 1 };
+        assert!(matches!(fmt.decode_payload(b"1"), Err(Error::NotAPayload)));
+        assert!(matches!(
+            fmt.encode_payload(&json!(1)),
+            Err(Error::NotAPayload)
+        ));
+
+        let msg = Message::new(vec![
+            ItemValue::Len(2),
+            ItemValue::String("ok".to_string()),
+            ItemValue::Json(json!({ "a": [1] })),
+        ]);
+        assert_eq!(msg.to_string(), r#"2; "ok"; {"a":[1]}"#);
+        assert_eq!(
+            format!("{:#}", msg),
+            "\n#0: 2\n#1: \"ok\"\n#2: {\n  \"a\": [\n    1\n  ]\n}"
+        );
+
+        assert!(matches!(
+            MessageFormat::new(&[ItemFormat::Cbor {
+                len: PayloadLen::Fixed { len: 10 }
+            }]),
+            Err(Error::FixedPayload { item_idx: 0 })
+        ));
+    }
+
+    #[test]
+    fn checksum_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::VarString { len_idx: 0 },
+            ItemFormat::Checksum {
+                checksum: Checksum::Crc16,
+                start: 0,
+                end: 2,
+            },
+        ])
+        .unwrap();
+
+        // The given value is replaced by the checksum.
+        let msg = Message::new(vec![
+            ItemValue::Len(9),
+            ItemValue::String("123456789".to_string()),
+            ItemValue::Uint(0),
+        ]);
+        let mut bytes = Vec::new();
+        MessageEncoder::new(&fmt, &mut bytes).encode(&msg).unwrap();
+        assert_eq!(&bytes[10..], &[0x93, 0x9d]);
+        assert_eq!(
+            MessageDecoder::new(&fmt, &mut bytes.deref())
+                .decode(Default::default())
+                .unwrap()[2],
+            ItemValue::Uint(0x939d)
+        );
+
+        bytes[1] ^= 0x01;
+        let e = MessageDecoder::new(&fmt, &mut bytes.deref())
+            .decode(Default::default())
+            .unwrap_err();
+        assert!(matches!(
+            e.error,
+            Error::ChecksumMismatch {
+                item_idx: 2,
+                actual: 0x939d,
+                ..
+            }
+        ));
+
+        for (start, end) in [(0, 0), (1, 3)] {
+            assert!(matches!(
+                MessageFormat::new(&[
+                    ItemFormat::Len { len: 1 },
+                    ItemFormat::VarString { len_idx: 0 },
+                    ItemFormat::Checksum {
+                        checksum: Checksum::Sum8,
+                        start,
+                        end,
+                    },
+                ]),
+                Err(Error::InvalidChecksumRange { item_idx: 2, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn names_ok() {
+        let fmt =
+            MessageFormat::new(&[ItemFormat::Len { len: 1 }, ItemFormat::Uint { len: 1 }]).unwrap();
+        let named = fmt
+            .clone()
+            .with_names(vec![Some("len".to_string())])
+            .unwrap();
+        assert_eq!((named.name(0), named.name(1)), (Some("len"), None));
+        assert_ne!(named, fmt);
+
+        // Names can be applied again or swapped.
+        let named = named
+            .with_names(vec![Some("len".to_string()), Some("cmd".to_string())])
+            .unwrap();
+        let swapped = named
+            .clone()
+            .with_names(vec![Some("cmd".to_string()), Some("len".to_string())])
+            .unwrap();
+        assert_eq!(
+            (swapped.name(0), swapped.name(1)),
+            (Some("cmd"), Some("len"))
+        );
+        // Names of the rest of items are kept.
+        assert!(matches!(
+            named.with_names(vec![Some("cmd".to_string())]),
+            Err(Error::InvalidItemName { item_idx: 1, .. })
+        ));
+
+        for names in [["a", "a"], ["a", "1b"], ["a", "b c"]] {
+            assert!(matches!(
+                fmt.clone()
+                    .with_names(names.iter().map(|n| Some(n.to_string()))),
+                Err(Error::InvalidItemName { item_idx: 1, .. })
+            ));
+        }
+    }
 }
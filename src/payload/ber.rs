@@ -0,0 +1,307 @@
+use serde_json::{json, Value};
+
+use crate::error::Result;
+
+use super::{hex_value, invalid, Reader, MAX_DEPTH};
+
+const ENCODING: &str = "BER";
+
+const CLASSES: [&str; 4] = ["universal", "application", "context", "private"];
+
+// Names of common universal tags, which are only for display.
+fn universal_type(tag: u64) -> Option<&'static str> {
+    Some(match tag {
+        0 => "END-OF-CONTENTS",
+        1 => "BOOLEAN",
+        2 => "INTEGER",
+        3 => "BIT STRING",
+        4 => "OCTET STRING",
+        5 => "NULL",
+        6 => "OBJECT IDENTIFIER",
+        10 => "ENUMERATED",
+        12 => "UTF8String",
+        16 => "SEQUENCE",
+        17 => "SET",
+        19 => "PrintableString",
+        22 => "IA5String",
+        23 => "UTCTime",
+        24 => "GeneralizedTime",
+        _ => return None,
+    })
+}
+
+// Universal strings whose values are shown as text.
+fn is_text(class: u8, tag: u64) -> bool {
+    class == 0 && matches!(tag, 12 | 19 | 22 | 23 | 24)
+}
+
+struct Decoder<'a> {
+    r: Reader<'a>,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            r: Reader {
+                encoding: ENCODING,
+                bytes,
+            },
+        }
+    }
+
+    // Class, whether constructed, and the tag number.
+    fn identifier(&mut self) -> Result<(u8, bool, u64)> {
+        let b = self.r.u8()?;
+        let mut tag = u64::from(b & 0x1f);
+        if tag == 0x1f {
+            // Base 128, the high bit is set except for the last byte.
+            tag = 0;
+            loop {
+                let b = self.r.u8()?;
+                if tag >> 57 != 0 {
+                    return Err(invalid(ENCODING, "the tag number is too large"));
+                }
+                tag = (tag << 7) | u64::from(b & 0x7f);
+                if b & 0x80 == 0 {
+                    break;
+                }
+            }
+        }
+        Ok((b >> 6, b & 0x20 != 0, tag))
+    }
+
+    // `None` if indefinite.
+    fn length(&mut self) -> Result<Option<u64>> {
+        let b = self.r.u8()?;
+        match b {
+            0x00..=0x7f => Ok(Some(u64::from(b))),
+            0x80 => Ok(None),
+            0x81..=0x88 => self.r.uint(u64::from(b & 0x7f)).map(Some),
+            _ => Err(invalid(ENCODING, format!("invalid length `{:#04x}`", b))),
+        }
+    }
+
+    // Whether the next element is an end-of-contents, which is consumed.
+    fn is_eoc(&mut self) -> Result<bool> {
+        match self.r.bytes {
+            [0, 0, ..] => {
+                self.r.take(2)?;
+                Ok(true)
+            }
+            [] => Err(invalid(
+                ENCODING,
+                "no end-of-contents of an indefinite length",
+            )),
+            _ => Ok(false),
+        }
+    }
+
+    fn elements(&mut self, depth: usize) -> Result<Vec<Value>> {
+        let mut elements = Vec::new();
+        while !self.r.bytes.is_empty() {
+            elements.push(self.element(depth)?);
+        }
+        Ok(elements)
+    }
+
+    fn element(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(ENCODING, "elements are nested too deeply"));
+        }
+
+        let (class, constructed, tag) = self.identifier()?;
+        let len = self.length()?;
+
+        let mut element = json!({ "class": CLASSES[class as usize], "tag": tag });
+        if let Some(ty) = universal_type(tag).filter(|_| class == 0) {
+            element["type"] = json!(ty);
+        }
+        match (constructed, len) {
+            (true, Some(len)) => {
+                let children = Decoder::new(self.r.take(len)?).elements(depth + 1)?;
+                element["children"] = Value::Array(children);
+            }
+            (true, None) => {
+                let mut children = Vec::new();
+                while !self.is_eoc()? {
+                    children.push(self.element(depth + 1)?);
+                }
+                element["children"] = Value::Array(children);
+                element["indefinite"] = json!(true);
+            }
+            (false, Some(len)) => {
+                let value = self.r.take(len)?;
+                match std::str::from_utf8(value) {
+                    Ok(text) if is_text(class, tag) => element["text"] = json!(text),
+                    _ => element["value"] = json!(hex::encode(value)),
+                }
+            }
+            (false, None) => {
+                return Err(invalid(
+                    ENCODING,
+                    "a primitive element can't have an indefinite length",
+                ))
+            }
+        }
+        Ok(element)
+    }
+}
+
+/// Decodes a BER payload, which may also be DER, to an array of elements.
+///
+/// An element is an object with `class`, `tag`, and `children` if constructed, otherwise `value`
+/// in HEX, or `text` for universal strings. `type` names universal tags, and `indefinite`
+/// is set for indefinite lengths.
+pub fn ber_decode(bytes: &[u8]) -> Result<Value> {
+    Decoder::new(bytes).elements(0).map(Value::Array)
+}
+
+fn put_len(buf: &mut Vec<u8>, len: usize) {
+    if len < 0x80 {
+        buf.push(len as u8);
+    } else {
+        let bytes = (len as u64).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        buf.push(0x80 | (bytes.len() - skip) as u8);
+        buf.extend_from_slice(&bytes[skip..]);
+    }
+}
+
+fn put_element(buf: &mut Vec<u8>, value: &Value, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(invalid(ENCODING, "elements are nested too deeply"));
+    }
+
+    let element = value
+        .as_object()
+        .ok_or_else(|| invalid(ENCODING, format!("`{}` is not an element", value)))?;
+    let class = match element.get("class") {
+        Some(class) => class
+            .as_str()
+            .and_then(|class| CLASSES.iter().position(|name| *name == class))
+            .ok_or_else(|| invalid(ENCODING, format!("unknown class `{}`", class)))?,
+        None => 0,
+    };
+    let tag = element
+        .get("tag")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| invalid(ENCODING, format!("`{}` has no tag", value)))?;
+    let children = element.get("children");
+
+    // Identifier.
+    let constructed = if children.is_some() { 0x20 } else { 0 };
+    if tag < 0x1f {
+        buf.push((class << 6) as u8 | constructed | tag as u8);
+    } else {
+        buf.push((class << 6) as u8 | constructed | 0x1f);
+        let groups = (64 - tag.leading_zeros() as usize).div_ceil(7);
+        for idx in (0..groups).rev() {
+            let more = if idx == 0 { 0 } else { 0x80 };
+            buf.push(more | ((tag >> (idx * 7)) & 0x7f) as u8);
+        }
+    }
+
+    // Length and contents.
+    let contents = match (children, element.get("text"), element.get("value")) {
+        (Some(children), _, _) => {
+            let children = children
+                .as_array()
+                .ok_or_else(|| invalid(ENCODING, format!("`{}` is not an array", children)))?;
+            let mut contents = Vec::new();
+            for child in children {
+                put_element(&mut contents, child, depth + 1)?;
+            }
+            if element.get("indefinite") == Some(&Value::Bool(true)) {
+                buf.push(0x80);
+                buf.extend_from_slice(&contents);
+                buf.extend_from_slice(&[0, 0]);
+                return Ok(());
+            }
+            contents
+        }
+        (None, Some(text), _) => text
+            .as_str()
+            .map(|text| text.as_bytes().to_vec())
+            .ok_or_else(|| invalid(ENCODING, format!("`{}` is not a string", text)))?,
+        (None, None, Some(v)) => hex_value(v).map_err(|details| invalid(ENCODING, details))?,
+        (None, None, None) => {
+            return Err(invalid(
+                ENCODING,
+                format!("`{}` has no value, text or children", value),
+            ))
+        }
+    };
+    put_len(buf, contents.len());
+    buf.extend_from_slice(&contents);
+    Ok(())
+}
+
+/// Encodes elements, an array or a single one, to a BER payload.
+///
+/// Lengths are definite in the shortest forms unless `indefinite` is set,
+/// so that the payload is DER if the elements are in order.
+pub fn ber_encode(value: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    match value {
+        Value::Array(elements) => {
+            for element in elements {
+                put_element(&mut buf, element, 0)?;
+            }
+        }
+        element => put_element(&mut buf, element, 0)?,
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{ber_decode, ber_encode};
+
+    #[test]
+    fn ber_ok() {
+        let bytes = hex::decode("300902010504026162800030800c02686900000101ff9f1f01ff").unwrap();
+        let value = ber_decode(&bytes).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {
+                    "class": "universal",
+                    "tag": 16,
+                    "type": "SEQUENCE",
+                    "children": [
+                        { "class": "universal", "tag": 2, "type": "INTEGER", "value": "05" },
+                        { "class": "universal", "tag": 4, "type": "OCTET STRING", "value": "6162" },
+                        { "class": "context", "tag": 0, "value": "" },
+                    ],
+                },
+                {
+                    "class": "universal",
+                    "tag": 16,
+                    "type": "SEQUENCE",
+                    "indefinite": true,
+                    "children": [
+                        { "class": "universal", "tag": 12, "type": "UTF8String", "text": "hi" },
+                    ],
+                },
+                { "class": "universal", "tag": 1, "type": "BOOLEAN", "value": "ff" },
+                { "class": "context", "tag": 31, "value": "ff" },
+            ])
+        );
+        assert_eq!(ber_encode(&value).unwrap(), bytes);
+
+        // Long lengths and tags in the shortest forms.
+        let value = json!({ "class": "private", "tag": 1000, "value": "00".repeat(200) });
+        let bytes = ber_encode(&value).unwrap();
+        assert_eq!(hex::encode(&bytes[..5]), "df876881c8");
+        assert_eq!(ber_decode(&bytes).unwrap(), json!([value]));
+
+        let decode = |s| ber_decode(&hex::decode(s).unwrap());
+        assert!(decode("0280").is_err());
+        assert!(decode("30050201").is_err());
+        assert!(decode("3080020101").is_err());
+        assert!(decode("0289").is_err());
+        assert!(ber_encode(&json!({ "class": "universal", "tag": 5 })).is_err());
+    }
+}
@@ -0,0 +1,293 @@
+use serde_json::{json, Map, Value};
+
+use crate::error::Result;
+
+use super::{
+    bytes_value, fits_f32, float_value, invalid, map_value, marker, Marker, Reader, MAX_DEPTH,
+};
+
+const ENCODING: &str = "CBOR";
+
+const BREAK: u8 = 0xff;
+
+// Converts a half-precision float to a double.
+fn f16_to_f64(bits: u16) -> f64 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exp = i32::from((bits >> 10) & 0x1f);
+    let frac = f64::from(bits & 0x3ff);
+    sign * match exp {
+        0 => frac * 2f64.powi(-24),
+        0x1f if frac == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + frac / 1024.0) * 2f64.powi(exp - 15),
+    }
+}
+
+struct Decoder<'a> {
+    r: Reader<'a>,
+}
+
+impl<'a> Decoder<'a> {
+    // Major type, additional information, and the argument, `None` if indefinite.
+    fn head(&mut self) -> Result<(u8, u8, Option<u64>)> {
+        let b = self.r.u8()?;
+        let (major, info) = (b >> 5, b & 0x1f);
+        let arg = match info {
+            0..=23 => Some(u64::from(info)),
+            24 => Some(self.r.uint(1)?),
+            25 => Some(self.r.uint(2)?),
+            26 => Some(self.r.uint(4)?),
+            27 => Some(self.r.uint(8)?),
+            31 if matches!(major, 2..=5 | 7) => None,
+            _ => {
+                return Err(invalid(
+                    ENCODING,
+                    format!("invalid additional information `{}`", info),
+                ))
+            }
+        };
+        Ok((major, info, arg))
+    }
+
+    // Whether the next byte is a break of an indefinite-length item, which is consumed.
+    fn is_break(&mut self) -> bool {
+        if self.r.peek() == Some(BREAK) {
+            self.r.bytes = &self.r.bytes[1..];
+            true
+        } else {
+            false
+        }
+    }
+
+    // Bytes of a string, which are chunks if the length is indefinite.
+    fn string_bytes(&mut self, major: u8, len: Option<u64>) -> Result<Vec<u8>> {
+        if let Some(len) = len {
+            return Ok(self.r.take(len)?.to_vec());
+        }
+
+        let mut bytes = Vec::new();
+        while !self.is_break() {
+            match self.head()? {
+                (m, _, Some(len)) if m == major => bytes.extend_from_slice(self.r.take(len)?),
+                _ => return Err(invalid(ENCODING, "invalid chunk of an indefinite string")),
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(ENCODING, "values are nested too deeply"));
+        }
+
+        let (major, info, arg) = self.head()?;
+        Ok(match major {
+            0 => Value::from(arg.unwrap()),
+            1 => {
+                let n = arg.unwrap();
+                if n > i64::MAX as u64 {
+                    return Err(invalid(ENCODING, "the negative integer is out of range"));
+                }
+                Value::from(-1 - n as i64)
+            }
+            2 => bytes_value(&self.string_bytes(major, arg)?),
+            3 => match String::from_utf8(self.string_bytes(major, arg)?) {
+                Ok(s) => Value::String(s),
+                Err(_) => return Err(invalid(ENCODING, "the string is not valid UTF-8")),
+            },
+            4 => {
+                let mut array = Vec::new();
+                match arg {
+                    Some(len) => {
+                        for _ in 0..len {
+                            array.push(self.value(depth + 1)?);
+                        }
+                    }
+                    None => {
+                        while !self.is_break() {
+                            array.push(self.value(depth + 1)?);
+                        }
+                    }
+                }
+                Value::Array(array)
+            }
+            5 => {
+                let mut entries = Vec::new();
+                match arg {
+                    Some(len) => {
+                        for _ in 0..len {
+                            entries.push((self.value(depth + 1)?, self.value(depth + 1)?));
+                        }
+                    }
+                    None => {
+                        while !self.is_break() {
+                            entries.push((self.value(depth + 1)?, self.value(depth + 1)?));
+                        }
+                    }
+                }
+                map_value(entries)
+            }
+            6 => json!({ "$tag": arg.unwrap(), "value": self.value(depth + 1)? }),
+            _ => match (info, arg) {
+                (20, _) => Value::Bool(false),
+                (21, _) => Value::Bool(true),
+                (25, Some(bits)) => float_value(f16_to_f64(bits as u16)),
+                (26, Some(bits)) => float_value(f64::from(f32::from_bits(bits as u32))),
+                (27, Some(bits)) => float_value(f64::from_bits(bits)),
+                (31, _) => return Err(invalid(ENCODING, "unexpected break")),
+                // Null, undefined and other simple values.
+                _ => Value::Null,
+            },
+        })
+    }
+}
+
+/// Decodes a CBOR payload of RFC 8949 to JSON.
+pub fn cbor_decode(bytes: &[u8]) -> Result<Value> {
+    let mut decoder = Decoder {
+        r: Reader {
+            encoding: ENCODING,
+            bytes,
+        },
+    };
+    let value = decoder.value(0)?;
+    decoder.r.end()?;
+    Ok(value)
+}
+
+fn put_head(buf: &mut Vec<u8>, major: u8, arg: u64) {
+    let major = major << 5;
+    if arg < 24 {
+        buf.push(major | arg as u8);
+    } else if arg <= u8::MAX.into() {
+        buf.extend_from_slice(&[major | 24, arg as u8]);
+    } else if arg <= u16::MAX.into() {
+        buf.push(major | 25);
+        buf.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= u32::MAX.into() {
+        buf.push(major | 26);
+        buf.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        buf.push(major | 27);
+        buf.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+fn put_float(buf: &mut Vec<u8>, v: f64) {
+    if fits_f32(v) {
+        buf.push(0xfa);
+        buf.extend_from_slice(&(v as f32).to_be_bytes());
+    } else {
+        buf.push(0xfb);
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn put_map<'a>(
+    buf: &mut Vec<u8>,
+    entries: impl ExactSizeIterator<Item = (Value, &'a Value)>,
+    depth: usize,
+) -> Result<()> {
+    put_head(buf, 5, entries.len() as u64);
+    for (k, v) in entries {
+        put_value(buf, &k, depth + 1)?;
+        put_value(buf, v, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn put_value(buf: &mut Vec<u8>, value: &Value, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(invalid(ENCODING, "values are nested too deeply"));
+    }
+
+    match value {
+        Value::Null => buf.push(0xf6),
+        Value::Bool(b) => buf.push(if *b { 0xf5 } else { 0xf4 }),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => put_head(buf, 0, n),
+            (_, Some(n)) => put_head(buf, 1, (-1 - n) as u64),
+            _ => put_float(buf, n.as_f64().unwrap()),
+        },
+        Value::String(s) => {
+            put_head(buf, 3, s.len() as u64);
+            buf.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(array) => {
+            put_head(buf, 4, array.len() as u64);
+            for v in array {
+                put_value(buf, v, depth + 1)?;
+            }
+        }
+        Value::Object(obj) => match marker(obj).map_err(|details| invalid(ENCODING, details))? {
+            Some(Marker::Bytes(bytes)) => {
+                put_head(buf, 2, bytes.len() as u64);
+                buf.extend_from_slice(&bytes);
+            }
+            Some(Marker::Float(v)) => put_float(buf, v),
+            Some(Marker::Map(entries)) => {
+                put_map(buf, entries.into_iter().map(|(k, v)| (k.clone(), v)), depth)?
+            }
+            Some(Marker::Tag(tag, v)) => {
+                put_head(buf, 6, tag);
+                put_value(buf, v, depth + 1)?;
+            }
+            Some(Marker::Ext(..)) => {
+                return Err(invalid(ENCODING, "extension types are of MessagePack"))
+            }
+            None => put_map(buf, map_entries(obj), depth)?,
+        },
+    }
+    Ok(())
+}
+
+fn map_entries(obj: &Map<String, Value>) -> impl ExactSizeIterator<Item = (Value, &Value)> {
+    obj.iter().map(|(k, v)| (Value::String(k.clone()), v))
+}
+
+/// Encodes JSON to a CBOR payload, integers and floats are in the shortest forms
+/// and lengths are definite.
+pub fn cbor_encode(value: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    put_value(&mut buf, value, 0)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{cbor_decode, cbor_encode};
+
+    #[test]
+    fn cbor_ok() {
+        let value = json!({
+            "id": 1000,
+            "temp": -12.5,
+            "ok": true,
+            "raw": { "$bytes": "cafe" },
+            "tags": ["a", null, -1],
+            "time": { "$tag": 1, "value": 1363896240 },
+            "codes": { "$map": [[1, "one"]] },
+        });
+        let bytes = cbor_encode(&value).unwrap();
+        assert_eq!(cbor_decode(&bytes).unwrap(), value);
+
+        // Examples of RFC 8949.
+        let decode = |s| cbor_decode(&hex::decode(s).unwrap()).unwrap();
+        assert_eq!(decode("3903e7"), json!(-1000));
+        assert_eq!(decode("f93c00"), json!(1.0));
+        assert_eq!(decode("f97c00"), json!({ "$float": "Infinity" }));
+        assert_eq!(decode("9f018202039f0405ffff"), json!([1, [2, 3], [4, 5]]));
+        assert_eq!(decode("7f657374726561646d696e67ff"), json!("streaming"));
+        assert_eq!(
+            hex::encode(cbor_encode(&json!(1.1)).unwrap()),
+            "fb3ff199999999999a"
+        );
+        assert_eq!(hex::encode(cbor_encode(&json!(-1000)).unwrap()), "3903e7");
+
+        assert!(cbor_decode(&hex::decode("8201").unwrap()).is_err());
+        assert!(cbor_decode(&hex::decode("0101").unwrap()).is_err());
+        assert!(cbor_encode(&json!({ "$ext": 1, "data": "00" })).is_err());
+    }
+}
@@ -0,0 +1,377 @@
+use serde_json::{json, Map, Value};
+
+use crate::error::{Error, Result};
+
+use super::{body_bytes, body_value, invalid};
+
+const ENCODING: &str = "HTTP";
+
+// Where the body of a message ends.
+enum BodyLen {
+    Fixed(usize),
+    Chunked,
+    // Responses without a length end with the connection.
+    Rest,
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+
+    // Whether more bytes are needed, rather than the bytes are invalid.
+    incomplete: bool,
+    // Whether the body ends with the connection.
+    until_end: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            incomplete: false,
+            until_end: false,
+        }
+    }
+
+    fn end_of_bytes(&mut self) -> Error {
+        self.incomplete = true;
+        invalid(ENCODING, "unexpected end of the payload")
+    }
+
+    // A line without the LF and the CR before it, bare LFs are accepted as CRLFs.
+    fn line(&mut self) -> Result<&'a str> {
+        let bytes = &self.bytes[self.pos..];
+        let end = match bytes.iter().position(|b| *b == b'\n') {
+            Some(end) => end,
+            None => return Err(self.end_of_bytes()),
+        };
+        self.pos += end + 1;
+        let line = bytes[..end].strip_suffix(b"\r").unwrap_or(&bytes[..end]);
+        std::str::from_utf8(line).map_err(|_| invalid(ENCODING, "the line is not UTF-8"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        match self.bytes.get(self.pos..self.pos.saturating_add(len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(self.end_of_bytes()),
+        }
+    }
+
+    // Header lines until the empty line, lines without a colon are kept as strings.
+    fn headers(&mut self) -> Result<Vec<Value>> {
+        let mut headers = Vec::new();
+        loop {
+            match self.line()? {
+                "" => return Ok(headers),
+                line => headers.push(match line.split_once(':') {
+                    Some((name, value)) => json!([name, value.trim()]),
+                    None => json!(line),
+                }),
+            }
+        }
+    }
+
+    fn chunks(&mut self) -> Result<(Vec<Value>, Vec<Value>)> {
+        let mut chunks = Vec::new();
+        loop {
+            let line = self.line()?;
+            // Chunk extensions are dropped.
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = usize::from_str_radix(size, 16)
+                .map_err(|_| invalid(ENCODING, format!("invalid chunk size `{}`", line)))?;
+            if size == 0 {
+                return Ok((chunks, self.headers()?));
+            }
+            chunks.push(body_value(self.take(size)?));
+            if !self.line()?.is_empty() {
+                return Err(invalid(ENCODING, "no CRLF after the chunk"));
+            }
+        }
+    }
+
+    fn message(&mut self) -> Result<Value> {
+        let start = self.line()?;
+        let mut parts = start.splitn(3, ' ');
+        let (first, second, third) = (parts.next().unwrap_or_default(), parts.next(), parts.next());
+        let mut msg = Map::new();
+        let is_response = first.starts_with("HTTP/");
+        if is_response {
+            let status = second
+                .and_then(|s| s.parse::<u16>().ok())
+                .ok_or_else(|| invalid(ENCODING, format!("invalid status line `{}`", start)))?;
+            msg.insert("version".to_string(), json!(first));
+            msg.insert("status".to_string(), json!(status));
+            msg.insert("reason".to_string(), json!(third.unwrap_or_default()));
+        } else {
+            let (target, version) = match (second, third) {
+                (Some(target), Some(version)) if !first.is_empty() => (target, version),
+                _ => {
+                    return Err(invalid(
+                        ENCODING,
+                        format!("invalid request line `{}`", start),
+                    ))
+                }
+            };
+            msg.insert("method".to_string(), json!(first));
+            msg.insert("target".to_string(), json!(target));
+            msg.insert("version".to_string(), json!(version));
+        }
+
+        let headers = self.headers()?;
+        let body_len = body_len(&headers, is_response.then(|| &msg["status"]))?;
+        msg.insert("headers".to_string(), Value::Array(headers));
+        match body_len {
+            BodyLen::Fixed(len) => {
+                msg.insert("body".to_string(), body_value(self.take(len)?));
+            }
+            BodyLen::Chunked => {
+                let (chunks, trailers) = self.chunks()?;
+                msg.insert("chunks".to_string(), Value::Array(chunks));
+                if !trailers.is_empty() {
+                    msg.insert("trailers".to_string(), Value::Array(trailers));
+                }
+            }
+            BodyLen::Rest => {
+                self.until_end = true;
+                let body = &self.bytes[self.pos..];
+                self.pos = self.bytes.len();
+                msg.insert("body".to_string(), body_value(body));
+            }
+        }
+        Ok(Value::Object(msg))
+    }
+}
+
+/// The value of the last header of the name, ignoring cases.
+pub(crate) fn http_header<'a>(headers: &'a [Value], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .rev()
+        .find_map(|h| match h.as_array().map(Vec::as_slice) {
+            Some([Value::String(n), Value::String(v)]) if n.eq_ignore_ascii_case(name) => {
+                Some(v.as_str())
+            }
+            _ => None,
+        })
+}
+
+// The length of the body by RFC 9112, `status` is `None` for requests.
+fn body_len(headers: &[Value], status: Option<&Value>) -> Result<BodyLen> {
+    if let Some(Value::Number(status)) = status {
+        match status.as_u64().unwrap_or_default() {
+            100..=199 | 204 | 304 => return Ok(BodyLen::Fixed(0)),
+            _ => {}
+        }
+    }
+    if let Some(codings) = http_header(headers, "Transfer-Encoding") {
+        let last = codings.rsplit(',').next().unwrap_or_default().trim();
+        if last.eq_ignore_ascii_case("chunked") {
+            return Ok(BodyLen::Chunked);
+        }
+    }
+    match http_header(headers, "Content-Length") {
+        Some(len) => len
+            .parse()
+            .map(BodyLen::Fixed)
+            .map_err(|_| invalid(ENCODING, format!("invalid Content-Length `{}`", len))),
+        None if status.is_some() => Ok(BodyLen::Rest),
+        None => Ok(BodyLen::Fixed(0)),
+    }
+}
+
+/// Decodes a payload of an HTTP/1.1 request or response.
+///
+/// The start line is split into `method`, `target` and `version`, or `version`, `status` and
+/// `reason`. `headers` are pairs of names and values in order, and lines without a colon are
+/// strings. The body is `body`, a string or `$bytes`, or `chunks` and `trailers` if chunked.
+/// A response without a length takes the rest of the payload.
+pub fn http_decode(bytes: &[u8]) -> Result<Value> {
+    let mut d = Decoder::new(bytes);
+    let msg = d.message()?;
+    if d.pos != bytes.len() {
+        return Err(invalid(
+            ENCODING,
+            format!("`{}` bytes after the message", bytes.len() - d.pos),
+        ));
+    }
+    Ok(msg)
+}
+
+/// Length of the first HTTP/1.1 message of the bytes, `None` if more bytes are needed.
+///
+/// A response without a length is an error, since it ends with the connection.
+pub fn http_len(bytes: &[u8]) -> Result<Option<usize>> {
+    let mut d = Decoder::new(bytes);
+    match d.message() {
+        Ok(_) if d.until_end => Err(invalid(
+            ENCODING,
+            "the body of the response ends with the connection",
+        )),
+        Ok(_) => Ok(Some(d.pos)),
+        Err(_) if d.incomplete => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn field<'a>(msg: &'a Map<String, Value>, key: &str, default: &'a str) -> Result<&'a str> {
+    match msg.get(key) {
+        Some(Value::String(s)) => Ok(s),
+        Some(v) => Err(invalid(ENCODING, format!("`{}` is not a string", v))),
+        None => Ok(default),
+    }
+}
+
+fn put_lines(buf: &mut Vec<u8>, headers: Option<&Value>) -> Result<()> {
+    let headers = match headers {
+        Some(Value::Array(headers)) => headers.as_slice(),
+        Some(v) => return Err(invalid(ENCODING, format!("`{}` is not an array", v))),
+        None => &[],
+    };
+    for h in headers {
+        match h {
+            Value::Array(pair) => match pair.as_slice() {
+                [Value::String(name), Value::String(value)] => {
+                    buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes())
+                }
+                _ => {
+                    return Err(invalid(
+                        ENCODING,
+                        format!("the header `{}` is not a pair of strings", h),
+                    ))
+                }
+            },
+            Value::String(line) => buf.extend_from_slice(format!("{}\r\n", line).as_bytes()),
+            _ => {
+                return Err(invalid(
+                    ENCODING,
+                    format!("the header `{}` is not a pair or a line", h),
+                ))
+            }
+        }
+    }
+    buf.extend_from_slice(b"\r\n");
+    Ok(())
+}
+
+/// Encodes a request or a response decoded by [`http_decode`] to an HTTP/1.1 payload.
+///
+/// The version defaults to `HTTP/1.1`. Lines are written as they are and no header is added,
+/// so that malformed messages can be composed.
+pub fn http_encode(value: &Value) -> Result<Vec<u8>> {
+    let msg = value
+        .as_object()
+        .ok_or_else(|| invalid(ENCODING, format!("`{}` is not an object", value)))?;
+    let version = field(msg, "version", "HTTP/1.1")?;
+
+    let mut buf = Vec::new();
+    let start = match (msg.get("method"), msg.get("status")) {
+        (Some(_), None) => format!(
+            "{} {} {}",
+            field(msg, "method", "")?,
+            field(msg, "target", "/")?,
+            version
+        ),
+        (None, Some(status)) => {
+            let status = status
+                .as_u64()
+                .filter(|status| *status <= 999)
+                .ok_or_else(|| invalid(ENCODING, format!("invalid status `{}`", status)))?;
+            format!("{} {:03} {}", version, status, field(msg, "reason", "")?)
+        }
+        _ => {
+            return Err(invalid(
+                ENCODING,
+                "the message should have either a method or a status",
+            ))
+        }
+    };
+    buf.extend_from_slice(start.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    put_lines(&mut buf, msg.get("headers"))?;
+
+    match (msg.get("body"), msg.get("chunks")) {
+        (body, None) => {
+            if let Some(body) = body {
+                buf.extend(body_bytes(ENCODING, body)?);
+            }
+        }
+        (None, Some(Value::Array(chunks))) => {
+            for chunk in chunks {
+                let chunk = body_bytes(ENCODING, chunk)?;
+                buf.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                buf.extend(chunk);
+                buf.extend_from_slice(b"\r\n");
+            }
+            buf.extend_from_slice(b"0\r\n");
+            put_lines(&mut buf, msg.get("trailers"))?;
+        }
+        (None, Some(v)) => return Err(invalid(ENCODING, format!("`{}` is not an array", v))),
+        (Some(_), Some(_)) => {
+            return Err(invalid(
+                ENCODING,
+                "the message should have either a body or chunks",
+            ))
+        }
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{http_decode, http_encode, http_len};
+
+    #[test]
+    fn http_ok() {
+        let value = json!({
+            "method": "POST",
+            "target": "/echo",
+            "version": "HTTP/1.1",
+            "headers": [["Host", "localhost"], ["Content-Length", "2"]],
+            "body": "hi",
+        });
+        let bytes = http_encode(&value).unwrap();
+        assert_eq!(
+            bytes,
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\nhi"
+        );
+        assert_eq!(http_decode(&bytes).unwrap(), value);
+
+        let value = json!({
+            "version": "HTTP/1.1",
+            "status": 200,
+            "reason": "OK",
+            "headers": [["Transfer-Encoding", "chunked"], "malformed"],
+            "chunks": ["abc", { "$bytes": "ff" }],
+            "trailers": [["Expires", "0"]],
+        });
+        let bytes = http_encode(&value).unwrap();
+        assert_eq!(
+            bytes,
+            &b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nmalformed\r\n\r\n\
+               3\r\nabc\r\n1\r\n\xff\r\n0\r\nExpires: 0\r\n\r\n"[..]
+        );
+        assert_eq!(http_decode(&bytes).unwrap(), value);
+
+        // Bare LFs, and a response which ends with the connection.
+        let bytes = b"HTTP/1.0 200 OK\nServer: x\n\nbody";
+        assert_eq!(http_decode(bytes).unwrap()["body"], "body");
+        assert!(http_len(bytes).is_err());
+
+        let bytes = b"GET / HTTP/1.1\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n";
+        assert_eq!(http_len(bytes).unwrap(), Some(18));
+        assert_eq!(http_len(&bytes[18..]).unwrap(), Some(27));
+        assert_eq!(
+            http_len(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nabc").unwrap(),
+            None
+        );
+        assert_eq!(http_len(b"HTTP/1.1 200 OK\r\nContent").unwrap(), None);
+        assert!(http_len(b"HTTP/1.1 OK\r\n\r\n").is_err());
+        assert!(http_encode(&json!({ "body": "" })).is_err());
+    }
+}
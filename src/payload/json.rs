@@ -0,0 +1,41 @@
+use serde_json::Value;
+
+use crate::error::Result;
+
+use super::invalid;
+
+const ENCODING: &str = "JSON";
+
+/// Decodes a JSON payload, trailing zeros and whitespaces of padding are ignored.
+pub fn json_decode(bytes: &[u8]) -> Result<Value> {
+    let end = bytes.iter().rposition(|b| *b != 0).map_or(0, |idx| idx + 1);
+    serde_json::from_slice(&bytes[..end]).map_err(|e| invalid(ENCODING, e.to_string()))
+}
+
+/// Encodes JSON to a compact payload.
+pub fn json_encode(value: &Value) -> Result<Vec<u8>> {
+    serde_json::to_vec(value).map_err(|e| invalid(ENCODING, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{json_decode, json_encode};
+
+    #[test]
+    fn json_ok() {
+        let value = json!({ "cmd": "ping", "seq": [1, 2.5, null] });
+        let bytes = json_encode(&value).unwrap();
+        assert_eq!(bytes, br#"{"cmd":"ping","seq":[1,2.5,null]}"#);
+        assert_eq!(json_decode(&bytes).unwrap(), value);
+
+        assert_eq!(
+            json_decode(b"{\"a\": 1}\n  \0\0").unwrap(),
+            json!({ "a": 1 })
+        );
+        assert!(json_decode(b"{\"a\": 1").is_err());
+        assert!(json_decode(b"{} {}").is_err());
+        assert!(json_decode(b"").is_err());
+    }
+}
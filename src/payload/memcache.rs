@@ -0,0 +1,238 @@
+use serde_json::{json, Map, Value};
+
+use crate::error::{Error, Result};
+
+use super::{body_bytes, body_value, invalid};
+
+const ENCODING: &str = "memcached";
+
+const STORAGE_COMMANDS: [&str; 6] = ["set", "add", "replace", "append", "prepend", "cas"];
+
+// Length of the data block which follows the line, `None` if there is no data block.
+fn data_len(line: &str) -> Result<Option<usize>> {
+    let words = line
+        .split(' ')
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>();
+    let len = match words.as_slice() {
+        [cmd, _, _, _, len, ..] if STORAGE_COMMANDS.contains(cmd) => len,
+        ["VALUE", _, _, len, ..] => len,
+        [cmd, ..] if STORAGE_COMMANDS.contains(cmd) || *cmd == "VALUE" => {
+            return Err(invalid(
+                ENCODING,
+                format!("the length of the data is missing in `{}`", line),
+            ))
+        }
+        _ => return Ok(None),
+    };
+    len.parse::<usize>()
+        .map(Some)
+        .map_err(|_| invalid(ENCODING, format!("invalid length of the data `{}`", len)))
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+
+    // Whether more bytes are needed, rather than the bytes are invalid.
+    incomplete: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            incomplete: false,
+        }
+    }
+
+    fn end_of_bytes(&mut self) -> Error {
+        self.incomplete = true;
+        invalid(ENCODING, "unexpected end of the payload")
+    }
+
+    // A line without the CRLF.
+    fn line(&mut self) -> Result<&'a str> {
+        let bytes = &self.bytes[self.pos..];
+        let end = match bytes.windows(2).position(|w| w == b"\r\n") {
+            Some(end) => end,
+            None => return Err(self.end_of_bytes()),
+        };
+        self.pos += end + 2;
+        match std::str::from_utf8(&bytes[..end]) {
+            Ok(line) if !line.trim().is_empty() => Ok(line),
+            Ok(_) => Err(invalid(ENCODING, "the line is empty")),
+            Err(_) => Err(invalid(ENCODING, "the line is not UTF-8")),
+        }
+    }
+
+    // A line with its data block if any.
+    fn entry(&mut self) -> Result<(&'a str, Value)> {
+        let line = self.line()?;
+        let mut entry = Map::new();
+        entry.insert("line".to_string(), json!(line));
+        if let Some(len) = data_len(line)? {
+            let end = len
+                .checked_add(2)
+                .ok_or_else(|| invalid(ENCODING, format!("the data of `{}` is too long", line)))?;
+            let rest = &self.bytes[self.pos..];
+            match rest.get(len..end) {
+                Some(b"\r\n") => {}
+                Some(_) => return Err(invalid(ENCODING, "no CRLF after the data block")),
+                None => return Err(self.end_of_bytes()),
+            }
+            entry.insert("data".to_string(), body_value(&rest[..len]));
+            self.pos += end;
+        }
+        Ok((line, Value::Object(entry)))
+    }
+
+    // A command or a reply, replies to retrieval commands are `VALUE` lines ending with `END`.
+    fn payload(&mut self) -> Result<Value> {
+        let (line, entry) = self.entry()?;
+        let mut entries = vec![entry];
+        if line.starts_with("VALUE ") {
+            loop {
+                let (line, entry) = self.entry()?;
+                entries.push(entry);
+                match line {
+                    "END" => break,
+                    _ if line.starts_with("VALUE ") => {}
+                    _ => {
+                        return Err(invalid(
+                            ENCODING,
+                            format!("`{}` is not a `VALUE` or `END` line", line),
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(Value::Array(entries))
+    }
+}
+
+/// Decodes a payload of the memcached text protocol, a command or a reply.
+///
+/// The payload is an array of lines, each is an object of `line` without the CRLF and `data`,
+/// a string or `$bytes`, if a data block follows the line. Only replies to retrieval commands,
+/// `VALUE` lines ending with `END`, have more than one line.
+pub fn memcache_decode(bytes: &[u8]) -> Result<Value> {
+    let mut d = Decoder::new(bytes);
+    let payload = d.payload()?;
+    if d.pos != bytes.len() {
+        return Err(invalid(
+            ENCODING,
+            format!("`{}` bytes after the payload", bytes.len() - d.pos),
+        ));
+    }
+    Ok(payload)
+}
+
+/// Length of the first memcached command or reply of the bytes, `None` if more bytes are
+/// needed.
+pub fn memcache_len(bytes: &[u8]) -> Result<Option<usize>> {
+    let mut d = Decoder::new(bytes);
+    match d.payload() {
+        Ok(_) => Ok(Some(d.pos)),
+        Err(_) if d.incomplete => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Encodes a payload decoded by [`memcache_decode`], the length in each line with a data block
+/// should be the length of the data.
+pub fn memcache_encode(value: &Value) -> Result<Vec<u8>> {
+    let entries = match value {
+        Value::Array(entries) if !entries.is_empty() => entries,
+        _ => {
+            return Err(invalid(
+                ENCODING,
+                format!("`{}` is not an array of lines", value),
+            ))
+        }
+    };
+
+    let mut buf = Vec::new();
+    for entry in entries {
+        let line = match entry.get("line") {
+            Some(Value::String(line)) if !line.trim().is_empty() && !line.contains("\r\n") => line,
+            _ => {
+                return Err(invalid(
+                    ENCODING,
+                    format!("`{}` has no `line` of a string", entry),
+                ))
+            }
+        };
+        buf.extend_from_slice(line.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+
+        match (data_len(line)?, entry.get("data")) {
+            (Some(len), Some(data)) => {
+                let data = body_bytes(ENCODING, data)?;
+                if data.len() != len {
+                    return Err(invalid(
+                        ENCODING,
+                        format!(
+                            "the data has `{}` bytes but `{}` is given in `{}`",
+                            data.len(),
+                            len,
+                            line
+                        ),
+                    ));
+                }
+                buf.extend(data);
+                buf.extend_from_slice(b"\r\n");
+            }
+            (None, None) => {}
+            (Some(_), None) => {
+                return Err(invalid(
+                    ENCODING,
+                    format!("the data of `{}` is missing", line),
+                ))
+            }
+            (None, Some(_)) => return Err(invalid(ENCODING, format!("`{}` takes no data", line))),
+        }
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{memcache_decode, memcache_encode, memcache_len};
+
+    #[test]
+    fn memcache_ok() {
+        let value = json!([{ "line": "set k 0 0 5", "data": "a\r\nb" }]);
+        assert!(memcache_encode(&value).is_err());
+        let value = json!([{ "line": "set k 0 0 4", "data": "a\r\nb" }]);
+        let bytes = memcache_encode(&value).unwrap();
+        assert_eq!(bytes, b"set k 0 0 4\r\na\r\nb\r\n");
+        assert_eq!(memcache_decode(&bytes).unwrap(), value);
+
+        let bytes = b"VALUE a 0 1\r\n\xff\r\nVALUE b 2 0 7\r\n\r\nEND\r\nSTORED\r\n";
+        assert_eq!(memcache_len(bytes).unwrap(), Some(38));
+        assert_eq!(
+            memcache_decode(&bytes[..38]).unwrap(),
+            json!([
+                { "line": "VALUE a 0 1", "data": { "$bytes": "ff" } },
+                { "line": "VALUE b 2 0 7", "data": "" },
+                { "line": "END" },
+            ])
+        );
+        assert_eq!(memcache_len(&bytes[38..]).unwrap(), Some(8));
+        assert_eq!(memcache_len(&bytes[..35]).unwrap(), None);
+        assert_eq!(memcache_len(b"get k").unwrap(), None);
+        assert_eq!(memcache_len(b"set k 0 0 2\r\nab").unwrap(), None);
+
+        assert!(memcache_len(b"set k 0 0 1\r\nab\r\n").is_err());
+        assert!(memcache_len(b"set k 0\r\n").is_err());
+        assert!(memcache_len(b"VALUE a 0 0\r\n\r\nSTORED\r\n").is_err());
+        assert!(memcache_len(b"\r\n").is_err());
+        assert!(memcache_len(b"set k 0 0 18446744073709551615\r\nab").is_err());
+        assert!(memcache_encode(&json!([{ "line": "get k", "data": "" }])).is_err());
+        assert!(memcache_encode(&json!([])).is_err());
+    }
+}
@@ -0,0 +1,214 @@
+mod ber;
+mod cbor;
+mod http;
+mod json;
+mod memcache;
+mod msgpack;
+mod resp;
+mod stomp;
+mod tlv;
+
+pub use ber::*;
+pub use cbor::*;
+pub use http::*;
+pub use json::*;
+pub use memcache::*;
+pub use msgpack::*;
+pub use resp::*;
+pub use stomp::*;
+pub use tlv::*;
+
+use std::result;
+
+use serde_json::{json, Map, Number, Value};
+
+use crate::error::Error;
+
+// Payloads nested deeper than this are rejected, so that the stack is not exhausted.
+const MAX_DEPTH: usize = 64;
+
+// Values which JSON can't represent are objects with a key starting with `$`:
+//  * `{"$bytes": "cafe"}`: a byte string in HEX.
+//  * `{"$float": "NaN"}`: a float which is not finite, `Infinity` or `-Infinity`.
+//  * `{"$map": [[1, "a"]]}`: a map with keys which are not strings.
+//  * `{"$tag": 1, "value": 0}`: a tagged value of CBOR.
+//  * `{"$ext": 1, "data": "cafe"}`: an extension type of MessagePack.
+//  * `{"$simple": "OK"}`, `{"$error": "ERR"}`, `{"$set": []}`, `{"$push": []}` and
+//    `{"$bignum": "1"}`: types of RESP which aren't bulk strings, arrays or maps.
+
+enum Marker<'a> {
+    Bytes(Vec<u8>),
+    Float(f64),
+    Map(Vec<(&'a Value, &'a Value)>),
+    Tag(u64, &'a Value),
+    Ext(i8, Vec<u8>),
+}
+
+fn invalid(encoding: &'static str, details: impl Into<String>) -> Error {
+    Error::InvalidPayload {
+        encoding,
+        details: details.into(),
+    }
+}
+
+fn hex_value(v: &Value) -> result::Result<Vec<u8>, String> {
+    v.as_str()
+        .and_then(|s| hex::decode(s).ok())
+        .ok_or_else(|| format!("`{}` is not bytes in HEX", v))
+}
+
+// `None` if the object is an ordinary map.
+fn marker(obj: &Map<String, Value>) -> result::Result<Option<Marker<'_>>, String> {
+    let get = |key| obj.get(key).filter(|_| obj.len() == 1);
+    let get_pair = |key, value_key| match (obj.get(key), obj.get(value_key)) {
+        (Some(k), Some(v)) if obj.len() == 2 => Some((k, v)),
+        _ => None,
+    };
+
+    if let Some(v) = get("$bytes") {
+        return hex_value(v).map(|bytes| Some(Marker::Bytes(bytes)));
+    }
+    if let Some(v) = get("$float") {
+        return match v.as_str() {
+            Some("NaN") => Ok(Some(Marker::Float(f64::NAN))),
+            Some("Infinity") => Ok(Some(Marker::Float(f64::INFINITY))),
+            Some("-Infinity") => Ok(Some(Marker::Float(f64::NEG_INFINITY))),
+            _ => Err(format!("`{}` is not `NaN`, `Infinity` or `-Infinity`", v)),
+        };
+    }
+    if let Some(v) = get("$map") {
+        let entries = v
+            .as_array()
+            .and_then(|entries| {
+                entries
+                    .iter()
+                    .map(|entry| match entry.as_array().map(Vec::as_slice) {
+                        Some([k, v]) => Some((k, v)),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| format!("`{}` is not an array of key-value pairs", v))?;
+        return Ok(Some(Marker::Map(entries)));
+    }
+    if let Some((tag, v)) = get_pair("$tag", "value") {
+        let tag = tag
+            .as_u64()
+            .ok_or_else(|| format!("the tag `{}` is not an unsigned integer", tag))?;
+        return Ok(Some(Marker::Tag(tag, v)));
+    }
+    if let Some((ty, data)) = get_pair("$ext", "data") {
+        let ty = ty
+            .as_i64()
+            .filter(|ty| i8::MIN as i64 <= *ty && *ty <= i8::MAX as i64)
+            .ok_or_else(|| format!("the extension type `{}` is not an `i8`", ty))?;
+        return Ok(Some(Marker::Ext(ty as i8, hex_value(data)?)));
+    }
+    Ok(None)
+}
+
+fn bytes_value(bytes: &[u8]) -> Value {
+    json!({ "$bytes": hex::encode(bytes) })
+}
+
+// Bodies of text protocols are strings, or `$bytes` if they are not UTF-8.
+pub(crate) fn body_value(body: &[u8]) -> Value {
+    match std::str::from_utf8(body) {
+        Ok(s) => json!(s),
+        Err(_) => bytes_value(body),
+    }
+}
+
+pub(crate) fn body_bytes(encoding: &'static str, v: &Value) -> result::Result<Vec<u8>, Error> {
+    let not_body = || invalid(encoding, format!("`{}` is not a string or bytes", v));
+    match v {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Object(obj) => match marker(obj).map_err(|details| invalid(encoding, details))? {
+            Some(Marker::Bytes(bytes)) => Ok(bytes),
+            _ => Err(not_body()),
+        },
+        _ => Err(not_body()),
+    }
+}
+
+fn float_value(v: f64) -> Value {
+    match Number::from_f64(v) {
+        Some(n) => Value::Number(n),
+        None if v.is_nan() => json!({ "$float": "NaN" }),
+        None if v > 0.0 => json!({ "$float": "Infinity" }),
+        None => json!({ "$float": "-Infinity" }),
+    }
+}
+
+fn map_value(entries: Vec<(Value, Value)>) -> Value {
+    if entries.iter().all(|(k, _)| k.is_string()) {
+        let map = entries
+            .into_iter()
+            .map(|(k, v)| match k {
+                Value::String(k) => (k, v),
+                _ => unreachable!(),
+            })
+            .collect();
+        Value::Object(map)
+    } else {
+        let entries = entries
+            .into_iter()
+            .map(|(k, v)| Value::Array(vec![k, v]))
+            .collect();
+        json!({ "$map": Value::Array(entries) })
+    }
+}
+
+// Whether the float can be encoded in 32 bits without loss.
+fn fits_f32(v: f64) -> bool {
+    v.is_nan() || f64::from(v as f32) == v
+}
+
+struct Reader<'a> {
+    encoding: &'static str,
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: u64) -> Result<&'a [u8], Error> {
+        if (self.bytes.len() as u64) < len {
+            return Err(invalid(self.encoding, "unexpected end of the payload"));
+        }
+        let (bytes, rest) = self.bytes.split_at(len as usize);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.first().copied()
+    }
+
+    // Big-endian unsigned integer of `len` bytes.
+    fn uint(&mut self, len: u64) -> Result<u64, Error> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |v, b| (v << 8) | u64::from(*b)))
+    }
+
+    fn string(&mut self, len: u64) -> Result<String, Error> {
+        let encoding = self.encoding;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| invalid(encoding, "the string is not valid UTF-8"))
+    }
+
+    fn end(&self) -> Result<(), Error> {
+        if self.bytes.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid(
+                self.encoding,
+                format!("`{}` bytes after the value", self.bytes.len()),
+            ))
+        }
+    }
+}
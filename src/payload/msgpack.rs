@@ -0,0 +1,304 @@
+use serde_json::{json, Value};
+
+use crate::error::Result;
+
+use super::{
+    bytes_value, fits_f32, float_value, invalid, map_value, marker, Marker, Reader, MAX_DEPTH,
+};
+
+const ENCODING: &str = "MessagePack";
+
+struct Decoder<'a> {
+    r: Reader<'a>,
+}
+
+impl<'a> Decoder<'a> {
+    fn array(&mut self, len: u64, depth: usize) -> Result<Value> {
+        let mut array = Vec::new();
+        for _ in 0..len {
+            array.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Array(array))
+    }
+
+    fn map(&mut self, len: u64, depth: usize) -> Result<Value> {
+        let mut entries = Vec::new();
+        for _ in 0..len {
+            entries.push((self.value(depth + 1)?, self.value(depth + 1)?));
+        }
+        Ok(map_value(entries))
+    }
+
+    fn ext(&mut self, len: u64) -> Result<Value> {
+        let ty = self.r.u8()? as i8;
+        Ok(json!({ "$ext": ty, "data": hex::encode(self.r.take(len)?) }))
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(ENCODING, "values are nested too deeply"));
+        }
+
+        let b = self.r.u8()?;
+        Ok(match b {
+            0x00..=0x7f => Value::from(b),
+            0x80..=0x8f => self.map(u64::from(b & 0x0f), depth)?,
+            0x90..=0x9f => self.array(u64::from(b & 0x0f), depth)?,
+            0xa0..=0xbf => Value::String(self.r.string(u64::from(b & 0x1f))?),
+            0xc0 => Value::Null,
+            0xc1 => return Err(invalid(ENCODING, "`0xc1` is never used")),
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.r.uint(1 << (b - 0xc4))?;
+                bytes_value(self.r.take(len)?)
+            }
+            0xc7..=0xc9 => {
+                let len = self.r.uint(1 << (b - 0xc7))?;
+                self.ext(len)?
+            }
+            0xca => float_value(f64::from(f32::from_bits(self.r.uint(4)? as u32))),
+            0xcb => float_value(f64::from_bits(self.r.uint(8)?)),
+            0xcc..=0xcf => Value::from(self.r.uint(1 << (b - 0xcc))?),
+            0xd0..=0xd3 => {
+                // Sign-extend the integer.
+                let len = 1u64 << (b - 0xd0);
+                let shift = 64 - len * 8;
+                Value::from((self.r.uint(len)? << shift) as i64 >> shift)
+            }
+            0xd4..=0xd8 => self.ext(1 << (b - 0xd4))?,
+            0xd9..=0xdb => {
+                let len = self.r.uint(1 << (b - 0xd9))?;
+                Value::String(self.r.string(len)?)
+            }
+            0xdc | 0xdd => {
+                let len = self.r.uint(2 << (b - 0xdc))?;
+                self.array(len, depth)?
+            }
+            0xde | 0xdf => {
+                let len = self.r.uint(2 << (b - 0xde))?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => Value::from(b as i8),
+        })
+    }
+}
+
+/// Decodes a MessagePack payload to JSON.
+pub fn msgpack_decode(bytes: &[u8]) -> Result<Value> {
+    let mut decoder = Decoder {
+        r: Reader {
+            encoding: ENCODING,
+            bytes,
+        },
+    };
+    let value = decoder.value(0)?;
+    decoder.r.end()?;
+    Ok(value)
+}
+
+// Puts the marker of a variable length with the smallest width, `fix` is for lengths below
+// `fix_max`, and `markers` are for 8, 16 and 32 bits.
+fn put_len(buf: &mut Vec<u8>, len: usize, fix: Option<(u8, usize)>, markers: [Option<u8>; 3]) {
+    match fix {
+        Some((fix, fix_max)) if len < fix_max => buf.push(fix | len as u8),
+        _ => match markers {
+            [Some(m), _, _] if len <= u8::MAX.into() => buf.extend_from_slice(&[m, len as u8]),
+            [_, Some(m), _] if len <= u16::MAX.into() => {
+                buf.push(m);
+                buf.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            [_, _, Some(m)] => {
+                buf.push(m);
+                buf.extend_from_slice(&(len as u32).to_be_bytes());
+            }
+            _ => unreachable!(),
+        },
+    }
+}
+
+fn put_int(buf: &mut Vec<u8>, n: i64) {
+    if n >= 0 {
+        let n = n as u64;
+        match n {
+            0..=0x7f => buf.push(n as u8),
+            0x80..=0xff => buf.extend_from_slice(&[0xcc, n as u8]),
+            0x100..=0xffff => {
+                buf.push(0xcd);
+                buf.extend_from_slice(&(n as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                buf.push(0xce);
+                buf.extend_from_slice(&(n as u32).to_be_bytes());
+            }
+            _ => {
+                buf.push(0xcf);
+                buf.extend_from_slice(&n.to_be_bytes());
+            }
+        }
+    } else if n >= -32 {
+        buf.push(n as u8);
+    } else if n >= i8::MIN.into() {
+        buf.extend_from_slice(&[0xd0, n as u8]);
+    } else if n >= i16::MIN.into() {
+        buf.push(0xd1);
+        buf.extend_from_slice(&(n as i16).to_be_bytes());
+    } else if n >= i32::MIN.into() {
+        buf.push(0xd2);
+        buf.extend_from_slice(&(n as i32).to_be_bytes());
+    } else {
+        buf.push(0xd3);
+        buf.extend_from_slice(&n.to_be_bytes());
+    }
+}
+
+fn put_float(buf: &mut Vec<u8>, v: f64) {
+    if fits_f32(v) {
+        buf.push(0xca);
+        buf.extend_from_slice(&(v as f32).to_be_bytes());
+    } else {
+        buf.push(0xcb);
+        buf.extend_from_slice(&v.to_be_bytes());
+    }
+}
+
+fn check_len(len: usize) -> Result<()> {
+    if len > u32::MAX as usize {
+        return Err(invalid(
+            ENCODING,
+            format!("the length `{}` is too large", len),
+        ));
+    }
+    Ok(())
+}
+
+fn put_value(buf: &mut Vec<u8>, value: &Value, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(invalid(ENCODING, "values are nested too deeply"));
+    }
+
+    match value {
+        Value::Null => buf.push(0xc0),
+        Value::Bool(b) => buf.push(if *b { 0xc3 } else { 0xc2 }),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) if n > i64::MAX as u64 => {
+                buf.push(0xcf);
+                buf.extend_from_slice(&n.to_be_bytes());
+            }
+            (_, Some(n)) => put_int(buf, n),
+            _ => put_float(buf, n.as_f64().unwrap()),
+        },
+        Value::String(s) => {
+            check_len(s.len())?;
+            put_len(
+                buf,
+                s.len(),
+                Some((0xa0, 32)),
+                [Some(0xd9), Some(0xda), Some(0xdb)],
+            );
+            buf.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(array) => {
+            check_len(array.len())?;
+            put_len(
+                buf,
+                array.len(),
+                Some((0x90, 16)),
+                [None, Some(0xdc), Some(0xdd)],
+            );
+            for v in array {
+                put_value(buf, v, depth + 1)?;
+            }
+        }
+        Value::Object(obj) => match marker(obj).map_err(|details| invalid(ENCODING, details))? {
+            Some(Marker::Bytes(bytes)) => {
+                check_len(bytes.len())?;
+                put_len(buf, bytes.len(), None, [Some(0xc4), Some(0xc5), Some(0xc6)]);
+                buf.extend_from_slice(&bytes);
+            }
+            Some(Marker::Float(v)) => put_float(buf, v),
+            Some(Marker::Map(entries)) => {
+                check_len(entries.len())?;
+                put_len(
+                    buf,
+                    entries.len(),
+                    Some((0x80, 16)),
+                    [None, Some(0xde), Some(0xdf)],
+                );
+                for (k, v) in entries {
+                    put_value(buf, k, depth + 1)?;
+                    put_value(buf, v, depth + 1)?;
+                }
+            }
+            Some(Marker::Ext(ty, data)) => {
+                check_len(data.len())?;
+                match data.len() {
+                    1 => buf.push(0xd4),
+                    2 => buf.push(0xd5),
+                    4 => buf.push(0xd6),
+                    8 => buf.push(0xd7),
+                    16 => buf.push(0xd8),
+                    len => put_len(buf, len, None, [Some(0xc7), Some(0xc8), Some(0xc9)]),
+                }
+                buf.push(ty as u8);
+                buf.extend_from_slice(&data);
+            }
+            Some(Marker::Tag(..)) => return Err(invalid(ENCODING, "tags are of CBOR")),
+            None => {
+                check_len(obj.len())?;
+                put_len(
+                    buf,
+                    obj.len(),
+                    Some((0x80, 16)),
+                    [None, Some(0xde), Some(0xdf)],
+                );
+                for (k, v) in obj {
+                    put_value(buf, &Value::String(k.clone()), depth + 1)?;
+                    put_value(buf, v, depth + 1)?;
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Encodes JSON to a MessagePack payload, integers, floats and lengths are in the shortest forms.
+pub fn msgpack_encode(value: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    put_value(&mut buf, value, 0)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{msgpack_decode, msgpack_encode};
+
+    #[test]
+    fn msgpack_ok() {
+        let value = json!({
+            "id": 1000,
+            "temp": -12.5,
+            "offset": -200,
+            "ok": false,
+            "raw": { "$bytes": "cafe" },
+            "tags": ["a", null, -1, u64::MAX],
+            "time": { "$ext": -1, "data": "00000001" },
+            "codes": { "$map": [[1, "one"]] },
+        });
+        let bytes = msgpack_encode(&value).unwrap();
+        assert_eq!(msgpack_decode(&bytes).unwrap(), value);
+
+        assert_eq!(
+            hex::encode(msgpack_encode(&json!({ "compact": true, "schema": 0 })).unwrap()),
+            "82a7636f6d70616374c3a6736368656d6100"
+        );
+        assert_eq!(hex::encode(msgpack_encode(&json!(-200)).unwrap()), "d1ff38");
+        assert_eq!(msgpack_decode(&[0xd0, 0x9c]).unwrap(), json!(-100));
+
+        assert!(msgpack_decode(&[0x92, 0x01]).is_err());
+        assert!(msgpack_decode(&[0xc1]).is_err());
+        assert!(msgpack_encode(&json!({ "$tag": 1, "value": 0 })).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+use serde_json::{json, Map, Value};
+
+use crate::error::{Error, Result};
+
+use super::{bytes_value, float_value, invalid, map_value, marker, Marker, MAX_DEPTH};
+
+const ENCODING: &str = "RESP";
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+
+    // Whether more bytes are needed, rather than the bytes are invalid.
+    incomplete: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            incomplete: false,
+        }
+    }
+
+    fn end_of_bytes(&mut self) -> Error {
+        self.incomplete = true;
+        invalid(ENCODING, "unexpected end of the payload")
+    }
+
+    // A line without the CRLF.
+    fn line(&mut self) -> Result<&'a [u8]> {
+        let bytes = &self.bytes[self.pos..];
+        match bytes.windows(2).position(|w| w == b"\r\n") {
+            Some(end) => {
+                self.pos += end + 2;
+                Ok(&bytes[..end])
+            }
+            None => Err(self.end_of_bytes()),
+        }
+    }
+
+    fn text(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.line()?).map_err(|_| invalid(ENCODING, "the line is not UTF-8"))
+    }
+
+    // `None` if the length is -1, which is null in RESP2.
+    fn len(&mut self) -> Result<Option<usize>> {
+        match self.text()? {
+            "-1" => Ok(None),
+            s => s
+                .parse()
+                .map(Some)
+                .map_err(|_| invalid(ENCODING, format!("invalid length `{}`", s))),
+        }
+    }
+
+    // Bytes of a length followed by CRLF.
+    fn blob(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.saturating_add(len);
+        match self.bytes.get(self.pos..end.saturating_add(2)) {
+            Some(bytes) if bytes.ends_with(b"\r\n") => {
+                self.pos = end + 2;
+                Ok(&bytes[..len])
+            }
+            Some(_) => Err(invalid(ENCODING, "no CRLF after the string")),
+            None => Err(self.end_of_bytes()),
+        }
+    }
+
+    fn values(&mut self, len: usize, depth: usize) -> Result<Vec<Value>> {
+        (0..len).map(|_| self.value(depth + 1)).collect()
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid(ENCODING, "values are nested too deeply"));
+        }
+
+        let ty = match self.bytes.get(self.pos) {
+            Some(ty) => *ty,
+            None => return Err(self.end_of_bytes()),
+        };
+        self.pos += 1;
+        Ok(match ty {
+            b'+' => json!({ "$simple": self.text()? }),
+            b'-' => json!({ "$error": self.text()? }),
+            b':' => {
+                let s = self.text()?;
+                let v: i64 = s
+                    .parse()
+                    .map_err(|_| invalid(ENCODING, format!("invalid integer `{}`", s)))?;
+                json!(v)
+            }
+            b'$' | b'!' | b'=' => {
+                let blob = match self.len()? {
+                    Some(len) => self.blob(len)?,
+                    None => return Ok(Value::Null),
+                };
+                match (ty, std::str::from_utf8(blob)) {
+                    (b'!', Ok(s)) => json!({ "$error": s }),
+                    // The format of verbatim strings, such as `txt:`, is dropped.
+                    (b'=', Ok(s)) if s.get(3..4) == Some(":") => json!(s[4..]),
+                    (_, Ok(s)) => json!(s),
+                    (_, Err(_)) => bytes_value(blob),
+                }
+            }
+            b'*' | b'~' | b'>' => {
+                let values = match self.len()? {
+                    Some(len) => self.values(len, depth)?,
+                    None => return Ok(Value::Null),
+                };
+                match ty {
+                    b'~' => json!({ "$set": values }),
+                    b'>' => json!({ "$push": values }),
+                    _ => Value::Array(values),
+                }
+            }
+            b'%' | b'|' => {
+                let len = self.len()?.unwrap_or_default();
+                let values = self.values(len.saturating_mul(2), depth)?;
+                let mut values = values.into_iter();
+                let mut entries = Vec::new();
+                while let (Some(k), Some(v)) = (values.next(), values.next()) {
+                    entries.push((k, v));
+                }
+                match ty {
+                    // Attributes are auxiliary data of the following reply, which are dropped.
+                    b'|' => self.value(depth)?,
+                    _ => map_value(entries),
+                }
+            }
+            b'_' => {
+                self.line()?;
+                Value::Null
+            }
+            b'#' => match self.line()? {
+                b"t" => json!(true),
+                b"f" => json!(false),
+                b => {
+                    return Err(invalid(
+                        ENCODING,
+                        format!("invalid boolean `{}`", String::from_utf8_lossy(b)),
+                    ))
+                }
+            },
+            b',' => {
+                let s = self.text()?;
+                match s {
+                    "inf" => float_value(f64::INFINITY),
+                    "-inf" => float_value(f64::NEG_INFINITY),
+                    "nan" => float_value(f64::NAN),
+                    _ => float_value(
+                        s.parse()
+                            .map_err(|_| invalid(ENCODING, format!("invalid double `{}`", s)))?,
+                    ),
+                }
+            }
+            b'(' => json!({ "$bignum": self.text()? }),
+            _ => {
+                return Err(invalid(
+                    ENCODING,
+                    format!("unknown type `{}`", char::from(ty).escape_default()),
+                ))
+            }
+        })
+    }
+}
+
+/// Decodes a payload of a RESP2 or RESP3 value.
+///
+/// Bulk strings are JSON strings, or `$bytes` if not UTF-8, and nulls of both versions are
+/// `null`. Simple strings, errors, sets, pushes and big numbers are objects of `$simple`,
+/// `$error`, `$set`, `$push` and `$bignum`.
+pub fn resp_decode(bytes: &[u8]) -> Result<Value> {
+    let mut d = Decoder::new(bytes);
+    let value = d.value(0)?;
+    if d.pos != bytes.len() {
+        return Err(invalid(
+            ENCODING,
+            format!("`{}` bytes after the value", bytes.len() - d.pos),
+        ));
+    }
+    Ok(value)
+}
+
+/// Length of the first RESP value of the bytes, `None` if more bytes are needed.
+pub fn resp_len(bytes: &[u8]) -> Result<Option<usize>> {
+    let mut d = Decoder::new(bytes);
+    match d.value(0) {
+        Ok(_) => Ok(Some(d.pos)),
+        Err(_) if d.incomplete => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn put_line(buf: &mut Vec<u8>, ty: u8, line: &str) -> Result<()> {
+    if line.contains(['\r', '\n']) {
+        return Err(invalid(
+            ENCODING,
+            format!("`{}` can't contain CR or LF", line.escape_default()),
+        ));
+    }
+    buf.push(ty);
+    buf.extend_from_slice(line.as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    Ok(())
+}
+
+fn put_blob(buf: &mut Vec<u8>, ty: u8, blob: &[u8]) {
+    buf.push(ty);
+    buf.extend_from_slice(format!("{}\r\n", blob.len()).as_bytes());
+    buf.extend_from_slice(blob);
+    buf.extend_from_slice(b"\r\n");
+}
+
+fn put_values<'a>(
+    buf: &mut Vec<u8>,
+    ty: u8,
+    values: impl ExactSizeIterator<Item = &'a Value>,
+    depth: usize,
+) -> Result<()> {
+    buf.push(ty);
+    buf.extend_from_slice(format!("{}\r\n", values.len()).as_bytes());
+    for v in values {
+        put_value(buf, v, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn put_map(buf: &mut Vec<u8>, entries: &[(&Value, &Value)], depth: usize) -> Result<()> {
+    buf.push(b'%');
+    buf.extend_from_slice(format!("{}\r\n", entries.len()).as_bytes());
+    for (k, v) in entries {
+        put_value(buf, k, depth + 1)?;
+        put_value(buf, v, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn put_value(buf: &mut Vec<u8>, value: &Value, depth: usize) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(invalid(ENCODING, "values are nested too deeply"));
+    }
+
+    match value {
+        // Null bulk string of RESP2, which RESP3 clients also accept.
+        Value::Null => buf.extend_from_slice(b"$-1\r\n"),
+        Value::Bool(v) => put_line(buf, b'#', if *v { "t" } else { "f" })?,
+        Value::Number(n) => match n.as_i64() {
+            Some(v) => put_line(buf, b':', &v.to_string())?,
+            None => put_line(buf, b',', &n.to_string())?,
+        },
+        Value::String(s) => put_blob(buf, b'$', s.as_bytes()),
+        Value::Array(values) => put_values(buf, b'*', values.iter(), depth)?,
+        Value::Object(obj) => put_object(buf, obj, depth)?,
+    }
+    Ok(())
+}
+
+fn put_object<'a>(buf: &mut Vec<u8>, obj: &'a Map<String, Value>, depth: usize) -> Result<()> {
+    let get = |key| obj.get(key).filter(|_| obj.len() == 1);
+    let as_str = |v: &Value| {
+        v.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| invalid(ENCODING, format!("`{}` is not a string", v)))
+    };
+    let as_array = |v: &'a Value| {
+        v.as_array()
+            .ok_or_else(|| invalid(ENCODING, format!("`{}` is not an array", v)))
+    };
+
+    if let Some(v) = get("$simple") {
+        return put_line(buf, b'+', &as_str(v)?);
+    }
+    if let Some(v) = get("$error") {
+        let s = as_str(v)?;
+        if s.contains(['\r', '\n']) {
+            put_blob(buf, b'!', s.as_bytes());
+            return Ok(());
+        }
+        return put_line(buf, b'-', &s);
+    }
+    if let Some(v) = get("$bignum") {
+        return put_line(buf, b'(', &as_str(v)?);
+    }
+    if let Some(v) = get("$set") {
+        return put_values(buf, b'~', as_array(v)?.iter(), depth);
+    }
+    if let Some(v) = get("$push") {
+        return put_values(buf, b'>', as_array(v)?.iter(), depth);
+    }
+    match marker(obj).map_err(|details| invalid(ENCODING, details))? {
+        Some(Marker::Bytes(bytes)) => put_blob(buf, b'$', &bytes),
+        Some(Marker::Float(v)) => put_line(
+            buf,
+            b',',
+            match v {
+                _ if v.is_nan() => "nan",
+                _ if v > 0.0 => "inf",
+                _ => "-inf",
+            },
+        )?,
+        Some(Marker::Map(entries)) => put_map(buf, &entries, depth)?,
+        Some(Marker::Tag(..) | Marker::Ext(..)) => {
+            return Err(invalid(ENCODING, "tags and extensions aren't supported"))
+        }
+        None => {
+            // Keys are bulk strings.
+            buf.push(b'%');
+            buf.extend_from_slice(format!("{}\r\n", obj.len()).as_bytes());
+            for (k, v) in obj {
+                put_blob(buf, b'$', k.as_bytes());
+                put_value(buf, v, depth + 1)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Encodes a value to a RESP payload, in the RESP2 types unless the value needs RESP3 ones.
+///
+/// Strings are bulk strings, so that an array of strings is a command.
+pub fn resp_encode(value: &Value) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    put_value(&mut buf, value, 0)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{resp_decode, resp_encode, resp_len};
+
+    #[test]
+    fn resp_ok() {
+        let value = json!(["SET", "key", "value"]);
+        let bytes = resp_encode(&value).unwrap();
+        assert_eq!(bytes, b"*3\r\n$3\r\nSET\r\n$3\r\nkey\r\n$5\r\nvalue\r\n");
+        assert_eq!(resp_decode(&bytes).unwrap(), value);
+
+        let value = json!([
+            { "$simple": "OK" },
+            { "$error": "ERR unknown" },
+            -1,
+            null,
+            true,
+            1.5,
+            { "$float": "Infinity" },
+            { "$set": [1] },
+            { "$bignum": "12345678901234567890" },
+            { "a": { "$bytes": "ff" } },
+        ]);
+        let bytes = resp_encode(&value).unwrap();
+        assert_eq!(resp_decode(&bytes).unwrap(), value);
+
+        // Nulls of RESP2 and RESP3, verbatim strings and attributes.
+        assert_eq!(
+            resp_decode(b"*4\r\n*-1\r\n_\r\n=7\r\ntxt:abc\r\n|1\r\n+ttl\r\n:3\r\n:7\r\n").unwrap(),
+            json!([null, null, "abc", 7])
+        );
+        // A verbatim string without the format is taken as it is, even if not split at a char.
+        assert_eq!(
+            resp_decode("=5\r\naaaé\r\n".as_bytes()).unwrap(),
+            json!("aaaé")
+        );
+
+        assert_eq!(resp_len(b"$5\r\nhello\r\n+OK\r\n").unwrap(), Some(11));
+        assert_eq!(resp_len(b"*2\r\n$5\r\nhello\r\n").unwrap(), None);
+        assert_eq!(resp_len(b"$5\r\nhel").unwrap(), None);
+        assert!(resp_len(b"?\r\n").is_err());
+        assert!(resp_decode(b"+OK\r\n+OK\r\n").is_err());
+        assert!(resp_encode(&json!({ "$simple": "a\r\n" })).is_err());
+    }
+}
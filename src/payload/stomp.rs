@@ -0,0 +1,252 @@
+use serde_json::{json, Map, Value};
+
+use crate::error::{Error, Result};
+
+use super::{body_bytes, body_value, invalid};
+
+const ENCODING: &str = "STOMP";
+
+// Headers of these frames are not escaped, for compatibility with STOMP 1.0.
+fn escapes_headers(command: &str) -> bool {
+    !matches!(command, "CONNECT" | "CONNECTED")
+}
+
+fn unescape(s: &str) -> Result<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next() {
+            Some('r') => '\r',
+            Some('n') => '\n',
+            Some('c') => ':',
+            Some('\\') => '\\',
+            _ => return Err(invalid(ENCODING, format!("invalid escape in `{}`", s))),
+        });
+    }
+    Ok(unescaped)
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            ':' => escaped.push_str("\\c"),
+            '\\' => escaped.push_str("\\\\"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+
+    // Whether more bytes are needed, rather than the bytes are invalid.
+    incomplete: bool,
+}
+
+impl<'a> Decoder<'a> {
+    fn end_of_bytes(&mut self) -> Error {
+        self.incomplete = true;
+        invalid(ENCODING, "unexpected end of the frame")
+    }
+
+    // A line without the EOL, which is LF or CRLF.
+    fn line(&mut self) -> Result<&'a str> {
+        let bytes = &self.bytes[self.pos..];
+        let end = match bytes.iter().position(|b| *b == b'\n') {
+            Some(end) => end,
+            None => return Err(self.end_of_bytes()),
+        };
+        self.pos += end + 1;
+        let line = bytes[..end].strip_suffix(b"\r").unwrap_or(&bytes[..end]);
+        std::str::from_utf8(line).map_err(|_| invalid(ENCODING, "the line is not UTF-8"))
+    }
+
+    fn frame(&mut self) -> Result<Value> {
+        let command = self.line()?;
+        if command.is_empty() {
+            return Err(invalid(ENCODING, "the frame has no command"));
+        }
+
+        let mut headers = Vec::new();
+        // The first of repeated headers is taken.
+        let mut content_len = None;
+        loop {
+            let line = self.line()?;
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| invalid(ENCODING, format!("invalid header `{}`", line)))?;
+            let (name, value) = if escapes_headers(command) {
+                (unescape(name)?, unescape(value)?)
+            } else {
+                (name.to_string(), value.to_string())
+            };
+            if name == "content-length" && content_len.is_none() {
+                content_len = Some(value.parse::<usize>().map_err(|_| {
+                    invalid(ENCODING, format!("invalid content-length `{}`", value))
+                })?);
+            }
+            headers.push(json!([name, value]));
+        }
+
+        let rest = &self.bytes[self.pos..];
+        let body_len = match content_len {
+            Some(len) if rest.len() <= len => return Err(self.end_of_bytes()),
+            Some(len) if rest[len] != 0 => {
+                return Err(invalid(ENCODING, "no NUL after the body of content-length"))
+            }
+            Some(len) => len,
+            None => match rest.iter().position(|b| *b == 0) {
+                Some(len) => len,
+                None => return Err(self.end_of_bytes()),
+            },
+        };
+        self.pos += body_len + 1;
+
+        let mut frame = Map::new();
+        frame.insert("command".to_string(), json!(command));
+        frame.insert("headers".to_string(), Value::Array(headers));
+        frame.insert("body".to_string(), body_value(&rest[..body_len]));
+        Ok(Value::Object(frame))
+    }
+}
+
+/// Decodes a payload of a STOMP 1.2 frame, which ends with NUL.
+///
+/// The frame is `command`, `headers` which are pairs of names and values in order, and `body`,
+/// a string or `$bytes`. Escapes in headers are unescaped, except for `CONNECT` and
+/// `CONNECTED` frames. The body is delimited by the first `content-length` if given.
+pub fn stomp_decode(bytes: &[u8]) -> Result<Value> {
+    let mut d = Decoder {
+        bytes,
+        pos: 0,
+        incomplete: false,
+    };
+    let frame = d.frame()?;
+    if d.pos != bytes.len() {
+        return Err(invalid(
+            ENCODING,
+            format!("`{}` bytes after the frame", bytes.len() - d.pos),
+        ));
+    }
+    Ok(frame)
+}
+
+/// Length of the first STOMP frame of the bytes with its NUL, `None` if more bytes are needed.
+pub fn stomp_len(bytes: &[u8]) -> Result<Option<usize>> {
+    let mut d = Decoder {
+        bytes,
+        pos: 0,
+        incomplete: false,
+    };
+    match d.frame() {
+        Ok(_) => Ok(Some(d.pos)),
+        Err(_) if d.incomplete => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Encodes a frame decoded by [`stomp_decode`] to a STOMP 1.2 payload.
+///
+/// No header is added, so a body containing NUL needs a `content-length` header.
+pub fn stomp_encode(value: &Value) -> Result<Vec<u8>> {
+    let frame = value
+        .as_object()
+        .ok_or_else(|| invalid(ENCODING, format!("`{}` is not an object", value)))?;
+    let command = match frame.get("command") {
+        Some(Value::String(command)) if !command.is_empty() && !command.contains('\n') => command,
+        _ => {
+            return Err(invalid(
+                ENCODING,
+                "the frame should have a command of a line",
+            ))
+        }
+    };
+
+    let mut buf = format!("{}\n", command).into_bytes();
+    let headers = match frame.get("headers") {
+        Some(Value::Array(headers)) => headers.as_slice(),
+        Some(v) => return Err(invalid(ENCODING, format!("`{}` is not an array", v))),
+        None => &[],
+    };
+    for h in headers {
+        match h.as_array().map(Vec::as_slice) {
+            Some([Value::String(name), Value::String(value)]) if escapes_headers(command) => {
+                buf.extend_from_slice(format!("{}:{}\n", escape(name), escape(value)).as_bytes())
+            }
+            Some([Value::String(name), Value::String(value)]) => {
+                buf.extend_from_slice(format!("{}:{}\n", name, value).as_bytes())
+            }
+            _ => {
+                return Err(invalid(
+                    ENCODING,
+                    format!("the header `{}` is not a pair of strings", h),
+                ))
+            }
+        }
+    }
+    buf.push(b'\n');
+    if let Some(body) = frame.get("body") {
+        buf.extend(body_bytes(ENCODING, body)?);
+    }
+    buf.push(0);
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{stomp_decode, stomp_encode, stomp_len};
+
+    #[test]
+    fn stomp_ok() {
+        let value = json!({
+            "command": "SEND",
+            "headers": [["destination", "/queue/a"], ["note", "a:b\\c"]],
+            "body": "hello",
+        });
+        let bytes = stomp_encode(&value).unwrap();
+        assert_eq!(
+            bytes,
+            b"SEND\ndestination:/queue/a\nnote:a\\cb\\\\c\n\nhello\0"
+        );
+        assert_eq!(stomp_decode(&bytes).unwrap(), value);
+
+        // Headers of `CONNECT` are not escaped, and CRLFs are accepted.
+        let bytes = b"CONNECT\r\naccept-version:1.2\r\nhost:a:b\r\n\r\n\0";
+        assert_eq!(
+            stomp_decode(bytes).unwrap(),
+            json!({
+                "command": "CONNECT",
+                "headers": [["accept-version", "1.2"], ["host", "a:b"]],
+                "body": "",
+            })
+        );
+
+        // The body of content-length may contain NUL.
+        let bytes = b"MESSAGE\ncontent-length:3\n\n\xff\0a\0ERROR\n";
+        assert_eq!(stomp_len(bytes).unwrap(), Some(30));
+        assert_eq!(
+            stomp_decode(&bytes[..30]).unwrap()["body"],
+            json!({ "$bytes": "ff0061" })
+        );
+        assert_eq!(stomp_len(&bytes[30..]).unwrap(), None);
+        assert_eq!(stomp_len(&bytes[..20]).unwrap(), None);
+        assert!(stomp_len(b"MESSAGE\ncontent-length:1\n\nab\0").is_err());
+        assert!(stomp_len(b"\n\n\0").is_err());
+        assert!(stomp_decode(b"SEND\nbad\\x:1\n\n\0").is_err());
+        assert!(stomp_encode(&json!({ "body": "" })).is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use serde_json::{json, Value};
+
+use crate::error::Result;
+
+use super::{hex_value, invalid, Reader};
+
+const ENCODING: &str = "TLV";
+
+/// Decodes a payload of flat TLVs, whose tags and lengths are big-endian of the widths in bytes,
+/// to an array of objects with `tag` and `value` in HEX.
+pub fn tlv_decode(bytes: &[u8], tag_width: usize, len_width: usize) -> Result<Value> {
+    let mut r = Reader {
+        encoding: ENCODING,
+        bytes,
+    };
+    let mut tlvs = Vec::new();
+    while !r.bytes.is_empty() {
+        let tag = r.uint(tag_width as u64)?;
+        let len = r.uint(len_width as u64)?;
+        tlvs.push(json!({ "tag": tag, "value": hex::encode(r.take(len)?) }));
+    }
+    Ok(Value::Array(tlvs))
+}
+
+fn put_uint(buf: &mut Vec<u8>, v: u64, width: usize, name: &str) -> Result<()> {
+    if width < 8 && v >> (width * 8) != 0 {
+        return Err(invalid(
+            ENCODING,
+            format!("the {} `{}` is wider than `{}` bytes", name, v, width),
+        ));
+    }
+    buf.extend_from_slice(&v.to_be_bytes()[8 - width..]);
+    Ok(())
+}
+
+/// Encodes an array of objects with `tag` and `value` in HEX to a payload of flat TLVs.
+pub fn tlv_encode(value: &Value, tag_width: usize, len_width: usize) -> Result<Vec<u8>> {
+    let tlvs = value
+        .as_array()
+        .ok_or_else(|| invalid(ENCODING, format!("`{}` is not an array", value)))?;
+
+    let mut buf = Vec::new();
+    for tlv in tlvs {
+        let (tag, v) = match (tlv.get("tag").and_then(Value::as_u64), tlv.get("value")) {
+            (Some(tag), Some(v)) => (tag, hex_value(v).map_err(|e| invalid(ENCODING, e))?),
+            _ => return Err(invalid(ENCODING, format!("`{}` has no tag or value", tlv))),
+        };
+        put_uint(&mut buf, tag, tag_width, "tag")?;
+        put_uint(&mut buf, v.len() as u64, len_width, "length")?;
+        buf.extend_from_slice(&v);
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{tlv_decode, tlv_encode};
+
+    #[test]
+    fn tlv_ok() {
+        let value = json!([{ "tag": 1, "value": "cafe" }, { "tag": 0x0203, "value": "" }]);
+        let bytes = tlv_encode(&value, 2, 1).unwrap();
+        assert_eq!(hex::encode(&bytes), "000102cafe020300");
+        assert_eq!(tlv_decode(&bytes, 2, 1).unwrap(), value);
+
+        assert!(tlv_encode(&value, 1, 1).is_err());
+        assert!(tlv_decode(&bytes, 2, 2).is_err());
+        assert!(tlv_encode(&json!([{ "tag": 1 }]), 1, 1).is_err());
+    }
+}
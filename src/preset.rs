@@ -0,0 +1,140 @@
+//! Message formats of well-known protocols.
+
+use crate::{framing::Framing, msg::MessageFormat};
+
+/// A predefined message format, described by the format DSL.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    /// Name of the protocol.
+    pub name: &'static str,
+    /// What the format covers.
+    pub description: &'static str,
+    /// The format in the format DSL.
+    pub dsl: &'static str,
+    /// Framing of streams, for messages whose ends aren't known from the format.
+    pub framing: Option<Framing>,
+}
+
+impl Preset {
+    /// Parses the format DSL.
+    pub fn format(&self) -> MessageFormat {
+        // Presets are checked by tests.
+        self.dsl.parse().unwrap()
+    }
+}
+
+/// All the presets.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "Modbus/TCP",
+        description: "Modbus/TCP ADU, the MBAP header and the unit id followed by the PDU",
+        dsl: "tid:u16; pid:u16; len:u16; unit_pdu:bytes[len]",
+        framing: None,
+    },
+    Preset {
+        name: "MQTT short packet",
+        description: "MQTT fixed header and the rest, only for remaining lengths up to 127 bytes \
+                      whose varint is a single byte, use the MQTT mode of clients for others",
+        dsl: "header:u8; len:u8; rest:bytes[len]",
+        framing: None,
+    },
+    Preset {
+        name: "DNS header",
+        description: "DNS header, questions and records follow it",
+        dsl: "id:u16; flags:u16; qdcount:u16; ancount:u16; nscount:u16; arcount:u16",
+        framing: None,
+    },
+    Preset {
+        name: "NTP",
+        description: "NTP packet without extensions, timestamps are in the NTP era",
+        dsl: "li_vn_mode:u8; stratum:u8; poll:i8; precision:i8; root_delay:u32; \
+              root_dispersion:u32; ref_id:u32; ref_ts:u64; orig_ts:u64; rx_ts:u64; tx_ts:u64",
+        framing: None,
+    },
+    Preset {
+        name: "RESP",
+        description: "A value of the Redis protocol RESP2 or RESP3, such as a command or a reply",
+        dsl: "value:resp[*]",
+        framing: Some(Framing::Resp { max_len: 1 << 20 }),
+    },
+    Preset {
+        name: "STOMP",
+        description: "A STOMP 1.2 frame, the command, headers and the body ending with NUL",
+        dsl: "frame:stomp[*]",
+        framing: Some(Framing::Stomp { max_len: 1 << 20 }),
+    },
+];
+
+/// Finds a preset by the name, ignoring cases.
+pub fn preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        msg::{ItemFormat, ItemValue, MessageDecoder, MessageFormat},
+        stream::MessageReader,
+    };
+
+    use super::{preset, PRESETS};
+
+    #[test]
+    fn presets_ok() {
+        for p in PRESETS {
+            let fmt: MessageFormat = p
+                .dsl
+                .parse()
+                .unwrap_or_else(|e| panic!("{}: {}", p.name, e));
+            // Otherwise the end of a message is unknown.
+            assert!(p.framing.is_some() || !fmt.is_greedy(), "{}", p.name);
+        }
+
+        let fmt = preset("modbus/tcp").unwrap().format();
+        assert_eq!(fmt[2], ItemFormat::Len { len: 2 });
+        // Read holding registers.
+        let bytes = hex::decode("000100000006010300000002").unwrap();
+        let msg = MessageDecoder::new(&fmt, &mut bytes.as_slice())
+            .decode(Default::default())
+            .unwrap();
+        assert_eq!(msg.values()[2], ItemValue::Len(6));
+        assert_eq!(
+            msg.values()[3],
+            ItemValue::Bytes(hex::decode("010300000002").unwrap())
+        );
+
+        let ntp = preset("NTP").unwrap().format();
+        let len = |f: &ItemFormat| match f {
+            ItemFormat::Uint { len } | ItemFormat::Int { len } => *len,
+            _ => unreachable!(),
+        };
+        assert_eq!(ntp.iter().map(len).sum::<usize>(), 48);
+
+        // Frames follow a heart-beat.
+        let stomp = preset("stomp").unwrap();
+        let bytes = b"\nMESSAGE\ndestination:/queue/a\n\nhi\0ERROR\nmessage:bad\n\n\0";
+        let mut reader = MessageReader::new(stomp.format(), &bytes[..])
+            .framer(stomp.framing.as_ref().map(|f| f.framer().unwrap()));
+        let msg = reader.read(&Default::default()).unwrap();
+        assert_eq!(
+            msg.values()[0],
+            ItemValue::Json(json!({
+                "command": "MESSAGE",
+                "headers": [["destination", "/queue/a"]],
+                "body": "hi",
+            }))
+        );
+        let msg = reader.read(&Default::default()).unwrap();
+        assert_eq!(
+            msg.values()[0],
+            ItemValue::Json(json!({
+                "command": "ERROR",
+                "headers": [["message", "bad"]],
+                "body": "",
+            }))
+        );
+        assert!(preset("unknown").is_none());
+    }
+}
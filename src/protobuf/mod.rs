@@ -0,0 +1,106 @@
+mod parser;
+mod wire;
+
+pub(crate) use wire::{get_varint, put_varint};
+
+use std::collections::HashMap;
+
+/// Type of a protobuf field, messages and enums are referenced by their full names.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProtoType {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+    Enum(String),
+    Message(String),
+}
+
+impl ProtoType {
+    fn scalar(name: &str) -> Option<Self> {
+        Some(match name {
+            "double" => Self::Double,
+            "float" => Self::Float,
+            "int32" => Self::Int32,
+            "int64" => Self::Int64,
+            "uint32" => Self::Uint32,
+            "uint64" => Self::Uint64,
+            "sint32" => Self::Sint32,
+            "sint64" => Self::Sint64,
+            "fixed32" => Self::Fixed32,
+            "fixed64" => Self::Fixed64,
+            "sfixed32" => Self::Sfixed32,
+            "sfixed64" => Self::Sfixed64,
+            "bool" => Self::Bool,
+            "string" => Self::String,
+            "bytes" => Self::Bytes,
+            _ => return None,
+        })
+    }
+
+    // Only repeated fields of numeric types can be packed.
+    fn is_packable(&self) -> bool {
+        !matches!(self, Self::String | Self::Bytes | Self::Message(_))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKind {
+    Single(ProtoType),
+    Repeated { ty: ProtoType, packed: bool },
+    Map { key: ProtoType, value: ProtoType },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoField {
+    pub name: String,
+    pub number: u32,
+    pub kind: FieldKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoMessage {
+    pub fields: Vec<ProtoField>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoEnum {
+    pub values: Vec<(String, i32)>,
+}
+
+/// Messages and enums of a `.proto` file, with the message of an item.
+///
+/// Messages are represented as JSON objects keyed by the names of fields, bytes are in HEX
+/// and enums are the names of values. Unknown fields are skipped when decoding.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtoSchema {
+    message: String,
+    messages: HashMap<String, ProtoMessage>,
+    enums: HashMap<String, ProtoEnum>,
+}
+
+impl ProtoSchema {
+    /// Full name of the message of the item.
+    pub fn message_name(&self) -> &str {
+        &self.message
+    }
+
+    pub(crate) fn message(&self, name: &str) -> Option<&ProtoMessage> {
+        self.messages.get(name)
+    }
+
+    pub(crate) fn enumeration(&self, name: &str) -> Option<&ProtoEnum> {
+        self.enums.get(name)
+    }
+}
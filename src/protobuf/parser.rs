@@ -0,0 +1,551 @@
+use std::{collections::HashMap, convert::TryFrom};
+
+use crate::error::{Error, Result};
+
+use super::{FieldKind, ProtoEnum, ProtoField, ProtoMessage, ProtoSchema, ProtoType};
+
+// Parses the subset of the `.proto` language describing the layout of messages:
+//  * `syntax`, `package`, `message`, `enum`, `oneof` and `map<K, V>`.
+//  * Labels `optional`, `required` and `repeated`, with the `packed` option.
+//  * `import`, `option`, `reserved`, `extensions`, `service` and `extend` are skipped,
+//    so types of imported files can't be referenced.
+//
+// Repeated scalars are packed by default in proto3, as `protoc` does.
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Punct(char),
+    End,
+}
+
+fn import_error(line: usize, details: impl Into<String>) -> Error {
+    Error::ProtoImport {
+        line,
+        details: details.into(),
+    }
+}
+
+fn tokenize(proto: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = proto.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                while matches!(chars.peek(), Some(c) if *c != '\n') {
+                    chars.next();
+                }
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if prev == '*' => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            prev = c;
+                        }
+                        None => return Err(import_error(line, "unterminated comment")),
+                    }
+                }
+                continue;
+            }
+            '"' | '\'' => {
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') => s.extend(chars.next()),
+                        Some('\n') | None => return Err(import_error(line, "unterminated string")),
+                        Some(c) => s.push(c),
+                    }
+                }
+                Token::Str(s)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' || c == '.' => {
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_alphanumeric() && c != '_' && c != '.' {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                Token::Ident(s)
+            }
+            c if c.is_ascii_digit() => {
+                // Floats are only used by options, which are skipped.
+                let mut s = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    let exponent = matches!(c, '+' | '-') && s.ends_with(['e', 'E']);
+                    if !c.is_ascii_alphanumeric() && c != '.' && !exponent {
+                        break;
+                    }
+                    s.push(c);
+                    chars.next();
+                }
+                Token::Number(s)
+            }
+            c => Token::Punct(c),
+        };
+        tokens.push((token, line));
+    }
+    tokens.push((Token::End, line));
+    Ok(tokens)
+}
+
+fn parse_integer(s: &str) -> Option<i64> {
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if s.len() > 1 && s.starts_with('0') {
+        i64::from_str_radix(&s[1..], 8).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Label {
+    Single,
+    Repeated,
+}
+
+// A field whose type is not resolved yet.
+#[derive(Debug, Clone)]
+struct RawField {
+    name: String,
+    number: u32,
+    label: Label,
+    ty: String,
+    map_key: Option<String>,
+    packed: Option<bool>,
+    line: usize,
+}
+
+#[derive(Default)]
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    proto3: bool,
+    package: String,
+    messages: HashMap<String, Vec<RawField>>,
+    enums: HashMap<String, ProtoEnum>,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, details: impl Into<String>) -> Error {
+        import_error(self.line(), details)
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.next() {
+            Token::Punct(p) if p == c => Ok(()),
+            _ => Err(import_error(
+                self.tokens[self.pos.saturating_sub(1)].1,
+                format!("expected `{}`", c),
+            )),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next() {
+            Token::Ident(s) => Ok(s),
+            _ => Err(self.error("expected an identifier")),
+        }
+    }
+
+    fn integer(&mut self) -> Result<i64> {
+        let negative = self.peek() == &Token::Punct('-');
+        if negative {
+            self.next();
+        }
+        match self.next() {
+            Token::Number(s) => parse_integer(&s)
+                .map(|v| if negative { -v } else { v })
+                .ok_or_else(|| self.error(format!("invalid integer `{}`", s))),
+            _ => Err(self.error("expected an integer")),
+        }
+    }
+
+    // Skip a statement, including aggregate values in braces.
+    fn skip_statement(&mut self) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Token::Punct('{') => depth += 1,
+                Token::Punct('}') => depth -= 1,
+                Token::Punct(';') if depth == 0 => return Ok(()),
+                Token::End => return Err(self.error("expected `;`")),
+                _ => {}
+            }
+        }
+    }
+
+    // Skip a definition with a body in braces, such as a service.
+    fn skip_block(&mut self) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Token::Punct('{') => depth += 1,
+                Token::Punct('}') if depth == 1 => return Ok(()),
+                Token::Punct('}') => depth -= 1,
+                Token::End => return Err(self.error("expected `}`")),
+                _ => {}
+            }
+        }
+    }
+
+    fn parse(&mut self) -> Result<()> {
+        loop {
+            match self.next() {
+                Token::End => return Ok(()),
+                Token::Punct(';') => {}
+                Token::Ident(word) => match word.as_str() {
+                    "syntax" => {
+                        self.expect('=')?;
+                        match self.next() {
+                            Token::Str(s) if s == "proto2" || s == "proto3" => {
+                                self.proto3 = s == "proto3";
+                            }
+                            _ => return Err(self.error("expected `proto2` or `proto3`")),
+                        }
+                        self.expect(';')?;
+                    }
+                    "package" => {
+                        self.package = self.ident()?;
+                        self.expect(';')?;
+                    }
+                    "import" | "option" => self.skip_statement()?,
+                    "service" | "extend" => self.skip_block()?,
+                    "message" => {
+                        let scope = self.package.clone();
+                        self.parse_message(&scope)?;
+                    }
+                    "enum" => {
+                        let scope = self.package.clone();
+                        self.parse_enum(&scope)?;
+                    }
+                    _ => return Err(self.error(format!("unexpected `{}`", word))),
+                },
+                _ => return Err(self.error("expected a definition")),
+            }
+        }
+    }
+
+    fn parse_message(&mut self, scope: &str) -> Result<()> {
+        let line = self.line();
+        let name = full_name(scope, &self.ident()?);
+        self.expect('{')?;
+
+        let mut fields = Vec::new();
+        loop {
+            let word = match self.next() {
+                Token::Punct('}') => break,
+                Token::Punct(';') => continue,
+                Token::Ident(word) => word,
+                _ => return Err(self.error("expected a field")),
+            };
+            match word.as_str() {
+                "message" => self.parse_message(&name)?,
+                "enum" => self.parse_enum(&name)?,
+                "option" | "reserved" | "extensions" => self.skip_statement()?,
+                "extend" => self.skip_block()?,
+                "oneof" => {
+                    // Fields of a oneof are fields of the message.
+                    self.ident()?;
+                    self.expect('{')?;
+                    loop {
+                        match self.next() {
+                            Token::Punct('}') => break,
+                            Token::Punct(';') => {}
+                            Token::Ident(word) if word == "option" => self.skip_statement()?,
+                            Token::Ident(ty) => fields.push(self.parse_field(Label::Single, ty)?),
+                            _ => return Err(self.error("expected a field")),
+                        }
+                    }
+                }
+                "map" if self.peek() == &Token::Punct('<') => {
+                    self.next();
+                    let key = self.ident()?;
+                    self.expect(',')?;
+                    let value = self.ident()?;
+                    self.expect('>')?;
+                    let mut field = self.parse_field(Label::Repeated, value)?;
+                    field.map_key = Some(key);
+                    fields.push(field);
+                }
+                "optional" | "required" => {
+                    let ty = self.ident()?;
+                    fields.push(self.parse_field(Label::Single, ty)?);
+                }
+                "repeated" => {
+                    let ty = self.ident()?;
+                    fields.push(self.parse_field(Label::Repeated, ty)?);
+                }
+                // Fields without labels in proto3.
+                _ => fields.push(self.parse_field(Label::Single, word)?),
+            }
+        }
+
+        if self.messages.insert(name.clone(), fields).is_some() {
+            return Err(import_error(
+                line,
+                format!("the message `{}` is defined more than once", name),
+            ));
+        }
+        Ok(())
+    }
+
+    fn parse_field(&mut self, label: Label, ty: String) -> Result<RawField> {
+        let line = self.line();
+        if ty == "group" {
+            return Err(self.error("groups are not supported"));
+        }
+        let name = self.ident()?;
+        self.expect('=')?;
+        let number = self.integer()?;
+        if !(1..=(1 << 29) - 1).contains(&number) {
+            return Err(self.error(format!("invalid field number `{}`", number)));
+        }
+
+        // Options, only `packed` matters.
+        let mut packed = None;
+        if self.peek() == &Token::Punct('[') {
+            self.next();
+            loop {
+                let mut option = String::new();
+                loop {
+                    match self.next() {
+                        Token::Punct('=') => break,
+                        Token::Ident(s) => option.push_str(&s),
+                        Token::Punct(c @ ('(' | ')')) => option.push(c),
+                        _ => return Err(self.error("expected an option")),
+                    }
+                }
+                let mut value = Vec::new();
+                let mut depth = 0;
+                let last = loop {
+                    match self.next() {
+                        Token::Punct(c @ (',' | ']')) if depth == 0 => break c,
+                        Token::Punct('{') => depth += 1,
+                        Token::Punct('}') => depth -= 1,
+                        Token::End => return Err(self.error("expected `]`")),
+                        token => value.push(token),
+                    }
+                };
+                if option == "packed" {
+                    packed = match value.as_slice() {
+                        [Token::Ident(s)] if s == "true" || s == "false" => Some(s == "true"),
+                        _ => return Err(self.error("expected `true` or `false`")),
+                    };
+                }
+                if last == ']' {
+                    break;
+                }
+            }
+        }
+        self.expect(';')?;
+
+        Ok(RawField {
+            name,
+            number: number as u32,
+            label,
+            ty,
+            map_key: None,
+            packed,
+            line,
+        })
+    }
+
+    fn parse_enum(&mut self, scope: &str) -> Result<()> {
+        let line = self.line();
+        let name = full_name(scope, &self.ident()?);
+        self.expect('{')?;
+
+        let mut values = Vec::new();
+        loop {
+            match self.next() {
+                Token::Punct('}') => break,
+                Token::Punct(';') => {}
+                Token::Ident(word) if word == "option" || word == "reserved" => {
+                    self.skip_statement()?
+                }
+                Token::Ident(value) => {
+                    self.expect('=')?;
+                    let number = self.integer()?;
+                    let number = i32::try_from(number)
+                        .map_err(|_| self.error(format!("invalid enum value `{}`", number)))?;
+                    // Options of values are skipped.
+                    if self.peek() == &Token::Punct('[') {
+                        self.skip_statement()?;
+                    } else {
+                        self.expect(';')?;
+                    }
+                    values.push((value, number));
+                }
+                _ => return Err(self.error("expected an enum value")),
+            }
+        }
+
+        if self
+            .enums
+            .insert(name.clone(), ProtoEnum { values })
+            .is_some()
+        {
+            return Err(import_error(
+                line,
+                format!("the enum `{}` is defined more than once", name),
+            ));
+        }
+        Ok(())
+    }
+
+    // Resolves a type referenced in the message `scope`, from the innermost scope.
+    fn resolve(&self, scope: &str, ty: &str, line: usize) -> Result<ProtoType> {
+        if let Some(ty) = ProtoType::scalar(ty) {
+            return Ok(ty);
+        }
+
+        let candidates = match ty.strip_prefix('.') {
+            Some(ty) => vec![ty.to_string()],
+            None => {
+                let mut scope = scope;
+                let mut candidates = vec![full_name(scope, ty)];
+                while let Some(pos) = scope.rfind('.') {
+                    scope = &scope[..pos];
+                    candidates.push(full_name(scope, ty));
+                }
+                candidates.push(ty.to_string());
+                candidates
+            }
+        };
+        candidates
+            .into_iter()
+            .find_map(|name| {
+                if self.messages.contains_key(&name) {
+                    Some(ProtoType::Message(name))
+                } else if self.enums.contains_key(&name) {
+                    Some(ProtoType::Enum(name))
+                } else {
+                    None
+                }
+            })
+            .ok_or_else(|| import_error(line, format!("unknown type `{}`", ty)))
+    }
+
+    fn resolve_field(&self, scope: &str, field: &RawField) -> Result<ProtoField> {
+        let ty = self.resolve(scope, &field.ty, field.line)?;
+        let kind = match (&field.map_key, field.label) {
+            (Some(key), _) => {
+                let key = match ProtoType::scalar(key) {
+                    Some(ProtoType::Double | ProtoType::Float | ProtoType::Bytes) | None => {
+                        return Err(import_error(
+                            field.line,
+                            format!("invalid type of map key `{}`", key),
+                        ))
+                    }
+                    Some(key) => key,
+                };
+                FieldKind::Map { key, value: ty }
+            }
+            (None, Label::Repeated) => FieldKind::Repeated {
+                packed: ty.is_packable() && field.packed.unwrap_or(self.proto3),
+                ty,
+            },
+            (None, Label::Single) => FieldKind::Single(ty),
+        };
+        Ok(ProtoField {
+            name: field.name.clone(),
+            number: field.number,
+            kind,
+        })
+    }
+}
+
+fn full_name(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+impl ProtoSchema {
+    /// Parses a `.proto` file, the message of the item is `name`, which is either the full name
+    /// or the name without the package and outer messages.
+    pub fn from_proto(proto: &str, name: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(proto)?,
+            ..Default::default()
+        };
+        parser.parse()?;
+
+        let mut messages = HashMap::with_capacity(parser.messages.len());
+        for (msg_name, raw_fields) in &parser.messages {
+            let mut fields: Vec<ProtoField> = Vec::with_capacity(raw_fields.len());
+            for raw_field in raw_fields {
+                if fields.iter().any(|f| f.number == raw_field.number) {
+                    return Err(import_error(
+                        raw_field.line,
+                        format!(
+                            "the field number `{}` is used more than once",
+                            raw_field.number
+                        ),
+                    ));
+                }
+                fields.push(parser.resolve_field(msg_name, raw_field)?);
+            }
+            messages.insert(msg_name.clone(), ProtoMessage { fields });
+        }
+
+        // The short name should be unique.
+        let suffix = format!(".{}", name);
+        let candidates = messages
+            .keys()
+            .filter(|msg_name| msg_name.ends_with(&suffix))
+            .collect::<Vec<_>>();
+        let message = match candidates.as_slice() {
+            _ if messages.contains_key(name) => name.to_string(),
+            [msg_name] => msg_name.to_string(),
+            _ => {
+                return Err(Error::NoSuchProtoMessage {
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        Ok(Self {
+            message,
+            messages,
+            enums: parser.enums,
+        })
+    }
+}
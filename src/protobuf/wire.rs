@@ -0,0 +1,541 @@
+use serde_json::{Map, Number, Value};
+
+use crate::error::{Error, Result};
+
+use super::{FieldKind, ProtoSchema, ProtoType};
+
+const VARINT: u8 = 0;
+const I64: u8 = 1;
+const LEN: u8 = 2;
+const I32: u8 = 5;
+
+// Nested messages deeper than this are rejected, so that the stack is not exhausted.
+const MAX_DEPTH: usize = 64;
+
+fn invalid(details: impl Into<String>) -> Error {
+    Error::InvalidProtobuf {
+        details: details.into(),
+    }
+}
+
+pub(crate) fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// Gets a varint at the front of the bytes, with the count of bytes of it.
+/// `None` if more bytes are needed.
+pub(crate) fn get_varint(bytes: &[u8]) -> Result<Option<(u64, usize)>> {
+    let mut v = 0u64;
+    for (idx, b) in bytes.iter().enumerate() {
+        if idx == 10 || (idx == 9 && *b > 1) {
+            return Err(invalid("the varint is too long"));
+        }
+        v |= u64::from(b & 0x7f) << (7 * idx);
+        if b & 0x80 == 0 {
+            return Ok(Some((v, idx + 1)));
+        }
+    }
+    Ok(None)
+}
+
+fn wire_type_of(ty: &ProtoType) -> u8 {
+    match ty {
+        ProtoType::Double | ProtoType::Fixed64 | ProtoType::Sfixed64 => I64,
+        ProtoType::Float | ProtoType::Fixed32 | ProtoType::Sfixed32 => I32,
+        ProtoType::String | ProtoType::Bytes | ProtoType::Message(_) => LEN,
+        _ => VARINT,
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("unexpected end of the message"));
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let (v, len) =
+            get_varint(self.bytes)?.ok_or_else(|| invalid("unexpected end of the message"))?;
+        self.bytes = &self.bytes[len..];
+        Ok(v)
+    }
+
+    fn fixed32(&mut self) -> Result<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn fixed64(&mut self) -> Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn len_delimited(&mut self) -> Result<&'a [u8]> {
+        let len = self.varint()?;
+        self.take(len as usize)
+    }
+
+    fn skip(&mut self, wire_type: u8) -> Result<()> {
+        match wire_type {
+            VARINT => self.varint().map(drop),
+            I64 => self.take(8).map(drop),
+            LEN => self.len_delimited().map(drop),
+            I32 => self.take(4).map(drop),
+            _ => Err(invalid(format!("unsupported wire type `{}`", wire_type))),
+        }
+    }
+}
+
+// Floats which are not finite are strings, as the JSON mapping of protobuf.
+fn float_value(v: f64) -> Value {
+    match Number::from_f64(v) {
+        Some(n) => Value::Number(n),
+        None if v.is_nan() => Value::String("NaN".to_string()),
+        None if v > 0.0 => Value::String("Infinity".to_string()),
+        None => Value::String("-Infinity".to_string()),
+    }
+}
+
+fn default_value(ty: &ProtoType) -> Value {
+    match ty {
+        ProtoType::Bool => Value::Bool(false),
+        ProtoType::String | ProtoType::Bytes => Value::String(String::new()),
+        ProtoType::Message(_) => Value::Object(Map::new()),
+        _ => Value::from(0),
+    }
+}
+
+// Integers may be strings, as 64-bit integers of the JSON mapping of protobuf.
+fn json_int(v: &Value, min: i64, max: i64) -> Result<i64> {
+    let n = match v {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    n.filter(|n| (min..=max).contains(n))
+        .ok_or_else(|| invalid(format!("`{}` is not an integer in `{}..={}`", v, min, max)))
+}
+
+fn json_uint(v: &Value, max: u64) -> Result<u64> {
+    let n = match v {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    n.filter(|n| *n <= max)
+        .ok_or_else(|| invalid(format!("`{}` is not an integer in `0..={}`", v, max)))
+}
+
+fn json_float(v: &Value) -> Result<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) if s == "NaN" => Some(f64::NAN),
+        Value::String(s) if s == "Infinity" => Some(f64::INFINITY),
+        Value::String(s) if s == "-Infinity" => Some(f64::NEG_INFINITY),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(format!("`{}` is not a number", v)))
+}
+
+fn put_tag(buf: &mut Vec<u8>, number: u32, wire_type: u8) {
+    put_varint(buf, u64::from(number) << 3 | u64::from(wire_type));
+}
+
+fn put_len_delimited(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+impl ProtoSchema {
+    /// Decodes bytes of the message to a JSON object.
+    pub fn decode(&self, bytes: &[u8]) -> Result<Value> {
+        self.decode_message(&self.message, bytes, 0)
+    }
+
+    /// Encodes a JSON object to bytes of the message, fields are in the order of definition.
+    pub fn encode(&self, value: &Value) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.encode_message(&self.message, value, &mut buf, 0)?;
+        Ok(buf)
+    }
+
+    fn decode_message(&self, name: &str, bytes: &[u8], depth: usize) -> Result<Value> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("messages are nested too deeply"));
+        }
+        let msg = self
+            .message(name)
+            .ok_or_else(|| invalid(format!("unknown message `{}`", name)))?;
+
+        let mut obj = Map::new();
+        let mut r = Reader { bytes };
+        while !r.is_empty() {
+            let tag = r.varint()?;
+            let (number, wire_type) = ((tag >> 3) as u32, (tag & 7) as u8);
+            let field = match msg.fields.iter().find(|f| f.number == number) {
+                Some(field) => field,
+                None => {
+                    r.skip(wire_type)?;
+                    continue;
+                }
+            };
+
+            match &field.kind {
+                FieldKind::Single(ty) => {
+                    let v = self.decode_value(ty, wire_type, &mut r, depth)?;
+                    obj.insert(field.name.clone(), v);
+                }
+                FieldKind::Repeated { ty, .. } => {
+                    // Packed or not, both should be accepted.
+                    let mut values = Vec::new();
+                    if wire_type == LEN && ty.is_packable() {
+                        let mut packed = Reader {
+                            bytes: r.len_delimited()?,
+                        };
+                        while !packed.is_empty() {
+                            values.push(self.decode_value(
+                                ty,
+                                wire_type_of(ty),
+                                &mut packed,
+                                depth,
+                            )?);
+                        }
+                    } else {
+                        values.push(self.decode_value(ty, wire_type, &mut r, depth)?);
+                    }
+                    match obj
+                        .entry(field.name.clone())
+                        .or_insert_with(|| Value::Array(Vec::new()))
+                    {
+                        Value::Array(array) => array.extend(values),
+                        _ => unreachable!(),
+                    }
+                }
+                FieldKind::Map { key, value } => {
+                    if wire_type != LEN {
+                        return Err(invalid(format!(
+                            "wire type `{}` mismatches the field `{}`",
+                            wire_type, field.name
+                        )));
+                    }
+                    let mut entry = Reader {
+                        bytes: r.len_delimited()?,
+                    };
+                    let (mut k, mut v) = (default_value(key), default_value(value));
+                    while !entry.is_empty() {
+                        let tag = entry.varint()?;
+                        let wire_type = (tag & 7) as u8;
+                        match tag >> 3 {
+                            1 => k = self.decode_value(key, wire_type, &mut entry, depth)?,
+                            2 => v = self.decode_value(value, wire_type, &mut entry, depth)?,
+                            _ => entry.skip(wire_type)?,
+                        }
+                    }
+                    let k = match k {
+                        Value::String(s) => s,
+                        k => k.to_string(),
+                    };
+                    match obj
+                        .entry(field.name.clone())
+                        .or_insert_with(|| Value::Object(Map::new()))
+                    {
+                        Value::Object(map) => map.insert(k, v),
+                        _ => unreachable!(),
+                    };
+                }
+            }
+        }
+        Ok(Value::Object(obj))
+    }
+
+    fn decode_value(
+        &self,
+        ty: &ProtoType,
+        wire_type: u8,
+        r: &mut Reader<'_>,
+        depth: usize,
+    ) -> Result<Value> {
+        if wire_type != wire_type_of(ty) {
+            return Err(invalid(format!(
+                "wire type `{}` mismatches the type `{:?}`",
+                wire_type, ty
+            )));
+        }
+
+        Ok(match ty {
+            ProtoType::Int32 => Value::from(r.varint()? as i32),
+            ProtoType::Int64 => Value::from(r.varint()? as i64),
+            ProtoType::Uint32 => Value::from(r.varint()? as u32),
+            ProtoType::Uint64 => Value::from(r.varint()?),
+            ProtoType::Sint32 | ProtoType::Sint64 => {
+                let v = r.varint()?;
+                let v = (v >> 1) as i64 ^ -((v & 1) as i64);
+                match ty {
+                    ProtoType::Sint32 => Value::from(v as i32),
+                    _ => Value::from(v),
+                }
+            }
+            ProtoType::Bool => Value::Bool(r.varint()? != 0),
+            ProtoType::Enum(name) => {
+                let v = r.varint()? as i32;
+                // Unknown values are kept as numbers.
+                match self
+                    .enumeration(name)
+                    .and_then(|e| e.values.iter().find(|(_, n)| *n == v))
+                {
+                    Some((name, _)) => Value::String(name.clone()),
+                    None => Value::from(v),
+                }
+            }
+            ProtoType::Fixed32 => Value::from(r.fixed32()?),
+            ProtoType::Sfixed32 => Value::from(r.fixed32()? as i32),
+            ProtoType::Float => float_value(f64::from(f32::from_bits(r.fixed32()?))),
+            ProtoType::Fixed64 => Value::from(r.fixed64()?),
+            ProtoType::Sfixed64 => Value::from(r.fixed64()? as i64),
+            ProtoType::Double => float_value(f64::from_bits(r.fixed64()?)),
+            ProtoType::String => match std::str::from_utf8(r.len_delimited()?) {
+                Ok(s) => Value::String(s.to_string()),
+                Err(_) => return Err(invalid("the string is not valid UTF-8")),
+            },
+            ProtoType::Bytes => Value::String(hex::encode(r.len_delimited()?)),
+            ProtoType::Message(name) => self.decode_message(name, r.len_delimited()?, depth + 1)?,
+        })
+    }
+
+    fn encode_message(
+        &self,
+        name: &str,
+        value: &Value,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("messages are nested too deeply"));
+        }
+        let msg = self
+            .message(name)
+            .ok_or_else(|| invalid(format!("unknown message `{}`", name)))?;
+        let obj = value
+            .as_object()
+            .ok_or_else(|| invalid(format!("the message `{}` should be an object", name)))?;
+        if let Some(key) = obj
+            .keys()
+            .find(|key| !msg.fields.iter().any(|f| &f.name == *key))
+        {
+            return Err(invalid(format!(
+                "there is no field `{}` in the message `{}`",
+                key, name
+            )));
+        }
+
+        for field in &msg.fields {
+            let v = match obj.get(&field.name) {
+                None | Some(Value::Null) => continue,
+                Some(v) => v,
+            };
+            let expected_array =
+                || invalid(format!("the field `{}` should be an array", field.name));
+
+            match &field.kind {
+                FieldKind::Single(ty) => {
+                    put_tag(buf, field.number, wire_type_of(ty));
+                    self.encode_value(ty, v, buf, depth)?;
+                }
+                FieldKind::Repeated { ty, packed: true } => {
+                    let mut packed = Vec::new();
+                    for v in v.as_array().ok_or_else(expected_array)? {
+                        self.encode_value(ty, v, &mut packed, depth)?;
+                    }
+                    put_tag(buf, field.number, LEN);
+                    put_len_delimited(buf, &packed);
+                }
+                FieldKind::Repeated { ty, packed: false } => {
+                    for v in v.as_array().ok_or_else(expected_array)? {
+                        put_tag(buf, field.number, wire_type_of(ty));
+                        self.encode_value(ty, v, buf, depth)?;
+                    }
+                }
+                FieldKind::Map { key, value } => {
+                    let map = v.as_object().ok_or_else(|| {
+                        invalid(format!("the field `{}` should be an object", field.name))
+                    })?;
+                    for (k, v) in map {
+                        let k = match key {
+                            ProtoType::Bool => Value::Bool(
+                                k.parse()
+                                    .map_err(|_| invalid(format!("`{}` is not a bool", k)))?,
+                            ),
+                            _ => Value::String(k.clone()),
+                        };
+                        let mut entry = Vec::new();
+                        put_tag(&mut entry, 1, wire_type_of(key));
+                        self.encode_value(key, &k, &mut entry, depth)?;
+                        put_tag(&mut entry, 2, wire_type_of(value));
+                        self.encode_value(value, v, &mut entry, depth)?;
+                        put_tag(buf, field.number, LEN);
+                        put_len_delimited(buf, &entry);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_value(
+        &self,
+        ty: &ProtoType,
+        v: &Value,
+        buf: &mut Vec<u8>,
+        depth: usize,
+    ) -> Result<()> {
+        match ty {
+            // Negative integers are sign-extended to 64 bits.
+            ProtoType::Int32 => {
+                let n = json_int(v, i32::MIN.into(), i32::MAX.into())?;
+                put_varint(buf, n as u64)
+            }
+            ProtoType::Int64 => put_varint(buf, json_int(v, i64::MIN, i64::MAX)? as u64),
+            ProtoType::Uint32 => put_varint(buf, json_uint(v, u32::MAX.into())?),
+            ProtoType::Uint64 => put_varint(buf, json_uint(v, u64::MAX)?),
+            ProtoType::Sint32 | ProtoType::Sint64 => {
+                let n = match ty {
+                    ProtoType::Sint32 => json_int(v, i32::MIN.into(), i32::MAX.into())?,
+                    _ => json_int(v, i64::MIN, i64::MAX)?,
+                };
+                put_varint(buf, ((n << 1) ^ (n >> 63)) as u64)
+            }
+            ProtoType::Bool => {
+                let b = v
+                    .as_bool()
+                    .ok_or_else(|| invalid(format!("`{}` is not a bool", v)))?;
+                put_varint(buf, b as u64)
+            }
+            ProtoType::Enum(name) => {
+                let n = match v {
+                    Value::String(s) => self
+                        .enumeration(name)
+                        .and_then(|e| e.values.iter().find(|(value, _)| value == s))
+                        .map(|(_, n)| *n)
+                        .ok_or_else(|| {
+                            invalid(format!("there is no value `{}` in the enum `{}`", s, name))
+                        })?,
+                    v => json_int(v, i32::MIN.into(), i32::MAX.into())? as i32,
+                };
+                put_varint(buf, i64::from(n) as u64)
+            }
+            ProtoType::Fixed32 => {
+                let n = json_uint(v, u32::MAX.into())? as u32;
+                buf.extend_from_slice(&n.to_le_bytes())
+            }
+            ProtoType::Sfixed32 => {
+                let n = json_int(v, i32::MIN.into(), i32::MAX.into())? as i32;
+                buf.extend_from_slice(&n.to_le_bytes())
+            }
+            ProtoType::Float => buf.extend_from_slice(&(json_float(v)? as f32).to_le_bytes()),
+            ProtoType::Fixed64 => buf.extend_from_slice(&json_uint(v, u64::MAX)?.to_le_bytes()),
+            ProtoType::Sfixed64 => {
+                buf.extend_from_slice(&json_int(v, i64::MIN, i64::MAX)?.to_le_bytes())
+            }
+            ProtoType::Double => buf.extend_from_slice(&json_float(v)?.to_le_bytes()),
+            ProtoType::String => {
+                let s = v
+                    .as_str()
+                    .ok_or_else(|| invalid(format!("`{}` is not a string", v)))?;
+                put_len_delimited(buf, s.as_bytes())
+            }
+            ProtoType::Bytes => {
+                let bytes = v
+                    .as_str()
+                    .and_then(|s| hex::decode(s).ok())
+                    .ok_or_else(|| invalid(format!("`{}` is not bytes in HEX", v)))?;
+                put_len_delimited(buf, &bytes)
+            }
+            ProtoType::Message(name) => {
+                let mut bytes = Vec::new();
+                self.encode_message(name, v, &mut bytes, depth + 1)?;
+                put_len_delimited(buf, &bytes)
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::protobuf::ProtoSchema;
+
+    #[test]
+    fn protobuf_ok() {
+        let proto = r#"
+            syntax = "proto3";
+            package demo;
+
+            message Point {
+                sint32 x = 1;
+                int32 y = 2;
+            }
+
+            // A shape.
+            message Shape {
+                enum Kind {
+                    KIND_UNKNOWN = 0;
+                    KIND_POLYGON = 1;
+                }
+
+                string name = 1;
+                Kind kind = 2;
+                repeated Point points = 3;
+                repeated uint32 tags = 4;
+                map<string, double> attrs = 5;
+                oneof color {
+                    bytes rgb = 6;
+                    fixed32 index = 7;
+                }
+                reserved 8 to 10;
+            }
+        "#;
+        let schema = ProtoSchema::from_proto(proto, "Shape").unwrap();
+        assert_eq!(schema.message_name(), "demo.Shape");
+
+        let value = json!({
+            "name": "tri",
+            "kind": "KIND_POLYGON",
+            "points": [{ "x": -1, "y": -1 }, { "x": 2 }],
+            "tags": [1, 300],
+            "attrs": { "area": 1.5 },
+            "rgb": "ff0000",
+        });
+        let bytes = schema.encode(&value).unwrap();
+        assert_eq!(
+            hex::encode(&bytes[..31]),
+            // name, kind, points with a sign-extended `y`, then packed tags.
+            "0a0374726910011a0d080110ffffffffffffffffff011a020804220301ac02"
+        );
+        assert_eq!(schema.decode(&bytes).unwrap(), value);
+
+        assert!(schema.encode(&json!({ "size": 1 })).is_err());
+        assert!(schema.decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ProtoSchema::from_proto(proto, "Circle").is_err());
+        assert!(ProtoSchema::from_proto("message A { B b = 1; }", "A").is_err());
+    }
+}
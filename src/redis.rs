@@ -0,0 +1,227 @@
+//! Redis commands over RESP, with a mock key store for servers.
+
+use std::{collections::BTreeMap, fmt, result};
+
+use serde_json::{json, Value};
+
+use crate::{
+    error::{Error, Result},
+    framing::Framing,
+    msg::{ItemValue, Message, MessageFormat},
+};
+
+/// The format of the Redis mode, each message is a RESP value split by `Framing::Resp`.
+pub fn format() -> MessageFormat {
+    "resp[*]".parse().unwrap()
+}
+
+pub fn framing() -> Framing {
+    Framing::Resp { max_len: 1 << 20 }
+}
+
+// Splits a line by whitespace unless quoted by `"`, the details are returned if the quote isn't
+// closed.
+pub(crate) fn split_args(line: &str) -> result::Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                arg.get_or_insert_with(String::new);
+            }
+            _ if c.is_whitespace() && !quoted => args.extend(arg.take()),
+            _ => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("the quote isn't closed in `{}`", line));
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// Builds a command of bulk strings from a line, arguments are split by whitespace
+/// unless quoted by `"`.
+pub fn command(line: &str) -> Result<Message> {
+    let args = split_args(line).map_err(|details| Error::InvalidRedis { details })?;
+    if args.is_empty() {
+        return Err(Error::InvalidRedis {
+            details: "the command is empty".to_string(),
+        });
+    }
+    Ok(Message::new(vec![ItemValue::Json(json!(args))]))
+}
+
+/// Displays a reply like `redis-cli`, nested values are indented.
+pub struct Reply<'a>(pub &'a Value);
+
+impl Reply<'_> {
+    fn fmt_indented(value: &Value, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let items = |values: &[Value], f: &mut fmt::Formatter<'_>| {
+            if values.is_empty() {
+                return write!(f, "(empty array)");
+            }
+            let width = values.len().to_string().len();
+            for (idx, v) in values.iter().enumerate() {
+                if idx != 0 {
+                    write!(f, "\n{:indent$}", "", indent = indent)?;
+                }
+                write!(f, "{:>width$}) ", idx + 1, width = width)?;
+                Self::fmt_indented(v, f, indent + width + 2)?;
+            }
+            Ok(())
+        };
+
+        match value {
+            Value::Null => write!(f, "(nil)"),
+            Value::Bool(v) => write!(f, "({})", v),
+            Value::Number(n) if n.is_i64() => write!(f, "(integer) {}", n),
+            Value::Number(n) => write!(f, "(double) {}", n),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Array(values) => items(values, f),
+            Value::Object(obj) => match obj.iter().next() {
+                Some((k, Value::String(s))) if obj.len() == 1 && k == "$simple" => {
+                    write!(f, "{}", s)
+                }
+                Some((k, Value::String(s))) if obj.len() == 1 && k == "$error" => {
+                    write!(f, "(error) {}", s)
+                }
+                Some((k, Value::Array(values)))
+                    if obj.len() == 1 && (k == "$set" || k == "$push") =>
+                {
+                    items(values, f)
+                }
+                // Maps are pairs of keys and values.
+                _ => {
+                    let pairs = obj
+                        .iter()
+                        .flat_map(|(k, v)| [json!(k), v.clone()])
+                        .collect::<Vec<_>>();
+                    items(&pairs, f)
+                }
+            },
+        }
+    }
+}
+
+impl fmt::Display for Reply<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Self::fmt_indented(self.0, f, 0)
+    }
+}
+
+fn error(details: impl fmt::Display) -> Value {
+    json!({ "$error": format!("ERR {}", details) })
+}
+
+/// Keys answered by the mock server, values are replied as they are by `GET`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KeyStore {
+    /// Values by keys.
+    pub keys: BTreeMap<String, Value>,
+}
+
+impl KeyStore {
+    /// Replies to a command of `PING`, `ECHO`, `GET`, `SET`, `DEL` or `EXISTS`,
+    /// other commands are answered by errors.
+    pub fn handle(&mut self, cmd: &Value) -> Value {
+        let args = match cmd.as_array().and_then(|args| {
+            args.iter()
+                .map(|arg| arg.as_str())
+                .collect::<Option<Vec<_>>>()
+        }) {
+            Some(args) if !args.is_empty() => args,
+            _ => return error("the command should be an array of strings"),
+        };
+
+        let name = args[0].to_ascii_uppercase();
+        match (name.as_str(), &args[1..]) {
+            ("PING", []) => json!({ "$simple": "PONG" }),
+            ("PING" | "ECHO", [msg]) => json!(msg),
+            ("GET", [key]) => self.keys.get(*key).cloned().unwrap_or_default(),
+            ("SET", [key, value]) => {
+                self.keys.insert(key.to_string(), json!(value));
+                json!({ "$simple": "OK" })
+            }
+            ("DEL", keys) if !keys.is_empty() => {
+                json!(keys
+                    .iter()
+                    .filter(|key| self.keys.remove(**key).is_some())
+                    .count())
+            }
+            ("EXISTS", keys) if !keys.is_empty() => {
+                json!(keys
+                    .iter()
+                    .filter(|key| self.keys.contains_key(**key))
+                    .count())
+            }
+            ("PING" | "ECHO" | "GET" | "SET" | "DEL" | "EXISTS", _) => error(format!(
+                "wrong number of arguments for '{}' command",
+                args[0].to_ascii_lowercase()
+            )),
+            _ => error(format!("unknown command '{}'", args[0])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::msg::ItemValue;
+
+    use super::{command, KeyStore, Reply};
+
+    #[test]
+    fn command_ok() {
+        let msg = command(r#"SET key "a value""#).unwrap();
+        assert_eq!(
+            msg.values()[0],
+            ItemValue::Json(json!(["SET", "key", "a value"]))
+        );
+        assert_eq!(
+            command(r#"SET key """#).unwrap().values()[0],
+            ItemValue::Json(json!(["SET", "key", ""]))
+        );
+        assert!(command(" ").is_err());
+        assert!(command(r#"GET "key"#).is_err());
+    }
+
+    #[test]
+    fn key_store_ok() {
+        let mut store = KeyStore::default();
+        store
+            .keys
+            .insert("list".to_string(), json!(["a", ["b", 1]]));
+
+        assert_eq!(store.handle(&json!(["ping"])), json!({ "$simple": "PONG" }));
+        assert_eq!(
+            store.handle(&json!(["SET", "k", "v"])),
+            json!({ "$simple": "OK" })
+        );
+        assert_eq!(store.handle(&json!(["GET", "k"])), json!("v"));
+        assert_eq!(store.handle(&json!(["EXISTS", "k", "list", "x"])), json!(2));
+        assert_eq!(store.handle(&json!(["DEL", "k", "x"])), json!(1));
+        assert_eq!(store.handle(&json!(["GET", "k"])), json!(null));
+        assert_eq!(
+            store.handle(&json!(["GET"])),
+            json!({ "$error": "ERR wrong number of arguments for 'get' command" })
+        );
+        assert_eq!(
+            store.handle(&json!(["FLUSHALL"])),
+            json!({ "$error": "ERR unknown command 'FLUSHALL'" })
+        );
+
+        let reply = store.handle(&json!(["GET", "list"]));
+        assert_eq!(
+            Reply(&reply).to_string(),
+            "1) \"a\"\n2) 1) \"b\"\n   2) (integer) 1"
+        );
+        assert_eq!(
+            Reply(&json!({ "$error": "ERR x" })).to_string(),
+            "(error) ERR x"
+        );
+    }
+}
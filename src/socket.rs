@@ -1,10 +1,11 @@
 use std::{
     collections::HashMap,
+    io::Write,
     net::{SocketAddr, TcpListener, TcpStream},
     ops::Deref,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        mpsc::{channel, Sender},
+        atomic::{AtomicBool, AtomicU16, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
         Arc, Mutex,
     },
     thread::{sleep, JoinHandle},
@@ -16,11 +17,85 @@ use socket2::{Domain, Protocol, Socket, Type};
 
 use crate::{
     error::{Error, Result},
-    msg::{Message, MessageDecoder, MessageEncoder, MessageFormat},
+    framing::{Framer, Framing},
+    http::{self, Summary},
+    memcache::{self, CacheStore},
+    modbus::{self, RegisterMap, Request, Response},
+    mqtt::{self, Mqtt, Packet},
+    msg::{ItemValue, Message, MessageEncoder, MessageFormat},
+    redis::{self, KeyStore, Reply},
+    stream::{self, MessageReader, MessageWriter, Resync},
+    websocket::{self, SharedWriter, WebSocket, WebSocketFramer},
 };
 
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+// Reads block at most this long, so that the stop flag is checked.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Creates a framer for a stream, the framing should have been checked.
+fn framer(framing: &Option<Framing>) -> Option<Box<dyn Framer>> {
+    framing.as_ref().map(|framing| framing.framer().unwrap())
+}
+
+// Framers of the reader and the writer of a stream, and the writer of the stream which is
+// shared with the framer of WebSocket to answer control frames.
+struct Transport {
+    reader_framer: Option<Box<dyn Framer>>,
+    writer_framer: Option<Box<dyn Framer>>,
+    writer: SharedWriter,
+}
+
+impl Transport {
+    fn new(
+        framing: &Option<Framing>,
+        websocket: &Option<WebSocket>,
+        client: bool,
+        stream: TcpStream,
+    ) -> Self {
+        let writer = SharedWriter(Arc::new(Mutex::new(stream)));
+        match websocket {
+            Some(ws) => Self {
+                reader_framer: Some(Box::new(
+                    WebSocketFramer::new(client, ws.clone()).replies(Some(writer.clone())),
+                )),
+                writer_framer: Some(Box::new(WebSocketFramer::new(client, ws.clone()))),
+                writer,
+            },
+            None => Self {
+                reader_framer: framer(framing),
+                writer_framer: framer(framing),
+                writer,
+            },
+        }
+    }
+}
+
+// Checks the framing, which is replaced by WebSocket if given.
+fn check_framing(
+    fmt: &MessageFormat,
+    framing: &Option<Framing>,
+    websocket: &Option<WebSocket>,
+) -> Result<()> {
+    match framing {
+        _ if websocket.is_some() => {}
+        Some(framing) => {
+            framing.framer()?;
+        }
+        None if fmt.is_greedy() => return Err(Error::GreedyWithoutFraming),
+        None => {}
+    }
+    Ok(())
+}
+
+/// TCP server, received messages are logged and messages can be sent to each client.
 pub struct Server {
     fmt: MessageFormat,
+    resync: Option<Resync>,
+    framing: Option<Framing>,
+    registers: Option<Arc<Mutex<RegisterMap>>>,
+    store: Option<Arc<Mutex<KeyStore>>>,
+    cache: Option<Arc<Mutex<CacheStore>>>,
+    websocket: Option<WebSocket>,
 
     stop_flag: Arc<AtomicBool>,
 
@@ -31,9 +106,16 @@ pub struct Server {
 }
 
 impl Server {
+    /// Exchanges messages of the format with clients.
     pub fn new(fmt: MessageFormat) -> Self {
         Self {
             fmt,
+            resync: None,
+            framing: None,
+            registers: None,
+            store: None,
+            cache: None,
+            websocket: None,
             stop_flag: Arc::new(AtomicBool::new(false)),
             listen_addr: None,
             tx_map: Default::default(),
@@ -41,17 +123,73 @@ impl Server {
         }
     }
 
+    /// Resynchronises streams of clients after corrupt messages.
+    pub fn resync(mut self, resync: Option<Resync>) -> Self {
+        self.resync = resync;
+        self
+    }
+
+    /// Splits streams of clients into frames, each frame contains a message.
+    pub fn framing(mut self, framing: Option<Framing>) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Answers Modbus/TCP requests from the register table, the format is replaced by the
+    /// Modbus/TCP ADU.
+    pub fn modbus(mut self, registers: Option<Arc<Mutex<RegisterMap>>>) -> Self {
+        if registers.is_some() {
+            self.fmt = modbus::format();
+        }
+        self.registers = registers;
+        self
+    }
+
+    /// Replies to Redis commands from the key store, the format and the framing are replaced
+    /// by RESP values.
+    pub fn redis(mut self, store: Option<Arc<Mutex<KeyStore>>>) -> Self {
+        if store.is_some() {
+            self.fmt = redis::format();
+            self.framing = Some(redis::framing());
+        }
+        self.store = store;
+        self
+    }
+
+    /// Replies to memcached commands from the item store, the format and the framing are
+    /// replaced by commands and replies of the text protocol.
+    pub fn memcache(mut self, cache: Option<Arc<Mutex<CacheStore>>>) -> Self {
+        if cache.is_some() {
+            self.fmt = memcache::format();
+            self.framing = Some(memcache::framing());
+        }
+        self.cache = cache;
+        self
+    }
+
+    /// Accepts WebSocket handshakes of clients, then each WebSocket message contains a message,
+    /// the framing is replaced.
+    pub fn websocket(mut self, websocket: Option<WebSocket>) -> Self {
+        self.websocket = websocket;
+        self
+    }
+
+    /// Address listened on, `None` if not running.
     pub fn listen_addr(&self) -> &Option<String> {
         &self.listen_addr
     }
 
+    /// Count of clients connected.
     pub fn client_len(&self) -> usize {
         self.tx_map.lock().unwrap().len()
     }
 
+    /// Listens in background threads, on `127.0.0.1:0` by default.
     pub fn run(&mut self, listen_addr: Option<&str>) -> Result<()> {
         let listen_addr = listen_addr.unwrap_or("127.0.0.1:0");
 
+        check_framing(&self.fmt, &self.framing, &self.websocket)?;
+
         let listen_addr: SocketAddr = listen_addr.parse().map_err(|_| Error::AddrParse {
             invalid_addr: listen_addr.to_string(),
         })?;
@@ -70,6 +208,12 @@ impl Server {
         self.listen_addr = Some(listen_addr.to_string());
 
         let fmt = self.fmt.clone();
+        let resync = self.resync.clone();
+        let framing = self.framing.clone();
+        let registers = self.registers.clone();
+        let store = self.store.clone();
+        let cache = self.cache.clone();
+        let websocket = self.websocket.clone();
         let listener: TcpListener = socket.try_clone().unwrap().into();
         let stop_flag = self.stop_flag.clone();
         let (disconnection_tx, disconnection_rx) = channel::<String>();
@@ -78,11 +222,11 @@ impl Server {
         let mut writer_handle_map = HashMap::<String, JoinHandle<()>>::default();
         self.handle = Some(std::thread::spawn(move || loop {
             if stop_flag.load(Ordering::Relaxed) {
-                tx_map.lock().unwrap().clear();
-
+                // Readers are joined first, since they add clients to the map after handshakes.
                 reader_handle_map.into_values().for_each(|h| {
                     h.join().ok();
                 });
+                tx_map.lock().unwrap().clear();
                 writer_handle_map.into_values().for_each(|h| {
                     h.join().ok();
                 });
@@ -98,63 +242,106 @@ impl Server {
             match listener.accept() {
                 Ok((stream, addr)) => {
                     info!("Server: Connection established, addr: `{}`", &addr);
+                    if let Err(e) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+                        warn!("Server: Failed to set read timeout, details: {}", e);
+                        continue;
+                    }
+
+                    let (tx, rx) = channel::<Message>();
+                    let Transport {
+                        reader_framer,
+                        writer_framer,
+                        writer,
+                    } = Transport::new(&framing, &websocket, false, stream.try_clone().unwrap());
 
                     {
-                        let fmt = fmt.clone();
-                        let mut stream = stream.try_clone().unwrap();
+                        let mut reader =
+                            MessageReader::new(fmt.clone(), stream.try_clone().unwrap())
+                                .resync(resync.clone())
+                                .framer(reader_framer);
                         let stop_flag = stop_flag.clone();
                         let disconnection_tx = disconnection_tx.clone();
+                        let registers = registers.clone();
+                        let store = store.clone();
+                        let cache = cache.clone();
+                        let tx_map = tx_map.clone();
+                        let handshake = websocket.is_some();
+                        let mut stream = stream.try_clone().unwrap();
                         reader_handle_map.insert(
                             addr.to_string(),
-                            std::thread::spawn(move || loop {
-                                if stop_flag.load(Ordering::Relaxed) {
-                                    break;
+                            // The handshake is in the thread of the client, so that a silent
+                            // client doesn't block accepting others, and the client is added
+                            // after the handshake, so that nothing is sent before.
+                            std::thread::spawn(move || {
+                                if handshake && !accept_websocket(&mut stream, &addr) {
+                                    disconnection_tx.send(addr.to_string()).unwrap();
+                                    return;
                                 }
+                                tx_map.lock().unwrap().insert(addr.to_string(), tx.clone());
 
-                                match MessageDecoder::new(&fmt, &mut stream)
-                                    .decode(stop_flag.clone())
-                                {
-                                    Ok(msg) => {
-                                        info!("Server: Received from `{}`, msg: {:?}", addr, msg);
-                                    }
-                                    Err(Error::EndOfStream) => {
-                                        disconnection_tx.send(addr.to_string()).unwrap();
+                                loop {
+                                    if stop_flag.load(Ordering::Relaxed) {
                                         break;
                                     }
-                                    Err(Error::Stopped) => break,
-                                    Err(e) => {
-                                        warn!(
-                                            "Server: Error occurs while reading message, error: {}",
-                                            e
-                                        );
+
+                                    match reader.read(&stop_flag) {
+                                        Ok(msg) => {
+                                            info!(
+                                                "Server: Received from `{}`, msg: {:#}",
+                                                addr, msg
+                                            );
+                                            if let Some(registers) = &registers {
+                                                serve_modbus(
+                                                    registers,
+                                                    &addr.to_string(),
+                                                    &msg,
+                                                    &tx,
+                                                );
+                                            }
+                                            if let Some(store) = &store {
+                                                serve_redis(store, &addr.to_string(), &msg, &tx);
+                                            }
+                                            if let Some(cache) = &cache {
+                                                serve_memcache(cache, &addr.to_string(), &msg, &tx);
+                                            }
+                                        }
+                                        Err(Error::EndOfStream) => {
+                                            disconnection_tx.send(addr.to_string()).unwrap();
+                                            break;
+                                        }
+                                        Err(Error::Stopped) => break,
+                                        Err(e) => {
+                                            warn!(
+                                                "Server: Error occurs while reading message, error: {}",
+                                                e
+                                            );
+                                        }
                                     }
                                 }
                             }),
                         );
                     }
 
-                    let (tx, rx) = channel::<Message>();
-
                     {
-                        let fmt = fmt.clone();
-                        let mut stream = stream.try_clone().unwrap();
+                        let mut writer =
+                            MessageWriter::new(fmt.clone(), writer).framer(writer_framer);
                         writer_handle_map.insert(
                             addr.to_string(),
                             std::thread::spawn(move || {
                                 while let Ok(msg) = rx.recv() {
-                                    if let Ok(()) =
-                                        MessageEncoder::new(&fmt, &mut stream).encode(&msg)
-                                    {
-                                        info!("Server: Sent to `{}`, msg: {:?}", addr, msg);
-                                    } else {
-                                        break;
+                                    match writer.write(&msg) {
+                                        Ok(()) => {
+                                            info!("Server: Sent to `{}`, msg: {:#}", addr, msg);
+                                        }
+                                        Err(Error::Io(_)) => break,
+                                        Err(e) => {
+                                            warn!("Server: Failed to write message, error: {}", e)
+                                        }
                                     }
                                 }
                             }),
                         );
                     }
-
-                    tx_map.lock().unwrap().insert(addr.to_string(), tx);
                 }
                 Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
                     sleep(Duration::from_millis(500));
@@ -166,6 +353,7 @@ impl Server {
         Ok(())
     }
 
+    /// Stops the background threads and disconnects clients, panics if not running.
     pub fn stop(&mut self) {
         if let Some(handle) = self.handle.take() {
             self.stop_flag.store(true, Ordering::Relaxed);
@@ -176,6 +364,7 @@ impl Server {
         }
     }
 
+    /// Sends a message to the client of the address.
     pub fn send_msg(&mut self, addr: &str, msg: Message) -> Result<()> {
         let tx_map = self.tx_map.lock().unwrap();
         if let Some(tx) = tx_map.get(addr) {
@@ -189,39 +378,243 @@ impl Server {
     }
 }
 
+// Accepts the WebSocket handshake of a client, returns whether it succeeds, which is logged.
+fn accept_websocket(stream: &mut TcpStream, addr: &SocketAddr) -> bool {
+    let mut accept = || -> Result<String> {
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .map_err(Error::Io)?;
+        let path = websocket::accept(stream)?;
+        stream
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .map_err(Error::Io)?;
+        Ok(path)
+    };
+    match accept() {
+        Ok(path) => {
+            info!(
+                "Server: WebSocket handshake of `{}`, path: `{}`",
+                addr, path
+            );
+            true
+        }
+        Err(e) => {
+            warn!(
+                "Server: WebSocket handshake of `{}` failed, details: {}",
+                addr, e
+            );
+            false
+        }
+    }
+}
+
+// Answers a Modbus/TCP request from the register table.
+fn serve_modbus(registers: &Mutex<RegisterMap>, addr: &str, msg: &Message, tx: &Sender<Message>) {
+    let (tid, unit, pdu) = match modbus::split_adu(msg) {
+        Ok(adu) => adu,
+        Err(e) => {
+            warn!(
+                "Server: Invalid Modbus request from `{}`, details: {}",
+                addr, e
+            );
+            return;
+        }
+    };
+    match Request::decode(pdu) {
+        Ok(req) => info!(
+            "Server: Modbus request from `{}`, unit: `{}`, {}",
+            addr, unit, req
+        ),
+        Err(e) => warn!(
+            "Server: Invalid Modbus request from `{}`, details: {}",
+            addr, e
+        ),
+    }
+    let resp = registers.lock().unwrap().serve(pdu);
+    info!("Server: Modbus response to `{}`, {}", addr, resp);
+    tx.send(modbus::adu(tid, unit, &resp.encode())).ok();
+}
+
+// Replies to a Redis command from the key store.
+fn serve_redis(store: &Mutex<KeyStore>, addr: &str, msg: &Message, tx: &Sender<Message>) {
+    let cmd = match msg.values().as_slice() {
+        [ItemValue::Json(cmd)] => cmd,
+        _ => {
+            warn!("Server: Invalid Redis command from `{}`", addr);
+            return;
+        }
+    };
+    let reply = store.lock().unwrap().handle(cmd);
+    info!("Server: Redis reply to `{}`:\n{}", addr, Reply(&reply));
+    tx.send(Message::new(vec![ItemValue::Json(reply)])).ok();
+}
+
+// Replies to a memcached command from the item store, unless the command is `noreply`.
+fn serve_memcache(cache: &Mutex<CacheStore>, addr: &str, msg: &Message, tx: &Sender<Message>) {
+    let cmd = match msg.values().as_slice() {
+        [ItemValue::Json(cmd)] => cmd,
+        _ => {
+            warn!("Server: Invalid memcached command from `{}`", addr);
+            return;
+        }
+    };
+    if let Some(reply) = cache.lock().unwrap().handle(cmd) {
+        info!(
+            "Server: memcached reply to `{}`:\n{}",
+            addr,
+            memcache::Reply(&reply)
+        );
+        tx.send(Message::new(vec![ItemValue::Json(reply)])).ok();
+    }
+}
+
+/// TCP client, received messages are logged.
 pub struct Client {
     fmt: MessageFormat,
+    resync: Option<Resync>,
+    framing: Option<Framing>,
+    modbus: bool,
+    redis: bool,
+    memcache: bool,
+    http: bool,
+    websocket: Option<WebSocket>,
+    mqtt: Option<Mqtt>,
 
     stop_flag: Arc<AtomicBool>,
 
     bind_addr: Option<String>,
     tx: Arc<Mutex<Option<Sender<Message>>>>,
+    writer: Option<SharedWriter>,
+    tid: u16,
+    packet_id: Arc<AtomicU16>,
 
     reader_handle: Option<JoinHandle<()>>,
     writer_handle: Option<JoinHandle<()>>,
 }
 
 impl Client {
+    /// Exchanges messages of the format with a server.
     pub fn new(fmt: MessageFormat) -> Client {
         Client {
             fmt,
+            resync: None,
+            framing: None,
+            modbus: false,
+            redis: false,
+            memcache: false,
+            http: false,
+            websocket: None,
+            mqtt: None,
             stop_flag: Arc::new(AtomicBool::new(false)),
             bind_addr: None,
             tx: Default::default(),
+            writer: None,
+            tid: 0,
+            packet_id: Default::default(),
             reader_handle: None,
             writer_handle: None,
         }
     }
 
+    /// Resynchronises the stream after corrupt messages.
+    pub fn resync(mut self, resync: Option<Resync>) -> Self {
+        self.resync = resync;
+        self
+    }
+
+    /// Splits the stream into frames, each frame contains a message.
+    pub fn framing(mut self, framing: Option<Framing>) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Sends Modbus/TCP requests and logs decoded responses, the format is replaced by the
+    /// Modbus/TCP ADU.
+    pub fn modbus(mut self, modbus: bool) -> Self {
+        if modbus {
+            self.fmt = modbus::format();
+        }
+        self.modbus = modbus;
+        self
+    }
+
+    /// Sends Redis commands and logs replies like `redis-cli`, the format and the framing are
+    /// replaced by RESP values.
+    pub fn redis(mut self, redis: bool) -> Self {
+        if redis {
+            self.fmt = redis::format();
+            self.framing = Some(redis::framing());
+        }
+        self.redis = redis;
+        self
+    }
+
+    /// Sends memcached commands and logs replies, the format and the framing are replaced by
+    /// commands and replies of the text protocol.
+    pub fn memcache(mut self, memcache: bool) -> Self {
+        if memcache {
+            self.fmt = memcache::format();
+            self.framing = Some(memcache::framing());
+        }
+        self.memcache = memcache;
+        self
+    }
+
+    /// Sends HTTP/1.1 requests and logs status lines of responses, the format and the framing
+    /// are replaced by HTTP messages.
+    pub fn http(mut self, http: bool) -> Self {
+        if http {
+            self.fmt = http::format();
+            self.framing = Some(http::framing());
+        }
+        self.http = http;
+        self
+    }
+
+    /// Opens a WebSocket handshake, then each WebSocket message contains a message, the framing
+    /// is replaced.
+    pub fn websocket(mut self, websocket: Option<WebSocket>) -> Self {
+        self.websocket = websocket;
+        self
+    }
+
+    /// Connects to an MQTT broker, then messages are published to the topic and payloads of
+    /// received publishes are decoded, the framing is replaced by MQTT packets.
+    pub fn mqtt(mut self, mqtt: Option<Mqtt>) -> Self {
+        if mqtt.is_some() {
+            self.framing = Some(Framing::Mqtt);
+        }
+        self.mqtt = mqtt;
+        self
+    }
+
+    /// Local address of the connection, `None` if not running.
     pub fn bind_addr(&self) -> &Option<String> {
         &self.bind_addr
     }
 
+    /// Connects in background threads.
     pub fn run(&mut self, bind_addr: Option<&str>, connect_addr: &str) -> Result<()> {
         let connect_addr: SocketAddr = connect_addr.parse().map_err(|_| Error::AddrParse {
             invalid_addr: connect_addr.to_string(),
         })?;
 
+        check_framing(&self.fmt, &self.framing, &self.websocket)?;
+        if let Some(mqtt) = &self.mqtt {
+            let details = if self.websocket.is_some() {
+                "MQTT over WebSocket is not supported"
+            } else if mqtt.qos > 2 {
+                "QoS should be 0, 1 or 2"
+            } else {
+                ""
+            };
+            if !details.is_empty() {
+                return Err(Error::InvalidMqtt {
+                    details: details.to_string(),
+                });
+            }
+        }
+
         let socket = Socket::new(Domain::IPV4, Type::STREAM, Some(Protocol::TCP)).unwrap();
         if let Some(bind_addr) = bind_addr {
             let bind_addr: SocketAddr = bind_addr.parse().map_err(|_| Error::AddrParse {
@@ -230,11 +623,38 @@ impl Client {
             socket.bind(&bind_addr.into()).map_err(Error::Io)?;
         }
         socket
-            .set_read_timeout(Some(Duration::from_millis(500)))
+            .set_read_timeout(Some(READ_TIMEOUT))
             .map_err(Error::Io)?;
         socket.connect(&connect_addr.into()).map_err(Error::Io)?;
         let bind_addr = socket.local_addr().map_err(Error::Io)?.as_socket().unwrap();
 
+        if let Some(ws) = &self.websocket {
+            let mut stream: TcpStream = socket.try_clone().map_err(Error::Io)?.into();
+            stream
+                .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+                .map_err(Error::Io)?;
+            websocket::connect(&mut stream, &connect_addr.to_string(), ws)?;
+            stream
+                .set_read_timeout(Some(READ_TIMEOUT))
+                .map_err(Error::Io)?;
+            info!("Client: WebSocket handshake completed, path: `{}`", ws.path);
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            let mut stream: TcpStream = socket.try_clone().map_err(Error::Io)?.into();
+            stream
+                .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+                .map_err(Error::Io)?;
+            let session_present = mqtt::connect(&mut stream, mqtt)?;
+            stream
+                .set_read_timeout(Some(READ_TIMEOUT))
+                .map_err(Error::Io)?;
+            info!(
+                "Client: MQTT connected, client id: `{}`, session present: `{}`",
+                mqtt.client_id, session_present
+            );
+        }
+
         info!(
             "Client: Started, bind: `{}`, connect to: `{}`",
             &bind_addr, &connect_addr
@@ -243,17 +663,73 @@ impl Client {
         self.stop_flag.store(false, Ordering::Relaxed);
         self.bind_addr = Some(bind_addr.to_string());
 
-        let fmt = self.fmt.clone();
         let stop_flag = self.stop_flag.clone();
-        let mut stream: TcpStream = socket.try_clone().map_err(Error::Io)?.into();
+        let modbus = self.modbus;
+        let redis = self.redis;
+        let memcache = self.memcache;
+        let http = self.http;
+        let Transport {
+            reader_framer,
+            writer_framer,
+            writer,
+        } = Transport::new(
+            &self.framing,
+            &self.websocket,
+            true,
+            socket.try_clone().map_err(Error::Io)?.into(),
+        );
+        let stream: TcpStream = socket.try_clone().map_err(Error::Io)?.into();
+        let mut reader = MessageReader::new(self.fmt.clone(), stream)
+            .resync(self.resync.clone())
+            .framer(reader_framer);
+        let (tx, rx) = channel::<Message>();
+        self.writer = Some(writer.clone());
+
+        if let Some(mqtt) = &self.mqtt {
+            self.reader_handle = Some(read_mqtt(
+                reader,
+                self.fmt.clone(),
+                mqtt.version,
+                writer.clone(),
+                stop_flag,
+            ));
+            let fmt = self.fmt.clone();
+            let mqtt = mqtt.clone();
+            let packet_id = self.packet_id.clone();
+            self.writer_handle = Some(std::thread::spawn(move || {
+                publish_mqtt(rx, &fmt, &mqtt, writer, &packet_id)
+            }));
+            self.tx.lock().unwrap().replace(tx);
+            return Ok(());
+        }
+
         self.reader_handle = Some(std::thread::spawn(move || loop {
             if stop_flag.load(Ordering::Relaxed) {
                 break;
             }
 
-            match MessageDecoder::new(&fmt, &mut stream).decode(stop_flag.clone()) {
+            match reader.read(&stop_flag) {
                 Ok(msg) => {
-                    info!("Client: Received from `{}`, msg: {:?}", &connect_addr, &msg);
+                    info!("Client: Received from `{}`, msg: {:#}", &connect_addr, &msg);
+                    if modbus {
+                        match modbus::split_adu(&msg)
+                            .and_then(|(tid, _, pdu)| Ok((tid, Response::decode(pdu)?)))
+                        {
+                            Ok((tid, resp)) => {
+                                info!("Client: Modbus response, tid: `{}`, {}", tid, resp)
+                            }
+                            Err(e) => warn!("Client: Invalid Modbus response, details: {}", e),
+                        }
+                    }
+                    if let (true, [ItemValue::Json(reply)]) = (redis, msg.values().as_slice()) {
+                        info!("Client: Redis reply:\n{}", Reply(reply));
+                    }
+                    if let (true, [ItemValue::Json(reply)]) = (memcache, msg.values().as_slice()) {
+                        info!("Client: memcached reply:\n{}", memcache::Reply(reply));
+                    }
+                    if let (true, [ItemValue::Json(resp)]) = (http, msg.values().as_slice()) {
+                        info!("Client: HTTP response, {}", Summary(resp));
+                    }
                 }
                 Err(Error::EndOfStream | Error::Stopped) => {
                     break;
@@ -264,15 +740,12 @@ impl Client {
             }
         }));
 
-        let (tx, rx) = channel::<Message>();
-
-        let fmt = self.fmt.clone();
-        let mut stream: TcpStream = socket.try_clone().map_err(Error::Io)?.into();
+        let mut writer = MessageWriter::new(self.fmt.clone(), writer).framer(writer_framer);
         self.writer_handle = Some(std::thread::spawn(move || {
             while let Ok(msg) = rx.recv() {
-                match MessageEncoder::new(&fmt, &mut stream).encode(&msg) {
+                match writer.write(&msg) {
                     Ok(()) => {
-                        info!("Client: Sent to `{}`, msg: {:?}", &connect_addr, &msg);
+                        info!("Client: Sent to `{}`, msg: {:#}", &connect_addr, &msg);
                     }
                     Err(Error::Io(_)) => break,
                     Err(e) => warn!("Client: Failed to write message, error: {}", e),
@@ -285,12 +758,26 @@ impl Client {
         Ok(())
     }
 
+    /// Stops the background threads and disconnects, panics if not running.
     pub fn stop(&mut self) {
         if let (Some(reader_handle), Some(writer_handle)) =
             (self.reader_handle.take(), self.writer_handle.take())
         {
+            if let Some(mut writer) = self.writer.take() {
+                if self.websocket.is_some() {
+                    writer
+                        .write_all(&websocket::close(websocket::CLOSE_NORMAL, true))
+                        .ok();
+                }
+                if let Some(mqtt) = &self.mqtt {
+                    writer
+                        .write_all(&Packet::Disconnect.encode(mqtt.version))
+                        .ok();
+                }
+            }
             self.stop_flag.store(true, Ordering::Relaxed);
             self.bind_addr = None;
+            self.writer = None;
             self.tx.lock().unwrap().take();
             reader_handle.join().unwrap();
             writer_handle.join().unwrap();
@@ -299,6 +786,7 @@ impl Client {
         }
     }
 
+    /// Sends a message to the server.
     pub fn send_msg(&mut self, msg: Message) -> Result<()> {
         if let Some(tx) = self.tx.lock().unwrap().deref() {
             tx.send(msg).ok();
@@ -307,20 +795,263 @@ impl Client {
             Err(Error::NotConnected)
         }
     }
+
+    /// Sends a Modbus/TCP request to the unit, transaction ids are increased for each request.
+    pub fn send_modbus(&mut self, unit: u8, req: &Request) -> Result<()> {
+        if !self.modbus {
+            return Err(Error::InvalidModbus {
+                details: "the client is not in Modbus mode".to_string(),
+            });
+        }
+        self.tid = self.tid.wrapping_add(1);
+        info!(
+            "Client: Modbus request, tid: `{}`, unit: `{}`, {}",
+            self.tid, unit, req
+        );
+        self.send_msg(modbus::adu(self.tid, unit, &req.encode()))
+    }
+
+    /// Sends a WebSocket ping, or a PINGREQ without the payload in the MQTT mode, whose
+    /// answer is logged.
+    pub fn send_ping(&mut self, payload: &[u8]) -> Result<()> {
+        let ping = match (&self.mqtt, &self.websocket) {
+            (Some(mqtt), _) => Packet::PingReq.encode(mqtt.version),
+            (None, Some(_)) => websocket::ping(payload, true)?,
+            (None, None) => {
+                return Err(Error::InvalidWebSocket {
+                    details: "the client is not over WebSocket".to_string(),
+                })
+            }
+        };
+        match &mut self.writer {
+            Some(writer) => writer.write_all(&ping).map_err(Error::Io),
+            None => Err(Error::NotConnected),
+        }
+    }
+
+    /// Subscribes to the topic filter with the maximum QoS, the SUBACK is logged.
+    pub fn subscribe(&mut self, filter: &str, qos: u8) -> Result<()> {
+        let version = match &self.mqtt {
+            Some(mqtt) => mqtt.version,
+            None => {
+                return Err(Error::InvalidMqtt {
+                    details: "the client is not in MQTT mode".to_string(),
+                })
+            }
+        };
+        if qos > 2 {
+            return Err(Error::InvalidMqtt {
+                details: "QoS should be 0, 1 or 2".to_string(),
+            });
+        }
+        let subscribe = Packet::Subscribe {
+            packet_id: next_packet_id(&self.packet_id),
+            filters: vec![(filter.to_string(), qos)],
+        };
+        info!("Client: MQTT {}", subscribe);
+        match &mut self.writer {
+            Some(writer) => writer
+                .write_all(&subscribe.encode(version))
+                .map_err(Error::Io),
+            None => Err(Error::NotConnected),
+        }
+    }
+
+    /// Sends a Redis command such as `SET key "a value"`.
+    pub fn send_command(&mut self, line: &str) -> Result<()> {
+        if !self.redis {
+            return Err(Error::InvalidRedis {
+                details: "the client is not in Redis mode".to_string(),
+            });
+        }
+        self.send_msg(redis::command(line)?)
+    }
+
+    /// Sends a memcached command such as `set key 0 0 "a value"`, the data of storage commands
+    /// is given in place of the length.
+    pub fn send_memcache_command(&mut self, line: &str) -> Result<()> {
+        if !self.memcache {
+            return Err(Error::InvalidMemcache {
+                details: "the client is not in memcached mode".to_string(),
+            });
+        }
+        self.send_msg(memcache::command(line)?)
+    }
+
+    /// Sends an HTTP/1.1 request, headers are lines of `Name: value` and lines without a colon
+    /// are sent as they are. `Content-Length` is added for the body unless the length is given.
+    pub fn send_request(
+        &mut self,
+        method: &str,
+        target: &str,
+        headers: &str,
+        body: &str,
+    ) -> Result<()> {
+        if !self.http {
+            return Err(Error::InvalidHttp {
+                details: "the client is not in HTTP mode".to_string(),
+            });
+        }
+        self.send_msg(http::request(method, target, headers, body)?)
+    }
+}
+
+// Packet ids are increased for each packet which needs one, `0` is skipped.
+fn next_packet_id(packet_id: &AtomicU16) -> u16 {
+    loop {
+        let id = packet_id.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+// Reads MQTT packets of a broker, payloads of publishes are decoded with the format and
+// acknowledged by their QoS.
+fn read_mqtt(
+    mut reader: MessageReader<TcpStream>,
+    fmt: MessageFormat,
+    version: mqtt::Version,
+    mut writer: SharedWriter,
+    stop_flag: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    std::thread::spawn(move || loop {
+        if stop_flag.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let packet = match reader
+            .read_frame(&stop_flag)
+            .and_then(|frame| Packet::decode(&frame, version))
+        {
+            Ok(packet) => packet,
+            Err(Error::EndOfStream | Error::Stopped) => break,
+            Err(e) => {
+                warn!("Client: Error occurs while reading packet, details: {}", e);
+                continue;
+            }
+        };
+        let reply = match &packet {
+            Packet::Publish {
+                qos,
+                topic,
+                packet_id,
+                payload,
+                ..
+            } => {
+                match stream::decode_frame(&fmt, payload) {
+                    Ok(msg) => info!(
+                        "Client: Received from topic `{}`, QoS: `{}`, msg: {:#}",
+                        topic, qos, msg
+                    ),
+                    Err(e) => warn!(
+                        "Client: Invalid payload from topic `{}`, details: {}",
+                        topic, e
+                    ),
+                }
+                let packet_id = *packet_id;
+                match qos {
+                    1 => Some(Packet::PubAck { packet_id }),
+                    2 => Some(Packet::PubRec { packet_id }),
+                    _ => None,
+                }
+            }
+            packet => {
+                info!("Client: MQTT {}", packet);
+                match *packet {
+                    Packet::PubRec { packet_id } => Some(Packet::PubRel { packet_id }),
+                    Packet::PubRel { packet_id } => Some(Packet::PubComp { packet_id }),
+                    _ => None,
+                }
+            }
+        };
+        if let Some(reply) = reply {
+            writer.write_all(&reply.encode(version)).ok();
+        }
+    })
+}
+
+// Publishes messages to the topic, a PINGREQ is sent every half of the keep alive while idle.
+fn publish_mqtt(
+    rx: Receiver<Message>,
+    fmt: &MessageFormat,
+    mqtt: &Mqtt,
+    mut writer: SharedWriter,
+    packet_id: &AtomicU16,
+) {
+    let interval = Duration::from_secs(mqtt.keep_alive.into()) / 2;
+    loop {
+        let msg = if interval.is_zero() {
+            rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            rx.recv_timeout(interval)
+        };
+        let res = match msg {
+            Ok(msg) => {
+                let mut payload = Vec::new();
+                MessageEncoder::new(fmt, &mut payload)
+                    .encode(&msg)
+                    .and_then(|()| {
+                        let publish = Packet::Publish {
+                            dup: false,
+                            qos: mqtt.qos,
+                            retain: mqtt.retain,
+                            topic: mqtt.topic.clone(),
+                            packet_id: if mqtt.qos > 0 {
+                                next_packet_id(packet_id)
+                            } else {
+                                0
+                            },
+                            payload,
+                        };
+                        writer
+                            .write_all(&publish.encode(mqtt.version))
+                            .map_err(Error::Io)?;
+                        info!(
+                            "Client: Published to topic `{}`, msg: {:#}",
+                            mqtt.topic, msg
+                        );
+                        Ok(())
+                    })
+            }
+            Err(RecvTimeoutError::Timeout) => writer
+                .write_all(&Packet::PingReq.encode(mqtt.version))
+                .map_err(Error::Io),
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        match res {
+            Ok(()) => {}
+            Err(Error::Io(_)) => break,
+            Err(e) => warn!("Client: Failed to publish message, error: {}", e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::{thread::sleep, time::Duration};
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex},
+        thread::sleep,
+        time::Duration,
+    };
 
     use simplelog::SimpleLogger;
 
     use crate::{
+        framing::Framer,
+        http,
+        memcache::{self, CacheStore},
+        modbus::{self, RegisterMap},
+        mqtt::{self, Mqtt, Packet, Version},
         msg::{ItemFormat, ItemValue, Message, MessageFormat},
+        redis::{self, KeyStore},
         socket::Client,
+        websocket::{self, WebSocket, WebSocketFramer},
     };
 
-    use super::Server;
+    use super::{Server, HANDSHAKE_TIMEOUT};
 
     #[test]
     fn send_msg_ok() {
@@ -357,4 +1088,316 @@ mod tests {
         s.stop();
         c.stop();
     }
+
+    #[test]
+    fn stop_with_idle_client() {
+        let fmt = MessageFormat::new(&[ItemFormat::Uint { len: 2 }]).unwrap();
+        let mut s = Server::new(fmt);
+        s.run(None).unwrap();
+
+        // The client never sends, the reader shouldn't block the stop.
+        let _stream = TcpStream::connect(s.listen_addr().as_ref().unwrap()).unwrap();
+        while s.client_len() == 0 {
+            sleep(Duration::from_millis(100));
+        }
+        s.stop();
+    }
+
+    #[test]
+    fn modbus_ok() {
+        let registers = Arc::new(Mutex::new(RegisterMap::new(8)));
+        let mut s = Server::new(modbus::format()).modbus(Some(registers.clone()));
+        s.run(None).unwrap();
+
+        let mut stream = TcpStream::connect(s.listen_addr().as_ref().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut exchange = |req: &str| {
+            stream.write_all(&hex::decode(req).unwrap()).unwrap();
+            let mut buf = [0; 32];
+            let n = stream.read(&mut buf).unwrap();
+            hex::encode(&buf[..n])
+        };
+
+        // Write single register, then read holding registers.
+        assert_eq!(
+            exchange("0001000000060106000204d2"),
+            "0001000000060106000204d2"
+        );
+        assert_eq!(
+            exchange("000200000006010300010002"),
+            "000200000007010304000004d2"
+        );
+        assert_eq!(registers.lock().unwrap().holding_registers[2], 1234);
+        // Illegal data address.
+        assert_eq!(exchange("000300000006010300070002"), "000300000003018302");
+
+        drop(stream);
+        s.stop();
+    }
+
+    #[test]
+    fn redis_ok() {
+        let store = Arc::new(Mutex::new(KeyStore::default()));
+        let mut s = Server::new(redis::format()).redis(Some(store.clone()));
+        s.run(None).unwrap();
+
+        let mut stream = TcpStream::connect(s.listen_addr().as_ref().unwrap()).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut exchange = |cmd: &[u8]| {
+            stream.write_all(cmd).unwrap();
+            let mut buf = [0; 64];
+            let n = stream.read(&mut buf).unwrap();
+            buf[..n].to_vec()
+        };
+
+        assert_eq!(
+            exchange(b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$2\r\nv1\r\n"),
+            b"+OK\r\n"
+        );
+        assert_eq!(exchange(b"*2\r\n$3\r\nGET\r\n$1\r\nk\r\n"), b"$2\r\nv1\r\n");
+        assert_eq!(exchange(b"*2\r\n$3\r\nGET\r\n$1\r\nx\r\n"), b"$-1\r\n");
+        assert_eq!(store.lock().unwrap().keys["k"], "v1");
+
+        drop(stream);
+        s.stop();
+    }
+
+    #[test]
+    fn memcache_ok() {
+        let cache = Arc::new(Mutex::new(CacheStore::default()));
+        let mut s = Server::new(memcache::format()).memcache(Some(cache.clone()));
+        s.run(None).unwrap();
+        let server_addr = s.listen_addr().as_ref().unwrap().clone();
+
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let mut exchange = |cmd: &[u8]| {
+            stream.write_all(cmd).unwrap();
+            let mut buf = [0; 64];
+            let n = stream.read(&mut buf).unwrap();
+            buf[..n].to_vec()
+        };
+
+        // The reply to `noreply` is skipped.
+        assert_eq!(
+            exchange(b"set a 0 0 1 noreply\r\n1\r\nset k 3 0 4\r\nv\r\n1\r\n"),
+            b"STORED\r\n"
+        );
+        assert_eq!(
+            exchange(b"get k a x\r\n"),
+            b"VALUE k 3 4\r\nv\r\n1\r\nVALUE a 0 1\r\n1\r\nEND\r\n"
+        );
+        assert_eq!(exchange(b"bogus\r\n"), b"ERROR\r\n");
+
+        let mut c = Client::new(memcache::format()).memcache(true);
+        c.run(None, &server_addr).unwrap();
+        c.send_memcache_command(r#"set c 0 0 "a value""#).unwrap();
+        while !cache.lock().unwrap().items.contains_key("c") {
+            sleep(Duration::from_millis(100));
+        }
+        assert_eq!(cache.lock().unwrap().items["c"].data, b"a value");
+        assert!(c.send_command("GET c").is_err());
+        c.stop();
+
+        drop(stream);
+        s.stop();
+    }
+
+    #[test]
+    fn http_ok() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let mut c = Client::new(http::format()).http(true);
+        c.run(None, &addr).unwrap();
+        let (mut stream, _) = listener.accept().unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+
+        c.send_request("POST", "/echo", "Host: localhost", "hi")
+            .unwrap();
+        let mut buf = [0; 128];
+        let n = stream.read(&mut buf).unwrap();
+        assert_eq!(
+            &buf[..n],
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 2\r\n\r\nhi"
+        );
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\n0\r\n\r\n")
+            .unwrap();
+
+        assert!(Client::new(http::format())
+            .send_request("GET", "/", "", "")
+            .is_err());
+
+        drop(stream);
+        c.stop();
+    }
+
+    #[test]
+    fn websocket_ok() {
+        let store = Arc::new(Mutex::new(KeyStore::default()));
+        let mut s = Server::new(redis::format())
+            .redis(Some(store.clone()))
+            .websocket(Some(WebSocket::default()));
+        s.run(None).unwrap();
+        let server_addr = s.listen_addr().as_ref().unwrap().clone();
+
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        websocket::connect(&mut stream, &server_addr, &WebSocket::default()).unwrap();
+        let mut framer = WebSocketFramer::new(true, WebSocket::default());
+        let ping = websocket::ping(b"", true).unwrap();
+        let cmd = framer.encode_frame(b"*1\r\n$4\r\nPING\r\n").unwrap();
+        let mut exchange = |frame: &[u8]| {
+            stream.write_all(frame).unwrap();
+            let mut buf = Vec::new();
+            loop {
+                if let Some(payload) = framer.decode_frame(&mut buf).unwrap() {
+                    return payload;
+                }
+                let mut bytes = [0; 64];
+                let n = stream.read(&mut bytes).unwrap();
+                buf.extend_from_slice(&bytes[..n]);
+            }
+        };
+
+        assert_eq!(exchange(&[ping, cmd].concat()), b"+PONG\r\n");
+
+        // A client over WebSocket.
+        let mut c = Client::new(redis::format())
+            .redis(true)
+            .websocket(Some(WebSocket {
+                max_frame_len: 4,
+                ..Default::default()
+            }));
+        c.run(None, &server_addr).unwrap();
+        c.send_ping(b"hi").unwrap();
+        c.send_command("SET k v").unwrap();
+        while store.lock().unwrap().keys.is_empty() {
+            sleep(Duration::from_millis(100));
+        }
+        assert_eq!(store.lock().unwrap().keys["k"], "v");
+        c.stop();
+
+        drop(stream);
+        s.stop();
+    }
+
+    #[test]
+    fn websocket_silent_client() {
+        let mut s = Server::new(redis::format())
+            .redis(Some(Default::default()))
+            .websocket(Some(WebSocket::default()));
+        s.run(None).unwrap();
+        let server_addr = s.listen_addr().as_ref().unwrap().clone();
+
+        // A client which never sends its handshake doesn't delay the handshake of others.
+        let silent = TcpStream::connect(&server_addr).unwrap();
+        let mut stream = TcpStream::connect(&server_addr).unwrap();
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT / 2))
+            .unwrap();
+        websocket::connect(&mut stream, &server_addr, &WebSocket::default()).unwrap();
+
+        drop(silent);
+        drop(stream);
+        s.stop();
+    }
+
+    #[test]
+    fn mqtt_ok() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let fmt = MessageFormat::new(&[ItemFormat::Uint { len: 2 }]).unwrap();
+        let options = Mqtt {
+            version: Version::V5,
+            client_id: "c".to_string(),
+            keep_alive: 0,
+            topic: "t".to_string(),
+            qos: 2,
+            retain: false,
+        };
+
+        // A stub broker, which accepts the connection.
+        let broker = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let connect = mqtt::read_packet(&mut stream).unwrap();
+            stream
+                .write_all(
+                    &Packet::ConnAck {
+                        session_present: false,
+                        code: 0,
+                    }
+                    .encode(Version::V5),
+                )
+                .unwrap();
+            (stream, connect)
+        });
+
+        let mut c = Client::new(fmt.clone()).mqtt(Some(options));
+        c.run(None, &addr).unwrap();
+        let (mut stream, connect) = broker.join().unwrap();
+        let read = |stream: &mut TcpStream| {
+            Packet::decode(&mqtt::read_packet(stream).unwrap(), Version::V5).unwrap()
+        };
+        assert_eq!(hex::encode(connect), "100e00044d5154540502000000000163");
+
+        c.subscribe("t/#", 1).unwrap();
+        assert_eq!(
+            read(&mut stream),
+            Packet::Subscribe {
+                packet_id: 1,
+                filters: vec![("t/#".to_string(), 1)],
+            }
+        );
+        c.send_msg(Message::new(vec![ItemValue::Uint(0xcafe)]))
+            .unwrap();
+        assert_eq!(
+            read(&mut stream),
+            Packet::Publish {
+                dup: false,
+                qos: 2,
+                retain: false,
+                topic: "t".to_string(),
+                packet_id: 2,
+                payload: vec![0xca, 0xfe],
+            }
+        );
+        c.send_ping(b"").unwrap();
+        assert_eq!(read(&mut stream), Packet::PingReq);
+
+        // The broker completes the publish, then publishes to the client.
+        let packets = [
+            Packet::PubRec { packet_id: 2 },
+            Packet::Publish {
+                dup: false,
+                qos: 1,
+                retain: false,
+                topic: "t/a".to_string(),
+                packet_id: 9,
+                payload: vec![0, 1],
+            },
+        ];
+        let bytes: Vec<u8> = packets.iter().flat_map(|p| p.encode(Version::V5)).collect();
+        stream.write_all(&bytes).unwrap();
+        assert_eq!(read(&mut stream), Packet::PubRel { packet_id: 2 });
+        assert_eq!(read(&mut stream), Packet::PubAck { packet_id: 9 });
+
+        c.stop();
+        assert_eq!(read(&mut stream), Packet::Disconnect);
+        assert!(Client::new(fmt).subscribe("t", 0).is_err());
+    }
 }
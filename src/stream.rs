@@ -0,0 +1,423 @@
+//! Messages over byte streams, with framing and resynchronisation.
+
+use std::{
+    io,
+    ops::Deref,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{
+    buffered::BufferedDecoder,
+    error::{Error, Result},
+    framing::Framer,
+    msg::{ItemFormat, Message, MessageDecoder, MessageEncoder, MessageFormat},
+};
+
+/// Decodes a message from a frame, which should contain exactly the message.
+pub fn decode_frame(fmt: &MessageFormat, frame: &[u8]) -> Result<Message> {
+    let mut bytes = frame;
+    let msg = match MessageDecoder::new(fmt, &mut bytes).decode(Default::default()) {
+        Ok(msg) => msg,
+        Err(e) if matches!(e.error, Error::EndOfStream) => {
+            return Err(Error::FrameTooShort { len: frame.len() })
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if !bytes.is_empty() {
+        return Err(Error::FrameTooLong {
+            expected_len: frame.len() - bytes.len(),
+            len: frame.len(),
+        });
+    }
+    Ok(msg)
+}
+
+/// The default max length of messages read without a framer.
+pub const DEFAULT_MAX_LEN: usize = 1 << 20;
+
+/// How to find the start of the next message after a corrupt one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resync {
+    /// Messages start with the magic constant.
+    Magic(Vec<u8>),
+    /// Skip bytes one by one until a message can be decoded.
+    Scan,
+    /// Skip bytes one by one until a message can be decoded with its checksums verified,
+    /// the format should have a checksum item.
+    Checksum,
+}
+
+/// Reads messages from a stream through a buffer, so that bytes of a corrupt message
+/// can be discarded to resynchronise the stream.
+///
+/// With a framer, the stream is split into frames and a message is decoded from each frame.
+pub struct MessageReader<R: io::Read> {
+    fmt: MessageFormat,
+    r: R,
+    buf: Vec<u8>,
+    decoder: BufferedDecoder,
+    resync: Option<Resync>,
+    framer: Option<Box<dyn Framer>>,
+}
+
+impl<R: io::Read> MessageReader<R> {
+    /// Reads by the format from the stream.
+    pub fn new(fmt: MessageFormat, r: R) -> Self {
+        Self {
+            decoder: BufferedDecoder::new(fmt.clone()).max_len(DEFAULT_MAX_LEN),
+            fmt,
+            r,
+            buf: Vec::default(),
+            resync: None,
+            framer: None,
+        }
+    }
+
+    /// Resync is useless with a framer, since a corrupt frame doesn't affect the others.
+    pub fn resync(mut self, resync: Option<Resync>) -> Self {
+        self.resync = resync;
+        self
+    }
+
+    /// Splits the stream into frames, each frame contains a message.
+    pub fn framer(mut self, framer: Option<Box<dyn Framer>>) -> Self {
+        self.framer = framer;
+        self
+    }
+
+    /// Without a framer, messages longer than `max_len` are corrupt, so that a corrupt length
+    /// doesn't buffer the stream without limit, `DEFAULT_MAX_LEN` by default.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.decoder = BufferedDecoder::new(self.fmt.clone()).max_len(max_len);
+        self
+    }
+
+    // Read more bytes from the stream to the buffer, the stream should block or have a
+    // read timeout, so that the stop flag is checked between reads.
+    fn fill(&mut self, stop_flag: &AtomicBool) -> Result<()> {
+        let mut bytes = [0u8; 4096];
+        loop {
+            match self.r.read(&mut bytes) {
+                Ok(0) => return Err(Error::EndOfStream),
+                Ok(n) => {
+                    self.buf.extend_from_slice(&bytes[..n]);
+                    return Ok(());
+                }
+                Err(e) => match e.kind() {
+                    io::ErrorKind::ConnectionReset => return Err(Error::EndOfStream),
+                    io::ErrorKind::WouldBlock
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::Interrupted => {
+                        if stop_flag.load(Ordering::Relaxed) {
+                            return Err(Error::Stopped);
+                        }
+                    }
+                    _ => return Err(Error::Io(e)),
+                },
+            }
+        }
+    }
+
+    // Decode a message from the buffer after `start`, returns it with the count of bytes
+    // consumed. EndOfStream means more bytes are needed.
+    fn decode(&mut self, start: usize) -> (Result<Message>, usize) {
+        match self.decoder.decode(&self.buf[start..]) {
+            Ok(Some(msg)) => (Ok(msg.to_message()), msg.raw().len()),
+            Ok(None) => (Err(Error::EndOfStream), 0),
+            Err(e) => {
+                let consumed = e.consumed;
+                (Err(e.into()), consumed)
+            }
+        }
+    }
+
+    /// Reads a message, an `Error::Resync` is returned after the stream is resynchronised
+    /// from a corrupt message.
+    pub fn read(&mut self, stop_flag: &AtomicBool) -> Result<Message> {
+        if self.framer.is_some() {
+            let frame = self.read_frame(stop_flag)?;
+            return decode_frame(&self.fmt, &frame);
+        }
+
+        loop {
+            match self.decode(0) {
+                (Ok(msg), consumed) => {
+                    self.buf.drain(..consumed);
+                    return Ok(msg);
+                }
+                (Err(Error::EndOfStream), _) => self.fill(stop_flag)?,
+                (Err(e), consumed) => match self.resync.clone() {
+                    // Skip the bytes decoded.
+                    None => {
+                        self.buf.drain(..consumed);
+                        return Err(e);
+                    }
+                    Some(resync) => {
+                        let discarded = self.discard(&resync, stop_flag)?;
+                        return Err(Error::Resync {
+                            discarded,
+                            source: Box::new(e),
+                        });
+                    }
+                },
+            }
+        }
+    }
+
+    /// Reads a frame without decoding it, the reader should have a framer.
+    pub fn read_frame(&mut self, stop_flag: &AtomicBool) -> Result<Vec<u8>> {
+        loop {
+            let framer = self.framer.as_mut().expect("the reader has no framer");
+            match framer.decode_frame(&mut self.buf)? {
+                Some(frame) => return Ok(frame),
+                None => self.fill(stop_flag)?,
+            }
+        }
+    }
+
+    // Discard bytes until the start of the next message, returns the count of bytes discarded.
+    fn discard(&mut self, resync: &Resync, stop_flag: &AtomicBool) -> Result<usize> {
+        // Otherwise any bytes which can be decoded would be taken as a message.
+        if *resync == Resync::Checksum
+            && !self
+                .fmt
+                .iter()
+                .any(|fmt| matches!(fmt, ItemFormat::Checksum { .. }))
+        {
+            return Err(Error::NoChecksumItem);
+        }
+
+        // Bytes before `start` are skipped, and drained at once before reading more bytes.
+        // The first byte is the start of the corrupt message.
+        let mut start = 1;
+        let mut discarded = 0;
+        loop {
+            match resync {
+                Resync::Magic(magic) if !magic.is_empty() => {
+                    if let Some(pos) = self.buf[start..]
+                        .windows(magic.len())
+                        .position(|w| w == magic.deref())
+                    {
+                        start += pos;
+                        break;
+                    }
+
+                    // Keep bytes which may be the beginning of the magic.
+                    start = start.max(self.buf.len() - self.buf.len().min(magic.len() - 1));
+                }
+                // Checksums are verified by the decoder, and candidates with corrupt lengths
+                // are rejected by the max length.
+                _ => match self.decode(start) {
+                    (Ok(_), _) => break,
+                    (Err(Error::EndOfStream), _) => {}
+                    (Err(_), _) => {
+                        start += 1;
+                        continue;
+                    }
+                },
+            }
+            self.buf.drain(..start);
+            discarded += start;
+            start = 0;
+            self.fill(stop_flag)?;
+        }
+        self.buf.drain(..start);
+        Ok(discarded + start)
+    }
+}
+
+/// Writes messages to a stream, wrapped into frames with a framer.
+pub struct MessageWriter<W: io::Write> {
+    fmt: MessageFormat,
+    w: W,
+    framer: Option<Box<dyn Framer>>,
+}
+
+impl<W: io::Write> MessageWriter<W> {
+    /// Writes by the format to the stream.
+    pub fn new(fmt: MessageFormat, w: W) -> Self {
+        Self {
+            fmt,
+            w,
+            framer: None,
+        }
+    }
+
+    /// Wraps messages into frames.
+    pub fn framer(mut self, framer: Option<Box<dyn Framer>>) -> Self {
+        self.framer = framer;
+        self
+    }
+
+    /// Encodes and writes a message.
+    pub fn write(&mut self, msg: &Message) -> Result<()> {
+        let mut bytes = Vec::new();
+        MessageEncoder::new(&self.fmt, &mut bytes).encode(msg)?;
+        if let Some(framer) = &self.framer {
+            bytes = framer.encode_frame(&bytes)?;
+        }
+        self.w.write_all(&bytes).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        checksum::Checksum,
+        error::Error,
+        framing::Framing,
+        msg::{ItemFormat, ItemValue, Message, MessageFormat},
+    };
+
+    use super::{MessageReader, MessageWriter, Resync};
+
+    #[test]
+    fn resync_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::FixedBytes { len: 2 },
+            ItemFormat::FixedString { len: 2 },
+        ])
+        .unwrap();
+        let msg = Message::new(vec![
+            ItemValue::Bytes(vec![0xca, 0xfe]),
+            ItemValue::String("ok".to_string()),
+        ]);
+
+        // A corrupt message with invalid UTF-8, and two bytes of garbage.
+        let bytes = b"\xca\xfeok\xca\xfe\xff\xffzz\xca\xfeok";
+        let mut reader =
+            MessageReader::new(fmt, &bytes[..]).resync(Some(Resync::Magic(vec![0xca, 0xfe])));
+        let stop_flag = Default::default();
+
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+        match reader.read(&stop_flag) {
+            Err(Error::Resync { discarded, .. }) => assert_eq!(discarded, 6),
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+        assert!(matches!(reader.read(&stop_flag), Err(Error::EndOfStream)));
+    }
+
+    #[test]
+    fn resync_checksum_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 1 },
+            ItemFormat::VarBytes { len_idx: 0 },
+            ItemFormat::Checksum {
+                checksum: Checksum::Crc16,
+                start: 0,
+                end: 2,
+            },
+        ])
+        .unwrap();
+        let msg = Message::new(vec![
+            ItemValue::Len(3),
+            ItemValue::Bytes(vec![1, 2, 3]),
+            ItemValue::Uint(0),
+        ]);
+        let mut bytes = Vec::new();
+        let mut writer = MessageWriter::new(fmt.clone(), &mut bytes);
+        // Candidates may have long lengths, which are rejected after the following bytes.
+        for _ in 0..64 {
+            writer.write(&msg).unwrap();
+        }
+        // Corrupt the payload of the second message.
+        bytes[8] ^= 0xff;
+
+        let mut reader = MessageReader::new(fmt.clone(), &bytes[..]).resync(Some(Resync::Checksum));
+        let stop_flag = Default::default();
+        let decoded = reader.read(&stop_flag).unwrap();
+        assert_eq!(decoded[1], msg[1]);
+        match reader.read(&stop_flag) {
+            Err(Error::Resync { discarded, source }) => {
+                assert_eq!(discarded, 6);
+                assert!(matches!(
+                    *source,
+                    Error::Decode(e) if matches!(e.error, Error::ChecksumMismatch { item_idx: 2, .. })
+                ));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        for _ in 2..64 {
+            assert_eq!(reader.read(&stop_flag).unwrap(), decoded);
+        }
+        assert!(matches!(reader.read(&stop_flag), Err(Error::EndOfStream)));
+
+        // Without a checksum item.
+        let fmt = MessageFormat::new(&[ItemFormat::FixedString { len: 2 }]).unwrap();
+        let mut reader = MessageReader::new(fmt, &b"\xff\xffok"[..]).resync(Some(Resync::Checksum));
+        assert!(matches!(
+            reader.read(&stop_flag),
+            Err(Error::NoChecksumItem)
+        ));
+    }
+
+    #[test]
+    fn resync_scan_ok() {
+        let fmt = MessageFormat::new(&[
+            ItemFormat::Len { len: 4 },
+            ItemFormat::VarString { len_idx: 0 },
+        ])
+        .unwrap();
+        let msg = Message::new(vec![ItemValue::Len(2), ItemValue::String("ok".to_string())]);
+
+        // Candidates at the garbage have corrupt lengths, which would be waited for forever.
+        let mut bytes = vec![0xff; 4];
+        bytes.extend_from_slice(b"\x00\x00\x00\x02\xffz\x00\x00\x00\x02ok");
+        let mut reader = MessageReader::new(fmt.clone(), &bytes[..])
+            .resync(Some(Resync::Scan))
+            .max_len(64);
+        let stop_flag = Default::default();
+        match reader.read(&stop_flag) {
+            Err(Error::Resync { discarded, source }) => {
+                assert_eq!(discarded, 10);
+                assert!(matches!(
+                    *source,
+                    Error::Decode(e) if matches!(e.error, Error::MessageTooLarge { max_len: 64 })
+                ));
+            }
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+        assert!(matches!(reader.read(&stop_flag), Err(Error::EndOfStream)));
+
+        // Without resync, the length is skipped.
+        let mut reader = MessageReader::new(fmt, &bytes[..]).max_len(64);
+        assert!(reader.read(&stop_flag).is_err());
+        assert!(reader.read(&stop_flag).is_err());
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+    }
+
+    #[test]
+    fn framing_ok() {
+        let fmt = MessageFormat::new(&[ItemFormat::FixedString { len: 2 }]).unwrap();
+        let msg = Message::new(vec![ItemValue::String("OK".to_string())]);
+        let framing = Framing::Delimiter {
+            delimiter: b"\r\n".to_vec(),
+            max_len: 16,
+        };
+
+        let mut bytes = Vec::new();
+        MessageWriter::new(fmt.clone(), &mut bytes)
+            .framer(Some(framing.framer().unwrap()))
+            .write(&msg)
+            .unwrap();
+        assert_eq!(bytes, b"OK\r\n");
+
+        // A corrupt frame doesn't affect the next one.
+        bytes.splice(0..0, b"ERROR\r\n".iter().cloned());
+        let mut reader =
+            MessageReader::new(fmt, &bytes[..]).framer(Some(framing.framer().unwrap()));
+        let stop_flag = Default::default();
+
+        assert!(matches!(
+            reader.read(&stop_flag),
+            Err(Error::FrameTooLong {
+                expected_len: 2,
+                len: 5
+            })
+        ));
+        assert_eq!(reader.read(&stop_flag).unwrap(), msg);
+        assert!(matches!(reader.read(&stop_flag), Err(Error::EndOfStream)));
+    }
+}
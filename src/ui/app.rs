@@ -1,26 +1,201 @@
-use std::ops::Deref;
+use std::{
+    collections::BTreeMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
 
 use eframe::{
-    egui::{self, Button, TextEdit, Widget},
+    egui::{self, Button, Color32, Label, Sense, TextEdit, Widget},
     epi,
 };
 use log::warn;
 use strum::IntoEnumIterator;
 
-use crate::{
-    error::{Error, Result},
-    msg::{ItemFormat, ItemValue, Message, MessageDecoder, MessageEncoder, MessageFormat},
-    socket::{Client, Server},
+use socket_toolbox::{
+    c_header, from_c_struct, from_ksy, from_proto, lua_dissector,
+    msg::{ItemFormat, ItemTrace, ItemValue, Message, MessageDecoder, MessageFormat, PayloadLen},
+    preset, rust_code,
+    stream::{self, MessageWriter, Resync},
+    CacheStore, Checksum, Client, DissectorTransport, Error, Framing, KeyStore, RegisterMap,
+    Result, Server,
 };
 
 use super::wrapper::ItemKindWrapper;
 use super::{
     widget,
-    wrapper::{ItemFormatWrapper, ItemValueWrapper},
+    wrapper::{
+        FramingKindWrapper, FramingWrapper, HttpRequestWrapper, ItemFormatWrapper,
+        ItemValueWrapper, ModbusFunctionWrapper, ModbusRequestWrapper, MqttWrapper,
+        WebSocketWrapper,
+    },
 };
 
+// Replace the items by the format, values of items whose kind is not changed are kept.
+fn replace_items(
+    fmt: &MessageFormat,
+    item_names: &mut Vec<String>,
+    item_kind_wrappers: &mut Vec<ItemKindWrapper>,
+    item_fmt_wrappers: &mut Vec<ItemFormatWrapper>,
+    item_value_wrappers: &mut Vec<ItemValueWrapper>,
+) {
+    let fmts = fmt.iter().map(ItemFormatWrapper::from).collect::<Vec<_>>();
+    let kinds = fmts
+        .iter()
+        .map(ItemKindWrapper::from_item_format)
+        .collect::<Vec<_>>();
+
+    *item_value_wrappers = kinds
+        .iter()
+        .enumerate()
+        .map(
+            |(idx, kind)| match (item_kind_wrappers.get(idx), item_value_wrappers.get(idx)) {
+                (Some(k), Some(v)) if k == kind => v.clone(),
+                _ => kind.default_item_value(),
+            },
+        )
+        .collect();
+    *item_kind_wrappers = kinds;
+    *item_fmt_wrappers = fmts;
+    *item_names = (0..fmt.len())
+        .map(|idx| fmt.name(idx).unwrap_or_default().to_string())
+        .collect();
+}
+
+// Encode the message, wrapped into a frame if framing.
+fn encode_msg(fmt: &MessageFormat, framing: Option<&Framing>, msg: &Message) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    MessageWriter::new(fmt.clone(), &mut bytes)
+        .framer(framing.map(Framing::framer).transpose()?)
+        .write(msg)?;
+    Ok(bytes)
+}
+
+// Decode a message from the bytes, only the first frame is decoded if framing.
+// Returns the message with the count of bytes remaining.
+fn decode_msg(
+    fmt: &MessageFormat,
+    framing: Option<&Framing>,
+    mut bytes: Vec<u8>,
+) -> Result<(Message, usize)> {
+    match framing {
+        Some(framing) => match framing.framer()?.decode_frame(&mut bytes)? {
+            Some(frame) => stream::decode_frame(fmt, &frame).map(|msg| (msg, bytes.len())),
+            None => Err(Error::EndOfStream),
+        },
+        None => {
+            let mut bytes = bytes.deref();
+            MessageDecoder::new(fmt, &mut bytes)
+                .decode(Default::default())
+                .map(|msg| (msg, bytes.len()))
+                .map_err(Error::from)
+        }
+    }
+}
+
+// Trace decoding of the bytes, or of the first frame if framing.
+// Returns the bytes traced with the trace.
+fn trace_msg(
+    fmt: &MessageFormat,
+    framing: Option<&Framing>,
+    bytes: Vec<u8>,
+) -> (Vec<u8>, Vec<ItemTrace>) {
+    let bytes = match framing {
+        Some(framing) => {
+            let mut buf = bytes.clone();
+            match framing
+                .framer()
+                .and_then(|mut framer| framer.decode_frame(&mut buf))
+            {
+                Ok(Some(frame)) => frame,
+                _ => return (bytes, Vec::new()),
+            }
+        }
+        None => bytes,
+    };
+    let (_, trace) = MessageDecoder::new(fmt, &mut bytes.deref()).decode_trace(Default::default());
+    (bytes, trace)
+}
+
+// Rows of WebSocket options in a grid, which should not be changed while running.
+fn websocket_ui(ui: &mut egui::Ui, ws: &mut WebSocketWrapper, running: bool, client: bool) {
+    ui.label("WebSocket:");
+    ui.scope(|ui| {
+        ui.set_enabled(!running);
+        ui.checkbox(&mut ws.enabled, "Handshake and frame messages");
+    });
+    ui.end_row();
+
+    if !ws.enabled {
+        return;
+    }
+    if client {
+        ui.label("Path:");
+        TextEdit::singleline(&mut ws.path).enabled(!running).ui(ui);
+        ui.end_row();
+    }
+    ui.label("Frames:");
+    ui.horizontal(|ui| {
+        ui.set_enabled(!running);
+        ui.checkbox(&mut ws.text, "Text");
+        ui.label("Max length:");
+        TextEdit::singleline(&mut ws.max_frame_len)
+            .hint_text("No limit if 0")
+            .desired_width(60.0)
+            .ui(ui);
+    });
+    ui.end_row();
+}
+
+// Rows of MQTT options in a grid, which should not be changed while running.
+fn mqtt_ui(ui: &mut egui::Ui, mqtt: &mut MqttWrapper, running: bool) {
+    ui.label("Client id:");
+    ui.horizontal(|ui| {
+        ui.set_enabled(!running);
+        TextEdit::singleline(&mut mqtt.client_id).ui(ui);
+        ui.checkbox(&mut mqtt.v5, "MQTT 5");
+    });
+    ui.end_row();
+
+    ui.label("Keep alive:");
+    TextEdit::singleline(&mut mqtt.keep_alive)
+        .hint_text("Seconds, no pings if 0")
+        .enabled(!running)
+        .ui(ui);
+    ui.end_row();
+
+    ui.label("Publish to:");
+    ui.horizontal(|ui| {
+        ui.set_enabled(!running);
+        TextEdit::singleline(&mut mqtt.topic)
+            .hint_text("Topic")
+            .ui(ui);
+        ui.label("QoS:");
+        TextEdit::singleline(&mut mqtt.qos)
+            .desired_width(30.0)
+            .ui(ui);
+        ui.checkbox(&mut mqtt.retain, "Retain");
+    });
+    ui.end_row();
+}
+
+// Count of coils and holding registers simulated by the Modbus server.
+const MODBUS_REGISTERS: usize = 256;
+
+// Count of registers shown in the register table.
+const MODBUS_SHOWN_REGISTERS: usize = 16;
+
+// Colours of items in the trace.
+const ITEM_COLORS: [Color32; 4] = [
+    Color32::LIGHT_BLUE,
+    Color32::GOLD,
+    Color32::from_rgb(120, 200, 120),
+    Color32::from_rgb(220, 140, 220),
+];
+
 #[derive(Default)]
 pub struct App {
+    // Names of items, which are optional.
+    item_names: Vec<String>,
     item_kind_wrappers: Vec<ItemKindWrapper>,
     item_fmt_wrappers: Vec<ItemFormatWrapper>,
     item_value_wrappers: Vec<ItemValueWrapper>,
@@ -33,17 +208,51 @@ pub struct App {
     msg_fmt: Option<MessageFormat>,
     msg_fmt_validation_error: Option<Error>,
 
+    fmt_text: String,
+    fmt_text_error: Option<Error>,
+    synced_fmt: Option<MessageFormat>,
+
+    import_path: String,
+    c_struct_name: String,
+
+    code_path: String,
+    dissector_port: String,
+    dissector_udp: bool,
+
+    resync_flag: bool,
+    resync_magic: String,
+
+    framing_wrapper: FramingWrapper,
+
     msg_hex: String,
+    trace_hovered_item: Option<usize>,
 
     client_bind_addr: String,
     client_connect_addr: String,
     client_run_flag: bool,
     client: Option<Client>,
+    client_modbus: bool,
+    modbus_request: ModbusRequestWrapper,
+    client_redis: bool,
+    redis_command: String,
+    client_memcache: bool,
+    memcache_command: String,
+    client_http: bool,
+    http_request: HttpRequestWrapper,
+    client_websocket: WebSocketWrapper,
+    client_mqtt: MqttWrapper,
 
     server_listen_addr: String,
     server_run_flag: bool,
     server: Option<Server>,
     server_target_addr: String,
+    server_modbus: bool,
+    server_registers: Option<Arc<Mutex<RegisterMap>>>,
+    server_redis: bool,
+    server_redis_keys: String,
+    server_memcache: bool,
+    server_memcache_items: String,
+    server_websocket: WebSocketWrapper,
 }
 
 impl epi::App for App {
@@ -63,6 +272,7 @@ impl epi::App for App {
 
     fn update(&mut self, ctx: &eframe::egui::CtxRef, _frame: &mut epi::Frame<'_>) {
         let Self {
+            item_names,
             item_kind_wrappers,
             item_fmt_wrappers,
             item_value_wrappers,
@@ -71,15 +281,44 @@ impl epi::App for App {
             item_values,
             msg_fmt,
             msg_fmt_validation_error,
+            fmt_text,
+            fmt_text_error,
+            synced_fmt,
+            import_path,
+            c_struct_name,
+            code_path,
+            dissector_port,
+            dissector_udp,
+            resync_flag,
+            resync_magic,
+            framing_wrapper,
             msg_hex,
+            trace_hovered_item,
             client_bind_addr,
             client_connect_addr,
             client_run_flag,
             client,
+            client_modbus,
+            modbus_request,
+            client_redis,
+            redis_command,
+            client_memcache,
+            memcache_command,
+            client_http,
+            http_request,
+            client_websocket,
+            client_mqtt,
             server_listen_addr,
             server_run_flag,
             server,
             server_target_addr,
+            server_modbus,
+            server_registers,
+            server_redis,
+            server_redis_keys,
+            server_memcache,
+            server_memcache_items,
+            server_websocket,
         } = self;
 
         egui::CentralPanel::default().show(ctx, |ui| {
@@ -88,6 +327,11 @@ impl epi::App for App {
                 dark_light::Mode::Light => ctx.set_visuals(egui::Visuals::light()),
             };
 
+            // Resync is disabled if not checked, otherwise scan for the magic if given,
+            // or by checksums if the format has them.
+            let mut resync = Ok(None);
+            let mut framing = Ok(None);
+
             ui.group(|ui| {
                 ui.label("Message");
                 ui.separator();
@@ -99,6 +343,7 @@ impl epi::App for App {
                     .num_columns(3)
                     .striped(true)
                     .show(ui, |ui| {
+                        ui.label("Name");
                         ui.label("Kind");
                         ui.label("Format");
                         ui.label("Value");
@@ -111,6 +356,15 @@ impl epi::App for App {
                             .zip(item_fmt_wrappers.iter_mut())
                             .enumerate()
                         {
+                            // Input item name.
+                            ui.vertical(|ui| {
+                                ui.set_enabled(can_modify_format);
+                                TextEdit::singleline(&mut item_names[idx])
+                                    .hint_text("Optional")
+                                    .desired_width(80.0)
+                                    .ui(ui);
+                            });
+
                             // Input item kind.
                             ui.vertical(|ui| {
                                 ui.set_enabled(can_modify_format);
@@ -120,7 +374,10 @@ impl epi::App for App {
                                 egui::ComboBox::from_id_source(idx)
                                     .selected_text(kind.to_string())
                                     .show_ui(ui, |ui| {
-                                        for k in ItemKindWrapper::iter() {
+                                        // Protobuf items can only be imported.
+                                        for k in ItemKindWrapper::iter()
+                                            .filter(|k| *k != ItemKindWrapper::Protobuf)
+                                        {
                                             ui.selectable_value(kind, k.clone(), k.to_string());
                                         }
                                     });
@@ -153,6 +410,86 @@ impl epi::App for App {
                                             ui.text_edit_singleline(len_idx);
                                         });
                                     }
+                                    ItemFormatWrapper::Checksum {
+                                        checksum,
+                                        start,
+                                        end,
+                                    } => {
+                                        egui::ComboBox::from_id_source(("checksum", idx))
+                                            .selected_text(checksum.to_string())
+                                            .show_ui(ui, |ui| {
+                                                for c in &Checksum::ALL {
+                                                    ui.selectable_value(
+                                                        checksum,
+                                                        *c,
+                                                        c.to_string(),
+                                                    );
+                                                }
+                                            });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Items:");
+                                            TextEdit::singleline(start).desired_width(30.0).ui(ui);
+                                            ui.label("..");
+                                            TextEdit::singleline(end).desired_width(30.0).ui(ui);
+                                        });
+                                    }
+                                    ItemFormatWrapper::Protobuf { len_idx, schema } => {
+                                        ui.label(schema.message_name());
+                                        ui.horizontal(|ui| {
+                                            ui.label("Length index:");
+                                            TextEdit::singleline(len_idx)
+                                                .hint_text("Greedy if empty")
+                                                .ui(ui);
+                                        });
+                                    }
+                                    ItemFormatWrapper::Cbor { len_idx }
+                                    | ItemFormatWrapper::MessagePack { len_idx }
+                                    | ItemFormatWrapper::Ber { len_idx }
+                                    | ItemFormatWrapper::Resp { len_idx }
+                                    | ItemFormatWrapper::Http { len_idx }
+                                    | ItemFormatWrapper::Stomp { len_idx }
+                                    | ItemFormatWrapper::Memcache { len_idx } => {
+                                        ui.horizontal(|ui| {
+                                            ui.label("Length index:");
+                                            TextEdit::singleline(len_idx)
+                                                .hint_text("Greedy if empty")
+                                                .ui(ui);
+                                        });
+                                    }
+                                    ItemFormatWrapper::Tlv {
+                                        len_idx,
+                                        tag_width,
+                                        len_width,
+                                    } => {
+                                        ui.horizontal(|ui| {
+                                            ui.label("Length index:");
+                                            TextEdit::singleline(len_idx)
+                                                .hint_text("Greedy if empty")
+                                                .ui(ui);
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Tag width:");
+                                            ui.text_edit_singleline(tag_width);
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.label("Length width:");
+                                            ui.text_edit_singleline(len_width);
+                                        });
+                                    }
+                                    ItemFormatWrapper::Json { len, fixed } => {
+                                        ui.checkbox(fixed, "Fixed length");
+                                        ui.horizontal(|ui| {
+                                            if *fixed {
+                                                ui.label("Length:");
+                                                ui.text_edit_singleline(len);
+                                            } else {
+                                                ui.label("Length index:");
+                                                TextEdit::singleline(len)
+                                                    .hint_text("Greedy if empty")
+                                                    .ui(ui);
+                                            }
+                                        });
+                                    }
                                 }
                             });
 
@@ -165,12 +502,30 @@ impl epi::App for App {
                                         // Len should be updated by Var items.
                                         *v = 0;
                                     }
+                                    // Computed when encoding.
+                                    ItemValueWrapper::Uint(_)
+                                        if matches!(fmt, ItemFormatWrapper::Checksum { .. }) =>
+                                    {
+                                        ui.label("Computed");
+                                    }
                                     ItemValueWrapper::Uint(s) | ItemValueWrapper::Int(s) => {
                                         ui.text_edit_singleline(s);
                                     }
                                     ItemValueWrapper::String(s) | ItemValueWrapper::Bytes(s) => {
                                         ui.text_edit_multiline(s);
                                     }
+                                    ItemValueWrapper::Json(s) => {
+                                        TextEdit::multiline(s).code_editor().ui(ui);
+                                        // Check the syntax while editing.
+                                        match serde_json::from_str(s) {
+                                            Ok(v) => {
+                                                ui.add(widget::JsonTree::new(("json", idx), &v));
+                                            }
+                                            Err(e) => {
+                                                ui.colored_label(Color32::RED, e.to_string());
+                                            }
+                                        }
+                                    }
                                 };
 
                                 // Update Len according to the max length of Var items.
@@ -202,6 +557,27 @@ impl epi::App for App {
                                             }
                                         }
                                     }
+                                    (fmt, ItemValueWrapper::Json(s)) => {
+                                        // The length is known after encoding.
+                                        if let (Ok(fmt), Ok(v)) =
+                                            (fmt.parse(), serde_json::from_str(s))
+                                        {
+                                            let payload_len = match fmt.payload_len() {
+                                                Some(PayloadLen::Var { len_idx }) => fmt
+                                                    .encode_payload(&v)
+                                                    .ok()
+                                                    .map(|payload| (*len_idx, payload.len())),
+                                                _ => None,
+                                            };
+                                            if let Some((len_idx, payload_len)) = payload_len {
+                                                if let Some(ItemValueWrapper::Len(len)) =
+                                                    item_value_wrappers.get_mut(len_idx)
+                                                {
+                                                    *len = (*len).max(payload_len as u64);
+                                                }
+                                            }
+                                        }
+                                    }
                                     _ => {}
                                 }
                             });
@@ -223,12 +599,118 @@ impl epi::App for App {
                         }
 
                         if let Some(idx) = removed_idx {
+                            item_names.remove(idx);
                             item_kind_wrappers.remove(idx);
                             item_fmt_wrappers.remove(idx);
                             item_value_wrappers.remove(idx);
                         }
                     });
 
+                // Input message format as text, which is synced with the grid.
+                let text_changed = ui
+                    .horizontal(|ui| {
+                        ui.label("Format:");
+                        TextEdit::multiline(fmt_text)
+                            .code_editor()
+                            .desired_rows(2)
+                            .enabled(can_modify_format)
+                            .ui(ui)
+                            .changed()
+                    })
+                    .inner;
+                if text_changed {
+                    match fmt_text.parse::<MessageFormat>() {
+                        Ok(fmt) => {
+                            replace_items(
+                                &fmt,
+                                item_names,
+                                item_kind_wrappers,
+                                item_fmt_wrappers,
+                                item_value_wrappers,
+                            );
+                            *fmt_text_error = None;
+                            *synced_fmt = Some(fmt);
+                        }
+                        Err(e) => *fmt_text_error = Some(e),
+                    }
+                }
+                if let Some(e) = fmt_text_error.as_ref() {
+                    ui.label(format!("Format error: {}", e));
+                }
+
+                // Import message format from a C struct, a Kaitai Struct or a protobuf message,
+                // or load a preset.
+                ui.horizontal(|ui| {
+                    ui.set_enabled(can_modify_format);
+
+                    ui.label("Import:");
+                    TextEdit::singleline(import_path)
+                        .hint_text("File path")
+                        .ui(ui);
+                    TextEdit::singleline(c_struct_name)
+                        .hint_text("Struct or message name")
+                        .ui(ui);
+                    let c_clicked = ui
+                        .button("C struct")
+                        .on_hover_text(
+                            "Integers are imported as big-endian, \
+                            so fields of little-endian structs are decoded with swapped bytes",
+                        )
+                        .clicked();
+                    let ksy_clicked = ui.button("Kaitai Struct").clicked();
+                    let proto_clicked = ui.button("Protobuf").clicked();
+
+                    let mut preset = None;
+                    egui::ComboBox::from_id_source("preset")
+                        .selected_text("Preset")
+                        .show_ui(ui, |ui| {
+                            for p in preset::PRESETS {
+                                if ui
+                                    .selectable_label(false, p.name)
+                                    .on_hover_text(p.description)
+                                    .clicked()
+                                {
+                                    preset = Some(p);
+                                }
+                            }
+                        });
+                    if let Some(p) = preset {
+                        replace_items(
+                            &p.format(),
+                            item_names,
+                            item_kind_wrappers,
+                            item_fmt_wrappers,
+                            item_value_wrappers,
+                        );
+                        *framing_wrapper = FramingWrapper::from(&p.framing);
+                    }
+
+                    if c_clicked || ksy_clicked || proto_clicked {
+                        match std::fs::read_to_string(&import_path)
+                            .map_err(Error::Io)
+                            .and_then(|s| {
+                                if c_clicked {
+                                    from_c_struct(&s, c_struct_name)
+                                } else if ksy_clicked {
+                                    from_ksy(&s)
+                                } else {
+                                    from_proto(&s, c_struct_name)
+                                }
+                            }) {
+                            Ok(fmt) => replace_items(
+                                &fmt,
+                                item_names,
+                                item_kind_wrappers,
+                                item_fmt_wrappers,
+                                item_value_wrappers,
+                            ),
+                            Err(e) => {
+                                warn!("App: Error occurs when import format, details: {}", e)
+                            }
+                        }
+                    }
+                });
+
                 *item_parse_error = None;
                 *item_fmts = None;
                 *item_values = None;
@@ -261,6 +743,7 @@ impl epi::App for App {
                     .clicked()
                     | item_kind_wrappers.is_empty()
                 {
+                    item_names.push(String::new());
                     item_kind_wrappers.push(ItemKindWrapper::Len);
                     item_fmt_wrappers
                         .push(item_kind_wrappers.last().unwrap().default_item_format());
@@ -272,7 +755,10 @@ impl epi::App for App {
                 *msg_fmt = None;
                 *msg_fmt_validation_error = None;
                 if let Some(item_fmts) = item_fmts {
-                    match MessageFormat::new(item_fmts) {
+                    let names = item_names
+                        .iter()
+                        .map(|name| Some(name.trim().to_string()).filter(|name| !name.is_empty()));
+                    match MessageFormat::new(item_fmts).and_then(|fmt| fmt.with_names(names)) {
                         Ok(fmt) => {
                             *msg_fmt = Some(fmt);
                         }
@@ -282,6 +768,154 @@ impl epi::App for App {
                     }
                 }
 
+                // Sync the text if the format is modified in the grid.
+                if msg_fmt.is_some() && msg_fmt != synced_fmt {
+                    *fmt_text = msg_fmt.as_ref().unwrap().to_string();
+                    *fmt_text_error = None;
+                    *synced_fmt = msg_fmt.clone();
+                }
+
+                // Generate code of message format.
+                ui.horizontal(|ui| {
+                    ui.set_enabled(msg_fmt.is_some());
+
+                    ui.label("Generate code:");
+                    TextEdit::singleline(code_path)
+                        .hint_text("Output path")
+                        .ui(ui);
+                    let rust_clicked = ui.button("Rust").clicked();
+                    let c_clicked = ui.button("C header").clicked();
+                    let lua_clicked = ui.button("Wireshark dissector").clicked();
+                    ui.label("on port:");
+                    TextEdit::singleline(dissector_port)
+                        .desired_width(60.0)
+                        .ui(ui);
+                    ui.checkbox(dissector_udp, "UDP");
+
+                    if rust_clicked || c_clicked || lua_clicked {
+                        let msg_fmt = msg_fmt.as_ref().unwrap();
+
+                        // Name the code by the file name.
+                        let path = std::path::Path::new(code_path.as_str());
+                        let name = path
+                            .file_stem()
+                            .and_then(|name| name.to_str())
+                            .unwrap_or("message");
+                        let transport = if *dissector_udp {
+                            DissectorTransport::Udp
+                        } else {
+                            DissectorTransport::Tcp
+                        };
+
+                        let code = if rust_clicked {
+                            Ok(rust_code(msg_fmt, name))
+                        } else if c_clicked {
+                            Ok(c_header(msg_fmt, name))
+                        } else {
+                            dissector_port
+                                .parse::<u16>()
+                                .map_err(|_| Error::AddrParse {
+                                    invalid_addr: dissector_port.clone(),
+                                })
+                                .map(|port| lua_dissector(msg_fmt, name, transport, port))
+                        };
+                        if let Err(e) =
+                            code.and_then(|code| std::fs::write(path, code).map_err(Error::Io))
+                        {
+                            warn!("App: Error occurs when generate code, details: {}", e);
+                        }
+                    }
+                });
+
+                // Resync streams after corrupt messages.
+                ui.horizontal(|ui| {
+                    ui.set_enabled(can_modify_format);
+
+                    ui.checkbox(resync_flag, "Resync");
+                    TextEdit::singleline(resync_magic)
+                        .hint_text("Magic in HEX, scan if empty")
+                        .enabled(*resync_flag)
+                        .ui(ui);
+                });
+                if *resync_flag {
+                    let has_checksum = msg_fmt.as_ref().is_some_and(|fmt| {
+                        fmt.iter().any(|f| matches!(f, ItemFormat::Checksum { .. }))
+                    });
+                    resync = if resync_magic.is_empty() && has_checksum {
+                        Ok(Some(Resync::Checksum))
+                    } else if resync_magic.is_empty() {
+                        Ok(Some(Resync::Scan))
+                    } else {
+                        hex::decode(resync_magic.as_str()).map(|magic| Some(Resync::Magic(magic)))
+                    };
+                }
+                if let Err(e) = &resync {
+                    ui.label(format!(
+                        "Parse error: The magic can not be decoded to bytes, details: {}",
+                        e
+                    ));
+                }
+
+                // Split streams into frames, each frame contains a message.
+                ui.horizontal(|ui| {
+                    ui.set_enabled(can_modify_format);
+
+                    ui.label("Framing:");
+                    let mut kind = FramingKindWrapper::from_framing(framing_wrapper);
+                    egui::ComboBox::from_id_source("framing")
+                        .selected_text(kind.to_string())
+                        .show_ui(ui, |ui| {
+                            for k in FramingKindWrapper::iter() {
+                                ui.selectable_value(&mut kind, k.clone(), k.to_string());
+                            }
+                        });
+                    if kind != FramingKindWrapper::from_framing(framing_wrapper) {
+                        *framing_wrapper = kind.default_framing();
+                    }
+
+                    match framing_wrapper {
+                        FramingWrapper::None
+                        | FramingWrapper::Mqtt => {}
+                        FramingWrapper::Slip { max_len }
+                        | FramingWrapper::Cobs { max_len }
+                        | FramingWrapper::Hdlc { max_len }
+                        | FramingWrapper::VarintPrefix { max_len }
+                        | FramingWrapper::Resp { max_len }
+                        | FramingWrapper::Http { max_len }
+                        | FramingWrapper::Stomp { max_len }
+                        | FramingWrapper::Memcache { max_len } => {
+                            ui.label("Max length:");
+                            TextEdit::singleline(max_len).desired_width(60.0).ui(ui);
+                        }
+                        FramingWrapper::Delimiter { delimiter, max_len } => {
+                            ui.label("Delimiter:");
+                            TextEdit::singleline(delimiter)
+                                .hint_text("HEX")
+                                .desired_width(80.0)
+                                .ui(ui);
+                            ui.label("Max length:");
+                            TextEdit::singleline(max_len).desired_width(60.0).ui(ui);
+                        }
+                        FramingWrapper::LengthPrefix {
+                            width,
+                            big_endian,
+                            includes_self,
+                            max_len,
+                        } => {
+                            ui.label("Width:");
+                            TextEdit::singleline(width).desired_width(30.0).ui(ui);
+                            ui.checkbox(big_endian, "Big-endian");
+                            ui.checkbox(includes_self, "Includes itself");
+                            ui.label("Max length:");
+                            TextEdit::singleline(max_len).desired_width(60.0).ui(ui);
+                        }
+                    }
+                });
+                framing = framing_wrapper.parse();
+                if let Err(e) = &framing {
+                    ui.label(format!("Parse error: {}", e));
+                }
+
                 ui.separator();
 
                 if let Some(e) = item_parse_error.as_ref() {
@@ -293,13 +927,14 @@ impl epi::App for App {
                 } else {
                     let msg_fmt = msg_fmt.as_ref().unwrap();
 
+                    // Encode and decode with the framing.
+                    let framing = framing.as_ref().ok().and_then(Option::as_ref);
+
                     if let Some(item_values) = item_values.as_ref() {
                         // Encode the input to bytes, show errors if fails.
-                        let mut buf = Vec::<u8>::default();
-                        let res = MessageEncoder::new(msg_fmt, &mut buf)
-                            .encode(&Message::new(item_values.clone()));
+                        let res = encode_msg(msg_fmt, framing, &Message::new(item_values.clone()));
                         match res {
-                            Ok(..) => {
+                            Ok(buf) => {
                                 ui.label(format!("Encode: {}", hex::encode_upper(buf)));
                             }
                             Err(e) => {
@@ -317,17 +952,14 @@ impl epi::App for App {
 
                         let mut msg = None;
                         if !msg_hex.is_empty() {
-                            match hex::decode(msg_hex) {
+                            match hex::decode(msg_hex.as_str()) {
                                 Ok(bytes) => {
-                                    let mut bytes = bytes.deref();
                                     let len = bytes.len();
-                                    match MessageDecoder::new(msg_fmt, &mut bytes)
-                                        .decode(Default::default())
-                                    {
-                                        Ok(m) => {
-                                            if !bytes.is_empty() {
+                                    match decode_msg(msg_fmt, framing, bytes) {
+                                        Ok((m, remaining)) => {
+                                            if remaining != 0 {
                                                 decode_err = Some(Error::MessageHexTooLong {
-                                                    expected_len: len - bytes.len(),
+                                                    expected_len: len - remaining,
                                                     len,
                                                 })
                                             } else {
@@ -367,22 +999,109 @@ impl epi::App for App {
                             e
                         ));
                     }
+
+                    // Hex dump of the trace, bytes of the hovered item are highlighted.
+                    if let Ok(bytes) = hex::decode(msg_hex.as_str()) {
+                        let (bytes, trace) = trace_msg(msg_fmt, framing, bytes);
+                        let mut hovered_item = None;
+                        ui.horizontal_wrapped(|ui| {
+                            for (pos, b) in bytes.iter().enumerate() {
+                                let label = Label::new(format!("{:02X}", b)).monospace();
+                                let item = trace
+                                    .iter()
+                                    .enumerate()
+                                    .find(|(_, t)| pos >= t.offset && pos < t.offset + t.raw.len());
+
+                                // Bytes not decoded.
+                                let (idx, t) = match item {
+                                    Some(item) => item,
+                                    None => {
+                                        ui.add(label.weak());
+                                        continue;
+                                    }
+                                };
+
+                                let mut label =
+                                    label.sense(Sense::hover()).text_color(if t.value.is_ok() {
+                                        ITEM_COLORS[idx % ITEM_COLORS.len()]
+                                    } else {
+                                        Color32::RED
+                                    });
+                                if *trace_hovered_item == Some(idx) {
+                                    label = label.background_color(ui.visuals().selection.bg_fill);
+                                }
+
+                                let response = ui.add(label);
+                                if response.hovered() {
+                                    hovered_item = Some(idx);
+                                }
+                                response.on_hover_text(match &t.value {
+                                    Ok(value) => format!(
+                                        "Item {}, offset: {}, len: {}, value: {:?}",
+                                        idx, t.offset, t.len, value
+                                    ),
+                                    Err(e) => format!(
+                                        "Item {}, offset: {}, len: {}, error: {}",
+                                        idx, t.offset, t.len, e
+                                    ),
+                                });
+                            }
+                        });
+                        *trace_hovered_item = hovered_item;
+                    }
                 }
             });
 
             // Group for server.
             ui.group(|ui| {
+                let websocket = server_websocket.parse();
                 ui.horizontal(|ui| {
                     ui.label("Server");
 
                     // Sever shouldn't run if item formats is not valid.
                     if widget::Toggle::new(server_run_flag)
-                        .enabled(msg_fmt.is_some())
+                        .enabled(
+                            msg_fmt.is_some()
+                                && resync.is_ok()
+                                && framing.is_ok()
+                                && websocket.is_ok(),
+                        )
                         .ui(ui)
                         .clicked()
                     {
                         if *server_run_flag {
-                            let mut new_server = Server::new(msg_fmt.as_ref().unwrap().clone());
+                            *server_registers = if *server_modbus {
+                                Some(Arc::new(Mutex::new(RegisterMap::new(MODBUS_REGISTERS))))
+                            } else {
+                                None
+                            };
+                            // Keys of the Redis mode are a JSON object, empty if not given.
+                            let store = match server_redis_keys.trim() {
+                                _ if !*server_redis => Ok(None),
+                                "" => Ok(Some(KeyStore::default())),
+                                keys => serde_json::from_str(keys)
+                                    .map(|keys| Some(KeyStore { keys }))
+                                    .map_err(|e| Error::InvalidRedis {
+                                        details: format!(
+                                            "the keys should be a JSON object, details: {}",
+                                            e
+                                        ),
+                                    }),
+                            };
+                            // Items of the memcached mode are a JSON object of strings, empty if
+                            // not given.
+                            let cache = match server_memcache_items.trim() {
+                                _ if !*server_memcache => Ok(None),
+                                "" => Ok(Some(CacheStore::default())),
+                                items => serde_json::from_str(items)
+                                    .map(|items: BTreeMap<String, String>| Some(CacheStore::from(items)))
+                                    .map_err(|e| Error::InvalidMemcache {
+                                        details: format!(
+                                            "the items should be a JSON object of strings, details: {}",
+                                            e
+                                        ),
+                                    }),
+                            };
 
                             let listen_addr = if server_listen_addr.is_empty() {
                                 None
@@ -390,15 +1109,27 @@ impl epi::App for App {
                                 Some(server_listen_addr.as_str())
                             };
 
-                            new_server.run(listen_addr).err().iter().for_each(|e| {
-                                warn!("App: Error occurs when run server, details: {}", e);
-                                *server_run_flag = false;
+                            let res = store.and_then(|store| {
+                                let cache = cache?;
+                                let mut new_server = Server::new(msg_fmt.as_ref().unwrap().clone())
+                                    .resync(resync.clone().unwrap())
+                                    .framing(framing.as_ref().ok().cloned().flatten())
+                                    .modbus(server_registers.clone())
+                                    .redis(store.map(|store| Arc::new(Mutex::new(store))))
+                                    .memcache(cache.map(|cache| Arc::new(Mutex::new(cache))))
+                                    .websocket(websocket.as_ref().ok().cloned().flatten());
+                                new_server.run(listen_addr).map(|_| new_server)
                             });
-
-                            if *server_run_flag {
-                                *server_listen_addr =
-                                    new_server.listen_addr().as_ref().unwrap().clone();
-                                server.replace(new_server);
+                            match res {
+                                Ok(new_server) => {
+                                    *server_listen_addr =
+                                        new_server.listen_addr().as_ref().unwrap().clone();
+                                    server.replace(new_server);
+                                }
+                                Err(e) => {
+                                    warn!("App: Error occurs when run server, details: {}", e);
+                                    *server_run_flag = false;
+                                }
                             }
                         } else {
                             server.take().unwrap().stop();
@@ -427,7 +1158,67 @@ impl epi::App for App {
 
                     ui.label("Send to:");
                     ui.text_edit_singleline(server_target_addr);
+                    ui.end_row();
+
+                    // Modes should not be changed while running, and only one can be chosen.
+                    ui.label("Modbus/TCP:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*server_run_flag);
+                        if ui
+                            .checkbox(server_modbus, "Answer from the register table")
+                            .changed()
+                        {
+                            *server_redis = false;
+                            *server_memcache = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Redis:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*server_run_flag);
+                        if ui.checkbox(server_redis, "Answer the keys").changed() {
+                            *server_modbus = false;
+                            *server_memcache = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    if *server_redis {
+                        ui.label("Keys:");
+                        TextEdit::multiline(server_redis_keys)
+                            .hint_text(r#"{"key": "value"}"#)
+                            .enabled(!*server_run_flag)
+                            .desired_rows(2)
+                            .ui(ui);
+                        ui.end_row();
+                    }
+
+                    ui.label("memcached:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*server_run_flag);
+                        if ui.checkbox(server_memcache, "Answer the items").changed() {
+                            *server_modbus = false;
+                            *server_redis = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    if *server_memcache {
+                        ui.label("Items:");
+                        TextEdit::multiline(server_memcache_items)
+                            .hint_text(r#"{"key": "value"}"#)
+                            .enabled(!*server_run_flag)
+                            .desired_rows(2)
+                            .ui(ui);
+                        ui.end_row();
+                    }
+
+                    websocket_ui(ui, server_websocket, *server_run_flag, false);
                 });
+                if let Err(e) = &websocket {
+                    ui.label(format!("Parse error: {}", e));
+                }
 
                 if ui
                     .add(egui::Button::new("send message").enabled(*server_run_flag))
@@ -449,21 +1240,53 @@ impl epi::App for App {
                             );
                         });
                 }
+
+                // The first registers of the simulated table.
+                if let Some(registers) = server_registers.as_ref().filter(|_| *server_run_flag) {
+                    let registers = registers.lock().unwrap();
+                    ui.label(format!(
+                        "Coils: {}",
+                        registers.coils[..MODBUS_SHOWN_REGISTERS]
+                            .iter()
+                            .map(|coil| if *coil { "1" } else { "0" })
+                            .collect::<String>()
+                    ));
+                    ui.label(format!(
+                        "Holding registers: {:?}",
+                        &registers.holding_registers[..MODBUS_SHOWN_REGISTERS]
+                    ));
+                }
             });
 
             // Group for client.
             ui.group(|ui| {
+                let websocket = client_websocket.parse();
+                let mqtt = client_mqtt.parse();
                 ui.horizontal(|ui| {
                     ui.label("Client");
 
                     // Client shouldn't run if item formats is not valid.
                     if widget::Toggle::new(client_run_flag)
-                        .enabled(msg_fmt.is_some())
+                        .enabled(
+                            msg_fmt.is_some()
+                                && resync.is_ok()
+                                && framing.is_ok()
+                                && websocket.is_ok()
+                                && mqtt.is_ok(),
+                        )
                         .ui(ui)
                         .clicked()
                     {
                         if *client_run_flag {
-                            let mut new_client = Client::new(msg_fmt.as_ref().unwrap().clone());
+                            let mut new_client = Client::new(msg_fmt.as_ref().unwrap().clone())
+                                .resync(resync.clone().unwrap())
+                                .framing(framing.as_ref().ok().cloned().flatten())
+                                .modbus(*client_modbus)
+                                .redis(*client_redis)
+                                .memcache(*client_memcache)
+                                .http(*client_http)
+                                .websocket(websocket.as_ref().ok().cloned().flatten())
+                                .mqtt(mqtt.as_ref().ok().cloned().flatten());
 
                             let bind_addr = if client_bind_addr.is_empty() {
                                 None
@@ -507,24 +1330,301 @@ impl epi::App for App {
                         .enabled(!*client_run_flag)
                         .ui(ui);
                     ui.end_row();
+
+                    // Modes should not be changed while running, and only one can be chosen.
+                    ui.label("Modbus/TCP:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*client_run_flag);
+                        if ui
+                            .checkbox(client_modbus, "Send requests of function codes")
+                            .changed()
+                        {
+                            *client_redis = false;
+                            *client_memcache = false;
+                            *client_http = false;
+                            client_mqtt.enabled = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Redis:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*client_run_flag);
+                        if ui.checkbox(client_redis, "Send commands").changed() {
+                            *client_modbus = false;
+                            *client_memcache = false;
+                            *client_http = false;
+                            client_mqtt.enabled = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("memcached:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*client_run_flag);
+                        if ui.checkbox(client_memcache, "Send commands").changed() {
+                            *client_modbus = false;
+                            *client_redis = false;
+                            *client_http = false;
+                            client_mqtt.enabled = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("HTTP:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*client_run_flag);
+                        if ui.checkbox(client_http, "Send requests").changed() {
+                            *client_modbus = false;
+                            *client_redis = false;
+                            *client_memcache = false;
+                            client_mqtt.enabled = false;
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("MQTT:");
+                    ui.scope(|ui| {
+                        ui.set_enabled(!*client_run_flag);
+                        if ui
+                            .checkbox(&mut client_mqtt.enabled, "Publish messages to a broker")
+                            .changed()
+                        {
+                            *client_modbus = false;
+                            *client_redis = false;
+                            *client_memcache = false;
+                            *client_http = false;
+                        }
+                    });
+                    ui.end_row();
+                    if client_mqtt.enabled {
+                        mqtt_ui(ui, client_mqtt, *client_run_flag);
+                    }
+
+                    websocket_ui(ui, client_websocket, *client_run_flag, true);
                 });
+                if let Err(e) = &websocket {
+                    ui.label(format!("Parse error: {}", e));
+                }
+                if let Err(e) = &mqtt {
+                    ui.label(format!("Parse error: {}", e));
+                }
 
-                if ui
-                    .add(egui::Button::new("send message").enabled(*client_run_flag))
-                    .clicked()
-                {
-                    client
-                        .as_mut()
-                        .unwrap()
-                        .send_msg(Message::new(item_values.as_ref().unwrap().clone()))
-                        .err()
-                        .iter()
-                        .for_each(|e| {
-                            warn!(
-                                "App: Error occurs when send message to server, details: {}",
-                                e
-                            );
-                        });
+                ui.horizontal(|ui| {
+                    if ui
+                        .add(egui::Button::new("send message").enabled(*client_run_flag))
+                        .clicked()
+                    {
+                        client
+                            .as_mut()
+                            .unwrap()
+                            .send_msg(Message::new(item_values.as_ref().unwrap().clone()))
+                            .err()
+                            .iter()
+                            .for_each(|e| {
+                                warn!(
+                                    "App: Error occurs when send message to server, details: {}",
+                                    e
+                                );
+                            });
+                    }
+
+                    if (client_websocket.enabled || client_mqtt.enabled)
+                        && ui
+                            .add(egui::Button::new("send ping").enabled(*client_run_flag))
+                            .clicked()
+                    {
+                        client
+                            .as_mut()
+                            .unwrap()
+                            .send_ping(&[])
+                            .err()
+                            .iter()
+                            .for_each(|e| {
+                                warn!("App: Error occurs when send ping, details: {}", e);
+                            });
+                    }
+                });
+
+                // Request of the Modbus mode.
+                if *client_modbus {
+                    let mut parse_err = None;
+                    ui.horizontal(|ui| {
+                        egui::ComboBox::from_id_source("modbus_function")
+                            .selected_text(modbus_request.function.to_string())
+                            .show_ui(ui, |ui| {
+                                for f in ModbusFunctionWrapper::iter() {
+                                    ui.selectable_value(
+                                        &mut modbus_request.function,
+                                        f.clone(),
+                                        f.to_string(),
+                                    );
+                                }
+                            });
+                        ui.label("Unit:");
+                        TextEdit::singleline(&mut modbus_request.unit)
+                            .desired_width(30.0)
+                            .ui(ui);
+                        ui.label("Address:");
+                        TextEdit::singleline(&mut modbus_request.addr)
+                            .desired_width(60.0)
+                            .ui(ui);
+                        ui.label(modbus_request.arg_label());
+                        TextEdit::singleline(&mut modbus_request.arg)
+                            .hint_text("Separated by commas")
+                            .ui(ui);
+
+                        let req = modbus_request.parse();
+                        let clicked = Button::new("send request")
+                            .enabled(*client_run_flag && req.is_ok())
+                            .ui(ui)
+                            .clicked();
+                        match req {
+                            Ok((unit, req)) if clicked => client
+                                .as_mut()
+                                .unwrap()
+                                .send_modbus(unit, &req)
+                                .err()
+                                .iter()
+                                .for_each(|e| {
+                                    warn!(
+                                        "App: Error occurs when send Modbus request, details: {}",
+                                        e
+                                    );
+                                }),
+                            Ok(_) => {}
+                            Err(e) => parse_err = Some(e),
+                        }
+                    });
+                    if let Some(e) = parse_err {
+                        ui.label(format!("Parse error: {}", e));
+                    }
+                }
+
+                // Command of the Redis mode.
+                if *client_redis {
+                    ui.horizontal(|ui| {
+                        ui.label("Command:");
+                        TextEdit::singleline(redis_command)
+                            .hint_text(r#"SET key "a value""#)
+                            .ui(ui);
+                        if Button::new("send command")
+                            .enabled(*client_run_flag)
+                            .ui(ui)
+                            .clicked()
+                        {
+                            client
+                                .as_mut()
+                                .unwrap()
+                                .send_command(redis_command)
+                                .err()
+                                .iter()
+                                .for_each(|e| {
+                                    warn!(
+                                        "App: Error occurs when send Redis command, details: {}",
+                                        e
+                                    );
+                                });
+                        }
+                    });
+                }
+
+                // Command of the memcached mode.
+                if *client_memcache {
+                    ui.horizontal(|ui| {
+                        ui.label("Command:");
+                        TextEdit::singleline(memcache_command)
+                            .hint_text(r#"set key 0 0 "a value""#)
+                            .ui(ui);
+                        if Button::new("send command")
+                            .enabled(*client_run_flag)
+                            .ui(ui)
+                            .clicked()
+                        {
+                            client
+                                .as_mut()
+                                .unwrap()
+                                .send_memcache_command(memcache_command)
+                                .err()
+                                .iter()
+                                .for_each(|e| {
+                                    warn!(
+                                        "App: Error occurs when send memcached command, details: {}",
+                                        e
+                                    );
+                                });
+                        }
+                    });
+                }
+
+                // Subscription of the MQTT mode.
+                if client_mqtt.enabled {
+                    ui.horizontal(|ui| {
+                        ui.label("Topic filter:");
+                        TextEdit::singleline(&mut client_mqtt.filter)
+                            .hint_text("sensors/+/temperature")
+                            .ui(ui);
+                        let qos = client_mqtt.qos.parse::<u8>();
+                        if Button::new("subscribe")
+                            .enabled(*client_run_flag && qos.is_ok())
+                            .ui(ui)
+                            .clicked()
+                        {
+                            client
+                                .as_mut()
+                                .unwrap()
+                                .subscribe(&client_mqtt.filter, qos.unwrap())
+                                .err()
+                                .iter()
+                                .for_each(|e| {
+                                    warn!("App: Error occurs when subscribe, details: {}", e);
+                                });
+                        }
+                    });
+                }
+
+                // Request of the HTTP mode.
+                if *client_http {
+                    ui.horizontal(|ui| {
+                        TextEdit::singleline(&mut http_request.method)
+                            .desired_width(60.0)
+                            .ui(ui);
+                        TextEdit::singleline(&mut http_request.target)
+                            .hint_text("Target")
+                            .ui(ui);
+                        if Button::new("send request")
+                            .enabled(*client_run_flag)
+                            .ui(ui)
+                            .clicked()
+                        {
+                            let HttpRequestWrapper {
+                                method,
+                                target,
+                                headers,
+                                body,
+                            } = http_request.deref();
+                            client
+                                .as_mut()
+                                .unwrap()
+                                .send_request(method, target, headers, body)
+                                .err()
+                                .iter()
+                                .for_each(|e| {
+                                    warn!(
+                                        "App: Error occurs when send HTTP request, details: {}",
+                                        e
+                                    );
+                                });
+                        }
+                    });
+                    TextEdit::multiline(&mut http_request.headers)
+                        .hint_text("Name: value")
+                        .desired_rows(3)
+                        .ui(ui);
+                    TextEdit::multiline(&mut http_request.body)
+                        .hint_text("Body")
+                        .desired_rows(2)
+                        .ui(ui);
                 }
             });
         });
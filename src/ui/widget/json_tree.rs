@@ -0,0 +1,58 @@
+use eframe::egui::{self, CollapsingHeader, Id, Widget};
+use serde_json::Value;
+
+/// Read-only tree of a JSON value, arrays and objects are collapsible.
+///
+/// ```
+/// # let ui = &mut egui::Ui::__test();
+/// # let value = serde_json::json!({ "id": 1 });
+/// ui.add(widget::JsonTree::new("payload", &value));
+/// ```
+#[must_use = "You should put this widget in an ui with `ui.add(widget);`"]
+pub struct JsonTree<'a> {
+    id: Id,
+    value: &'a Value,
+}
+
+impl<'a> JsonTree<'a> {
+    pub fn new(id_source: impl std::hash::Hash, value: &'a Value) -> Self {
+        Self {
+            id: Id::new(id_source),
+            value,
+        }
+    }
+}
+
+fn show_value(ui: &mut egui::Ui, id: Id, key: &str, value: &Value) -> egui::Response {
+    let (children, summary): (Vec<(String, &Value)>, _) = match value {
+        Value::Array(array) => (
+            array
+                .iter()
+                .enumerate()
+                .map(|(idx, v)| (format!("[{}]", idx), v))
+                .collect(),
+            format!("{}: [{}]", key, array.len()),
+        ),
+        Value::Object(obj) => (
+            obj.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            format!("{}: {{{}}}", key, obj.len()),
+        ),
+        _ => return ui.monospace(format!("{}: {}", key, value)),
+    };
+
+    CollapsingHeader::new(summary)
+        .id_source(id)
+        .default_open(true)
+        .show(ui, |ui| {
+            for (k, v) in children {
+                show_value(ui, id.with(&k), &k, v);
+            }
+        })
+        .header_response
+}
+
+impl<'a> Widget for JsonTree<'a> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        show_value(ui, self.id, "value", self.value)
+    }
+}
@@ -1,3 +1,5 @@
+mod json_tree;
 mod toggle;
 
+pub(super) use json_tree::*;
 pub(super) use toggle::*;
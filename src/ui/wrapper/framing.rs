@@ -0,0 +1,227 @@
+use socket_toolbox::Framing;
+
+use super::msg::{parse_integer, ParseError, ParseResult};
+
+#[derive(Debug, Clone, PartialEq, strum_macros::Display, strum_macros::EnumIter)]
+pub enum FramingKindWrapper {
+    None,
+    Delimiter,
+    #[strum(to_string = "SLIP")]
+    Slip,
+    #[strum(to_string = "COBS")]
+    Cobs,
+    #[strum(to_string = "HDLC")]
+    Hdlc,
+    LengthPrefix,
+    VarintPrefix,
+    #[strum(to_string = "RESP")]
+    Resp,
+    #[strum(to_string = "HTTP")]
+    Http,
+    #[strum(to_string = "MQTT")]
+    Mqtt,
+    #[strum(to_string = "STOMP")]
+    Stomp,
+    #[strum(to_string = "memcached")]
+    Memcache,
+}
+
+impl FramingKindWrapper {
+    pub fn from_framing(framing: &FramingWrapper) -> Self {
+        match framing {
+            FramingWrapper::None => Self::None,
+            FramingWrapper::Delimiter { .. } => Self::Delimiter,
+            FramingWrapper::Slip { .. } => Self::Slip,
+            FramingWrapper::Cobs { .. } => Self::Cobs,
+            FramingWrapper::Hdlc { .. } => Self::Hdlc,
+            FramingWrapper::LengthPrefix { .. } => Self::LengthPrefix,
+            FramingWrapper::VarintPrefix { .. } => Self::VarintPrefix,
+            FramingWrapper::Resp { .. } => Self::Resp,
+            FramingWrapper::Http { .. } => Self::Http,
+            FramingWrapper::Mqtt => Self::Mqtt,
+            FramingWrapper::Stomp { .. } => Self::Stomp,
+            FramingWrapper::Memcache { .. } => Self::Memcache,
+        }
+    }
+
+    pub fn default_framing(&self) -> FramingWrapper {
+        match self {
+            Self::None => FramingWrapper::None,
+            Self::Delimiter => FramingWrapper::Delimiter {
+                delimiter: "0d0a".to_string(),
+                max_len: 4096.to_string(),
+            },
+            Self::Slip => FramingWrapper::Slip {
+                max_len: 4096.to_string(),
+            },
+            Self::Cobs => FramingWrapper::Cobs {
+                max_len: 4096.to_string(),
+            },
+            Self::Hdlc => FramingWrapper::Hdlc {
+                max_len: 4096.to_string(),
+            },
+            Self::LengthPrefix => FramingWrapper::LengthPrefix {
+                width: 4.to_string(),
+                big_endian: true,
+                includes_self: false,
+                max_len: 65536.to_string(),
+            },
+            Self::VarintPrefix => FramingWrapper::VarintPrefix {
+                max_len: 65536.to_string(),
+            },
+            Self::Resp => FramingWrapper::Resp {
+                max_len: 1048576.to_string(),
+            },
+            Self::Http => FramingWrapper::Http {
+                max_len: 1048576.to_string(),
+            },
+            Self::Mqtt => FramingWrapper::Mqtt,
+            Self::Stomp => FramingWrapper::Stomp {
+                max_len: 1048576.to_string(),
+            },
+            Self::Memcache => FramingWrapper::Memcache {
+                max_len: 1048576.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum FramingWrapper {
+    #[default]
+    None,
+    Delimiter {
+        delimiter: String,
+        max_len: String,
+    },
+    Slip {
+        max_len: String,
+    },
+    Cobs {
+        max_len: String,
+    },
+    Hdlc {
+        max_len: String,
+    },
+    LengthPrefix {
+        width: String,
+        big_endian: bool,
+        includes_self: bool,
+        max_len: String,
+    },
+    VarintPrefix {
+        max_len: String,
+    },
+    Resp {
+        max_len: String,
+    },
+    Http {
+        max_len: String,
+    },
+    Mqtt,
+    Stomp {
+        max_len: String,
+    },
+    Memcache {
+        max_len: String,
+    },
+}
+
+impl From<&Option<Framing>> for FramingWrapper {
+    fn from(framing: &Option<Framing>) -> Self {
+        match framing {
+            None => Self::None,
+            Some(Framing::Delimiter { delimiter, max_len }) => Self::Delimiter {
+                delimiter: hex::encode(delimiter),
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Slip { max_len }) => Self::Slip {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Cobs { max_len }) => Self::Cobs {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Hdlc { max_len }) => Self::Hdlc {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::LengthPrefix {
+                width,
+                big_endian,
+                includes_self,
+                max_len,
+            }) => Self::LengthPrefix {
+                width: width.to_string(),
+                big_endian: *big_endian,
+                includes_self: *includes_self,
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::VarintPrefix { max_len }) => Self::VarintPrefix {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Resp { max_len }) => Self::Resp {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Http { max_len }) => Self::Http {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Mqtt) => Self::Mqtt,
+            Some(Framing::Stomp { max_len }) => Self::Stomp {
+                max_len: max_len.to_string(),
+            },
+            Some(Framing::Memcache { max_len }) => Self::Memcache {
+                max_len: max_len.to_string(),
+            },
+        }
+    }
+}
+
+impl FramingWrapper {
+    pub fn parse(&self) -> ParseResult<Option<Framing>> {
+        match self {
+            Self::None => Ok(None),
+            Self::Delimiter { delimiter, max_len } => Ok(Some(Framing::Delimiter {
+                delimiter: hex::decode(delimiter).map_err(|e| ParseError::Bytes {
+                    s: delimiter.clone(),
+                    e,
+                })?,
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Slip { max_len } => Ok(Some(Framing::Slip {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Cobs { max_len } => Ok(Some(Framing::Cobs {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Hdlc { max_len } => Ok(Some(Framing::Hdlc {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::LengthPrefix {
+                width,
+                big_endian,
+                includes_self,
+                max_len,
+            } => Ok(Some(Framing::LengthPrefix {
+                width: parse_integer::<usize>(width)?,
+                big_endian: *big_endian,
+                includes_self: *includes_self,
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::VarintPrefix { max_len } => Ok(Some(Framing::VarintPrefix {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Resp { max_len } => Ok(Some(Framing::Resp {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Http { max_len } => Ok(Some(Framing::Http {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Mqtt => Ok(Some(Framing::Mqtt)),
+            Self::Stomp { max_len } => Ok(Some(Framing::Stomp {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+            Self::Memcache { max_len } => Ok(Some(Framing::Memcache {
+                max_len: parse_integer::<usize>(max_len)?,
+            })),
+        }
+    }
+}
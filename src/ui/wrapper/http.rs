@@ -0,0 +1,19 @@
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequestWrapper {
+    pub method: String,
+    pub target: String,
+    /// Lines of `Name: value`.
+    pub headers: String,
+    pub body: String,
+}
+
+impl Default for HttpRequestWrapper {
+    fn default() -> Self {
+        Self {
+            method: "GET".to_string(),
+            target: "/".to_string(),
+            headers: "Host: localhost".to_string(),
+            body: Default::default(),
+        }
+    }
+}
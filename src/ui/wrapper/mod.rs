@@ -1,3 +1,13 @@
+mod framing;
+mod http;
+mod modbus;
+mod mqtt;
 mod msg;
+mod websocket;
 
+pub(super) use framing::*;
+pub(super) use http::*;
+pub(super) use modbus::*;
+pub(super) use mqtt::*;
 pub(super) use msg::*;
+pub(super) use websocket::*;
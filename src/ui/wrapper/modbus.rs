@@ -0,0 +1,96 @@
+use socket_toolbox::ModbusRequest as Request;
+
+use super::msg::{parse_integer, ParseResult};
+
+#[derive(Debug, Clone, PartialEq, Default, strum_macros::Display, strum_macros::EnumIter)]
+pub enum ModbusFunctionWrapper {
+    #[default]
+    #[strum(to_string = "Read coils")]
+    ReadCoils,
+    #[strum(to_string = "Read holding registers")]
+    ReadHoldingRegisters,
+    #[strum(to_string = "Write single coil")]
+    WriteSingleCoil,
+    #[strum(to_string = "Write single register")]
+    WriteSingleRegister,
+    #[strum(to_string = "Write multiple coils")]
+    WriteMultipleCoils,
+    #[strum(to_string = "Write multiple registers")]
+    WriteMultipleRegisters,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModbusRequestWrapper {
+    pub function: ModbusFunctionWrapper,
+    pub unit: String,
+    pub addr: String,
+    /// The count of reads, or values of writes separated by commas, coils are 0 or 1.
+    pub arg: String,
+}
+
+impl Default for ModbusRequestWrapper {
+    fn default() -> Self {
+        Self {
+            function: Default::default(),
+            unit: 1.to_string(),
+            addr: 0.to_string(),
+            arg: 1.to_string(),
+        }
+    }
+}
+
+impl ModbusRequestWrapper {
+    pub fn arg_label(&self) -> &'static str {
+        match self.function {
+            ModbusFunctionWrapper::ReadCoils | ModbusFunctionWrapper::ReadHoldingRegisters => {
+                "Count:"
+            }
+            ModbusFunctionWrapper::WriteSingleCoil | ModbusFunctionWrapper::WriteSingleRegister => {
+                "Value:"
+            }
+            ModbusFunctionWrapper::WriteMultipleCoils
+            | ModbusFunctionWrapper::WriteMultipleRegisters => "Values:",
+        }
+    }
+
+    /// Returns the unit id and the request.
+    pub fn parse(&self) -> ParseResult<(u8, Request)> {
+        let unit = parse_integer::<u8>(&self.unit)?;
+        let addr = parse_integer::<u16>(&self.addr)?;
+        let values = || {
+            self.arg
+                .split(',')
+                .map(|s| parse_integer::<u16>(s.trim()))
+                .collect::<ParseResult<Vec<_>>>()
+        };
+        let coils = || values().map(|v| v.into_iter().map(|v| v != 0).collect());
+
+        let req = match self.function {
+            ModbusFunctionWrapper::ReadCoils => Request::ReadCoils {
+                addr,
+                count: parse_integer(&self.arg)?,
+            },
+            ModbusFunctionWrapper::ReadHoldingRegisters => Request::ReadHoldingRegisters {
+                addr,
+                count: parse_integer(&self.arg)?,
+            },
+            ModbusFunctionWrapper::WriteSingleCoil => Request::WriteSingleCoil {
+                addr,
+                value: parse_integer::<u8>(&self.arg)? != 0,
+            },
+            ModbusFunctionWrapper::WriteSingleRegister => Request::WriteSingleRegister {
+                addr,
+                value: parse_integer(&self.arg)?,
+            },
+            ModbusFunctionWrapper::WriteMultipleCoils => Request::WriteMultipleCoils {
+                addr,
+                values: coils()?,
+            },
+            ModbusFunctionWrapper::WriteMultipleRegisters => Request::WriteMultipleRegisters {
+                addr,
+                values: values()?,
+            },
+        };
+        Ok((unit, req))
+    }
+}
@@ -0,0 +1,48 @@
+use socket_toolbox::{Mqtt, MqttVersion as Version};
+
+use super::msg::{parse_integer, ParseResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MqttWrapper {
+    pub enabled: bool,
+    pub v5: bool,
+    pub client_id: String,
+    pub keep_alive: String,
+    pub topic: String,
+    /// QoS of publishes and subscriptions.
+    pub qos: String,
+    pub retain: bool,
+    pub filter: String,
+}
+
+impl Default for MqttWrapper {
+    fn default() -> Self {
+        let mqtt = Mqtt::default();
+        Self {
+            enabled: false,
+            v5: mqtt.version == Version::V5,
+            client_id: mqtt.client_id,
+            keep_alive: mqtt.keep_alive.to_string(),
+            topic: mqtt.topic,
+            qos: mqtt.qos.to_string(),
+            retain: mqtt.retain,
+            filter: "#".to_string(),
+        }
+    }
+}
+
+impl MqttWrapper {
+    pub fn parse(&self) -> ParseResult<Option<Mqtt>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        Ok(Some(Mqtt {
+            version: if self.v5 { Version::V5 } else { Version::V311 },
+            client_id: self.client_id.clone(),
+            keep_alive: parse_integer(&self.keep_alive)?,
+            topic: self.topic.clone(),
+            qos: parse_integer(&self.qos)?,
+            retain: self.retain,
+        }))
+    }
+}
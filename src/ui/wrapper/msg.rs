@@ -1,10 +1,10 @@
-use std::{num::ParseIntError, str::FromStr};
+use std::{fmt, num::ParseIntError, str::FromStr, sync::Arc};
 
 use hex::FromHexError;
 
-use crate::{
-    error::Error,
-    msg::{ItemFormat, ItemValue},
+use socket_toolbox::{
+    msg::{ItemFormat, ItemValue, PayloadLen},
+    Checksum, Error, ProtoSchema,
 };
 
 #[derive(Debug, Clone, PartialEq, strum_macros::ToString, strum_macros::EnumIter)]
@@ -16,6 +16,26 @@ pub enum ItemKindWrapper {
     VarString,
     FixedBytes,
     VarBytes,
+    Checksum,
+    /// Can only be imported, since the schema is needed.
+    Protobuf,
+    #[strum(to_string = "CBOR")]
+    Cbor,
+    MessagePack,
+    #[strum(to_string = "JSON")]
+    Json,
+    #[strum(to_string = "BER")]
+    Ber,
+    #[strum(to_string = "RESP")]
+    Resp,
+    #[strum(to_string = "HTTP")]
+    Http,
+    #[strum(to_string = "STOMP")]
+    Stomp,
+    #[strum(to_string = "memcached")]
+    Memcache,
+    #[strum(to_string = "TLV")]
+    Tlv,
 }
 
 impl ItemKindWrapper {
@@ -28,6 +48,17 @@ impl ItemKindWrapper {
             ItemFormatWrapper::VarString { .. } => Self::VarString,
             ItemFormatWrapper::FixedBytes { .. } => Self::FixedBytes,
             ItemFormatWrapper::VarBytes { .. } => Self::VarBytes,
+            ItemFormatWrapper::Checksum { .. } => Self::Checksum,
+            ItemFormatWrapper::Protobuf { .. } => Self::Protobuf,
+            ItemFormatWrapper::Cbor { .. } => Self::Cbor,
+            ItemFormatWrapper::MessagePack { .. } => Self::MessagePack,
+            ItemFormatWrapper::Json { .. } => Self::Json,
+            ItemFormatWrapper::Ber { .. } => Self::Ber,
+            ItemFormatWrapper::Resp { .. } => Self::Resp,
+            ItemFormatWrapper::Http { .. } => Self::Http,
+            ItemFormatWrapper::Stomp { .. } => Self::Stomp,
+            ItemFormatWrapper::Memcache { .. } => Self::Memcache,
+            ItemFormatWrapper::Tlv { .. } => Self::Tlv,
         }
     }
 
@@ -44,18 +75,66 @@ impl ItemKindWrapper {
             Self::VarBytes => ItemFormatWrapper::VarBytes {
                 len_idx: 0.to_string(),
             },
+            Self::Checksum => ItemFormatWrapper::Checksum {
+                checksum: Checksum::Crc16,
+                start: 0.to_string(),
+                end: 1.to_string(),
+            },
+            Self::Protobuf => unreachable!("protobuf items can only be imported"),
+            Self::Cbor => ItemFormatWrapper::Cbor {
+                len_idx: 0.to_string(),
+            },
+            Self::MessagePack => ItemFormatWrapper::MessagePack {
+                len_idx: 0.to_string(),
+            },
+            Self::Json => ItemFormatWrapper::Json {
+                len: 0.to_string(),
+                fixed: false,
+            },
+            Self::Ber => ItemFormatWrapper::Ber {
+                len_idx: 0.to_string(),
+            },
+            Self::Resp => ItemFormatWrapper::Resp {
+                len_idx: 0.to_string(),
+            },
+            Self::Http => ItemFormatWrapper::Http {
+                len_idx: 0.to_string(),
+            },
+            Self::Stomp => ItemFormatWrapper::Stomp {
+                len_idx: 0.to_string(),
+            },
+            Self::Memcache => ItemFormatWrapper::Memcache {
+                len_idx: 0.to_string(),
+            },
+            Self::Tlv => ItemFormatWrapper::Tlv {
+                len_idx: 0.to_string(),
+                tag_width: 1.to_string(),
+                len_width: 1.to_string(),
+            },
         }
     }
 
     pub fn default_item_value(&self) -> ItemValueWrapper {
         match self {
             Self::Len => ItemValueWrapper::Len(0),
-            Self::Uint => ItemValueWrapper::Uint(0.to_string()),
+            Self::Uint | Self::Checksum => ItemValueWrapper::Uint(0.to_string()),
             Self::Int => ItemValueWrapper::Int(0.to_string()),
             Self::FixedString => ItemValueWrapper::String(Default::default()),
             Self::VarString => ItemValueWrapper::String(Default::default()),
             Self::FixedBytes => ItemValueWrapper::Bytes(Default::default()),
             Self::VarBytes => ItemValueWrapper::Bytes(Default::default()),
+            Self::Protobuf | Self::Cbor | Self::MessagePack | Self::Json => {
+                ItemValueWrapper::Json("{}".to_string())
+            }
+            Self::Ber | Self::Resp | Self::Tlv => ItemValueWrapper::Json("[]".to_string()),
+            Self::Http => ItemValueWrapper::Json(
+                r#"{"method": "GET", "target": "/", "headers": [], "body": ""}"#.to_string(),
+            ),
+            Self::Stomp => ItemValueWrapper::Json(
+                r#"{"command": "SEND", "headers": [["destination", "/queue/a"]], "body": ""}"#
+                    .to_string(),
+            ),
+            Self::Memcache => ItemValueWrapper::Json(r#"[{"line": "get key"}]"#.to_string()),
         }
     }
 }
@@ -67,11 +146,13 @@ pub enum ItemValueWrapper {
     Int(String),
     String(String),
     Bytes(String),
+    Json(String),
 }
 
 pub enum ParseError {
     Integer { s: String, e: ParseIntError },
     Bytes { s: String, e: FromHexError },
+    Json { s: String, details: String },
 }
 
 impl ParseError {
@@ -87,13 +168,38 @@ impl ParseError {
                 item_idx,
                 e: *e,
             },
+            ParseError::Json { s, details } => Error::JsonParse {
+                s: s.clone(),
+                item_idx,
+                details: details.clone(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Integer { s, e } => {
+                write!(f, "`{}` couldn't be parsed to a integer, details: {}", s, e)
+            }
+            ParseError::Bytes { s, e } => {
+                write!(f, "`{}` couldn't be parsed to bytes, details: {}", s, e)
+            }
+            ParseError::Json { s, details } => {
+                write!(
+                    f,
+                    "`{}` couldn't be parsed to JSON, details: {}",
+                    s, details
+                )
+            }
         }
     }
 }
 
 pub type ParseResult<T> = Result<T, ParseError>;
 
-fn parse_integer<T>(s: &str) -> ParseResult<T>
+pub(super) fn parse_integer<T>(s: &str) -> ParseResult<T>
 where
     T: FromStr<Err = ParseIntError>,
 {
@@ -113,6 +219,14 @@ impl ItemValueWrapper {
             Self::Bytes(s) => hex::decode(s)
                 .map(ItemValue::Bytes)
                 .map_err(|e| ParseError::Bytes { s: s.clone(), e }),
+            Self::Json(s) => {
+                serde_json::from_str(s)
+                    .map(ItemValue::Json)
+                    .map_err(|e| ParseError::Json {
+                        s: s.clone(),
+                        details: e.to_string(),
+                    })
+            }
         }
     }
 }
@@ -125,19 +239,102 @@ impl From<&ItemValue> for ItemValueWrapper {
             ItemValue::Int(v) => Self::Int(v.to_string()),
             ItemValue::String(s) => Self::String(s.clone()),
             ItemValue::Bytes(bytes) => Self::Bytes(hex::encode(bytes)),
+            ItemValue::Json(v) => Self::Json(serde_json::to_string_pretty(v).unwrap()),
         }
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ItemFormatWrapper {
-    Len { len: String },
-    Uint { len: String },
-    Int { len: String },
-    FixedString { len: String },
-    VarString { len_idx: String },
-    FixedBytes { len: String },
-    VarBytes { len_idx: String },
+    Len {
+        len: String,
+    },
+    Uint {
+        len: String,
+    },
+    Int {
+        len: String,
+    },
+    FixedString {
+        len: String,
+    },
+    VarString {
+        len_idx: String,
+    },
+    FixedBytes {
+        len: String,
+    },
+    VarBytes {
+        len_idx: String,
+    },
+    /// Covers the items `start..end`.
+    Checksum {
+        checksum: Checksum,
+        start: String,
+        end: String,
+    },
+    /// Greedy if the index of length is empty.
+    Protobuf {
+        len_idx: String,
+        schema: Arc<ProtoSchema>,
+    },
+    /// Greedy if the index of length is empty.
+    Cbor {
+        len_idx: String,
+    },
+    /// Greedy if the index of length is empty.
+    MessagePack {
+        len_idx: String,
+    },
+    /// `len` is the length if fixed, otherwise the index of length, which is greedy if empty.
+    Json {
+        len: String,
+        fixed: bool,
+    },
+    /// Greedy if the index of length is empty.
+    Ber {
+        len_idx: String,
+    },
+    /// Greedy if the index of length is empty.
+    Resp {
+        len_idx: String,
+    },
+    /// Greedy if the index of length is empty.
+    Http {
+        len_idx: String,
+    },
+    /// Greedy if the index of length is empty.
+    Stomp {
+        len_idx: String,
+    },
+    /// Greedy if the index of length is empty.
+    Memcache {
+        len_idx: String,
+    },
+    /// Greedy if the index of length is empty.
+    Tlv {
+        len_idx: String,
+        tag_width: String,
+        len_width: String,
+    },
+}
+
+fn parse_payload_len(len_idx: &str) -> ParseResult<PayloadLen> {
+    if len_idx.is_empty() {
+        Ok(PayloadLen::Greedy)
+    } else {
+        Ok(PayloadLen::Var {
+            len_idx: parse_integer::<usize>(len_idx)?,
+        })
+    }
+}
+
+fn payload_len_idx(len: &PayloadLen) -> String {
+    match len {
+        PayloadLen::Var { len_idx } => len_idx.to_string(),
+        PayloadLen::Greedy => String::new(),
+        PayloadLen::Fixed { .. } => unreachable!("only JSON payloads can have a fixed length"),
+    }
 }
 
 impl ItemFormatWrapper {
@@ -158,6 +355,136 @@ impl ItemFormatWrapper {
             Self::VarBytes { len_idx } => {
                 parse_integer::<usize>(len_idx).map(|len_idx| ItemFormat::VarBytes { len_idx })
             }
+            Self::Checksum {
+                checksum,
+                start,
+                end,
+            } => Ok(ItemFormat::Checksum {
+                checksum: *checksum,
+                start: parse_integer::<usize>(start)?,
+                end: parse_integer::<usize>(end)?,
+            }),
+            Self::Protobuf { len_idx, schema } => Ok(ItemFormat::Protobuf {
+                len: parse_payload_len(len_idx)?,
+                schema: schema.clone(),
+            }),
+            Self::Cbor { len_idx } => {
+                parse_payload_len(len_idx).map(|len| ItemFormat::Cbor { len })
+            }
+            Self::MessagePack { len_idx } => {
+                parse_payload_len(len_idx).map(|len| ItemFormat::MessagePack { len })
+            }
+            Self::Json { len, fixed: true } => {
+                parse_integer::<usize>(len).map(|len| ItemFormat::Json {
+                    len: PayloadLen::Fixed { len },
+                })
+            }
+            Self::Json { len, fixed: false } => {
+                parse_payload_len(len).map(|len| ItemFormat::Json { len })
+            }
+            Self::Ber { len_idx } => parse_payload_len(len_idx).map(|len| ItemFormat::Ber { len }),
+            Self::Resp { len_idx } => {
+                parse_payload_len(len_idx).map(|len| ItemFormat::Resp { len })
+            }
+            Self::Http { len_idx } => {
+                parse_payload_len(len_idx).map(|len| ItemFormat::Http { len })
+            }
+            Self::Stomp { len_idx } => {
+                parse_payload_len(len_idx).map(|len| ItemFormat::Stomp { len })
+            }
+            Self::Memcache { len_idx } => {
+                parse_payload_len(len_idx).map(|len| ItemFormat::Memcache { len })
+            }
+            Self::Tlv {
+                len_idx,
+                tag_width,
+                len_width,
+            } => Ok(ItemFormat::Tlv {
+                len: parse_payload_len(len_idx)?,
+                tag_width: parse_integer::<usize>(tag_width)?,
+                len_width: parse_integer::<usize>(len_width)?,
+            }),
+        }
+    }
+}
+
+impl From<&ItemFormat> for ItemFormatWrapper {
+    fn from(fmt: &ItemFormat) -> Self {
+        match fmt {
+            ItemFormat::Len { len } => Self::Len {
+                len: len.to_string(),
+            },
+            ItemFormat::Uint { len } => Self::Uint {
+                len: len.to_string(),
+            },
+            ItemFormat::Int { len } => Self::Int {
+                len: len.to_string(),
+            },
+            ItemFormat::FixedString { len } => Self::FixedString {
+                len: len.to_string(),
+            },
+            ItemFormat::VarString { len_idx } => Self::VarString {
+                len_idx: len_idx.to_string(),
+            },
+            ItemFormat::FixedBytes { len } => Self::FixedBytes {
+                len: len.to_string(),
+            },
+            ItemFormat::VarBytes { len_idx } => Self::VarBytes {
+                len_idx: len_idx.to_string(),
+            },
+            ItemFormat::Checksum {
+                checksum,
+                start,
+                end,
+            } => Self::Checksum {
+                checksum: *checksum,
+                start: start.to_string(),
+                end: end.to_string(),
+            },
+            ItemFormat::Protobuf { len, schema } => Self::Protobuf {
+                len_idx: payload_len_idx(len),
+                schema: schema.clone(),
+            },
+            ItemFormat::Cbor { len } => Self::Cbor {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::MessagePack { len } => Self::MessagePack {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::Json {
+                len: PayloadLen::Fixed { len },
+            } => Self::Json {
+                len: len.to_string(),
+                fixed: true,
+            },
+            ItemFormat::Json { len } => Self::Json {
+                len: payload_len_idx(len),
+                fixed: false,
+            },
+            ItemFormat::Ber { len } => Self::Ber {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::Resp { len } => Self::Resp {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::Http { len } => Self::Http {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::Stomp { len } => Self::Stomp {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::Memcache { len } => Self::Memcache {
+                len_idx: payload_len_idx(len),
+            },
+            ItemFormat::Tlv {
+                len,
+                tag_width,
+                len_width,
+            } => Self::Tlv {
+                len_idx: payload_len_idx(len),
+                tag_width: tag_width.to_string(),
+                len_width: len_width.to_string(),
+            },
         }
     }
 }
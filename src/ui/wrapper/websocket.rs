@@ -0,0 +1,36 @@
+use socket_toolbox::WebSocket;
+
+use super::msg::{parse_integer, ParseResult};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocketWrapper {
+    pub enabled: bool,
+    /// Only for clients.
+    pub path: String,
+    pub text: bool,
+    pub max_frame_len: String,
+}
+
+impl Default for WebSocketWrapper {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: "/".to_string(),
+            text: false,
+            max_frame_len: 0.to_string(),
+        }
+    }
+}
+
+impl WebSocketWrapper {
+    pub fn parse(&self) -> ParseResult<Option<WebSocket>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        Ok(Some(WebSocket {
+            path: self.path.clone(),
+            text: self.text,
+            max_frame_len: parse_integer(&self.max_frame_len)?,
+        }))
+    }
+}
@@ -0,0 +1,538 @@
+//! WebSocket of RFC 6455 over TCP, each WebSocket message contains a message of the format.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use log::info;
+use serde_json::Value;
+
+use crate::{
+    error::{Error, Result},
+    framing::Framer,
+    payload::{http_decode, http_header},
+};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// Handshakes larger than this are rejected.
+const MAX_HEAD_LEN: usize = 8192;
+// Messages larger than this are rejected, so that the memory is not exhausted.
+const MAX_MESSAGE_LEN: usize = 16 << 20;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xa;
+
+/// The status code of a normal closure.
+pub const CLOSE_NORMAL: u16 = 1000;
+
+/// Options of the WebSocket transport.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebSocket {
+    /// The resource requested by clients, such as `/chat`.
+    pub path: String,
+    /// Messages are sent in text frames rather than binary ones.
+    pub text: bool,
+    /// Messages longer than this are sent in fragments, no limit if `0`.
+    pub max_frame_len: usize,
+}
+
+impl Default for WebSocket {
+    fn default() -> Self {
+        Self {
+            path: "/".to_string(),
+            text: false,
+            max_frame_len: 0,
+        }
+    }
+}
+
+fn invalid(details: impl Into<String>) -> Error {
+    Error::InvalidWebSocket {
+        details: details.into(),
+    }
+}
+
+// Random bytes for keys and masks, which only need to be unpredictable to peers.
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    for chunk in bytes.chunks_mut(8) {
+        let v = RandomState::new().build_hasher().finish().to_be_bytes();
+        chunk.copy_from_slice(&v[..chunk.len()]);
+    }
+    bytes
+}
+
+/// The `Sec-WebSocket-Accept` of a `Sec-WebSocket-Key`.
+pub fn accept_key(key: &str) -> String {
+    let mut sha1 = sha1_smol::Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(GUID.as_bytes());
+    base64::encode(sha1.digest().bytes())
+}
+
+// Reads the head of an HTTP message byte by byte, so that no byte of frames is consumed.
+fn read_head(stream: &mut impl Read) -> Result<Value> {
+    let mut head = Vec::new();
+    let mut byte = [0];
+    while !head.ends_with(b"\r\n\r\n") {
+        if head.len() >= MAX_HEAD_LEN {
+            return Err(invalid("the handshake is too large"));
+        }
+        match stream.read(&mut byte) {
+            Ok(0) => return Err(Error::EndOfStream),
+            Ok(_) => head.push(byte[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(Error::Io(e)),
+        }
+    }
+    http_decode(&head).map_err(|e| invalid(e.to_string()))
+}
+
+fn header<'a>(head: &'a Value, name: &str) -> Option<&'a str> {
+    http_header(head["headers"].as_array()?, name)
+}
+
+/// Opens the handshake of a client, `host` is the `Host` header.
+pub fn connect<S: Read + Write>(stream: &mut S, host: &str, ws: &WebSocket) -> Result<()> {
+    let key = base64::encode(random_bytes::<16>());
+    let req = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        ws.path, host, key
+    );
+    stream.write_all(req.as_bytes()).map_err(Error::Io)?;
+
+    let resp = read_head(stream)?;
+    if resp["status"] != 101 {
+        return Err(invalid(format!(
+            "the server responded `{} {}`",
+            resp["status"],
+            resp["reason"].as_str().unwrap_or_default()
+        )));
+    }
+    match header(&resp, "Sec-WebSocket-Accept") {
+        Some(accept) if accept == accept_key(&key) => Ok(()),
+        _ => Err(invalid("`Sec-WebSocket-Accept` doesn't match the key")),
+    }
+}
+
+/// Accepts the handshake of a client, returns the requested path.
+///
+/// Invalid handshakes are answered by `400 Bad Request`.
+pub fn accept<S: Read + Write>(stream: &mut S) -> Result<String> {
+    let req = read_head(stream)?;
+    let key = match (
+        req["method"].as_str(),
+        header(&req, "Upgrade"),
+        header(&req, "Sec-WebSocket-Version"),
+        header(&req, "Sec-WebSocket-Key"),
+    ) {
+        (Some("GET"), Some(upgrade), Some("13"), Some(key))
+            if upgrade.eq_ignore_ascii_case("websocket") =>
+        {
+            key
+        }
+        _ => {
+            stream
+                .write_all(
+                    b"HTTP/1.1 400 Bad Request\r\nSec-WebSocket-Version: 13\r\n\
+                      Content-Length: 0\r\n\r\n",
+                )
+                .ok();
+            return Err(invalid("the request is not a WebSocket handshake"));
+        }
+    };
+
+    let resp = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    stream.write_all(resp.as_bytes()).map_err(Error::Io)?;
+    Ok(req["target"].as_str().unwrap_or_default().to_string())
+}
+
+/// Encodes a frame, frames sent by clients are masked.
+pub fn encode_frame(opcode: u8, fin: bool, payload: &[u8], masked: bool) -> Vec<u8> {
+    let mut frame = vec![if fin { 0x80 | opcode } else { opcode }];
+    let mask_bit = if masked { 0x80 } else { 0 };
+    match payload.len() {
+        len if len < 126 => frame.push(mask_bit | len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    if masked {
+        let mask = random_bytes::<4>();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+    } else {
+        frame.extend_from_slice(payload);
+    }
+    frame
+}
+
+/// A stream written by both the reader, which answers pings and closes, and the writer,
+/// each write is a whole frame so that frames aren't interleaved.
+#[derive(Clone)]
+pub struct SharedWriter(pub Arc<Mutex<dyn Write + Send>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+/// Each frame of the framer is the payload of a WebSocket message, whose fragments are
+/// joined. Pings are answered by pongs, and a close ends the stream after it's answered.
+pub struct WebSocketFramer {
+    client: bool,
+    ws: WebSocket,
+    replies: Option<SharedWriter>,
+
+    // The opcode and the payload of a fragmented message.
+    message: Option<(u8, Vec<u8>)>,
+}
+
+impl WebSocketFramer {
+    /// Frames of clients are masked, and frames of the peer are expected the other way.
+    pub fn new(client: bool, ws: WebSocket) -> Self {
+        Self {
+            client,
+            ws,
+            replies: None,
+            message: None,
+        }
+    }
+
+    /// Where pongs and closes are written, control frames aren't answered without it.
+    pub fn replies(mut self, replies: Option<SharedWriter>) -> Self {
+        self.replies = replies;
+        self
+    }
+
+    fn reply(&mut self, opcode: u8, payload: &[u8]) {
+        if let Some(replies) = &mut self.replies {
+            replies
+                .write_all(&encode_frame(opcode, true, payload, self.client))
+                .ok();
+        }
+    }
+
+    // Takes a frame from the buffer, returns the FIN, the opcode and the unmasked payload.
+    fn take_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<(bool, u8, Vec<u8>)>> {
+        let (b0, b1) = match buf.as_slice() {
+            [b0, b1, ..] => (*b0, *b1),
+            _ => return Ok(None),
+        };
+        if b0 & 0x70 != 0 {
+            return Err(frame_error(buf, "reserved bits are set"));
+        }
+        let masked = b1 & 0x80 != 0;
+        if masked == self.client {
+            return Err(frame_error(
+                buf,
+                if self.client {
+                    "frames of servers should not be masked"
+                } else {
+                    "frames of clients should be masked"
+                },
+            ));
+        }
+
+        let (len, mut pos) = match b1 & 0x7f {
+            126 if buf.len() >= 4 => (u64::from(u16::from_be_bytes([buf[2], buf[3]])), 4),
+            127 if buf.len() >= 10 => {
+                let mut len = [0; 8];
+                len.copy_from_slice(&buf[2..10]);
+                (u64::from_be_bytes(len), 10)
+            }
+            126 | 127 => return Ok(None),
+            len => (u64::from(len), 2),
+        };
+        let pending = self.message.as_ref().map_or(0, |(_, m)| m.len() as u64);
+        if len.saturating_add(pending) > MAX_MESSAGE_LEN as u64 {
+            buf.clear();
+            return Err(Error::FrameTooLarge {
+                max_len: MAX_MESSAGE_LEN,
+            });
+        }
+        let len = len as usize;
+
+        let mask = if masked {
+            match buf.get(pos..pos + 4) {
+                Some(mask) => {
+                    pos += 4;
+                    [mask[0], mask[1], mask[2], mask[3]]
+                }
+                None => return Ok(None),
+            }
+        } else {
+            [0; 4]
+        };
+        if buf.len() < pos + len {
+            return Ok(None);
+        }
+
+        let payload = buf
+            .drain(..pos + len)
+            .skip(pos)
+            .zip(mask.iter().cycle())
+            .map(|(b, m)| b ^ m)
+            .collect();
+        Ok(Some((b0 & 0x80 != 0, b0 & 0x0f, payload)))
+    }
+}
+
+// The stream can't be recovered after a protocol error.
+fn frame_error(buf: &mut Vec<u8>, details: &str) -> Error {
+    buf.clear();
+    Error::InvalidFrame {
+        details: details.to_string(),
+    }
+}
+
+impl Framer for WebSocketFramer {
+    fn decode_frame(&mut self, buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+        while let Some((fin, opcode, payload)) = self.take_frame(buf)? {
+            if opcode >= OPCODE_CLOSE && (!fin || payload.len() > 125) {
+                return Err(frame_error(buf, "control frames should be short and whole"));
+            }
+
+            match (opcode, self.message.take()) {
+                (OPCODE_PING, message) => {
+                    self.message = message;
+                    self.reply(OPCODE_PONG, &payload);
+                }
+                (OPCODE_PONG, message) => {
+                    self.message = message;
+                    info!(
+                        "WebSocket: Pong received, payload: {}",
+                        hex::encode(&payload)
+                    );
+                }
+                (OPCODE_CLOSE, _) => {
+                    // The status code is echoed.
+                    let code = payload.get(..2).unwrap_or_default().to_vec();
+                    info!(
+                        "WebSocket: Closed by the peer, payload: {}",
+                        hex::encode(&payload)
+                    );
+                    self.reply(OPCODE_CLOSE, &code);
+                    buf.clear();
+                    return Err(Error::EndOfStream);
+                }
+                (OPCODE_TEXT | OPCODE_BINARY, None) => self.message = Some((opcode, payload)),
+                (OPCODE_CONTINUATION, Some((opcode, mut message))) => {
+                    message.extend(payload);
+                    self.message = Some((opcode, message));
+                }
+                (OPCODE_CONTINUATION, None) => {
+                    return Err(frame_error(buf, "a continuation without a message"))
+                }
+                (OPCODE_TEXT | OPCODE_BINARY, Some(_)) => {
+                    return Err(frame_error(buf, "a message inside a fragmented one"))
+                }
+                (opcode, _) => {
+                    return Err(frame_error(buf, &format!("unknown opcode `{:#x}`", opcode)))
+                }
+            }
+
+            if fin && opcode < OPCODE_CLOSE {
+                if let Some((opcode, message)) = self.message.take() {
+                    if opcode == OPCODE_TEXT && std::str::from_utf8(&message).is_err() {
+                        return Err(Error::InvalidFrame {
+                            details: "the text message is not UTF-8".to_string(),
+                        });
+                    }
+                    return Ok(Some(message));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn encode_frame(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let opcode = if self.ws.text {
+            std::str::from_utf8(bytes).map_err(|_| Error::InvalidFraming {
+                details: "the message is not UTF-8 for a text frame".to_string(),
+            })?;
+            OPCODE_TEXT
+        } else {
+            OPCODE_BINARY
+        };
+        if self.ws.max_frame_len == 0 || bytes.len() <= self.ws.max_frame_len {
+            return Ok(encode_frame(opcode, true, bytes, self.client));
+        }
+
+        let mut frames = Vec::new();
+        let mut chunks = bytes.chunks(self.ws.max_frame_len).peekable();
+        let mut opcode = opcode;
+        while let Some(chunk) = chunks.next() {
+            frames.extend(encode_frame(
+                opcode,
+                chunks.peek().is_none(),
+                chunk,
+                self.client,
+            ));
+            opcode = OPCODE_CONTINUATION;
+        }
+        Ok(frames)
+    }
+}
+
+/// Encodes a ping of a client or a server.
+pub fn ping(payload: &[u8], client: bool) -> Result<Vec<u8>> {
+    if payload.len() > 125 {
+        return Err(invalid("the payload of a ping should be at most 125 bytes"));
+    }
+    Ok(encode_frame(OPCODE_PING, true, payload, client))
+}
+
+/// Encodes a close of a client or a server with the status code.
+pub fn close(code: u16, client: bool) -> Vec<u8> {
+    encode_frame(OPCODE_CLOSE, true, &code.to_be_bytes(), client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{self, Read, Write},
+        sync::{Arc, Mutex},
+    };
+
+    use crate::{error::Error, framing::Framer};
+
+    use super::{
+        accept, accept_key, close, connect, encode_frame, ping, SharedWriter, WebSocket,
+        WebSocketFramer, OPCODE_BINARY, OPCODE_CONTINUATION, OPCODE_PONG,
+    };
+
+    // Bytes read from `r` and written to `w`.
+    struct Duplex<'a> {
+        r: &'a [u8],
+        w: Vec<u8>,
+    }
+
+    impl Read for Duplex<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.r.read(buf)
+        }
+    }
+
+    impl Write for Duplex<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.w.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn handshake_ok() {
+        // The example of RFC 6455.
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        let req = b"GET /chat HTTP/1.1\r\nHost: server.example.com\r\nUpgrade: websocket\r\n\
+                    Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                    Sec-WebSocket-Version: 13\r\n\r\n\x81";
+        let mut stream = Duplex {
+            r: req,
+            w: Vec::new(),
+        };
+        assert_eq!(accept(&mut stream).unwrap(), "/chat");
+        // The first byte of frames is left.
+        assert_eq!(stream.r, b"\x81");
+        assert!(String::from_utf8_lossy(&stream.w)
+            .contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mut stream = Duplex {
+            r: b"GET / HTTP/1.1\r\nHost: x\r\n\r\n",
+            w: Vec::new(),
+        };
+        assert!(accept(&mut stream).is_err());
+        assert!(String::from_utf8_lossy(&stream.w).starts_with("HTTP/1.1 400 Bad Request"));
+
+        // The response can't match the random key.
+        let mut stream = Duplex {
+            r: b"HTTP/1.1 101 Switching Protocols\r\n\
+                 Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n",
+            w: Vec::new(),
+        };
+        assert!(connect(&mut stream, "localhost", &WebSocket::default()).is_err());
+        assert!(String::from_utf8_lossy(&stream.w).starts_with("GET / HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn framer_ok() {
+        let ws = WebSocket {
+            max_frame_len: 2,
+            ..Default::default()
+        };
+        let client = WebSocketFramer::new(true, ws.clone());
+        let replies = Arc::new(Mutex::new(Vec::new()));
+        let mut server =
+            WebSocketFramer::new(false, ws).replies(Some(SharedWriter(replies.clone())));
+
+        // Fragments of a masked message, with a ping between them.
+        let mut buf = client.encode_frame(b"abc").unwrap();
+        assert_eq!(buf[0], OPCODE_BINARY);
+        assert_eq!(buf[1], 0x82);
+        buf.splice(8..8, ping(b"hi", true).unwrap());
+        assert_eq!(server.decode_frame(&mut buf[..7].to_vec()).unwrap(), None);
+        assert_eq!(server.decode_frame(&mut buf).unwrap().unwrap(), b"abc");
+        assert!(buf.is_empty());
+        assert_eq!(
+            *replies.lock().unwrap(),
+            encode_frame(OPCODE_PONG, true, b"hi", false)
+        );
+
+        // A close is answered, and ends the stream.
+        replies.lock().unwrap().clear();
+        let mut buf = close(1000, true);
+        assert!(matches!(
+            server.decode_frame(&mut buf),
+            Err(Error::EndOfStream)
+        ));
+        assert_eq!(*replies.lock().unwrap(), close(1000, false));
+
+        // Frames of clients should be masked.
+        let mut buf = encode_frame(OPCODE_BINARY, true, b"abc", false);
+        assert!(server.decode_frame(&mut buf).is_err());
+        assert!(buf.is_empty());
+        let mut buf = encode_frame(OPCODE_CONTINUATION, true, b"abc", true);
+        assert!(server.decode_frame(&mut buf).is_err());
+
+        // Lengths of 16 bits.
+        let payload = vec![7; 300];
+        let mut client = WebSocketFramer::new(true, Default::default());
+        let mut buf = WebSocketFramer::new(false, Default::default())
+            .encode_frame(&payload)
+            .unwrap();
+        assert_eq!(&buf[..4], [0x82, 126, 1, 44]);
+        assert_eq!(client.decode_frame(&mut buf).unwrap().unwrap(), payload);
+    }
+}